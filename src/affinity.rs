@@ -0,0 +1,90 @@
+use crate::ast::Value;
+use crate::datetime;
+use crate::schema::ColumnType;
+
+/// Converts a value written to, or compared with, a column of `column_type`
+/// the way SQLite's column affinity does:
+///
+/// - INTEGER, REAL and BOOLEAN columns read text that is a number as that
+///   number, so `'42'` and `' 4.5 '` become 42 and 4.5. Other text is left
+///   as it is.
+/// - BOOLEAN columns then read 1 and 0, and the text `'true'` and
+///   `'false'`, as TRUE and FALSE. INTEGER and REAL columns read TRUE and
+///   FALSE as 1 and 0.
+/// - TEXT columns read numbers as their text, so 42 becomes `'42'`, and
+///   TRUE and FALSE as `'1'` and `'0'`.
+/// - DATE, TIME and TIMESTAMP columns read text naming a time in their
+///   canonical form, so `'2024-03-10T09:05+01:00'` becomes
+///   `'2024-03-10 08:05:00'` in a TIMESTAMP column.
+/// - BLOB columns read text as its UTF-8 bytes.
+/// - NULL stays NULL in every column.
+///
+/// Whole numbers are stored without a fraction in INTEGER columns by
+/// `value_to_field`, which converts with this first.
+pub fn convert(value: &Value, column_type: &ColumnType) -> Value {
+    match (value, column_type) {
+        (Value::String(s), ColumnType::Boolean) if s.trim().eq_ignore_ascii_case("true") => Value::Boolean(true),
+        (Value::String(s), ColumnType::Boolean) if s.trim().eq_ignore_ascii_case("false") => Value::Boolean(false),
+        (Value::String(s), ColumnType::Integer | ColumnType::Float | ColumnType::Boolean) => match s.trim().parse::<f64>() {
+            Ok(n) if n.is_finite() => convert(&Value::Number(n), column_type),
+            _ => value.clone()
+        },
+        (Value::Number(n), ColumnType::Boolean) if *n == 0.0 || *n == 1.0 => Value::Boolean(*n == 1.0),
+        (Value::Boolean(b), ColumnType::Integer | ColumnType::Float) => Value::Number(f64::from(u8::from(*b))),
+        (Value::Boolean(b), ColumnType::String) => Value::String(u8::from(*b).to_string()),
+        (Value::Number(n), ColumnType::String) => Value::String(n.to_string()),
+        (Value::String(s), ColumnType::Blob) => Value::Blob(s.as_bytes().to_vec()),
+        (Value::String(s), ColumnType::Date | ColumnType::Time | ColumnType::Timestamp) => {
+            datetime::canonical(column_type, s).map_or_else(|| value.clone(), Value::String)
+        }
+        _ => value.clone()
+    }
+}
+
+/// Whether a value given in a statement can be written to, or compared
+/// with, a column of `column_type`. NULL always can.
+///
+/// Ordinary tables take any value `convert` turns into the column's kind:
+/// numbers and numeric text for INTEGER, REAL and BOOLEAN columns, text
+/// naming a time for DATE, TIME and TIMESTAMP columns, and anything but a
+/// blob for TEXT columns. Other text is almost certainly a mistake. Blobs
+/// only go into BLOB columns, which take text too.
+///
+/// STRICT tables convert nothing: INTEGER columns take whole numbers,
+/// REAL columns any number, BOOLEAN columns TRUE, FALSE, 0 and 1, TEXT
+/// columns text, and DATE, TIME and TIMESTAMP columns only text already in
+/// canonical form. INTEGER and REAL columns also take TRUE and FALSE, and
+/// BLOB columns take blobs.
+pub fn fits(value: &Value, column_type: &ColumnType, strict: bool) -> bool {
+    if strict {
+        return match (column_type, value) {
+            (_, Value::Null | Value::Parameter(_)) => true,
+            (ColumnType::Integer, Value::Number(n)) => n.fract() == 0.0,
+            (ColumnType::Float, Value::Number(_)) => true,
+            (ColumnType::Boolean, Value::Number(n)) => *n == 0.0 || *n == 1.0,
+            (ColumnType::Integer | ColumnType::Float | ColumnType::Boolean, Value::Boolean(_)) => true,
+            (ColumnType::String, Value::String(_)) => true,
+            (ColumnType::Blob, Value::Blob(_)) => true,
+            (ColumnType::Date | ColumnType::Time | ColumnType::Timestamp, Value::String(s)) => {
+                datetime::canonical(column_type, s).as_ref() == Some(s)
+            }
+            _ => false
+        };
+    }
+
+    match (column_type, value) {
+        (_, Value::Null | Value::Parameter(_)) => true,
+        (ColumnType::Blob, value) => matches!(value, Value::Blob(_) | Value::String(_)),
+        (_, Value::Blob(_)) => false,
+        (ColumnType::String, _) => true,
+        (ColumnType::Date | ColumnType::Time | ColumnType::Timestamp, Value::String(s)) => datetime::canonical(column_type, s).is_some(),
+        (ColumnType::Date | ColumnType::Time | ColumnType::Timestamp, _) => false,
+        (_, value) => matches!(convert(value, column_type), Value::Number(_) | Value::Boolean(_))
+    }
+}
+
+/// Whether columns of this type hold numbers, which a comparison with a
+/// TEXT column converts the text side to.
+pub fn is_numeric(column_type: &ColumnType) -> bool {
+    matches!(column_type, ColumnType::Integer | ColumnType::Float | ColumnType::Boolean)
+}
@@ -1,12 +1,28 @@
-#[derive(Debug, PartialEq)]
+use serde::{Deserialize, Serialize};
+
+use crate::schema::{ColumnConstraint, ColumnType, TableConstraint};
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum ASTNode {
     Select(SelectStatement),
     Insert(InsertStatement),
     Delete(DeleteStatement),
-    Update(UpdateStatement)
+    Update(UpdateStatement),
+    CreateTable(CreateTableStatement),
+    Pragma(PragmaStatement),
+    Begin(TransactionMode),
+    Commit,
+    Rollback,
+    Explain(ExplainStatement),
+    CreateView(CreateViewStatement),
+    DropView(DropViewStatement),
+    CreateTrigger(CreateTriggerStatement),
+    DropTrigger(DropTriggerStatement),
+    /// Gathers statistics on the named table, or on every table.
+    Analyze(Option<String>)
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct SelectStatement {
     pub columns: Vec<String>,
     pub table: String, 
@@ -14,32 +30,151 @@ pub struct SelectStatement {
     pub order_by: Vec<OrderByClause>
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct InsertStatement {
     pub table: String, 
     pub columns: Vec<String>,
-    pub values: Vec<Value>
+    pub source: InsertSource
+}
+
+/// Where the rows of an INSERT come from.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum InsertSource {
+    /// One list of values per row.
+    Values(Vec<Vec<Value>>),
+    Select(Box<SelectStatement>)
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct UpdateStatement {
     pub table: String, 
     pub updates: Vec<(String, Value)>,
     pub condition: Option<Condition>
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct DeleteStatement {
     pub table: String, 
     pub condition: Option<Condition>
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct CreateTableStatement {
+    pub table: String,
+    pub columns: Vec<ColumnDefinition>,
+    pub constraints: Vec<TableConstraint>,
+    /// Values must be of their column's exact type, see `affinity::fits`.
+    pub strict: bool
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ColumnDefinition {
+    pub name: String,
+    pub column_type: ColumnType,
+    pub constraints: Vec<ColumnConstraint>
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct CreateViewStatement {
+    pub name: String,
+    /// Names for the selected columns, or empty to keep their own.
+    pub columns: Vec<String>,
+    pub query: SelectStatement
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct DropViewStatement {
+    pub name: String,
+    pub if_exists: bool
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct CreateTriggerStatement {
+    pub name: String,
+    pub table: String,
+    pub timing: TriggerTiming,
+    pub event: TriggerEvent,
+    /// The trigger only runs for rows this holds for.
+    pub when: Option<Condition>,
+    /// The INSERT, UPDATE, DELETE and SELECT statements run for each row.
+    pub body: Vec<ASTNode>,
+    /// The `NEW.column` and `OLD.column` references in the WHEN clause and
+    /// the body, which are parameters there, by index less one.
+    pub references: Vec<String>
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum TriggerTiming {
+    Before,
+    After,
+    /// Runs in place of a write to a view, which cannot be written itself.
+    InsteadOf
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum TriggerEvent {
+    Insert,
+    /// An UPDATE of any of these columns, or of any column if there are none.
+    Update(Vec<String>),
+    Delete
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct DropTriggerStatement {
+    pub name: String,
+    pub if_exists: bool
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct PragmaStatement {
+    pub name: String,
+    pub value: Option<Value>
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ExplainStatement {
+    pub mode: ExplainMode,
+    pub statement: Box<ASTNode>
+}
+
+/// `EXPLAIN` lists the operations a statement runs as; `EXPLAIN QUERY PLAN`
+/// only the tables it scans and the sorts it makes.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum ExplainMode {
+    Program,
+    QueryPlan
+}
+
+/// When a transaction takes hold of the database: `Deferred` waits for its
+/// first write, `Immediate` and `Exclusive` claim it at BEGIN.
+#[derive(Debug, PartialEq, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum TransactionMode {
+    #[default]
+    Deferred,
+    Immediate,
+    Exclusive
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Condition {
-    Comparison(String, ComparisonOperator, Value)
+    Comparison(String, ComparisonOperator, Value),
+    Compare(Expression, ComparisonOperator, Expression),
+    IsNull(Expression),
+    IsNotNull(Expression),
+    /// `expr IS TRUE`: the expression is a number other than zero. Unlike
+    /// `Truth`, this is false rather than unknown for NULL.
+    IsTrue(Expression),
+    /// `expr IS FALSE`: the expression is zero.
+    IsFalse(Expression),
+    /// An expression used as a condition on its own, as in `WHERE active`:
+    /// true when it is a number other than zero and unknown when NULL.
+    Truth(Expression),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>)
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum ComparisonOperator {
     Equals,
     NotEquals,
@@ -49,19 +184,98 @@ pub enum ComparisonOperator {
     LessEqualThan,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum Expression {
+    Literal(Value),
+    Column(String),
+    Negate(Box<Expression>),
+    Binary(Box<Expression>, ArithmeticOperator, Box<Expression>),
+    /// A call to one of `datetime::FUNCTIONS` or `blob::FUNCTIONS`, by its
+    /// lowercase name.
+    Function(String, Vec<Expression>)
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum ArithmeticOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Concat
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Value {
     Number(f64),
-    String(String)
+    String(String),
+    /// TRUE or FALSE. Stored as 1 and 0, as SQLite does, and equal to those
+    /// numbers in comparisons.
+    Boolean(bool),
+    /// Bytes, written `X'ABCD'`. Stored as hex digits, and ordered after
+    /// text in comparisons, as in SQLite.
+    Blob(Vec<u8>),
+    Null,
+    /// A placeholder of a prepared statement, by its 1-based index. It
+    /// reads as NULL until a value is bound to it.
+    Parameter(usize)
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Value::Number(value as f64)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Number(value)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Boolean(value)
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(value: Vec<u8>) -> Self {
+        Value::Blob(value)
+    }
+}
+
+impl From<&[u8]> for Value {
+    fn from(value: &[u8]) -> Self {
+        Value::Blob(value.to_vec())
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::String(value)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Null, Into::into)
+    }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct OrderByClause {
     pub column: String, 
     pub order: OrderDirection
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum OrderDirection {
     Asc, 
     Desc 
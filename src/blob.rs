@@ -0,0 +1,65 @@
+use crate::ast::Value;
+use crate::evaluator::to_text;
+
+/// The SQL functions for turning blobs into hex and back, and for measuring
+/// them, that `call` evaluates.
+pub const FUNCTIONS: [&str; 3] = ["hex", "unhex", "length"];
+
+pub fn is_function(name: &str) -> bool {
+    FUNCTIONS.contains(&name.to_ascii_lowercase().as_str())
+}
+
+/// Runs one of the `FUNCTIONS` the way SQLite does:
+///
+/// - `hex(x)` is the bytes of a blob, or of the text of any other value, as
+///   upper case hex digits. It is the empty string for NULL.
+/// - `unhex(x [, ignored])` is the blob that the hex digits in `x` stand for,
+///   skipping any characters of `ignored` between pairs of digits. It is
+///   NULL when anything else is in the way.
+/// - `length(x)` is the number of bytes in a blob and of characters in
+///   the text of any other value. It is NULL for NULL.
+///
+/// Any other number of arguments gives NULL.
+pub fn call(name: &str, args: &[Value]) -> Value {
+    match (name.to_ascii_lowercase().as_str(), args) {
+        ("hex", [Value::Null]) => Value::String(String::new()),
+        ("hex", [value]) => Value::String(to_hex(&bytes(value))),
+        ("unhex", [Value::Null, ..] | [_, Value::Null]) => Value::Null,
+        ("unhex", [value]) => from_hex(&to_text(value), "").map_or(Value::Null, Value::Blob),
+        ("unhex", [value, ignored]) => from_hex(&to_text(value), &to_text(ignored)).map_or(Value::Null, Value::Blob),
+        ("length", [Value::Null]) => Value::Null,
+        ("length", [Value::Blob(bytes)]) => Value::Number(bytes.len() as f64),
+        ("length", [value]) => Value::Number(to_text(value).chars().count() as f64),
+        _ => Value::Null
+    }
+}
+
+/// The bytes of a blob, or of the text of any other value.
+fn bytes(value: &Value) -> Vec<u8> {
+    match value {
+        Value::Blob(bytes) => bytes.clone(),
+        value => to_text(value).into_bytes()
+    }
+}
+
+/// Bytes as upper case hex digits, two to a byte. This is also how a blob
+/// is stored, so the storage format stays text.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02X}")).collect()
+}
+
+/// The bytes that pairs of hex digits in either case stand for, with any
+/// characters of `ignored` skipped between pairs, or `None` if there is
+/// anything else in the text or an odd digit out.
+pub fn from_hex(text: &str, ignored: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut chars = text.chars();
+    while let Some(high) = chars.next() {
+        if ignored.contains(high) {
+            continue;
+        }
+        let low = chars.next()?;
+        bytes.push((high.to_digit(16)? * 16 + low.to_digit(16)?) as u8);
+    }
+    Some(bytes)
+}
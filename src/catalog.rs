@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use crate::ast::{ASTNode, Condition, Expression, InsertSource, OrderDirection, SelectStatement, TriggerEvent, TriggerTiming, Value};
+use crate::datastore::DataStore;
+use crate::execution_engine::Row;
+use crate::explain::{condition_sql, expression_sql, value_sql};
+use crate::schema::{ColumnConstraint, ColumnType, ForeignKey, ForeignKeyAction, TableConstraint, TableSchema, TriggerSchema, ViewSchema};
+
+/// The read-only table listing every table, view, index and trigger, as in
+/// SQLite. It is
+/// also known by its older name, `sqlite_master`.
+pub const SCHEMA_TABLE: &str = "sqlite_schema";
+
+pub const TABLE_INFO_COLUMNS: [&str; 6] = ["cid", "name", "type", "notnull", "dflt_value", "pk"];
+pub const INDEX_LIST_COLUMNS: [&str; 5] = ["seq", "name", "unique", "origin", "partial"];
+
+static SCHEMA_TABLE_SCHEMA: LazyLock<TableSchema> = LazyLock::new(|| {
+    let columns = [
+        ("type", ColumnType::String),
+        ("name", ColumnType::String),
+        ("tbl_name", ColumnType::String),
+        ("rootpage", ColumnType::Integer),
+        ("sql", ColumnType::String)
+    ];
+    TableSchema {
+        columns: columns.iter().map(|(name, column_type)| (name.to_string(), column_type.clone())).collect(),
+        column_order: columns.iter().map(|(name, _)| name.to_string()).collect(),
+        ..Default::default()
+    }
+});
+
+pub fn is_schema_table(table: &str) -> bool {
+    table.eq_ignore_ascii_case(SCHEMA_TABLE) || table.eq_ignore_ascii_case("sqlite_master")
+}
+
+/// The columns of the catalog table.
+pub fn schema_table() -> &'static TableSchema {
+    &SCHEMA_TABLE_SCHEMA
+}
+
+/// The rows of the catalog table, in the stored form `scan` reads, with
+/// tables and views in name order, each followed by its indexes and then
+/// its triggers. Tables have no pages, so every rootpage is 0.
+pub fn schema_rows(data_store: &DataStore) -> Vec<HashMap<String, String>> {
+    let row = |kind: &str, name: &str, table: &str, sql: Option<String>| {
+        let mut row = HashMap::from([
+            ("type".to_string(), kind.to_string()),
+            ("name".to_string(), name.to_string()),
+            ("tbl_name".to_string(), table.to_string()),
+            ("rootpage".to_string(), "0".to_string())
+        ]);
+        if let Some(sql) = sql {
+            row.insert("sql".to_string(), sql);
+        }
+        row
+    };
+
+    let mut names = data_store.table_names();
+    names.extend(data_store.view_names());
+    names.sort();
+
+    let mut rows = Vec::new();
+    for name in names {
+        if let Some(view) = data_store.get_view(&name) {
+            rows.push(row("view", &name, &name, Some(create_view_sql(&name, view))));
+        } else if let Some(schema) = data_store.get_table_schema(&name) {
+            rows.push(row("table", &name, &name, Some(create_table_sql(&name, schema))));
+            if let Some(index) = primary_key_index(&name, schema) {
+                rows.push(row("index", &index, &name, None));
+            }
+        }
+        for (trigger_name, trigger) in data_store.triggers_on(&name) {
+            rows.push(row("trigger", &trigger_name, &name, Some(create_trigger_sql(&trigger_name, &trigger))));
+        }
+    }
+    rows
+}
+
+/// A CREATE TABLE statement that makes a table with this schema.
+pub fn create_table_sql(table: &str, schema: &TableSchema) -> String {
+    let mut definitions: Vec<String> = schema.column_names().iter().map(|column| {
+        let mut definition = format!("{column} {}", schema.columns[column].sql_name());
+        for constraint in schema.constraints_for(column) {
+            definition.push(' ');
+            definition.push_str(&match constraint {
+                ColumnConstraint::NotNull => "NOT NULL".to_string(),
+                ColumnConstraint::Default(Expression::Literal(value)) => format!("DEFAULT {}", value_sql(value)),
+                ColumnConstraint::Default(expr @ Expression::Binary(..)) => format!("DEFAULT {}", expression_sql(expr)),
+                ColumnConstraint::Default(expr) => format!("DEFAULT ({})", expression_sql(expr)),
+                ColumnConstraint::Check(condition) => check_sql(condition),
+                ColumnConstraint::References(fk) => references_sql(fk),
+                ColumnConstraint::PrimaryKey { autoincrement: true } => "PRIMARY KEY AUTOINCREMENT".to_string(),
+                ColumnConstraint::PrimaryKey { autoincrement: false } => "PRIMARY KEY".to_string()
+            });
+        }
+        definition
+    }).collect();
+
+    definitions.extend(schema.table_constraints.iter().map(|constraint| match constraint {
+        TableConstraint::PrimaryKey(columns) => format!("PRIMARY KEY ({})", columns.join(", ")),
+        TableConstraint::Check(condition) => check_sql(condition),
+        TableConstraint::ForeignKey(fk) => format!("FOREIGN KEY ({}) {}", fk.columns.join(", "), references_sql(fk))
+    }));
+
+    let strict = if schema.strict { " STRICT" } else { "" };
+    format!("CREATE TABLE {table} ({}){strict}", definitions.join(", "))
+}
+
+/// A CREATE VIEW statement for a view, naming its columns.
+pub fn create_view_sql(name: &str, view: &ViewSchema) -> String {
+    format!("CREATE VIEW {name} ({}) AS {}", view.columns.join(", "), select_sql(&view.query))
+}
+
+pub fn select_sql(stmt: &SelectStatement) -> String {
+    let mut sql = format!("SELECT {} FROM {}", stmt.columns.join(", "), stmt.table);
+    if let Some(condition) = &stmt.condition {
+        sql.push_str(&format!(" WHERE {}", condition_sql(condition)));
+    }
+    if !stmt.order_by.is_empty() {
+        let keys: Vec<String> = stmt.order_by.iter()
+            .map(|clause| format!("{} {}", clause.column, if clause.order == OrderDirection::Asc { "ASC" } else { "DESC" }))
+            .collect();
+        sql.push_str(&format!(" ORDER BY {}", keys.join(", ")));
+    }
+    sql
+}
+
+/// A CREATE TRIGGER statement for a trigger, with its `NEW.` and `OLD.`
+/// references written back in place of the parameters they became.
+pub fn create_trigger_sql(name: &str, trigger: &TriggerSchema) -> String {
+    let timing = match trigger.timing {
+        TriggerTiming::Before => "BEFORE",
+        TriggerTiming::After => "AFTER",
+        TriggerTiming::InsteadOf => "INSTEAD OF"
+    };
+    let event = match &trigger.event {
+        TriggerEvent::Insert => "INSERT".to_string(),
+        TriggerEvent::Delete => "DELETE".to_string(),
+        TriggerEvent::Update(columns) if columns.is_empty() => "UPDATE".to_string(),
+        TriggerEvent::Update(columns) => format!("UPDATE OF {}", columns.join(", "))
+    };
+
+    let mut sql = format!("CREATE TRIGGER {name} {timing} {event} ON {} FOR EACH ROW", trigger.table);
+    if let Some(when) = &trigger.when {
+        sql.push_str(&format!(" WHEN {}", condition_sql(&with_references(when, &trigger.references))));
+    }
+    sql.push_str(" BEGIN");
+    for statement in &trigger.body {
+        sql.push_str(&format!(" {};", trigger_statement_sql(statement, &trigger.references)));
+    }
+    sql.push_str(" END");
+    sql
+}
+
+/// A statement of a trigger's body. Only SELECT, INSERT, UPDATE and DELETE
+/// can be there.
+fn trigger_statement_sql(statement: &ASTNode, references: &[String]) -> String {
+    let value = |value: &Value| match value {
+        Value::Parameter(index) => references[index - 1].clone(),
+        value => value_sql(value)
+    };
+    let select = |stmt: &SelectStatement| select_sql(&SelectStatement {
+        condition: stmt.condition.as_ref().map(|condition| with_references(condition, references)),
+        ..stmt.clone()
+    });
+    let filter = |condition: &Option<Condition>| condition.as_ref()
+        .map(|condition| format!(" WHERE {}", condition_sql(&with_references(condition, references))))
+        .unwrap_or_default();
+
+    match statement {
+        ASTNode::Select(stmt) => select(stmt),
+        ASTNode::Insert(stmt) => {
+            let mut sql = format!("INSERT INTO {}", stmt.table);
+            if !stmt.columns.is_empty() {
+                sql.push_str(&format!(" ({})", stmt.columns.join(", ")));
+            }
+            match &stmt.source {
+                InsertSource::Values(rows) => {
+                    let rows: Vec<String> = rows.iter()
+                        .map(|values| format!("({})", values.iter().map(value).collect::<Vec<_>>().join(", ")))
+                        .collect();
+                    sql.push_str(&format!(" VALUES {}", rows.join(", ")));
+                }
+                InsertSource::Select(stmt) => sql.push_str(&format!(" {}", select(stmt)))
+            }
+            sql
+        }
+        ASTNode::Update(stmt) => {
+            let assignments: Vec<String> = stmt.updates.iter().map(|(column, update)| format!("{column} = {}", value(update))).collect();
+            format!("UPDATE {} SET {}{}", stmt.table, assignments.join(", "), filter(&stmt.condition))
+        }
+        ASTNode::Delete(stmt) => format!("DELETE FROM {}{}", stmt.table, filter(&stmt.condition)),
+        _ => String::new()
+    }
+}
+
+/// A condition with each reference written as a column named after it,
+/// which `condition_sql` writes out as it is.
+fn with_references(condition: &Condition, references: &[String]) -> Condition {
+    let inner_references = |inner: &Condition| Box::new(with_references(inner, references));
+    match condition {
+        Condition::Comparison(column, op, Value::Parameter(index)) => {
+            Condition::Compare(Expression::Column(column.clone()), op.clone(), Expression::Column(references[index - 1].clone()))
+        }
+        Condition::Comparison(..) => condition.clone(),
+        Condition::Compare(left, op, right) => Condition::Compare(expression_references(left, references), op.clone(), expression_references(right, references)),
+        Condition::IsNull(expr) => Condition::IsNull(expression_references(expr, references)),
+        Condition::IsNotNull(expr) => Condition::IsNotNull(expression_references(expr, references)),
+        Condition::IsTrue(expr) => Condition::IsTrue(expression_references(expr, references)),
+        Condition::IsFalse(expr) => Condition::IsFalse(expression_references(expr, references)),
+        Condition::Truth(expr) => Condition::Truth(expression_references(expr, references)),
+        Condition::And(left, right) => Condition::And(inner_references(left), inner_references(right)),
+        Condition::Or(left, right) => Condition::Or(inner_references(left), inner_references(right)),
+        Condition::Not(inner) => Condition::Not(inner_references(inner))
+    }
+}
+
+fn expression_references(expr: &Expression, references: &[String]) -> Expression {
+    match expr {
+        Expression::Literal(Value::Parameter(index)) => Expression::Column(references[index - 1].clone()),
+        Expression::Negate(inner) => Expression::Negate(Box::new(expression_references(inner, references))),
+        Expression::Binary(left, op, right) => Expression::Binary(
+            Box::new(expression_references(left, references)),
+            op.clone(),
+            Box::new(expression_references(right, references))
+        ),
+        Expression::Function(name, args) => Expression::Function(name.clone(), args.iter().map(|arg| expression_references(arg, references)).collect()),
+        expr => expr.clone()
+    }
+}
+
+/// `PRAGMA table_info`: a row per column, in declaration order, with `pk`
+/// the column's position in the primary key or 0.
+pub fn table_info(schema: &TableSchema) -> Vec<Row> {
+    let primary_key = schema.primary_key();
+    schema.column_names().iter().enumerate().map(|(cid, column)| {
+        let default = schema.default_for(column).map_or(Value::Null, |default| Value::String(match default {
+            Expression::Literal(value) => value_sql(value),
+            expr => expression_sql(expr)
+        }));
+        let pk = primary_key.iter().position(|key| key == column).map_or(0, |position| position + 1);
+        Row { values: vec![
+            Value::Number(cid as f64),
+            Value::String(column.clone()),
+            Value::String(schema.columns[column].sql_name().to_string()),
+            Value::Number(if schema.is_not_null(column) { 1.0 } else { 0.0 }),
+            default,
+            Value::Number(pk as f64)
+        ] }
+    }).collect()
+}
+
+/// `PRAGMA index_list`. The only indexes are the ones kept for a primary
+/// key that is not the rowid, which SQLite names the same way.
+pub fn index_list(table: &str, schema: &TableSchema) -> Vec<Row> {
+    primary_key_index(table, schema).into_iter().enumerate().map(|(seq, name)| Row { values: vec![
+        Value::Number(seq as f64),
+        Value::String(name),
+        Value::Number(1.0),
+        Value::String("pk".to_string()),
+        Value::Number(0.0)
+    ] }).collect()
+}
+
+pub(crate) fn primary_key_index(table: &str, schema: &TableSchema) -> Option<String> {
+    let has_index = !schema.primary_key().is_empty() && schema.rowid_alias().is_none();
+    has_index.then(|| format!("sqlite_autoindex_{table}_1"))
+}
+
+/// AND and OR come back from `condition_sql` in parentheses already.
+fn check_sql(condition: &Condition) -> String {
+    match condition {
+        Condition::And(..) | Condition::Or(..) => format!("CHECK {}", condition_sql(condition)),
+        condition => format!("CHECK ({})", condition_sql(condition))
+    }
+}
+
+fn references_sql(fk: &ForeignKey) -> String {
+    let mut sql = format!("REFERENCES {} ({})", fk.parent_table, fk.parent_columns.join(", "));
+    for (event, action) in [("DELETE", &fk.on_delete), ("UPDATE", &fk.on_update)] {
+        let action = match action {
+            ForeignKeyAction::NoAction => continue,
+            ForeignKeyAction::Restrict => "RESTRICT",
+            ForeignKeyAction::Cascade => "CASCADE",
+            ForeignKeyAction::SetNull => "SET NULL",
+            ForeignKeyAction::SetDefault => "SET DEFAULT"
+        };
+        sql.push_str(&format!(" ON {event} {action}"));
+    }
+    sql
+}
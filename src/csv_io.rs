@@ -0,0 +1,241 @@
+use std::io::{Read, Write};
+
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+
+use crate::ast::{ASTNode, ColumnDefinition, CreateTableStatement, InsertSource, InsertStatement, Value};
+use crate::{blob, datetime};
+use crate::evaluator::to_text;
+use crate::execution_engine::{ExecutionEngine, ExecutionError};
+use crate::schema::{ColumnType, TableSchema};
+
+/// Rows inserted per statement while importing. A batch with a bad row in
+/// it is retried one row at a time to find which rows to reject.
+const IMPORT_BATCH_SIZE: usize = 500;
+
+/// How an external CSV file is laid out.
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    pub delimiter: u8,
+    /// Whether the first record names the columns. Without one, fields are
+    /// matched to the table's columns in order.
+    pub has_header: bool
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions { delimiter: b',', has_header: true }
+    }
+}
+
+#[derive(Debug)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub rejected: Vec<RejectedRow>
+}
+
+/// A record that could not be imported, by its line in the input.
+#[derive(Debug)]
+pub struct RejectedRow {
+    pub line: u64,
+    pub error: ExecutionError
+}
+
+/// Loads CSV data into `table`, creating it with a TEXT column per header
+/// field if it does not exist yet. Header names are matched to columns
+/// regardless of case or order, and each field is converted to its
+/// column's type. Records that do not convert or that break a constraint
+/// are left out and reported; the rest are inserted in one transaction,
+/// unless one is already open.
+pub fn import_csv<R: Read>(engine: &mut ExecutionEngine, input: R, table: &str, options: &CsvOptions) -> Result<ImportReport, ExecutionError> {
+    let mut reader = ReaderBuilder::new()
+        .delimiter(options.delimiter)
+        .has_headers(options.has_header)
+        .flexible(true)
+        .from_reader(input);
+
+    let header = if options.has_header {
+        Some(reader.headers().map_err(csv_error)?.clone())
+    } else {
+        None
+    };
+
+    if engine.data_store().get_table_schema(table).is_none() {
+        let header = header.as_ref().ok_or_else(|| ExecutionError::TableNotFound(table.to_string()))?;
+        let columns = header.iter().map(|name| (name.trim().to_string(), ColumnType::String)).collect();
+        create_table(engine, table, columns)?;
+    }
+    let schema = engine.data_store().get_table_schema(table)
+        .ok_or_else(|| ExecutionError::TableNotFound(table.to_string()))?
+        .clone();
+    let columns = match &header {
+        Some(header) => map_header(header, &schema)?,
+        None => schema.column_names()
+    };
+
+    let records = reader.into_records().map(|record| {
+        let record = record.map_err(csv_error)?;
+        let line = record.position().map_or(0, |position| position.line());
+        Ok((line, convert_record(&record, table, &columns, &schema).map(|values| (columns.clone(), values))))
+    });
+    import_rows(engine, table, records)
+}
+
+/// Writes the rows of a SELECT as CSV, headed by the column names, and
+/// returns how many rows were written. NULL is written as an empty field
+/// and a blob as its hex digits, which import reads back into a BLOB column.
+/// An empty blob is an empty field too, so it is read back as NULL.
+pub fn export_csv<W: Write>(engine: &mut ExecutionEngine, ast: &ASTNode, output: W, options: &CsvOptions) -> Result<usize, ExecutionError> {
+    let mut writer = WriterBuilder::new().delimiter(options.delimiter).from_writer(output);
+    let cursor = engine.query(ast)?;
+    if options.has_header {
+        writer.write_record(cursor.columns()).map_err(csv_error)?;
+    }
+
+    let mut written = 0;
+    for row in cursor {
+        writer.write_record(row?.values.iter().map(|value| match value {
+            Value::Blob(bytes) => blob::to_hex(bytes),
+            value => to_text(value)
+        })).map_err(csv_error)?;
+        written += 1;
+    }
+    writer.flush()?;
+    Ok(written)
+}
+
+/// Creates `table` with the given columns and no constraints.
+pub(crate) fn create_table(engine: &mut ExecutionEngine, table: &str, columns: Vec<(String, ColumnType)>) -> Result<(), ExecutionError> {
+    let columns = columns.into_iter()
+        .map(|(name, column_type)| ColumnDefinition { name, column_type, constraints: Vec::new() })
+        .collect();
+    engine.execute(&ASTNode::CreateTable(CreateTableStatement { table: table.to_string(), columns, constraints: Vec::new(), strict: false }))?;
+    Ok(())
+}
+
+/// The table column each header field names.
+fn map_header(header: &StringRecord, schema: &TableSchema) -> Result<Vec<String>, ExecutionError> {
+    header.iter()
+        .map(|field| find_column(schema, field.trim()).ok_or_else(|| ExecutionError::ColumnNotFound(field.trim().to_string())))
+        .collect()
+}
+
+/// The column `name` refers to, ignoring case.
+pub(crate) fn find_column(schema: &TableSchema, name: &str) -> Option<String> {
+    schema.column_names().into_iter()
+        .find(|column| column.eq_ignore_ascii_case(name))
+        .or_else(|| schema.has_column(name).then(|| name.to_string()))
+}
+
+/// A record read for import: its line, and either the columns it sets with
+/// their values or why it cannot be imported.
+pub(crate) type ImportRecord = (u64, Result<(Vec<String>, Vec<Value>), ExecutionError>);
+
+/// Inserts records in batches inside one transaction, unless one is
+/// already open, collecting the ones that are rejected. An error reading
+/// the input stops the import and rolls it back.
+pub(crate) fn import_rows(engine: &mut ExecutionEngine, table: &str, records: impl Iterator<Item = Result<ImportRecord, ExecutionError>>) -> Result<ImportReport, ExecutionError> {
+    let owns_transaction = !engine.in_transaction();
+    if owns_transaction {
+        engine.begin(Default::default())?;
+    }
+    let result = insert_records(engine, table, records);
+    match (&result, owns_transaction) {
+        (Ok(_), true) => engine.commit()?,
+        (Err(_), true) => engine.rollback()?,
+        _ => {}
+    }
+    result
+}
+
+fn insert_records(engine: &mut ExecutionEngine, table: &str, records: impl Iterator<Item = Result<ImportRecord, ExecutionError>>) -> Result<ImportReport, ExecutionError> {
+    let mut report = ImportReport { imported: 0, rejected: Vec::new() };
+    let mut columns = Vec::new();
+    let mut batch = Vec::new();
+
+    for record in records {
+        let (line, row) = record?;
+        match row {
+            Ok((row_columns, values)) => {
+                // A batch is one INSERT, so it can only hold rows that set
+                // the same columns.
+                if row_columns != columns || batch.len() == IMPORT_BATCH_SIZE {
+                    insert_batch(engine, table, &columns, std::mem::take(&mut batch), &mut report)?;
+                    columns = row_columns;
+                }
+                batch.push((line, values));
+            }
+            Err(error) => report.rejected.push(RejectedRow { line, error })
+        }
+    }
+    insert_batch(engine, table, &columns, batch, &mut report)?;
+
+    report.rejected.sort_by_key(|rejected| rejected.line);
+    Ok(report)
+}
+
+fn insert_batch(engine: &mut ExecutionEngine, table: &str, columns: &[String], batch: Vec<(u64, Vec<Value>)>, report: &mut ImportReport) -> Result<(), ExecutionError> {
+    if batch.is_empty() {
+        return Ok(());
+    }
+    let insert = |rows: Vec<Vec<Value>>| ASTNode::Insert(InsertStatement {
+        table: table.to_string(),
+        columns: columns.to_vec(),
+        source: InsertSource::Values(rows)
+    });
+
+    let rows = batch.iter().map(|(_, values)| values.clone()).collect();
+    if engine.execute(&insert(rows)).is_ok() {
+        report.imported += batch.len();
+        return Ok(());
+    }
+
+    for (line, values) in batch {
+        match engine.execute(&insert(vec![values])) {
+            Ok(_) => report.imported += 1,
+            Err(ExecutionError::Storage(error)) if error.kind() != std::io::ErrorKind::InvalidData => {
+                return Err(ExecutionError::Storage(error));
+            }
+            Err(error) => report.rejected.push(RejectedRow { line, error })
+        }
+    }
+    Ok(())
+}
+
+fn convert_record(record: &StringRecord, table: &str, columns: &[String], schema: &TableSchema) -> Result<Vec<Value>, ExecutionError> {
+    if record.len() != columns.len() {
+        return Err(ExecutionError::ValueCountMismatch(table.to_string()));
+    }
+    columns.iter().zip(record.iter())
+        .map(|(column, field)| {
+            let column_type = schema.column_type(column).unwrap_or(&ColumnType::String);
+            convert_field(field, column_type).ok_or_else(|| ExecutionError::TypeMismatch(column.clone()))
+        })
+        .collect()
+}
+
+/// Reads a field as a value of `column_type`, or `None` if it is not one.
+/// An empty field is NULL, except in a TEXT column.
+fn convert_field(field: &str, column_type: &ColumnType) -> Option<Value> {
+    let trimmed = field.trim();
+    if trimmed.is_empty() && *column_type != ColumnType::String {
+        return Some(Value::Null);
+    }
+    match column_type {
+        ColumnType::Integer => trimmed.parse::<i64>().map(|n| n as f64).ok()
+            .or_else(|| trimmed.parse::<f64>().ok().filter(|n| n.fract() == 0.0))
+            .map(Value::Number),
+        ColumnType::Float => trimmed.parse::<f64>().ok().map(Value::Number),
+        ColumnType::Boolean => match trimmed.to_ascii_lowercase().as_str() {
+            "true" | "1" => Some(Value::Boolean(true)),
+            "false" | "0" => Some(Value::Boolean(false)),
+            _ => None
+        },
+        ColumnType::String => Some(Value::String(field.to_string())),
+        ColumnType::Date | ColumnType::Time | ColumnType::Timestamp => datetime::canonical(column_type, trimmed).map(Value::String),
+        ColumnType::Blob => blob::from_hex(trimmed, "").map(Value::Blob)
+    }
+}
+
+fn csv_error(error: csv::Error) -> ExecutionError {
+    ExecutionError::Storage(error.into())
+}
@@ -0,0 +1,173 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use tempfile::TempDir;
+
+use crate::ast::{ASTNode, PragmaStatement, TransactionMode};
+use crate::datastore::DataStore;
+use crate::execution_engine::{Cursor, ExecutionEngine, ExecutionError, QueryResult};
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::sqlite_file::SqliteFile;
+use crate::statement::Statement;
+
+/// A handle on a data directory that can be cloned and shared between
+/// threads.
+///
+/// Every clone runs statements through the same connections: one writer,
+/// which statements that change anything take turns on, and a pool of
+/// readers, so any number of SELECTs run at once and alongside the writer.
+///
+/// Each SELECT sees every transaction committed before it started and none
+/// of one still open. Readers only wait while a write is being made
+/// visible: a commit, or in rollback-journal mode a statement run outside
+/// a transaction.
+#[derive(Clone)]
+pub struct Database {
+    shared: Arc<Shared>
+}
+
+struct Shared {
+    data_directory: PathBuf,
+    writer: Mutex<ExecutionEngine>,
+    readers: Mutex<Vec<ExecutionEngine>>,
+    /// The private data directory a SQLite file was read into, which is
+    /// removed once the last handle goes. A database with one is read-only.
+    copy: Option<TempDir>
+}
+
+impl Database {
+    pub fn open<P: AsRef<Path>>(data_directory: P) -> io::Result<Self> {
+        let data_directory = data_directory.as_ref().to_path_buf();
+        let writer = ExecutionEngine::new(DataStore::new(&data_directory)?);
+        Ok(Database {
+            shared: Arc::new(Shared {
+                data_directory,
+                writer: Mutex::new(writer),
+                readers: Mutex::new(Vec::new()),
+                copy: None
+            })
+        })
+    }
+
+    /// Opens a SQLite 3 database file, read-only, so SELECTs run against
+    /// its tables.
+    ///
+    /// Pages are not read on demand: every row of the file is copied up
+    /// front into a private data directory that goes away with the last
+    /// handle, and SELECTs run against that snapshot. Opening a large file
+    /// therefore takes time and temporary disk space in proportion to its
+    /// size, and later changes to the file are not seen. Each
+    /// column keeps the values SQLite stored, whatever its declared type.
+    /// Views are created too where this engine can parse their SQL; indexes
+    /// and triggers are left out, as are tables declared WITHOUT ROWID.
+    pub fn open_sqlite_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = SqliteFile::open(path)?;
+        let copy = TempDir::new()?;
+
+        let mut data_store = DataStore::new(copy.path())?;
+        for (name, schema, rows) in file.tables()? {
+            data_store.create_table(name.clone(), schema)?;
+            data_store.insert_rows(&name, rows)?;
+        }
+        let mut writer = ExecutionEngine::new(data_store);
+        for entry in file.schema()?.into_iter().filter(|entry| entry.kind == "view") {
+            if let Some(ast) = entry.sql.and_then(|sql| Parser::new(Lexer::new(&sql)).parse().ok()) {
+                let _ = writer.execute(&ast);
+            }
+        }
+
+        Ok(Database {
+            shared: Arc::new(Shared {
+                data_directory: copy.path().to_path_buf(),
+                writer: Mutex::new(writer),
+                readers: Mutex::new(Vec::new()),
+                copy: Some(copy)
+            })
+        })
+    }
+
+    /// Runs one statement: a SELECT or EXPLAIN on a reader, anything else on the
+    /// writer. Transactions span several statements on the writer, so they
+    /// go through `transaction` instead. A read-only database refuses
+    /// anything but SELECT, EXPLAIN and PRAGMAs that only read.
+    pub fn execute(&self, ast: &ASTNode) -> Result<QueryResult, ExecutionError> {
+        match ast {
+            ASTNode::Select(_) | ASTNode::Explain(_) => self.read(|engine| engine.execute(ast)),
+            ASTNode::Pragma(PragmaStatement { value: None, .. }) if self.is_read_only() => self.writer().execute(ast),
+            _ if self.is_read_only() => Err(Self::read_only()),
+            ASTNode::Begin(_) | ASTNode::Commit | ASTNode::Rollback => Err(ExecutionError::Storage(
+                io::Error::new(io::ErrorKind::InvalidInput, "use Database::transaction to run a transaction")
+            )),
+            _ => self.writer().execute(ast)
+        }
+    }
+
+    /// Parses and checks `sql` on a reader. The statement it gives back runs
+    /// through `execute` or `query` once its parameters are bound, as in
+    /// `db.execute(&statement.to_ast())`.
+    pub fn prepare(&self, sql: &str) -> Result<Statement, ExecutionError> {
+        self.read(|engine| engine.prepare(sql))
+    }
+
+    /// Runs a SELECT on a reader and passes its rows to `f` as they are
+    /// read. The reader goes back to the pool once `f` returns.
+    pub fn query<T>(&self, ast: &ASTNode, f: impl FnOnce(Cursor<'_>) -> Result<T, ExecutionError>) -> Result<T, ExecutionError> {
+        self.read(|engine| f(engine.query(ast)?))
+    }
+
+    /// Runs `f` on the writer inside a transaction, which is committed if
+    /// `f` succeeds and rolled back if it fails. Readers go on seeing the
+    /// state from before it until the commit.
+    pub fn transaction<T>(&self, f: impl FnOnce(&mut ExecutionEngine) -> Result<T, ExecutionError>) -> Result<T, ExecutionError> {
+        if self.is_read_only() {
+            return Err(Self::read_only());
+        }
+        let mut writer = self.writer();
+        writer.begin(TransactionMode::Deferred)?;
+        match f(&mut writer) {
+            Ok(value) => {
+                writer.commit()?;
+                Ok(value)
+            }
+            Err(err) => {
+                writer.rollback()?;
+                Err(err)
+            }
+        }
+    }
+
+    /// Whether this is a SQLite file opened by `open_sqlite_file`, which
+    /// nothing may change.
+    pub fn is_read_only(&self) -> bool {
+        self.shared.copy.is_some()
+    }
+
+    fn read_only() -> ExecutionError {
+        ExecutionError::Storage(io::Error::new(io::ErrorKind::PermissionDenied, "attempt to write a readonly database"))
+    }
+
+    /// Runs `f` on a reader from the pool, opening a new one when all of
+    /// them are in use.
+    fn read<T>(&self, f: impl FnOnce(&mut ExecutionEngine) -> Result<T, ExecutionError>) -> Result<T, ExecutionError> {
+        let pooled = self.shared.readers.lock().unwrap_or_else(PoisonError::into_inner).pop();
+        let mut reader = match pooled {
+            Some(reader) => reader,
+            None => ExecutionEngine::new(DataStore::new(&self.shared.data_directory)?)
+        };
+        let result = f(&mut reader);
+        self.shared.readers.lock().unwrap_or_else(PoisonError::into_inner).push(reader);
+        result
+    }
+
+    /// The writer, with any transaction left open by a thread that panicked
+    /// while holding it rolled back.
+    fn writer(&self) -> MutexGuard<'_, ExecutionEngine> {
+        let mut writer = self.shared.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        if writer.in_transaction() {
+            let _ = writer.rollback();
+        }
+        writer
+    }
+}
@@ -1,11 +1,56 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::{File, OpenOptions};
-use std::io::{self, BufReader, BufWriter};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use csv::{ReaderBuilder, WriterBuilder };
+use serde::Serialize;
 use tempfile::NamedTempFile;
 
-use crate::schema::TableSchema;
+use crate::blob;
+use crate::ast::{TransactionMode, Value};
+use crate::lock::{DirectoryLock, LockMode};
+use crate::evaluator::{column_value, evaluate_condition, evaluate_expression, value_to_field};
+use crate::schema::{DatabaseSchema, ForeignKey, ForeignKeyAction, TableSchema, TriggerSchema, ViewSchema, ROWID_COLUMN};
+use crate::statistics::TableStatistics;
+use crate::wal::{Change, RowChanges, WriteAheadLog};
+
+/// Stored in place of a field whose value is NULL. Rows handed out by the
+/// store omit NULL columns entirely.
+const NULL_MARKER: &str = "\\N";
+
+/// Directory inside the data directory where an open transaction keeps its
+/// copies of the files it changes.
+const JOURNAL_DIRECTORY: &str = "journal";
+
+/// Written into the journal once every staged file is on disk. A journal
+/// holding it is applied when the store is next opened; any other is dropped.
+const COMMIT_MARKER: &str = "COMMIT";
+
+/// Prefix of the temporary files a change is written to before it replaces
+/// the original. Any still around when the store is opened are left over
+/// from a crash and are removed.
+const PENDING_PREFIX: &str = ".pending-";
+
+/// The write-ahead log. While it exists the store is in WAL mode.
+const WAL_FILE: &str = "database-wal";
+
+/// Number of frames after which a commit also checkpoints the log.
+const WAL_AUTOCHECKPOINT: usize = 1000;
+
+/// The file every store opened on the directory locks before touching it.
+const LOCK_FILE: &str = "database-lock";
+
+/// Locked by the one store that is writing, while readers carry on.
+const RESERVED_LOCK_FILE: &str = "database-reserved";
+
+const DEFAULT_BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+type RowCondition<'a> = &'a dyn Fn(&HashMap<String, String>) -> bool;
+
+/// A row of a parent table as it was before a statement, paired with what it
+/// became: `None` when the row was deleted.
+type ParentChange = (HashMap<String, String>, Option<HashMap<String, String>>);
 
 pub struct Row {
     pub values: Vec<String>
@@ -20,25 +65,178 @@ pub enum ColumnType {
 
 pub struct DataStore {
     data_directory: PathBuf,
-    schemas: HashMap<String, TableSchema> 
+    schemas: HashMap<String, TableSchema>,
+    foreign_keys: bool,
+    max_rowids: HashMap<String, i64>,
+    sequences: HashMap<String, i64>,
+    statistics: HashMap<String, TableStatistics>,
+    views: HashMap<String, ViewSchema>,
+    triggers: HashMap<String, TriggerSchema>,
+    last_insert_rowid: i64,
+    transaction: Option<Transaction>,
+    wal: Option<WriteAheadLog>,
+    wal_rows: HashMap<String, RowChanges>,
+    lock: DirectoryLock,
+    lock_depth: usize,
+    change_counter: Option<u64>,
+    busy_timeout: Duration
+}
+
+/// How committed changes reach the data files, as set by
+/// `PRAGMA journal_mode`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum JournalMode {
+    /// Changed tables are staged in a journal directory and moved into
+    /// place on commit.
+    Delete,
+    /// Commits append the changed rows to a write-ahead log, which a
+    /// checkpoint later folds into the data files.
+    Wal
+}
+
+/// An open transaction. In rollback-journal mode the tables it writes to
+/// are copied into the journal and changed there; in WAL mode the changed
+/// rows are kept in `rows` instead. Either way the originals stay as they
+/// were until commit, and the in-memory state it started from is kept for
+/// rollback. Tables it creates in WAL mode get their data files straight
+/// away, so those are listed in `created` for rollback to remove.
+struct Transaction {
+    mode: TransactionMode,
+    holds_lock: bool,
+    staged: HashSet<String>,
+    created: Vec<String>,
+    rows: HashMap<String, RowChanges>,
+    catalog_changed: bool,
+    schemas: HashMap<String, TableSchema>,
+    sequences: HashMap<String, i64>,
+    statistics: HashMap<String, TableStatistics>,
+    views: HashMap<String, ViewSchema>,
+    triggers: HashMap<String, TriggerSchema>,
+    max_rowids: HashMap<String, i64>
+}
+
+/// The rows of a table in file order, with the changes still waiting in the
+/// write-ahead log or in the open transaction laid over them. Rows that only
+/// exist in those changes come last, in rowid order.
+struct TableRows {
+    records: csv::StringRecordsIntoIter<BufReader<File>>,
+    headers: Vec<String>,
+    alias: Option<String>,
+    changes: RowChanges
+}
+
+impl Iterator for TableRows {
+    type Item = io::Result<HashMap<String, String>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let row = match self.records.next() {
+                Some(Ok(record)) => DataStore::from_record(&self.headers, &record, self.alias.as_ref()),
+                Some(Err(err)) => return Some(Err(err.into())),
+                None => match self.changes.pop_first()? {
+                    (_, Some(row)) => return Some(Ok(self.with_alias(row))),
+                    (_, None) => continue
+                }
+            };
+
+            let rowid = row.get(ROWID_COLUMN).and_then(|field| field.parse::<i64>().ok());
+            match rowid.and_then(|rowid| self.changes.remove(&rowid)) {
+                None => return Some(Ok(row)),
+                Some(Some(changed)) => return Some(Ok(self.with_alias(changed))),
+                Some(None) => continue
+            }
+        }
+    }
+}
+
+impl TableRows {
+    fn with_alias(&self, mut row: HashMap<String, String>) -> HashMap<String, String> {
+        if let (Some(alias), Some(rowid)) = (&self.alias, row.get(ROWID_COLUMN).cloned()) {
+            row.insert(alias.clone(), rowid);
+        }
+        row
+    }
+}
+
+/// Where a statement that rewrites a table sends the rows it keeps: a new
+/// copy of the data file, or in WAL mode just the rows that changed.
+enum RowSink {
+    File { temp_file: NamedTempFile, writer: Box<csv::Writer<BufWriter<File>>> },
+    Changes { removed: Vec<i64>, written: Vec<(i64, HashMap<String, String>)> }
+}
+
+impl RowSink {
+    /// Passes on a row that stays in the table. `changed_from` is the rowid
+    /// it had before the statement, if the statement changed it.
+    fn keep(&mut self, headers: &[String], row: &HashMap<String, String>, changed_from: Option<i64>) -> io::Result<()> {
+        match self {
+            RowSink::File { writer, .. } => writer.write_record(DataStore::to_record(headers, row))?,
+            RowSink::Changes { removed, written } => {
+                if let Some(old_rowid) = changed_from {
+                    removed.push(old_rowid);
+                    let stored = headers.iter().filter_map(|col| row.get(col).map(|field| (col.clone(), field.clone()))).collect();
+                    written.push((DataStore::parse_rowid(&row[ROWID_COLUMN])?, stored));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, rowid: i64) {
+        if let RowSink::Changes { removed, .. } = self {
+            removed.push(rowid);
+        }
+    }
 }
 
 impl DataStore {
+    /// Opens the store in `data_directory`, creating it if needed. Whatever
+    /// a crashed session left behind is recovered first when no other store
+    /// is using the directory; otherwise this only waits for a shared lock.
     pub fn new<P: AsRef<Path>>(data_directory: P) -> io::Result<Self> {
         let data_dir = data_directory.as_ref().to_path_buf();
         std::fs::create_dir_all(&data_dir)?;
+        let lock = DirectoryLock::open(&data_dir.join(LOCK_FILE), &data_dir.join(RESERVED_LOCK_FILE))?;
 
         let mut store = DataStore {
             data_directory: data_dir,
-            schemas: HashMap::new()
+            schemas: HashMap::new(),
+            foreign_keys: true,
+            max_rowids: HashMap::new(),
+            sequences: HashMap::new(),
+            statistics: HashMap::new(),
+            views: HashMap::new(),
+            triggers: HashMap::new(),
+            last_insert_rowid: 0,
+            transaction: None,
+            wal: None,
+            wal_rows: HashMap::new(),
+            lock,
+            lock_depth: 0,
+            change_counter: None,
+            busy_timeout: DEFAULT_BUSY_TIMEOUT
         };
 
-        store.load_schemas()?;
+        store.busy_timeout = Duration::ZERO;
+        let repaired = store.locked(LockMode::Exclusive, |store| {
+            store.repair_files()?;
+            store.add_missing_rowids()?;
+            if store.wal.as_ref().is_some_and(|wal| wal.frame_count() > 0) {
+                store.checkpoint()?;
+            }
+            Ok(())
+        });
+        store.busy_timeout = DEFAULT_BUSY_TIMEOUT;
+        match repaired {
+            Err(err) if err.kind() == io::ErrorKind::ResourceBusy => store.locked(LockMode::Shared, |_| Ok(()))?,
+            result => result?
+        }
         Ok(store)
     }
 
     fn load_schemas(&mut self) -> io::Result<()> {
         let schema_file = self.data_directory.join("schemas.json");
+        self.schemas.clear();
         if schema_file.exists() {
             let file = File::open(schema_file)?;
             self.schemas = serde_json::from_reader(file)?;
@@ -47,68 +245,578 @@ impl DataStore {
     }
 
     fn save_schemas(&mut self) -> io::Result<()> {
-        let schema_file = self.data_directory.join("schemas.json");
-        let file = File::create(schema_file)?;
-        serde_json::to_writer(file, &self.schemas)?;
+        // Inside a transaction the schemas are written on commit.
+        if let Some(transaction) = &mut self.transaction {
+            transaction.catalog_changed = true;
+            return Ok(());
+        }
+        self.save_json("schemas.json", &self.schemas)
+    }
+
+    /// The largest rowid ever handed out to each AUTOINCREMENT table, kept
+    /// apart from the schemas since it changes with every insert.
+    fn load_sequences(&mut self) -> io::Result<()> {
+        let sequence_file = self.data_directory.join("sequences.json");
+        self.sequences.clear();
+        if sequence_file.exists() {
+            let file = File::open(sequence_file)?;
+            self.sequences = serde_json::from_reader(file)?;
+        }
+        Ok(())
+    }
+
+    fn save_sequences(&mut self) -> io::Result<()> {
+        if let Some(transaction) = &mut self.transaction {
+            transaction.catalog_changed = true;
+            return Ok(());
+        }
+        self.save_json("sequences.json", &self.sequences)
+    }
+
+    /// What the last `ANALYZE` of each table found, for the planner.
+    fn load_statistics(&mut self) -> io::Result<()> {
+        let statistics_file = self.data_directory.join("statistics.json");
+        self.statistics.clear();
+        if statistics_file.exists() {
+            let file = File::open(statistics_file)?;
+            self.statistics = serde_json::from_reader(file)?;
+        }
+        Ok(())
+    }
+
+    fn save_statistics(&mut self) -> io::Result<()> {
+        if let Some(transaction) = &mut self.transaction {
+            transaction.catalog_changed = true;
+            return Ok(());
+        }
+        self.save_json("statistics.json", &self.statistics)
+    }
+
+    /// The stored SELECT of each view.
+    fn load_views(&mut self) -> io::Result<()> {
+        let views_file = self.data_directory.join("views.json");
+        self.views.clear();
+        if views_file.exists() {
+            let file = File::open(views_file)?;
+            self.views = serde_json::from_reader(file)?;
+        }
+        Ok(())
+    }
+
+    fn save_views(&mut self) -> io::Result<()> {
+        if let Some(transaction) = &mut self.transaction {
+            transaction.catalog_changed = true;
+            return Ok(());
+        }
+        self.save_json("views.json", &self.views)
+    }
+
+    fn load_triggers(&mut self) -> io::Result<()> {
+        let triggers_file = self.data_directory.join("triggers.json");
+        self.triggers.clear();
+        if triggers_file.exists() {
+            let file = File::open(triggers_file)?;
+            self.triggers = serde_json::from_reader(file)?;
+        }
+        Ok(())
+    }
+
+    fn save_triggers(&mut self) -> io::Result<()> {
+        if let Some(transaction) = &mut self.transaction {
+            transaction.catalog_changed = true;
+            return Ok(());
+        }
+        self.save_json("triggers.json", &self.triggers)
+    }
+
+    fn save_json<T: Serialize>(&self, file_name: &str, value: &T) -> io::Result<()> {
+        let temp_file = self.pending_file()?;
+        let mut writer = BufWriter::new(&temp_file);
+        serde_json::to_writer(&mut writer, value)?;
+        writer.flush()?;
+        drop(writer);
+        Self::replace_file(temp_file, &self.data_directory.join(file_name))
+    }
+
+    /// Cleans up after a crash: removes abandoned temporary files and the
+    /// data files of tables whose creation never committed, cuts off a row
+    /// that was only partly appended and brings AUTOINCREMENT sequences up
+    /// to the rows that made it to disk. A table whose data file is missing
+    /// cannot be repaired, so opening the store fails.
+    fn repair_files(&mut self) -> io::Result<()> {
+        for entry in std::fs::read_dir(&self.data_directory)? {
+            let entry = entry?;
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let orphaned = file_name.strip_suffix(".csv").is_some_and(|table_name| {
+                !self.schemas.contains_key(table_name)
+                    && Self::read_headers(&entry.path()).is_ok_and(|headers| headers.first().map(String::as_str) == Some(ROWID_COLUMN))
+            });
+            if file_name.starts_with(PENDING_PREFIX) || orphaned {
+                std::fs::remove_file(entry.path())?;
+            }
+        }
+
+        let mut table_names: Vec<String> = self.schemas.keys().cloned().collect();
+        table_names.sort();
+        for table_name in table_names {
+            let file_path = self.data_directory.join(format!("{}.csv", table_name));
+            if !file_path.exists() {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("missing data file for table {table_name}")));
+            }
+            Self::truncate_partial_record(&file_path)?;
+
+            if self.schemas[&table_name].is_autoincrement() {
+                let max_rowid = self.max_rowid(&table_name)?;
+                if self.sequences.get(&table_name).is_some_and(|seq| *seq < max_rowid) {
+                    self.sequences.insert(table_name, max_rowid);
+                    self.save_sequences()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Every complete record ends in a newline, so anything after the last
+    /// one is what was left of an append cut short.
+    fn truncate_partial_record(file_path: &Path) -> io::Result<()> {
+        let mut file = OpenOptions::new().read(true).write(true).open(file_path)?;
+        let mut end = file.seek(SeekFrom::End(0))?;
+        let mut buffer = [0; 4096];
+        while end > 0 {
+            let start = end.saturating_sub(buffer.len() as u64);
+            let chunk = &mut buffer[..(end - start) as usize];
+            file.seek(SeekFrom::Start(start))?;
+            file.read_exact(chunk)?;
+            if let Some(newline) = chunk.iter().rposition(|byte| *byte == b'\n') {
+                end = start + newline as u64 + 1;
+                break;
+            }
+            end = start;
+        }
+
+        if end < file.metadata()?.len() {
+            file.set_len(end)?;
+            file.sync_all()?;
+        }
+        Ok(())
+    }
+
+    /// A temporary file in the data directory, so it can be renamed over the
+    /// file it replaces.
+    fn pending_file(&self) -> io::Result<NamedTempFile> {
+        tempfile::Builder::new().prefix(PENDING_PREFIX).tempfile_in(&self.data_directory)
+    }
+
+    /// Moves a fully written temporary file over `path`, making sure both the
+    /// contents and the rename are on disk before returning. The file keeps
+    /// the permissions of the one it replaces.
+    fn replace_file(temp_file: NamedTempFile, path: &Path) -> io::Result<()> {
+        match std::fs::metadata(path) {
+            Ok(metadata) => temp_file.as_file().set_permissions(metadata.permissions())?,
+            Err(_) => Self::set_new_file_permissions(temp_file.as_file())?
+        }
+        temp_file.as_file().sync_all()?;
+        temp_file.persist(path).map_err(|err| err.error)?;
+        Self::sync_directory(path.parent().unwrap_or(Path::new(".")))
+    }
+
+    /// Temporary files are only readable by their owner; a new data file
+    /// gets the mode `File::create` would have given it.
+    #[cfg(unix)]
+    fn set_new_file_permissions(file: &File) -> io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o644))
+    }
+
+    #[cfg(not(unix))]
+    fn set_new_file_permissions(_file: &File) -> io::Result<()> {
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn sync_directory(directory: &Path) -> io::Result<()> {
+        File::open(directory)?.sync_all()
+    }
+
+    #[cfg(not(unix))]
+    fn sync_directory(_directory: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Tables written before rowids existed get one per row, numbered in
+    /// file order.
+    fn add_missing_rowids(&mut self) -> io::Result<()> {
+        for table_name in self.schemas.keys() {
+            let file_path = self.data_directory.join(format!("{}.csv", table_name));
+            let headers = Self::read_headers(&file_path)?;
+            if headers.first().map(String::as_str) == Some(ROWID_COLUMN) {
+                continue;
+            }
+
+            let mut reader = ReaderBuilder::new().from_reader(BufReader::new(File::open(&file_path)?));
+
+            let temp_file = self.pending_file()?;
+            let mut writer = WriterBuilder::new().from_writer(BufWriter::new(&temp_file));
+            writer.write_record(std::iter::once(ROWID_COLUMN).chain(headers.iter().map(String::as_str)))?;
+
+            for (i, result) in reader.records().enumerate() {
+                let record = result?;
+                let rowid = (i + 1).to_string();
+                writer.write_record(std::iter::once(rowid.as_str()).chain(record.iter()))?;
+            }
+
+            writer.flush()?;
+            drop(writer);
+            Self::replace_file(temp_file, &file_path)?;
+        }
         Ok(())
     }
 
-    pub fn create_table(&mut self, name: String, schema: TableSchema) -> io::Result<()> {
-        if self.schemas.contains_key(&name) {
+    pub fn create_table(&mut self, name: String, mut schema: TableSchema) -> io::Result<()> {
+        if !self.ready_to_write() {
+            return self.prepare_write(|store| store.create_table(name, schema));
+        }
+
+        if self.schemas.contains_key(&name) || self.views.contains_key(&name) {
             return Err(io::Error::new(io::ErrorKind::AlreadyExists, "Table already exists"));
         }
 
-        let data_file_path = self.data_directory.join(format!("{}.csv", name));
-        let data_file = File::create(data_file_path)?;
-        let mut writer = WriterBuilder::new().from_writer(data_file);
-        writer.write_record(schema.columns.keys())?;
-        
+        schema.column_order = schema.column_names();
+
+        if schema.is_autoincrement() && schema.rowid_alias().is_none() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "AUTOINCREMENT is only allowed on an INTEGER PRIMARY KEY"));
+        }
+
+        for fk in schema.foreign_keys() {
+            let parent_schema = if fk.parent_table == name {
+                &schema
+            } else {
+                self.schemas.get(&fk.parent_table)
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Referenced table {} not found", fk.parent_table)))?
+            };
+            let columns_exist = fk.columns.iter().all(|col| schema.columns.contains_key(col))
+                && fk.parent_columns.iter().all(|col| parent_schema.columns.contains_key(col));
+            if !columns_exist || fk.columns.len() != fk.parent_columns.len() {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("foreign key mismatch - {name} referencing {}", fk.parent_table)));
+            }
+        }
+
+        // The data file goes in place before the schema that refers to it, so
+        // a crash in between leaves at most an unused file behind.
+        let data_file_path = self.writable_table_path(&name)?;
+        let temp_file = self.pending_file()?;
+        let mut writer = WriterBuilder::new().from_writer(BufWriter::new(&temp_file));
+        // An INTEGER PRIMARY KEY is the rowid, so it has no field of its own.
+        let alias = schema.rowid_alias();
+        let stored_columns = schema.column_order.iter().filter(|col| Some(*col) != alias.as_ref());
+        writer.write_record(std::iter::once(&ROWID_COLUMN.to_string()).chain(stored_columns))?;
+        writer.flush()?;
+        drop(writer);
+        Self::replace_file(temp_file, &data_file_path)?;
+        if let (Some(transaction), Some(_)) = (&mut self.transaction, &self.wal) {
+            transaction.created.push(name.clone());
+        }
+
         self.schemas.insert(name, schema);
         self.save_schemas()?;
         Ok(())
     }
 
+    pub fn create_view(&mut self, name: String, view: ViewSchema) -> io::Result<()> {
+        if !self.ready_to_write() {
+            return self.prepare_write(|store| store.create_view(name, view));
+        }
+
+        if self.schemas.contains_key(&name) || self.views.contains_key(&name) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, "Table already exists"));
+        }
+        self.views.insert(name, view);
+        self.save_views()
+    }
+
+    pub fn drop_view(&mut self, name: &str) -> io::Result<()> {
+        if !self.ready_to_write() {
+            return self.prepare_write(|store| store.drop_view(name));
+        }
+
+        if self.views.remove(name).is_none() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("no such view: {name}")));
+        }
+        // The view's INSTEAD OF triggers go with it.
+        let trigger_count = self.triggers.len();
+        self.triggers.retain(|_, trigger| trigger.table != name);
+        if self.triggers.len() != trigger_count {
+            self.save_triggers()?;
+        }
+        self.save_views()
+    }
+
+    pub fn create_trigger(&mut self, name: String, trigger: TriggerSchema) -> io::Result<()> {
+        if !self.ready_to_write() {
+            return self.prepare_write(|store| store.create_trigger(name, trigger));
+        }
+
+        if self.triggers.contains_key(&name) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("trigger {name} already exists")));
+        }
+        self.triggers.insert(name, trigger);
+        self.save_triggers()
+    }
+
+    pub fn drop_trigger(&mut self, name: &str) -> io::Result<()> {
+        if !self.ready_to_write() {
+            return self.prepare_write(|store| store.drop_trigger(name));
+        }
+
+        if self.triggers.remove(name).is_none() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("no such trigger: {name}")));
+        }
+        self.save_triggers()
+    }
+
+    pub fn get_trigger(&self, name: &str) -> Option<&TriggerSchema> {
+        self.triggers.get(name)
+    }
+
+    /// The triggers on a table or view, in name order.
+    pub fn triggers_on(&self, table: &str) -> Vec<(String, TriggerSchema)> {
+        let mut triggers: Vec<(String, TriggerSchema)> = self.triggers.iter()
+            .filter(|(_, trigger)| trigger.table == table)
+            .map(|(name, trigger)| (name.clone(), trigger.clone()))
+            .collect();
+        triggers.sort_by(|(a, _), (b, _)| a.cmp(b));
+        triggers
+    }
+
+    pub fn get_view(&self, name: &str) -> Option<&ViewSchema> {
+        self.views.get(name)
+    }
+
+    /// Every view, in name order.
+    pub fn view_names(&self) -> Vec<String> {
+        let mut views: Vec<String> = self.views.keys().cloned().collect();
+        views.sort();
+        views
+    }
+
+    /// The columns a view has and their types, taken from the table, or
+    /// view, it selects from. `None` if that no longer exists.
+    pub fn view_schema(&self, name: &str) -> Option<TableSchema> {
+        let view = self.views.get(name)?;
+        let base = match self.schemas.get(&view.query.table) {
+            Some(schema) => schema.clone(),
+            None => self.view_schema(&view.query.table)?
+        };
+        let selected = if view.query.columns.contains(&"*".to_string()) {
+            base.column_names()
+        } else {
+            view.query.columns.clone()
+        };
+
+        let columns = view.columns.iter().zip(&selected)
+            .map(|(column, selected)| (column.clone(), base.column_type(selected).cloned().unwrap_or(crate::schema::ColumnType::String)))
+            .collect();
+        Some(TableSchema { columns, column_order: view.columns.clone(), ..Default::default() })
+    }
+
+    /// Reads `table_name`, or every table, and keeps statistics on its
+    /// rows for the planner. They are not kept up to date as rows change;
+    /// running ANALYZE again refreshes them.
+    pub fn analyze(&mut self, table_name: Option<&str>) -> io::Result<()> {
+        if !self.ready_to_write() {
+            return self.prepare_write(|store| store.analyze(table_name));
+        }
+
+        let tables = match table_name {
+            Some(table_name) if !self.schemas.contains_key(table_name) => {
+                return Err(io::Error::new(io::ErrorKind::NotFound, "Table not found"));
+            }
+            Some(table_name) => vec![table_name.to_string()],
+            None => self.table_names()
+        };
+        for table in tables {
+            let statistics = TableStatistics::collect(self.scan(&table)?, &self.schemas[&table])?;
+            self.statistics.insert(table, statistics);
+        }
+        self.save_statistics()
+    }
+
+    /// What the last `ANALYZE` found in a table, if it has been analyzed.
+    pub fn table_statistics(&self, table_name: &str) -> Option<&TableStatistics> {
+        self.statistics.get(table_name)
+    }
+
+    /// The largest rowid an AUTOINCREMENT table has handed out, which is
+    /// never given out again.
+    pub fn sequence(&self, table_name: &str) -> Option<i64> {
+        self.sequences.get(table_name).copied()
+    }
+
     pub fn insert_row(&mut self, table_name: &str, row: HashMap<String, String>) -> io::Result<()> {
+        self.insert_row_with_nulls(table_name, row.into_iter().map(|(k, v)| (k, Some(v))).collect())
+    }
+
+    /// Like `insert_row`, but a `None` value stores an explicit NULL rather
+    /// than falling back to the column default.
+    pub fn insert_row_with_nulls(&mut self, table_name: &str, row: HashMap<String, Option<String>>) -> io::Result<()> {
+        self.insert_rows(table_name, vec![row]).map(|_| ())
+    }
+
+    /// Inserts several rows as one write, returning how many there were.
+    /// Every row is checked before any is written, so a row that breaks a
+    /// constraint keeps the whole batch out.
+    pub fn insert_rows(&mut self, table_name: &str, rows: Vec<HashMap<String, Option<String>>>) -> io::Result<usize> {
+        self.insert_rows_returning(table_name, rows).map(|rows| rows.len())
+    }
+
+    /// Like `insert_rows`, but returns the rows as they were stored, with
+    /// their rowids and defaults filled in.
+    pub fn insert_rows_returning(&mut self, table_name: &str, rows: Vec<HashMap<String, Option<String>>>) -> io::Result<Vec<HashMap<String, String>>> {
+        if !self.ready_to_write() {
+            return self.prepare_write(|store| store.insert_rows_returning(table_name, rows));
+        }
+
         let table_schema = self.schemas.get(table_name)
-            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Table not found"))?;
-        
-        if row.len() != table_schema.columns.len() {
-            return Err(io::Error::new(io::ErrorKind::InvalidData, "Row does not match schema"));
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Table not found"))?
+            .clone();
+        let alias = table_schema.rowid_alias();
+        let primary_key = table_schema.primary_key();
+        let checks_primary_key = !primary_key.is_empty() && alias.is_none();
+
+        let mut max_rowid = self.max_rowid(table_name)?;
+        let mut sequence = if table_schema.is_autoincrement() { self.sequences.get(table_name).copied().unwrap_or(0) } else { 0 };
+        // Keys already in the table are read once, and only when needed.
+        let mut stored_rowids: Option<HashSet<i64>> = None;
+        let mut primary_keys = if checks_primary_key { Some(self.key_set(table_name, &primary_key)?) } else { None };
+        let mut batch_rowids = HashSet::new();
+
+        let mut complete_rows = Vec::with_capacity(rows.len());
+        for row in rows {
+            let mut complete_row = Self::complete_row(table_name, &table_schema, &row)?;
+
+            let explicit_rowid = alias.as_ref().and_then(|alias| complete_row.get(alias))
+                .or(row.get(ROWID_COLUMN).and_then(|field| field.as_ref()));
+            let rowid = match explicit_rowid {
+                Some(field) => {
+                    let rowid = Self::parse_rowid(field)?;
+                    if stored_rowids.is_none() {
+                        stored_rowids = Some(self.rowids(table_name)?);
+                    }
+                    if batch_rowids.contains(&rowid) || stored_rowids.as_ref().is_some_and(|stored| stored.contains(&rowid)) {
+                        return Err(Self::unique_failed(table_name, &[alias.clone().unwrap_or_else(|| ROWID_COLUMN.to_string())]));
+                    }
+                    rowid
+                }
+                None => max_rowid.max(sequence) + 1
+            };
+            complete_row.insert(ROWID_COLUMN.to_string(), rowid.to_string());
+            if let Some(alias) = &alias {
+                complete_row.insert(alias.clone(), rowid.to_string());
+            }
+
+            Self::check_constraints(table_name, &table_schema, &complete_row)?;
+            if let (Some(keys), Some(key)) = (&mut primary_keys, Self::key_of(&complete_row, &primary_key, &table_schema)) {
+                if !keys.insert(key) {
+                    return Err(Self::unique_failed(table_name, &primary_key));
+                }
+            }
+
+            batch_rowids.insert(rowid);
+            max_rowid = max_rowid.max(rowid);
+            if table_schema.is_autoincrement() {
+                sequence = sequence.max(rowid);
+            }
+            complete_rows.push((rowid, complete_row));
         }
-        
-        let file_path = self.data_directory.join(format!("{}.csv", table_name));
-        let file = OpenOptions::new().append(true).open(file_path)?;
-        let mut writer = WriterBuilder::new().from_writer(file);
-        
-        let record: Vec<String> = table_schema.columns.keys()
-            .map(|col| row.get(col).cloned().unwrap_or_default())
+
+        let checked: Vec<HashMap<String, String>> = complete_rows.iter().map(|(_, row)| row.clone()).collect();
+        self.check_parent_keys(table_name, &table_schema, &checked)?;
+
+        let file_path = self.writable_table_path(table_name)?;
+        let headers = Self::read_headers(&file_path)?;
+
+        if self.wal.is_some() {
+            let changes = complete_rows.iter()
+                .map(|(rowid, row)| {
+                    let stored = headers.iter().filter_map(|col| row.get(col).map(|field| (col.clone(), field.clone()))).collect();
+                    (*rowid, Some(stored))
+                })
+                .collect();
+            self.stage_rows(table_name, changes)?;
+        } else {
+            // Rows are appended in place. Inside a transaction the file is
+            // the journal copy, published on commit; otherwise a record cut
+            // short by a crash is removed by `truncate_partial_record` on the
+            // next open.
+            let file = OpenOptions::new().append(true).open(file_path)?;
+            let mut writer = WriterBuilder::new().from_writer(BufWriter::new(file));
+            for (_, row) in &complete_rows {
+                writer.write_record(Self::to_record(&headers, row))?;
+            }
+            writer.flush()?;
+            if self.transaction.is_none() {
+                writer.get_ref().get_ref().sync_data()?;
+            }
+        }
+
+        self.max_rowids.insert(table_name.to_string(), max_rowid);
+        if table_schema.is_autoincrement() && self.sequences.get(table_name).is_none_or(|seq| *seq < sequence) {
+            self.sequences.insert(table_name.to_string(), sequence);
+            self.save_sequences()?;
+        }
+        if let Some((rowid, _)) = complete_rows.last() {
+            self.last_insert_rowid = *rowid;
+        }
+
+        Ok(complete_rows.into_iter().map(|(_, row)| row).collect())
+    }
+
+    /// A row to insert with every column it leaves out filled in from the
+    /// column's default, where there is one.
+    pub(crate) fn complete_row(table_name: &str, table_schema: &TableSchema, row: &HashMap<String, Option<String>>) -> io::Result<HashMap<String, String>> {
+        if let Some(col) = row.keys().find(|col| !table_schema.has_column(col)) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Table {table_name} has no column named {col}")));
+        }
+        let row: HashMap<&str, &Option<String>> = row.iter()
+            .map(|(col, field)| (table_schema.resolve_column(col), field))
             .collect();
-        
-        writer.write_record(&record)?;
-        
-        Ok(())
+
+        let mut complete_row = HashMap::new();
+        for col in table_schema.column_names() {
+            let field = match row.get(col.as_str()) {
+                Some(field) => (*field).clone(),
+                None => table_schema.default_for(&col).and_then(|default| {
+                    let value = evaluate_expression(default, &HashMap::new(), table_schema);
+                    value_to_field(&value, &table_schema.columns[&col])
+                })
+            };
+            if let Some(field) = field {
+                complete_row.insert(col, field);
+            }
+        }
+        Ok(complete_row)
     }
 
-    pub fn select(&self, table_name: &str, columns: &[String], condition: Option<&dyn Fn(&HashMap<String, String>) -> bool>) -> io::Result<Vec<HashMap<String, String>>> {
-        let _table_schema = self.schemas.get(table_name)
+    /// The rowid of the most recent successful insert through this store,
+    /// or 0 if there has not been one.
+    pub fn last_insert_rowid(&self) -> i64 {
+        self.last_insert_rowid
+    }
+
+    /// Reads the rows of a table that satisfy `condition`. This takes no
+    /// lock of its own: when other stores may be writing to the directory,
+    /// hold a shared `lock` around it so the rows are read as one state.
+    pub fn select(&self, table_name: &str, columns: &[String], condition: Option<RowCondition>) -> io::Result<Vec<HashMap<String, String>>> {
+        let table_schema = self.schemas.get(table_name)
             .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Table not found"))?;
         
-        let file_path = self.data_directory.join(format!("{}.csv", table_name));
-        let file = File::open(file_path)?;
-        let mut reader = ReaderBuilder::new().from_reader(BufReader::new(file));
-
-        let headers: Vec<String> = reader.headers()?.iter().map(|s| s.to_string()).collect();
-
         let mut result = Vec::new();
-        for record in reader.records() {
-            let record = record?;
-            let row: HashMap<String, String> = headers.iter().zip(record.iter())
-                .map(|(k, v)| (k.clone(), v.to_string()))
-                .collect();
-            
-            if condition.map_or(true, |cond| cond(&row)) {
+        for row in self.scan(table_name)? {
+            let row = row?;
+
+            if condition.is_none_or(|cond| cond(&row)) {
                 let selected_row: HashMap<String, String> = columns.iter()
-                    .filter_map(|col| row.get(col).map(|val| (col.clone(), val.clone())))
+                    .filter_map(|col| row.get(table_schema.resolve_column(col)).map(|val| (col.clone(), val.clone())))
                     .collect();
                 result.push(selected_row);
             }
@@ -117,85 +825,696 @@ impl DataStore {
         Ok(result)
     }
 
+    /// Reads every row of a table, one at a time as the iterator is
+    /// advanced, so only the row in hand is kept in memory. The iterator
+    /// does not borrow the store; the same locking advice as for `select`
+    /// applies for as long as it is in use.
+    pub fn scan(&self, table_name: &str) -> io::Result<impl Iterator<Item = io::Result<HashMap<String, String>>>> {
+        self.rows(table_name)
+    }
+
     pub fn update(&mut self, table_name: &str, updates: HashMap<String, String>, condition: impl Fn(&HashMap<String, String>) -> bool) -> io::Result<usize> {
-        let _table_schema = self.schemas.get(table_name)
-            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Table not found"))?;
-        
-        let file_path = self.data_directory.join(format!("{}.csv", table_name));
-        let file = File::open(&file_path)?;
-        let mut reader = ReaderBuilder::new().from_reader(BufReader::new(file));
-        
-        let headers: Vec<String> = reader.headers()?.iter().map(|s| s.to_string()).collect();
-        
-        let temp_file = NamedTempFile::new_in(&self.data_directory)?;
-        let mut writer = WriterBuilder::new().from_writer(BufWriter::new(&temp_file));
+        self.update_with(table_name, |row| {
+            if !condition(row) {
+                return false;
+            }
+            for (col, value) in &updates {
+                row.insert(col.clone(), value.clone());
+            }
+            true
+        })
+    }
+
+    /// Rewrites the rows for which `apply` returns true, letting the caller
+    /// change them in place. Removing a column from the row sets it to NULL.
+    /// Nothing is written if any changed row breaks a constraint.
+    pub fn update_with(&mut self, table_name: &str, mut apply: impl FnMut(&mut HashMap<String, String>) -> bool) -> io::Result<usize> {
+        if !self.ready_to_write() {
+            return self.prepare_write(|store| store.update_with(table_name, apply));
+        }
+        if self.transaction.is_none() && self.has_parent_actions(table_name) {
+            return self.autocommit(|store| store.update_with(table_name, apply));
+        }
+
+        let table_schema = self.schemas.get(table_name)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Table not found"))?
+            .clone();
+        let referencing = self.referencing_foreign_keys(table_name);
         
-        writer.write_record(&headers)?;
+        let file_path = self.writable_table_path(table_name)?;
+        let headers = Self::read_headers(&file_path)?;
+        let mut sink = self.row_sink(&headers)?;
         
+        let alias = table_schema.rowid_alias();
+        let primary_key = table_schema.primary_key();
+        let mut rowids = HashSet::new();
+        let mut primary_keys = HashSet::new();
+
         let mut updated_count = 0;
-        for result in reader.records() {
-            let record = result?;
-            let mut row: HashMap<String, String> = headers.iter()
-                .zip(record.iter())
-                .map(|(k, v)| (k.clone(), v.to_string()))
-                .collect();
+        let mut updated_rows = Vec::new();
+        let mut changes = Vec::new();
+        let mut remaining_child_keys = vec![HashSet::new(); referencing.len()];
+        for row in self.rows(table_name)? {
+            let mut row = row?;
+            let original = if referencing.is_empty() { None } else { Some(row.clone()) };
+            let old_rowid = row.get(ROWID_COLUMN).cloned();
+            let mut changed_from = None;
             
-            if condition(&row) {
-                for (col, value) in &updates {
-                    if let Some(_index) = headers.iter().position(|h| h == col) {
-                        row.insert(col.clone(), value.clone());
+            if apply(&mut row) {
+                changed_from = Some(Self::parse_rowid(old_rowid.as_deref().unwrap_or_default())?);
+                if let Some(col) = row.keys().find(|col| !table_schema.has_column(col)) {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Table {table_name} has no column named {col}")));
+                }
+                // Keep the rowid and its alias in step, whichever one changed.
+                if let Some(alias) = &alias {
+                    if row.get(alias) != old_rowid.as_ref() {
+                        let field = row.get(alias).cloned().ok_or_else(Self::datatype_mismatch)?;
+                        row.insert(ROWID_COLUMN.to_string(), field);
+                    } else if let Some(rowid) = row.get(ROWID_COLUMN).cloned() {
+                        row.insert(alias.clone(), rowid);
                     }
                 }
+                let rowid = Self::parse_rowid(row.get(ROWID_COLUMN).ok_or_else(Self::datatype_mismatch)?)?;
+                row.insert(ROWID_COLUMN.to_string(), rowid.to_string());
+                if let Some(alias) = &alias {
+                    row.insert(alias.clone(), rowid.to_string());
+                }
+                Self::check_constraints(table_name, &table_schema, &row)?;
                 updated_count += 1;
-            }
-            
-            writer.write_record(&record)?;
+
+                if self.foreign_keys && !table_schema.foreign_keys().is_empty() {
+                    updated_rows.push(row.clone());
+                }
+                if let Some(original) = original {
+                    changes.push((original, Some(row.clone())));
+                }
+            }
+
+            let rowid = Self::parse_rowid(&row[ROWID_COLUMN])?;
+            if !rowids.insert(rowid) {
+                return Err(Self::unique_failed(table_name, &[alias.clone().unwrap_or_else(|| ROWID_COLUMN.to_string())]));
+            }
+            if alias.is_none() && !primary_key.is_empty() {
+                if let Some(key) = Self::key_of(&row, &primary_key, &table_schema) {
+                    if !primary_keys.insert(key) {
+                        return Err(Self::unique_failed(table_name, &primary_key));
+                    }
+                }
+            }
+
+            Self::collect_self_references(table_name, &table_schema, &referencing, &row, &mut remaining_child_keys);
+            sink.keep(&headers, &row, changed_from)?;
         }
-        
-        drop(writer);
 
-        std::fs::rename(temp_file.path(), &file_path)?;
+        self.check_parent_keys(table_name, &table_schema, &updated_rows)?;
+        self.check_restricted_children(table_name, &referencing, &changes, &remaining_child_keys)?;
+
+        self.finish_rows(table_name, &file_path, sink)?;
+
+        let max_rowid = rowids.iter().copied().max().unwrap_or(0);
+        self.max_rowids.insert(table_name.to_string(), max_rowid);
+        if table_schema.is_autoincrement() && self.sequences.get(table_name).is_none_or(|seq| *seq < max_rowid) {
+            self.sequences.insert(table_name.to_string(), max_rowid);
+            self.save_sequences()?;
+        }
+
+        self.apply_parent_actions(table_name, &referencing, &changes)?;
         
         Ok(updated_count)
     }
 
-    pub fn delete(&mut self, table_name: &str, condition: impl Fn(&HashMap<String, String>) -> bool) -> io::Result<usize> {
-        let _table_schema = self.schemas.get(table_name)
-            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Table not found"))?;
-        
-        let file_path = self.data_directory.join(format!("{}.csv", table_name));
-        let file = File::open(&file_path)?;
-        let mut reader = ReaderBuilder::new().from_reader(BufReader::new(file));
-        
-        let headers: Vec<String> = reader.headers()?.iter().map(|s| s.to_string()).collect();
-        
-        let temp_file = NamedTempFile::new_in(&self.data_directory)?;
-        let mut writer = WriterBuilder::new().from_writer(BufWriter::new(&temp_file));
+    pub fn delete(&mut self, table_name: &str, mut condition: impl FnMut(&HashMap<String, String>) -> bool) -> io::Result<usize> {
+        if !self.ready_to_write() {
+            return self.prepare_write(|store| store.delete(table_name, condition));
+        }
+        if self.transaction.is_none() && self.has_parent_actions(table_name) {
+            return self.autocommit(|store| store.delete(table_name, condition));
+        }
+
+        let table_schema = self.schemas.get(table_name)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Table not found"))?
+            .clone();
+        let referencing = self.referencing_foreign_keys(table_name);
         
-        writer.write_record(&headers)?;
+        let file_path = self.writable_table_path(table_name)?;
+        let headers = Self::read_headers(&file_path)?;
+        let mut sink = self.row_sink(&headers)?;
         
         let mut deleted_count = 0;
-        for result in reader.records() {
-            let record = result?;
-            let row: HashMap<String, String> = headers.iter()
-                .zip(record.iter())
-                .map(|(k, v)| (k.clone(), v.to_string()))
-                .collect();
+        let mut max_rowid = 0;
+        let mut changes = Vec::new();
+        let mut remaining_child_keys = vec![HashSet::new(); referencing.len()];
+        for row in self.rows(table_name)? {
+            let row = row?;
+            let rowid = Self::parse_rowid(&row[ROWID_COLUMN])?;
             
             if !condition(&row) {
-                writer.write_record(&record)?;
+                max_rowid = max_rowid.max(rowid);
+                Self::collect_self_references(table_name, &table_schema, &referencing, &row, &mut remaining_child_keys);
+                sink.keep(&headers, &row, None)?;
             } else {
+                sink.remove(rowid);
                 deleted_count += 1;
+                if !referencing.is_empty() {
+                    changes.push((row, None));
+                }
             }
         }
-        
-        drop(writer);
 
-        std::fs::rename(temp_file.path(), &file_path)?;
+
+        self.check_restricted_children(table_name, &referencing, &changes, &remaining_child_keys)?;
+
+        self.finish_rows(table_name, &file_path, sink)?;
+        self.max_rowids.insert(table_name.to_string(), max_rowid);
+
+        self.apply_parent_actions(table_name, &referencing, &changes)?;
         
         Ok(deleted_count)
     }
 
+    /// Starts a transaction. Until it is committed, changes are visible only
+    /// through this store; a rollback, or a crash before commit, discards them.
+    pub fn begin(&mut self, mode: TransactionMode) -> io::Result<()> {
+        if self.transaction.is_some() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "cannot start a transaction within a transaction"));
+        }
+        let holds_lock = mode != TransactionMode::Deferred;
+        match mode {
+            TransactionMode::Deferred => {}
+            TransactionMode::Immediate => self.acquire(LockMode::Reserved)?,
+            TransactionMode::Exclusive => self.acquire(LockMode::Exclusive)?
+        }
+        self.transaction = Some(Transaction {
+            mode,
+            holds_lock,
+            staged: HashSet::new(),
+            created: Vec::new(),
+            rows: HashMap::new(),
+            catalog_changed: false,
+            schemas: self.schemas.clone(),
+            sequences: self.sequences.clone(),
+            statistics: self.statistics.clone(),
+            views: self.views.clone(),
+            triggers: self.triggers.clone(),
+            max_rowids: self.max_rowids.clone()
+        });
+        Ok(())
+    }
+
+    /// Makes every change of the open transaction visible at once. The staged
+    /// files and the commit marker reach the disk before any original is
+    /// replaced, so a crash part way through is finished on the next open.
+    /// Readers are locked out only while this runs.
+    pub fn commit(&mut self) -> io::Result<()> {
+        let transaction = self.transaction.as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "cannot commit - no transaction is active"))?;
+        let holds_lock = transaction.holds_lock;
+        let result = if holds_lock {
+            self.locked(LockMode::Exclusive, |store| store.finish_commit())
+        } else {
+            self.finish_commit()
+        };
+        if holds_lock && self.transaction.is_none() {
+            self.release()?;
+        }
+        result
+    }
+
+    fn finish_commit(&mut self) -> io::Result<()> {
+        let Some(transaction) = &self.transaction else { return Ok(()) };
+        if self.wal.is_some() {
+            return self.commit_to_wal();
+        }
+        if transaction.staged.is_empty() && !transaction.catalog_changed {
+            self.transaction = None;
+            return Ok(());
+        }
+
+        // If the journal cannot be completed the transaction stays open, so
+        // it can still be retried or rolled back.
+        self.write_commit_marker()?;
+        self.transaction = None;
+
+        Self::apply_journal(&self.data_directory)
+    }
+
+    /// Appends the open transaction's changes to the write-ahead log. Once
+    /// they are on disk they are visible, and the data files are untouched.
+    fn commit_to_wal(&mut self) -> io::Result<()> {
+        let Some(transaction) = &self.transaction else { return Ok(()) };
+
+        let mut tables: Vec<&String> = transaction.rows.keys().collect();
+        tables.sort();
+        let mut changes: Vec<Change> = tables.into_iter()
+            .map(|table| Change::Rows { table: table.clone(), rows: transaction.rows[table].clone() })
+            .collect();
+        if transaction.catalog_changed {
+            changes.push(Change::Catalog {
+                schemas: self.schemas.clone(),
+                sequences: self.sequences.clone(),
+                statistics: self.statistics.clone(),
+                views: self.views.clone(),
+                triggers: self.triggers.clone()
+            });
+        }
+
+        if let (Some(wal), false) = (&mut self.wal, changes.is_empty()) {
+            wal.append_commit(&changes)?;
+        }
+
+        let Some(transaction) = self.transaction.take() else { return Ok(()) };
+        for (table, rows) in transaction.rows {
+            self.wal_rows.entry(table).or_default().extend(rows);
+        }
+
+        if self.wal.as_ref().is_some_and(|wal| wal.frame_count() >= WAL_AUTOCHECKPOINT) {
+            self.checkpoint()?;
+        }
+        Ok(())
+    }
+
+    fn write_commit_marker(&self) -> io::Result<()> {
+        let journal = self.data_directory.join(JOURNAL_DIRECTORY);
+        std::fs::create_dir_all(&journal)?;
+        serde_json::to_writer(File::create(journal.join("schemas.json"))?, &self.schemas)?;
+        serde_json::to_writer(File::create(journal.join("sequences.json"))?, &self.sequences)?;
+        serde_json::to_writer(File::create(journal.join("statistics.json"))?, &self.statistics)?;
+        serde_json::to_writer(File::create(journal.join("views.json"))?, &self.views)?;
+        serde_json::to_writer(File::create(journal.join("triggers.json"))?, &self.triggers)?;
+        for entry in std::fs::read_dir(&journal)? {
+            File::open(entry?.path())?.sync_all()?;
+        }
+        Self::sync_directory(&journal)?;
+
+        let mut marker = File::create(journal.join(COMMIT_MARKER))?;
+        marker.write_all(b"commit")?;
+        marker.sync_all()?;
+        Self::sync_directory(&journal)
+    }
+
+    /// Discards every change of the open transaction.
+    pub fn rollback(&mut self) -> io::Result<()> {
+        let transaction = self.transaction.take()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "cannot rollback - no transaction is active"))?;
+        self.schemas = transaction.schemas;
+        self.sequences = transaction.sequences;
+        self.statistics = transaction.statistics;
+        self.views = transaction.views;
+        self.triggers = transaction.triggers;
+        self.max_rowids = transaction.max_rowids;
+
+        let journal = self.data_directory.join(JOURNAL_DIRECTORY);
+        let mut removed = if journal.exists() { std::fs::remove_dir_all(journal) } else { Ok(()) };
+        for table_name in transaction.created {
+            match std::fs::remove_file(self.data_directory.join(format!("{}.csv", table_name))) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => removed = Err(err),
+                _ => {}
+            }
+        }
+        if transaction.holds_lock {
+            self.release()?;
+        }
+        removed
+    }
+
+    /// Folds every change in the write-ahead log into the data files and
+    /// empties the log, returning how many frames it held. Does nothing
+    /// outside WAL mode.
+    pub fn checkpoint(&mut self) -> io::Result<usize> {
+        if self.wal.is_some() && self.lock.mode() != Some(LockMode::Exclusive) {
+            return self.locked(LockMode::Exclusive, |store| store.checkpoint());
+        }
+        let Some(frame_count) = self.wal.as_ref().map(WriteAheadLog::frame_count) else { return Ok(0) };
+        if self.transaction.is_some() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "cannot checkpoint within a transaction"));
+        }
+
+        let mut tables: Vec<String> = self.wal_rows.keys().cloned().collect();
+        tables.sort();
+        for table_name in tables {
+            let file_path = self.data_directory.join(format!("{}.csv", table_name));
+            let headers = Self::read_headers(&file_path)?;
+            let temp_file = self.pending_file()?;
+            let mut writer = WriterBuilder::new().from_writer(BufWriter::new(&temp_file));
+            writer.write_record(&headers)?;
+            for row in self.rows(&table_name)? {
+                writer.write_record(Self::to_record(&headers, &row?))?;
+            }
+            writer.flush()?;
+            drop(writer);
+            Self::replace_file(temp_file, &file_path)?;
+        }
+        self.save_schemas()?;
+        self.save_sequences()?;
+        self.save_statistics()?;
+        self.save_views()?;
+        self.save_triggers()?;
+
+        // Only once every data file is replaced may the log be emptied; a
+        // crash before that replays it again, which changes nothing twice.
+        self.wal_rows.clear();
+        if let Some(wal) = &mut self.wal {
+            wal.reset()?;
+        }
+        Ok(frame_count)
+    }
+
+    pub fn journal_mode(&self) -> JournalMode {
+        if self.wal.is_some() { JournalMode::Wal } else { JournalMode::Delete }
+    }
+
+    /// Switches between the rollback journal and the write-ahead log. The
+    /// choice is kept in the data directory: the store is in WAL mode for as
+    /// long as the log file exists.
+    pub fn set_journal_mode(&mut self, mode: JournalMode) -> io::Result<()> {
+        if self.lock.mode() != Some(LockMode::Exclusive) {
+            return self.locked(LockMode::Exclusive, |store| store.set_journal_mode(mode));
+        }
+        if mode == self.journal_mode() {
+            return Ok(());
+        }
+        if self.transaction.is_some() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "cannot change journal mode from within a transaction"));
+        }
+
+        let wal_path = self.data_directory.join(WAL_FILE);
+        match mode {
+            JournalMode::Wal => {
+                let (wal, _) = WriteAheadLog::open(&wal_path)?;
+                self.wal = Some(wal);
+            }
+            JournalMode::Delete => {
+                self.checkpoint()?;
+                self.wal = None;
+                std::fs::remove_file(wal_path)?;
+            }
+        }
+        Self::sync_directory(&self.data_directory)
+    }
+
+    /// Reads the committed transactions of the write-ahead log, dropping any
+    /// frames after its last commit.
+    fn load_wal(&mut self) -> io::Result<()> {
+        self.wal = None;
+        self.wal_rows.clear();
+        let wal_path = self.data_directory.join(WAL_FILE);
+        if !wal_path.exists() {
+            return Ok(());
+        }
+
+        let (wal, changes) = WriteAheadLog::open(&wal_path)?;
+        for change in changes {
+            match change {
+                Change::Rows { table, rows } => self.wal_rows.entry(table).or_default().extend(rows),
+                Change::Catalog { schemas, sequences, statistics, views, triggers } => {
+                    self.schemas = schemas;
+                    self.sequences = sequences;
+                    self.statistics = statistics;
+                    self.views = views;
+                    self.triggers = triggers;
+                }
+            }
+        }
+        self.wal = Some(wal);
+        Ok(())
+    }
+
+    /// How long to wait for another store's lock before failing with
+    /// "database is locked".
+    pub fn set_busy_timeout(&mut self, timeout: Duration) {
+        self.busy_timeout = timeout;
+    }
+
+    pub fn busy_timeout(&self) -> Duration {
+        self.busy_timeout
+    }
+
+    /// Locks the data directory until the matching `unlock`. Calls nest, and
+    /// asking for a stronger lock than the one held upgrades it. Anything
+    /// another store changed since this one last held the lock is reloaded
+    /// first.
+    pub fn lock(&mut self, mode: LockMode) -> io::Result<()> {
+        self.acquire(mode)
+    }
+
+    pub fn unlock(&mut self) -> io::Result<()> {
+        self.release()
+    }
+
+    fn acquire(&mut self, mode: LockMode) -> io::Result<()> {
+        if self.lock.mode() >= Some(mode) {
+            self.lock_depth += 1;
+            return Ok(());
+        }
+
+        self.lock.lock(mode, self.busy_timeout)?;
+        self.lock_depth += 1;
+        if let Err(err) = self.catch_up(mode) {
+            self.lock_depth -= 1;
+            if self.lock_depth == 0 {
+                let _ = self.lock.unlock();
+            }
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// Brings this store up to date after taking the lock: a journal no
+    /// writer holds the reserved lock for was left by a crashed one and is
+    /// recovered, and the catalog is reread if another store has written
+    /// since.
+    fn catch_up(&mut self, mode: LockMode) -> io::Result<()> {
+        let journal = self.data_directory.join(JOURNAL_DIRECTORY);
+        let hot_journal = journal.exists()
+            && self.transaction.as_ref().is_none_or(|transaction| !transaction.holds_lock)
+            && !self.lock.reserved_elsewhere()?;
+        if hot_journal {
+            self.lock.lock(LockMode::Exclusive, self.busy_timeout)?;
+            self.recover_journal()?;
+            self.lock.lock(mode, self.busy_timeout)?;
+        }
+
+        let counter = self.lock.change_counter()?;
+        if hot_journal || self.change_counter != Some(counter) {
+            self.reload()?;
+            self.change_counter = Some(counter);
+        }
+        Ok(())
+    }
+
+    fn release(&mut self) -> io::Result<()> {
+        if self.lock_depth == 0 {
+            return Ok(());
+        }
+        self.lock_depth -= 1;
+        if self.lock_depth > 0 {
+            return Ok(());
+        }
+        if self.lock.mode() == Some(LockMode::Exclusive) {
+            self.change_counter = Some(self.lock.bump_change_counter()?);
+        }
+        self.lock.unlock()
+    }
+
+    fn locked<T>(&mut self, mode: LockMode, f: impl FnOnce(&mut Self) -> io::Result<T>) -> io::Result<T> {
+        self.acquire(mode)?;
+        let result = f(self);
+        let released = self.release();
+        let value = result?;
+        released?;
+        Ok(value)
+    }
+
+    /// Rereads the catalog and the write-ahead log, which another store may
+    /// have changed since this one last held the lock.
+    fn reload(&mut self) -> io::Result<()> {
+        self.load_schemas()?;
+        self.load_sequences()?;
+        self.load_statistics()?;
+        self.load_views()?;
+        self.load_triggers()?;
+        self.load_wal()?;
+        self.max_rowids.clear();
+        // A transaction that is only now taking the lock has not written
+        // anything yet, so it starts from the state just read.
+        if let Some(transaction) = &mut self.transaction {
+            transaction.schemas = self.schemas.clone();
+            transaction.sequences = self.sequences.clone();
+            transaction.statistics = self.statistics.clone();
+            transaction.views = self.views.clone();
+            transaction.triggers = self.triggers.clone();
+            transaction.max_rowids.clear();
+        }
+        Ok(())
+    }
+
+    /// Whether a write can go ahead as it is: an open transaction already
+    /// holds the reserved lock, or there is none and, outside WAL mode, the
+    /// exclusive lock is held.
+    fn ready_to_write(&self) -> bool {
+        match &self.transaction {
+            Some(transaction) => transaction.holds_lock,
+            None => self.wal.is_none() && self.lock.mode() == Some(LockMode::Exclusive)
+        }
+    }
+
+    /// Takes what a write needs and then runs it. A transaction keeps its
+    /// changes to itself, so it only needs the reserved lock, which it keeps
+    /// until it ends; in WAL mode a write outside one becomes a transaction
+    /// of its own. Anything else writes the data files in place and needs
+    /// the exclusive lock.
+    fn prepare_write<T>(&mut self, write: impl FnOnce(&mut Self) -> io::Result<T>) -> io::Result<T> {
+        match &mut self.transaction {
+            Some(_) => {
+                self.acquire(LockMode::Reserved)?;
+                if let Some(transaction) = &mut self.transaction {
+                    transaction.holds_lock = true;
+                }
+                write(self)
+            }
+            None if self.wal.is_some() => self.locked(LockMode::Reserved, |store| store.autocommit(write)),
+            None => self.locked(LockMode::Exclusive, write)
+        }
+    }
+
+    /// Runs a write made outside a transaction as a transaction of its own,
+    /// which is how it reaches the write-ahead log.
+    fn autocommit<T>(&mut self, write: impl FnOnce(&mut Self) -> io::Result<T>) -> io::Result<T> {
+        self.begin(TransactionMode::Deferred)?;
+        match write(self) {
+            Ok(result) => {
+                self.commit()?;
+                Ok(result)
+            }
+            Err(err) => {
+                self.rollback()?;
+                Err(err)
+            }
+        }
+    }
+
+    /// Every row of a table as this store currently sees it.
+    fn rows(&self, table_name: &str) -> io::Result<TableRows> {
+        let table_schema = self.schemas.get(table_name)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Table not found"))?;
+        let file = File::open(self.table_path(table_name))?;
+        let mut reader = ReaderBuilder::new().from_reader(BufReader::new(file));
+        let headers = reader.headers()?.iter().map(|s| s.to_string()).collect();
+
+        let mut changes = self.wal_rows.get(table_name).cloned().unwrap_or_default();
+        if let Some(rows) = self.transaction.as_ref().and_then(|transaction| transaction.rows.get(table_name)) {
+            changes.extend(rows.clone());
+        }
+
+        Ok(TableRows { records: reader.into_records(), headers, alias: table_schema.rowid_alias(), changes })
+    }
+
+    fn row_sink(&self, headers: &[String]) -> io::Result<RowSink> {
+        if self.wal.is_some() {
+            return Ok(RowSink::Changes { removed: Vec::new(), written: Vec::new() });
+        }
+        let temp_file = self.pending_file()?;
+        let mut writer = Box::new(WriterBuilder::new().from_writer(BufWriter::new(temp_file.reopen()?)));
+        writer.write_record(headers)?;
+        Ok(RowSink::File { temp_file, writer })
+    }
+
+    fn finish_rows(&mut self, table_name: &str, file_path: &Path, sink: RowSink) -> io::Result<()> {
+        match sink {
+            RowSink::File { temp_file, mut writer } => {
+                writer.flush()?;
+                drop(writer);
+                Self::replace_file(temp_file, file_path)
+            }
+            RowSink::Changes { removed, written } => {
+                // Removals go first so that rows trading rowids both survive.
+                let mut changes: RowChanges = removed.into_iter().map(|rowid| (rowid, None)).collect();
+                changes.extend(written.into_iter().map(|(rowid, row)| (rowid, Some(row))));
+                self.stage_rows(table_name, changes)
+            }
+        }
+    }
+
+    /// Records row changes in the open transaction, for WAL mode.
+    fn stage_rows(&mut self, table_name: &str, changes: RowChanges) -> io::Result<()> {
+        let transaction = self.transaction.as_mut()
+            .ok_or_else(|| io::Error::other("row changes outside a transaction"))?;
+        transaction.rows.entry(table_name.to_string()).or_default().extend(changes);
+        Ok(())
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.transaction.is_some()
+    }
+
+    pub fn transaction_mode(&self) -> Option<TransactionMode> {
+        self.transaction.as_ref().map(|transaction| transaction.mode)
+    }
+
+    /// Finishes or discards a transaction that was open when the store was
+    /// last closed without committing or rolling back.
+    fn recover_journal(&self) -> io::Result<()> {
+        let journal = self.data_directory.join(JOURNAL_DIRECTORY);
+        if journal.join(COMMIT_MARKER).exists() {
+            Self::apply_journal(&self.data_directory)?;
+        } else if journal.exists() {
+            std::fs::remove_dir_all(journal)?;
+        }
+        Ok(())
+    }
+
+    /// Moves every file of a committed journal over its original and then
+    /// removes the journal. Repeating it after a crash part way through is safe.
+    fn apply_journal(data_directory: &Path) -> io::Result<()> {
+        let journal = data_directory.join(JOURNAL_DIRECTORY);
+        for entry in std::fs::read_dir(&journal)? {
+            let entry = entry?;
+            if entry.file_name() != COMMIT_MARKER {
+                std::fs::rename(entry.path(), data_directory.join(entry.file_name()))?;
+            }
+        }
+        Self::sync_directory(data_directory)?;
+        std::fs::remove_dir_all(journal)
+    }
+
+    /// The file a table is read from, which is its journal copy once an open
+    /// transaction has written to it.
+    fn table_path(&self, table_name: &str) -> PathBuf {
+        match &self.transaction {
+            Some(transaction) if transaction.staged.contains(table_name) => {
+                self.data_directory.join(JOURNAL_DIRECTORY).join(format!("{}.csv", table_name))
+            }
+            _ => self.data_directory.join(format!("{}.csv", table_name))
+        }
+    }
+
+    /// The file a write to a table should change. Inside a transaction the
+    /// table is first copied into the journal.
+    fn writable_table_path(&mut self, table_name: &str) -> io::Result<PathBuf> {
+        if let (Some(transaction), None) = (&mut self.transaction, &self.wal) {
+            if !transaction.staged.contains(table_name) {
+                let journal = self.data_directory.join(JOURNAL_DIRECTORY);
+                std::fs::create_dir_all(&journal)?;
+                let original = self.data_directory.join(format!("{}.csv", table_name));
+                if original.exists() {
+                    std::fs::copy(original, journal.join(format!("{}.csv", table_name)))?;
+                }
+                transaction.staged.insert(table_name.to_string());
+            }
+        }
+        Ok(self.table_path(table_name))
+    }
+
+    /// Turns foreign key enforcement on or off, like SQLite's
+    /// `PRAGMA foreign_keys`. It is on for every newly opened store.
+    pub fn set_foreign_keys(&mut self, enabled: bool) {
+        self.foreign_keys = enabled;
+    }
+
+    pub fn foreign_keys_enabled(&self) -> bool {
+        self.foreign_keys
+    }
+
     pub fn get_table_schema(&self, table_name: &str) -> Option<&TableSchema> {
         self.schemas.get(table_name)
     }
@@ -204,7 +1523,308 @@ impl DataStore {
         self.schemas.get_mut(table_name)
     }
 
+    /// Every table, and every view as if it were a table.
+    pub fn schema(&self) -> DatabaseSchema {
+        let mut tables = self.schemas.clone();
+        for view in self.views.keys() {
+            if let Some(schema) = self.view_schema(view) {
+                tables.insert(view.clone(), schema);
+            }
+        }
+        DatabaseSchema { tables }
+    }
+
+    /// Every table, in name order.
+    pub fn table_names(&self) -> Vec<String> {
+        let mut tables: Vec<String> = self.schemas.keys().cloned().collect();
+        tables.sort();
+        tables
+    }
+
     pub fn table_exists(&self, table_name: &str) -> bool {
         self.schemas.contains_key(table_name)
     }
-}
\ No newline at end of file
+
+    fn check_constraints(table_name: &str, table_schema: &TableSchema, row: &HashMap<String, String>) -> io::Result<()> {
+        for col in table_schema.column_names() {
+            if table_schema.is_not_null(&col) && !row.contains_key(&col) {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("NOT NULL constraint failed: {table_name}.{col}")));
+            }
+        }
+
+        // As in SQL, a CHECK that evaluates to NULL does not reject the row.
+        for check in table_schema.checks() {
+            if evaluate_condition(check, row, table_schema) == Some(false) {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("CHECK constraint failed: {table_name}")));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Foreign keys in any table, including this one, that point at `table_name`.
+    pub fn referencing_foreign_keys(&self, table_name: &str) -> Vec<(String, ForeignKey)> {
+        if !self.foreign_keys {
+            return Vec::new();
+        }
+        let mut referencing = Vec::new();
+        for (child, schema) in &self.schemas {
+            for fk in schema.foreign_keys() {
+                if fk.parent_table == table_name {
+                    referencing.push((child.clone(), fk.clone()));
+                }
+            }
+        }
+        referencing
+    }
+
+    /// Makes sure every non-NULL foreign key in `rows` names an existing parent row.
+    fn check_parent_keys(&self, table_name: &str, table_schema: &TableSchema, rows: &[HashMap<String, String>]) -> io::Result<()> {
+        if !self.foreign_keys || rows.is_empty() {
+            return Ok(());
+        }
+
+        for fk in table_schema.foreign_keys() {
+            let mut parent_keys = self.key_set(&fk.parent_table, &fk.parent_columns)?;
+            if fk.parent_table == table_name {
+                // A row may reference itself or another row written with it.
+                parent_keys.extend(rows.iter().filter_map(|row| Self::key_of(row, &fk.parent_columns, table_schema)));
+            }
+
+            for row in rows {
+                if let Some(key) = Self::key_of(row, &fk.columns, table_schema) {
+                    if !parent_keys.contains(&key) {
+                        return Err(Self::foreign_key_failed());
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Rejects a parent change if a RESTRICT or NO ACTION child still refers
+    /// to a key that is going away.
+    fn check_restricted_children(&self, table_name: &str, referencing: &[(String, ForeignKey)], changes: &[ParentChange], remaining_child_keys: &[HashSet<Vec<String>>]) -> io::Result<()> {
+        for (i, (child, fk)) in referencing.iter().enumerate() {
+            let action = if changes.iter().all(|(_, new)| new.is_none()) { &fk.on_delete } else { &fk.on_update };
+            if !matches!(action, ForeignKeyAction::NoAction | ForeignKeyAction::Restrict) {
+                continue;
+            }
+
+            let removed_keys = self.removed_parent_keys(table_name, fk, changes);
+            if removed_keys.is_empty() {
+                continue;
+            }
+
+            // Rows of a self-referencing table are checked as they stand after
+            // the statement, so a whole subtree can go at once.
+            let child_keys = if child == table_name {
+                remaining_child_keys[i].clone()
+            } else {
+                self.key_set(child, &fk.columns)?
+            };
+
+            if removed_keys.keys().any(|key| child_keys.contains(key)) {
+                return Err(Self::foreign_key_failed());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether a write to the table can go on to change the tables that
+    /// refer to it. Such a write runs as a transaction of its own, so that
+    /// a child row the action cannot change undoes the parent change too.
+    fn has_parent_actions(&self, table_name: &str) -> bool {
+        self.referencing_foreign_keys(table_name).iter()
+            .flat_map(|(_, fk)| [&fk.on_delete, &fk.on_update])
+            .any(|action| !matches!(action, ForeignKeyAction::NoAction | ForeignKeyAction::Restrict))
+    }
+
+    /// Carries a committed parent change over to CASCADE, SET NULL and
+    /// SET DEFAULT children.
+    fn apply_parent_actions(&mut self, table_name: &str, referencing: &[(String, ForeignKey)], changes: &[ParentChange]) -> io::Result<()> {
+        for (child, fk) in referencing {
+            let is_delete = changes.iter().all(|(_, new)| new.is_none());
+            let action = if is_delete { &fk.on_delete } else { &fk.on_update };
+
+            let removed_keys = self.removed_parent_keys(table_name, fk, changes);
+            if removed_keys.is_empty() {
+                continue;
+            }
+
+            let child_schema = self.schemas[child].clone();
+            let matches_removed = |row: &HashMap<String, String>| {
+                Self::key_of(row, &fk.columns, &child_schema).and_then(|key| removed_keys.get(&key).cloned())
+            };
+
+            match action {
+                ForeignKeyAction::NoAction | ForeignKeyAction::Restrict => {}
+                ForeignKeyAction::Cascade if is_delete => {
+                    self.delete(child, |row| matches_removed(row).is_some())?;
+                }
+                ForeignKeyAction::Cascade => {
+                    self.update_with(child, |row| match matches_removed(row) {
+                        Some(new_fields) => {
+                            for (col, field) in fk.columns.iter().zip(new_fields.unwrap_or_default()) {
+                                match field {
+                                    Some(field) => row.insert(col.clone(), field),
+                                    None => row.remove(col)
+                                };
+                            }
+                            true
+                        }
+                        None => false
+                    })?;
+                }
+                ForeignKeyAction::SetNull | ForeignKeyAction::SetDefault => {
+                    let set_default = *action == ForeignKeyAction::SetDefault;
+                    self.update_with(child, |row| {
+                        if matches_removed(row).is_none() {
+                            return false;
+                        }
+                        for col in &fk.columns {
+                            let default = child_schema.default_for(col)
+                                .filter(|_| set_default)
+                                .and_then(|expr| value_to_field(&evaluate_expression(expr, &HashMap::new(), &child_schema), &child_schema.columns[col]));
+                            match default {
+                                Some(field) => row.insert(col.clone(), field),
+                                None => row.remove(col)
+                            };
+                        }
+                        true
+                    })?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parent keys that `changes` delete or rewrite, mapped to the new key
+    /// fields for updates and to `None` for deletes.
+    fn removed_parent_keys(&self, table_name: &str, fk: &ForeignKey, changes: &[ParentChange]) -> HashMap<Vec<String>, Option<Vec<Option<String>>>> {
+        let parent_schema = &self.schemas[table_name];
+        let mut removed = HashMap::new();
+        for (old, new) in changes {
+            let Some(old_key) = Self::key_of(old, &fk.parent_columns, parent_schema) else { continue };
+            match new {
+                None => { removed.insert(old_key, None); }
+                Some(new) if Self::key_of(new, &fk.parent_columns, parent_schema).as_ref() != Some(&old_key) => {
+                    let new_fields = fk.parent_columns.iter().map(|col| new.get(col).cloned()).collect();
+                    removed.insert(old_key, Some(new_fields));
+                }
+                Some(_) => {}
+            }
+        }
+        removed
+    }
+
+    fn collect_self_references(table_name: &str, table_schema: &TableSchema, referencing: &[(String, ForeignKey)], row: &HashMap<String, String>, child_keys: &mut [HashSet<Vec<String>>]) {
+        for (i, (child, fk)) in referencing.iter().enumerate() {
+            if child == table_name {
+                if let Some(key) = Self::key_of(row, &fk.columns, table_schema) {
+                    child_keys[i].insert(key);
+                }
+            }
+        }
+    }
+
+    fn key_set(&self, table_name: &str, columns: &[String]) -> io::Result<HashSet<Vec<String>>> {
+        let schema = self.schemas.get(table_name)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Referenced table {table_name} not found")))?;
+        let rows = self.select(table_name, columns, None)?;
+        Ok(rows.iter().filter_map(|row| Self::key_of(row, columns, schema)).collect())
+    }
+
+    /// The typed text of `columns` in `row`, so that 1 in an INTEGER column
+    /// matches 1 in a FLOAT one, or `None` if any of them is NULL.
+    fn key_of(row: &HashMap<String, String>, columns: &[String], schema: &TableSchema) -> Option<Vec<String>> {
+        columns.iter().map(|col| match column_value(row, schema, col) {
+            Value::Null | Value::Parameter(_) => None,
+            Value::Number(n) => Some(n.to_string()),
+            Value::Boolean(b) => Some(u8::from(b).to_string()),
+            Value::Blob(bytes) => Some(blob::to_hex(&bytes)),
+            Value::String(s) => Some(s)
+        }).collect()
+    }
+
+    fn max_rowid(&mut self, table_name: &str) -> io::Result<i64> {
+        if let Some(max_rowid) = self.max_rowids.get(table_name) {
+            return Ok(*max_rowid);
+        }
+        let mut max_rowid = 0;
+        for row in self.select(table_name, &[ROWID_COLUMN.to_string()], None)? {
+            max_rowid = max_rowid.max(Self::parse_rowid(&row[ROWID_COLUMN])?);
+        }
+        self.max_rowids.insert(table_name.to_string(), max_rowid);
+        Ok(max_rowid)
+    }
+
+    fn rowids(&self, table_name: &str) -> io::Result<HashSet<i64>> {
+        self.scan(table_name)?
+            .map(|row| Self::parse_rowid(&row?[ROWID_COLUMN]))
+            .collect()
+    }
+
+    /// Rowids are integers; a float is accepted only if it is whole.
+    fn parse_rowid(field: &str) -> io::Result<i64> {
+        if let Ok(rowid) = field.parse::<i64>() {
+            return Ok(rowid);
+        }
+        match field.parse::<f64>() {
+            Ok(n) if n.fract() == 0.0 => Ok(n as i64),
+            _ => Err(Self::datatype_mismatch())
+        }
+    }
+
+    fn datatype_mismatch() -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, "datatype mismatch")
+    }
+
+    fn unique_failed(table_name: &str, columns: &[String]) -> io::Error {
+        let columns: Vec<String> = columns.iter().map(|col| format!("{table_name}.{col}")).collect();
+        io::Error::new(io::ErrorKind::InvalidData, format!("UNIQUE constraint failed: {}", columns.join(", ")))
+    }
+
+    fn foreign_key_failed() -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, "FOREIGN KEY constraint failed")
+    }
+
+    fn read_headers(file_path: &Path) -> io::Result<Vec<String>> {
+        let file = File::open(file_path)?;
+        let mut reader = ReaderBuilder::new().from_reader(BufReader::new(file));
+        Ok(reader.headers()?.iter().map(|s| s.to_string()).collect())
+    }
+
+    /// Builds a row from a stored record, copying the rowid into its
+    /// INTEGER PRIMARY KEY alias if the table has one.
+    fn from_record(headers: &[String], record: &csv::StringRecord, alias: Option<&String>) -> HashMap<String, String> {
+        let mut row: HashMap<String, String> = headers.iter().zip(record.iter())
+            .filter(|(_, v)| *v != NULL_MARKER)
+            .map(|(k, v)| (k.clone(), v.to_string()))
+            .collect();
+        if let (Some(alias), Some(rowid)) = (alias, row.get(ROWID_COLUMN)) {
+            row.insert(alias.clone(), rowid.clone());
+        }
+        row
+    }
+
+    fn to_record<'r>(headers: &'r [String], row: &'r HashMap<String, String>) -> impl Iterator<Item = &'r str> {
+        headers.iter().map(|col| row.get(col).map_or(NULL_MARKER, |v| v.as_str()))
+    }
+}
+
+impl Drop for DataStore {
+    /// A transaction still open when the store goes away is rolled back,
+    /// and the write-ahead log is checkpointed.
+    fn drop(&mut self) {
+        if self.transaction.is_some() {
+            let _ = self.rollback();
+        }
+        // Leave the log for a later store rather than wait for a lock here.
+        self.busy_timeout = Duration::ZERO;
+        let _ = self.checkpoint();
+    }
+}
@@ -0,0 +1,400 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::ast::Value;
+use crate::evaluator::to_text;
+use crate::schema::ColumnType;
+
+const DAY_MS: i64 = 86_400_000;
+/// Milliseconds from the start of the Julian day count to 1970-01-01.
+const UNIX_EPOCH_MS: i64 = 210_866_760_000_000;
+/// Julian day numbers from here on are read as Unix seconds by 'auto'.
+const AUTO_JULIAN_DAY_LIMIT: f64 = 5_373_484.5;
+/// Moments are kept within this many milliseconds of the start of the day
+/// count, about 285,000 years, so the calendar arithmetic cannot overflow.
+/// Anything past it is long out of range anyway.
+const MOMENT_LIMIT_MS: i64 = 1 << 53;
+/// More months than this move any moment past `MOMENT_LIMIT_MS`.
+const MONTH_LIMIT: f64 = 10_000_000.0;
+
+/// SQLite's date and time functions. Each takes a time value followed by
+/// modifiers, except `strftime`, whose format string comes first.
+pub const FUNCTIONS: [&str; 6] = ["date", "time", "datetime", "julianday", "unixepoch", "strftime"];
+
+pub fn is_function(name: &str) -> bool {
+    FUNCTIONS.contains(&name.to_ascii_lowercase().as_str())
+}
+
+/// A moment in UTC, in milliseconds since noon on the first day of the
+/// Julian day count, the way SQLite keeps it.
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Moment(i64);
+
+/// The parts of a time value written as text, before they are checked.
+struct Parts {
+    date: Option<(i64, i64, i64)>,
+    /// Hours, minutes and milliseconds into the minute.
+    time: Option<(i64, i64, i64)>,
+    /// Minutes east of UTC the value was written in.
+    offset: i64
+}
+
+/// Runs one of the `FUNCTIONS` the way SQLite does, giving NULL for a time
+/// value or modifier it cannot read. Times are always UTC: there is no time
+/// zone database, so 'localtime' and 'utc' leave a time as it is.
+pub fn call(name: &str, args: &[Value]) -> Value {
+    let name = name.to_ascii_lowercase();
+    let (format, args) = match name.as_str() {
+        "strftime" => match args.split_first() {
+            Some((Value::Null, _)) | None => return Value::Null,
+            Some((format, args)) => (Some(to_text(format)), args)
+        },
+        _ => (None, args)
+    };
+
+    // With no time value at all these functions read the current time.
+    let now = [Value::String("now".to_string())];
+    let args = if args.is_empty() { &now[..] } else { args };
+    let Some(moment) = moment(&args[0], &args[1..]) else { return Value::Null };
+
+    match name.as_str() {
+        "date" => Value::String(format_date(moment)),
+        "time" => Value::String(format_time(moment)),
+        "datetime" => Value::String(format!("{} {}", format_date(moment), format_time(moment))),
+        "julianday" => Value::Number(moment.0 as f64 / DAY_MS as f64),
+        "unixepoch" => Value::Number((moment.0 - UNIX_EPOCH_MS).div_euclid(1000) as f64),
+        "strftime" => format.and_then(|format| strftime(&format, moment)).map_or(Value::Null, Value::String),
+        _ => Value::Null
+    }
+}
+
+/// The stored form of a time value written to a DATE, TIME or TIMESTAMP
+/// column: `YYYY-MM-DD`, `HH:MM:SS` and `YYYY-MM-DD HH:MM:SS`, with `.SSS`
+/// after the seconds when there is a fraction of one. These sort as text
+/// in time order. A DATE takes a date with no time of day, a TIME a time
+/// of day alone and a TIMESTAMP either; a time given with a zone is moved
+/// to UTC. `None` for text that is none of these, for a day past the end
+/// of its month, and for any other column type.
+pub fn canonical(column_type: &ColumnType, text: &str) -> Option<String> {
+    let parts = parse_parts(text.trim())?;
+    let moment = parts_moment(&parts)?;
+    if let Some((year, month, day)) = parts.date {
+        // The calendar would run a day past the end of its month on into
+        // the next, which a column should refuse instead.
+        if civil(Moment(from_civil(year, month, day) * DAY_MS + UNIX_EPOCH_MS)) != (year, month, day) {
+            return None;
+        }
+    }
+
+    let midnight = clock(moment) == (0, 0, 0);
+    match (column_type, parts.date, parts.time) {
+        (ColumnType::Date, Some(_), None) => Some(format_date(moment)),
+        (ColumnType::Date, Some(_), Some(_)) if midnight => Some(format_date(moment)),
+        (ColumnType::Time, None, Some(_)) => Some(format_time_fraction(moment)),
+        (ColumnType::Timestamp, Some(_), _) => Some(format!("{} {}", format_date(moment), format_time_fraction(moment))),
+        _ => None
+    }
+}
+
+/// Reads a time value and applies the modifiers after it, in order.
+fn moment(value: &Value, modifiers: &[Value]) -> Option<Moment> {
+    let number = match value {
+        Value::Number(n) => Some(*n),
+        Value::String(s) => s.trim().parse::<f64>().ok(),
+        Value::Boolean(_) | Value::Blob(_) | Value::Null | Value::Parameter(_) => return None
+    };
+
+    // 'unixepoch', 'julianday' and 'auto' say how a number is read, so they
+    // can only come first.
+    let first = modifiers.first().map(|modifier| to_text(modifier).trim().to_ascii_lowercase());
+    let (mut moment, modifiers) = match (number, first.as_deref()) {
+        (Some(n), Some("unixepoch")) => (from_unix_seconds(n)?, &modifiers[1..]),
+        (Some(n), Some("julianday")) => (from_julian_day(n)?, &modifiers[1..]),
+        (Some(n), Some("auto")) if (0.0..AUTO_JULIAN_DAY_LIMIT).contains(&n) => (from_julian_day(n)?, &modifiers[1..]),
+        (Some(n), Some("auto")) => (from_unix_seconds(n)?, &modifiers[1..]),
+        (Some(n), _) => (from_julian_day(n)?, modifiers),
+        (None, _) if to_text(value).trim().eq_ignore_ascii_case("now") => (now(), modifiers),
+        (None, _) => (parts_moment(&parse_parts(to_text(value).trim())?)?, modifiers)
+    };
+
+    for modifier in modifiers {
+        if *modifier == Value::Null {
+            return None;
+        }
+        moment = modify(moment, to_text(modifier).trim())?;
+    }
+    in_range(moment)
+}
+
+fn now() -> Moment {
+    let unix_ms = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_millis() as i64);
+    Moment(unix_ms + UNIX_EPOCH_MS)
+}
+
+fn from_julian_day(day: f64) -> Option<Moment> {
+    within_limit(millis(day * DAY_MS as f64)?)
+}
+
+fn from_unix_seconds(seconds: f64) -> Option<Moment> {
+    within_limit(millis(seconds * 1000.0)?.checked_add(UNIX_EPOCH_MS)?)
+}
+
+/// Rounds a number of milliseconds, `None` when it is past `MOMENT_LIMIT_MS`
+/// and so cannot be cast without saturating.
+fn millis(value: f64) -> Option<i64> {
+    let millis = value.round();
+    (millis.abs() <= MOMENT_LIMIT_MS as f64).then_some(millis as i64)
+}
+
+fn within_limit(millis: i64) -> Option<Moment> {
+    (-MOMENT_LIMIT_MS..=MOMENT_LIMIT_MS).contains(&millis).then_some(Moment(millis))
+}
+
+/// Only years 0000 to 9999 can be written out.
+fn in_range(moment: Moment) -> Option<Moment> {
+    let (year, _, _) = civil(moment);
+    (0..=9999).contains(&year).then_some(moment)
+}
+
+/// Splits `YYYY-MM-DD`, `HH:MM[:SS[.SSS]]`, or a date then a time after a
+/// space or `T`, followed by an optional `Z` or `+HH:MM`/`-HH:MM` zone.
+fn parse_parts(text: &str) -> Option<Parts> {
+    let (date, rest) = match text.get(..10).filter(|date| date.as_bytes().get(4) == Some(&b'-')) {
+        Some(date) => {
+            let fields = fixed_fields(date, b'-', &[4, 2, 2])?;
+            let (year, month, day) = (fields[0], fields[1], fields[2]);
+            if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+                return None;
+            }
+            let rest = &text[10..];
+            let rest = rest.strip_prefix(' ').or_else(|| rest.strip_prefix('T')).or_else(|| rest.strip_prefix('t')).unwrap_or(rest);
+            (Some((year, month, day)), rest.trim_start())
+        }
+        None => (None, text)
+    };
+
+    let (rest, offset) = zone(rest)?;
+    let time = if rest.is_empty() {
+        None
+    } else {
+        let (clock, fraction) = rest.split_once('.').map_or((rest, None), |(clock, fraction)| (clock, Some(fraction)));
+        let fields = match clock.len() {
+            5 => fixed_fields(clock, b':', &[2, 2])?,
+            8 => fixed_fields(clock, b':', &[2, 2, 2])?,
+            _ => return None
+        };
+        let (hour, minute, second) = (fields[0], fields[1], fields.get(2).copied().unwrap_or(0));
+        if hour > 23 || minute > 59 || second > 59 || (fraction.is_some() && fields.len() == 2) {
+            return None;
+        }
+        let millis = match fraction {
+            Some(digits) if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) => {
+                (format!("0.{digits}").parse::<f64>().ok()? * 1000.0).round() as i64
+            }
+            Some(_) => return None,
+            None => 0
+        };
+        Some((hour, minute, second * 1000 + millis))
+    };
+
+    if date.is_none() && time.is_none() {
+        return None;
+    }
+    Some(Parts { date, time, offset })
+}
+
+/// Splits a trailing zone off a time.
+fn zone(text: &str) -> Option<(&str, i64)> {
+    if let Some(rest) = text.strip_suffix('Z').or_else(|| text.strip_suffix('z')) {
+        return Some((rest.trim_end(), 0));
+    }
+    let Some(sign_at) = text.len().checked_sub(6).filter(|at| matches!(text.as_bytes()[*at], b'+' | b'-')) else {
+        return Some((text, 0));
+    };
+    // A date alone ends in `-DD`, which is not a zone.
+    if !text[..sign_at].contains(':') {
+        return Some((text, 0));
+    }
+    let fields = fixed_fields(&text[sign_at + 1..], b':', &[2, 2])?;
+    let minutes = fields[0] * 60 + fields[1];
+    let sign = if text.as_bytes()[sign_at] == b'-' { -1 } else { 1 };
+    Some((text[..sign_at].trim_end(), sign * minutes))
+}
+
+/// Reads fields of exactly the given widths of digits, split by `separator`.
+fn fixed_fields(text: &str, separator: u8, widths: &[usize]) -> Option<Vec<i64>> {
+    let fields: Vec<&str> = text.split(separator as char).collect();
+    if fields.len() != widths.len() {
+        return None;
+    }
+    fields.iter().zip(widths)
+        .map(|(field, width)| (field.len() == *width && field.bytes().all(|b| b.is_ascii_digit())).then(|| field.parse().ok()).flatten())
+        .collect()
+}
+
+/// A time alone is on 2000-01-01, as in SQLite.
+fn parts_moment(parts: &Parts) -> Option<Moment> {
+    let (year, month, day) = parts.date.unwrap_or((2000, 1, 1));
+    let (hour, minute, millis) = parts.time.unwrap_or((0, 0, 0));
+    let unix_ms = from_civil(year, month, day) * DAY_MS + (hour * 60 + minute - parts.offset) * 60_000 + millis;
+    in_range(Moment(unix_ms + UNIX_EPOCH_MS))
+}
+
+/// Applies one modifier: `NNN days`, `hours`, `minutes`, `seconds`,
+/// `months` or `years`, `start of day`, `start of month`, `start of year`,
+/// or `weekday N`.
+fn modify(moment: Moment, modifier: &str) -> Option<Moment> {
+    let modifier = modifier.to_ascii_lowercase();
+    match modifier.as_str() {
+        "localtime" | "utc" => return Some(moment),
+        "start of day" => return Some(start_of_day(moment)),
+        "start of month" => {
+            let (year, month, _) = civil(moment);
+            return Some(Moment(from_civil(year, month, 1) * DAY_MS + UNIX_EPOCH_MS));
+        }
+        "start of year" => {
+            let (year, _, _) = civil(moment);
+            return Some(Moment(from_civil(year, 1, 1) * DAY_MS + UNIX_EPOCH_MS));
+        }
+        _ => {}
+    }
+
+    if let Some(weekday) = modifier.strip_prefix("weekday ") {
+        let weekday: i64 = weekday.trim().parse().ok().filter(|weekday| (0..7).contains(weekday))?;
+        let ahead = (weekday - day_of_week(moment)).rem_euclid(7);
+        return within_limit(moment.0 + ahead * DAY_MS);
+    }
+
+    let (amount, unit) = modifier.split_once(char::is_whitespace)?;
+    let amount: f64 = amount.parse().ok().filter(|amount: &f64| amount.is_finite())?;
+    let unit = unit.trim();
+    let unit = unit.strip_suffix('s').unwrap_or(unit);
+    let unit_ms = match unit {
+        "day" => DAY_MS,
+        "hour" => 3_600_000,
+        "minute" => 60_000,
+        "second" => 1000,
+        // Whole months and years move the calendar date, and a day past
+        // the end of the month runs on into the next; a fraction of one is
+        // added as 30 or 365 days.
+        "month" | "year" => {
+            if amount.abs() > MONTH_LIMIT {
+                return None;
+            }
+            let whole = amount.trunc() as i64;
+            let months = if unit == "month" { whole } else { whole * 12 };
+            let (year, month, day) = civil(moment);
+            let total = year * 12 + (month - 1) + months;
+            let time_of_day = (moment.0 - UNIX_EPOCH_MS).rem_euclid(DAY_MS);
+            let moved = from_civil(total.div_euclid(12), total.rem_euclid(12) + 1, day) * DAY_MS + time_of_day + UNIX_EPOCH_MS;
+            let days = if unit == "month" { 30.0 } else { 365.0 };
+            return within_limit(within_limit(moved)?.0 + millis(amount.fract() * days * DAY_MS as f64)?);
+        }
+        _ => return None
+    };
+    within_limit(moment.0.checked_add(millis(amount * unit_ms as f64)?)?)
+}
+
+fn start_of_day(moment: Moment) -> Moment {
+    Moment((moment.0 - UNIX_EPOCH_MS).div_euclid(DAY_MS) * DAY_MS + UNIX_EPOCH_MS)
+}
+
+/// 0 for Sunday to 6 for Saturday. 1970-01-01 was a Thursday.
+fn day_of_week(moment: Moment) -> i64 {
+    ((moment.0 - UNIX_EPOCH_MS).div_euclid(DAY_MS) + 4).rem_euclid(7)
+}
+
+/// Days from 1970-01-01 to a date of the proleptic Gregorian calendar. A
+/// day past the end of its month runs on into the next.
+fn from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The year, month and day of a moment.
+fn civil(moment: Moment) -> (i64, i64, i64) {
+    let days = (moment.0 - UNIX_EPOCH_MS).div_euclid(DAY_MS) + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    (year_of_era + era * 400 + i64::from(month <= 2), month, day)
+}
+
+/// Hours, minutes and milliseconds into the minute.
+fn clock(moment: Moment) -> (i64, i64, i64) {
+    let millis = (moment.0 - UNIX_EPOCH_MS).rem_euclid(DAY_MS);
+    (millis / 3_600_000, millis / 60_000 % 60, millis % 60_000)
+}
+
+fn format_date(moment: Moment) -> String {
+    let (year, month, day) = civil(moment);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+fn format_time(moment: Moment) -> String {
+    let (hour, minute, millis) = clock(moment);
+    format!("{hour:02}:{minute:02}:{:02}", millis / 1000)
+}
+
+fn format_time_fraction(moment: Moment) -> String {
+    let (_, _, millis) = clock(moment);
+    match millis % 1000 {
+        0 => format_time(moment),
+        fraction => format!("{}.{fraction:03}", format_time(moment))
+    }
+}
+
+/// Writes a moment with SQLite's `strftime` substitutions, or `None` for
+/// one it does not know.
+fn strftime(format: &str, moment: Moment) -> Option<String> {
+    let (year, month, day) = civil(moment);
+    let (hour, minute, millis) = clock(moment);
+    let day_of_year = (moment.0 - UNIX_EPOCH_MS).div_euclid(DAY_MS) - from_civil(year, 1, 1);
+    let weekday = day_of_week(moment);
+    let twelve_hour = if hour % 12 == 0 { 12 } else { hour % 12 };
+
+    let mut out = String::new();
+    let mut chars = format.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '%' {
+            out.push(ch);
+            continue;
+        }
+        match chars.next()? {
+            'd' => out.push_str(&format!("{day:02}")),
+            'e' => out.push_str(&format!("{day:2}")),
+            'f' => out.push_str(&format!("{:02}.{:03}", millis / 1000, millis % 1000)),
+            'F' => out.push_str(&format_date(moment)),
+            'H' => out.push_str(&format!("{hour:02}")),
+            'I' => out.push_str(&format!("{twelve_hour:02}")),
+            'j' => out.push_str(&format!("{:03}", day_of_year + 1)),
+            'J' => out.push_str(&(moment.0 as f64 / DAY_MS as f64).to_string()),
+            'k' => out.push_str(&format!("{hour:2}")),
+            'l' => out.push_str(&format!("{twelve_hour:2}")),
+            'm' => out.push_str(&format!("{month:02}")),
+            'M' => out.push_str(&format!("{minute:02}")),
+            'p' => out.push_str(if hour < 12 { "AM" } else { "PM" }),
+            'P' => out.push_str(if hour < 12 { "am" } else { "pm" }),
+            'R' => out.push_str(&format!("{hour:02}:{minute:02}")),
+            's' => out.push_str(&(moment.0 - UNIX_EPOCH_MS).div_euclid(1000).to_string()),
+            'S' => out.push_str(&format!("{:02}", millis / 1000)),
+            'T' => out.push_str(&format_time(moment)),
+            'u' => out.push_str(&(if weekday == 0 { 7 } else { weekday }).to_string()),
+            'w' => out.push_str(&weekday.to_string()),
+            // Weeks start on Monday, and days before the first Monday are
+            // in week 00.
+            'W' => out.push_str(&format!("{:02}", (day_of_year + 7 - (weekday + 6) % 7) / 7)),
+            'Y' => out.push_str(&format!("{year:04}")),
+            '%' => out.push('%'),
+            _ => return None
+        }
+    }
+    Some(out)
+}
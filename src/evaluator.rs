@@ -0,0 +1,211 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::affinity::{convert, is_numeric};
+use crate::{blob, datetime};
+use crate::ast::{ArithmeticOperator, ComparisonOperator, Condition, Expression, Value};
+use crate::schema::{ColumnType, TableSchema};
+
+/// Reads a stored field as a typed value. Columns missing from the row are
+/// NULL; numeric columns holding text that does not parse stay as text,
+/// BOOLEAN columns read their stored 1 and 0 as TRUE and FALSE, and BLOB
+/// columns read their hex digits as the bytes.
+pub fn column_value(row: &HashMap<String, String>, schema: &TableSchema, column: &str) -> Value {
+    let column = schema.resolve_column(column);
+    match row.get(column) {
+        None => Value::Null,
+        Some(field) => match schema.column_type(column) {
+            Some(column_type) if is_numeric(column_type) => field.parse::<f64>()
+                .map(|n| convert(&Value::Number(n), column_type))
+                .unwrap_or_else(|_| Value::String(field.clone())),
+            Some(ColumnType::Blob) => blob::from_hex(field, "").map_or_else(|| Value::String(field.clone()), Value::Blob),
+            _ => Value::String(field.clone())
+        }
+    }
+}
+
+/// Converts a value into the text stored for a column, or `None` for NULL,
+/// after the column's affinity is applied to it.
+pub fn value_to_field(value: &Value, column_type: &ColumnType) -> Option<String> {
+    match (&convert(value, column_type), column_type) {
+        (Value::Null | Value::Parameter(_), _) => None,
+        (Value::Boolean(b), _) => Some(if *b { "1" } else { "0" }.to_string()),
+        (Value::Blob(bytes), _) => Some(blob::to_hex(bytes)),
+        (Value::Number(n), ColumnType::Integer) if n.fract() == 0.0 => Some(format!("{}", *n as i64)),
+        (Value::Number(n), _) => Some(n.to_string()),
+        (Value::String(s), _) => Some(s.clone())
+    }
+}
+
+pub fn evaluate_expression(expr: &Expression, row: &HashMap<String, String>, schema: &TableSchema) -> Value {
+    match expr {
+        Expression::Literal(Value::Parameter(_)) => Value::Null,
+        Expression::Literal(value) => value.clone(),
+        Expression::Column(column) => column_value(row, schema, column),
+        Expression::Negate(inner) => match evaluate_expression(inner, row, schema) {
+            Value::Null => Value::Null,
+            value => Value::Number(-to_number(&value).unwrap_or(0.0))
+        },
+        Expression::Binary(left, op, right) => {
+            let left = evaluate_expression(left, row, schema);
+            let right = evaluate_expression(right, row, schema);
+            apply_arithmetic(&left, op, &right)
+        }
+        Expression::Function(name, args) => {
+            let args: Vec<Value> = args.iter().map(|arg| evaluate_expression(arg, row, schema)).collect();
+            if blob::is_function(name) {
+                blob::call(name, &args)
+            } else {
+                datetime::call(name, &args)
+            }
+        }
+    }
+}
+
+/// Evaluates a condition with SQL three-valued logic: `None` means unknown,
+/// which is what any comparison against NULL produces.
+pub fn evaluate_condition(condition: &Condition, row: &HashMap<String, String>, schema: &TableSchema) -> Option<bool> {
+    match condition {
+        Condition::Comparison(column, op, value) => {
+            let value = schema.column_type(column).map_or_else(|| value.clone(), |column_type| convert(value, column_type));
+            compare(&column_value(row, schema, column), op, &value)
+        }
+        Condition::Compare(left, op, right) => {
+            let (left_type, right_type) = (column_type(left, schema), column_type(right, schema));
+            let mut left = evaluate_expression(left, row, schema);
+            let mut right = evaluate_expression(right, row, schema);
+            // As in SQLite, a column's affinity applies to the other side
+            // when that is not a column, or is a TEXT column compared with
+            // a numeric one.
+            match (left_type, right_type) {
+                (Some(column_type), None) => right = convert(&right, column_type),
+                (None, Some(column_type)) => left = convert(&left, column_type),
+                (Some(left_type), Some(right_type)) if is_numeric(left_type) != is_numeric(right_type) => {
+                    let numeric = if is_numeric(left_type) { left_type } else { right_type };
+                    left = convert(&left, numeric);
+                    right = convert(&right, numeric);
+                }
+                _ => {}
+            }
+            compare(&left, op, &right)
+        }
+        Condition::IsNull(expr) => Some(evaluate_expression(expr, row, schema) == Value::Null),
+        Condition::IsNotNull(expr) => Some(evaluate_expression(expr, row, schema) != Value::Null),
+        Condition::IsTrue(expr) => Some(truth(&evaluate_expression(expr, row, schema)) == Some(true)),
+        Condition::IsFalse(expr) => Some(truth(&evaluate_expression(expr, row, schema)) == Some(false)),
+        Condition::Truth(expr) => truth(&evaluate_expression(expr, row, schema)),
+        // The right side is only evaluated when the left does not settle
+        // the result, so the planner puts the term most likely to settle it
+        // first.
+        Condition::And(left, right) => {
+            match evaluate_condition(left, row, schema) {
+                Some(false) => Some(false),
+                left => match (left, evaluate_condition(right, row, schema)) {
+                    (_, Some(false)) => Some(false),
+                    (Some(true), Some(true)) => Some(true),
+                    _ => None
+                }
+            }
+        }
+        Condition::Or(left, right) => {
+            match evaluate_condition(left, row, schema) {
+                Some(true) => Some(true),
+                left => match (left, evaluate_condition(right, row, schema)) {
+                    (_, Some(true)) => Some(true),
+                    (Some(false), Some(false)) => Some(false),
+                    _ => None
+                }
+            }
+        }
+        Condition::Not(inner) => evaluate_condition(inner, row, schema).map(|b| !b)
+    }
+}
+
+/// The type of the column an expression reads, if it is a bare column.
+fn column_type<'a>(expr: &Expression, schema: &'a TableSchema) -> Option<&'a ColumnType> {
+    match expr {
+        Expression::Column(column) => schema.column_type(column),
+        _ => None
+    }
+}
+
+/// Whether a value counts as true where a condition is expected: NULL is
+/// unknown, and anything else is true when it reads as a number other than
+/// zero, so text that is not a number is false, as in SQLite.
+fn truth(value: &Value) -> Option<bool> {
+    match value {
+        Value::Null | Value::Parameter(_) => None,
+        value => Some(to_number(value).is_some_and(|n| n != 0.0))
+    }
+}
+
+/// Orders two non-NULL values: numbers sort before text and text before
+/// blobs, as in SQLite. TRUE and FALSE are the numbers 1 and 0.
+pub fn compare_values(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Null | Value::Parameter(_), _) | (_, Value::Null | Value::Parameter(_)) => None,
+        (Value::Boolean(b), _) => compare_values(&Value::Number(f64::from(u8::from(*b))), right),
+        (_, Value::Boolean(b)) => compare_values(left, &Value::Number(f64::from(u8::from(*b)))),
+        (Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (Value::Blob(a), Value::Blob(b)) => Some(a.cmp(b)),
+        (Value::Number(_), Value::String(_) | Value::Blob(_)) | (Value::String(_), Value::Blob(_)) => Some(Ordering::Less),
+        (Value::String(_) | Value::Blob(_), Value::Number(_)) | (Value::Blob(_), Value::String(_)) => Some(Ordering::Greater)
+    }
+}
+
+fn compare(left: &Value, op: &ComparisonOperator, right: &Value) -> Option<bool> {
+    let ordering = compare_values(left, right)?;
+    Some(match op {
+        ComparisonOperator::Equals => ordering == Ordering::Equal,
+        ComparisonOperator::NotEquals => ordering != Ordering::Equal,
+        ComparisonOperator::GreaterThan => ordering == Ordering::Greater,
+        ComparisonOperator::LessThan => ordering == Ordering::Less,
+        ComparisonOperator::GreaterEqualThan => ordering != Ordering::Less,
+        ComparisonOperator::LessEqualThan => ordering != Ordering::Greater
+    })
+}
+
+fn apply_arithmetic(left: &Value, op: &ArithmeticOperator, right: &Value) -> Value {
+    if *left == Value::Null || *right == Value::Null {
+        return Value::Null;
+    }
+
+    if *op == ArithmeticOperator::Concat {
+        return Value::String(format!("{}{}", to_text(left), to_text(right)));
+    }
+
+    let (a, b) = (to_number(left).unwrap_or(0.0), to_number(right).unwrap_or(0.0));
+    match op {
+        ArithmeticOperator::Add => Value::Number(a + b),
+        ArithmeticOperator::Subtract => Value::Number(a - b),
+        ArithmeticOperator::Multiply => Value::Number(a * b),
+        ArithmeticOperator::Divide if b == 0.0 => Value::Null,
+        ArithmeticOperator::Divide => Value::Number(a / b),
+        ArithmeticOperator::Modulo if b == 0.0 => Value::Null,
+        ArithmeticOperator::Modulo => Value::Number(a % b),
+        ArithmeticOperator::Concat => unreachable!()
+    }
+}
+
+fn to_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => Some(*n),
+        Value::Boolean(b) => Some(f64::from(u8::from(*b))),
+        Value::String(s) => s.trim().parse().ok(),
+        Value::Blob(bytes) => std::str::from_utf8(bytes).ok()?.trim().parse().ok(),
+        Value::Null | Value::Parameter(_) => None
+    }
+}
+
+/// The text form of a value, as `||` writes it. NULL is the empty string,
+/// and a blob is its bytes read as UTF-8.
+pub fn to_text(value: &Value) -> String {
+    match value {
+        Value::Number(n) => n.to_string(),
+        Value::Boolean(b) => u8::from(*b).to_string(),
+        Value::String(s) => s.clone(),
+        Value::Blob(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+        Value::Null | Value::Parameter(_) => String::new()
+    }
+}
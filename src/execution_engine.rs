@@ -1,143 +1,846 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::affinity;
 use crate::ast::{
-    ASTNode, ComparisonOperator, DeleteStatement, InsertStatement, SelectStatement, UpdateStatement, Value,
+    ASTNode, CreateTableStatement, CreateTriggerStatement, CreateViewStatement, DropTriggerStatement, DropViewStatement, TransactionMode, TriggerEvent, TriggerTiming, DeleteStatement, ExplainMode, ExplainStatement, InsertSource, InsertStatement, PragmaStatement, SelectStatement, UpdateStatement, Value,
     OrderByClause, OrderDirection, Condition
 };
 
-use crate::datastore::{ DataStore };
-use crate::schema::TableSchema;
+use crate::catalog::{index_list, is_schema_table, schema_rows, schema_table, table_info, SCHEMA_TABLE, INDEX_LIST_COLUMNS, TABLE_INFO_COLUMNS};
+use crate::datastore::{ DataStore, JournalMode };
+use crate::lock::LockMode;
+use crate::evaluator::{column_value, compare_values, evaluate_condition, value_to_field};
+use crate::explain::{plan_rows, program, program_rows, query_plan, PROGRAM_COLUMNS, QUERY_PLAN_COLUMNS};
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::planner::order_condition;
+use crate::schema::{TableSchema, TriggerSchema, ViewSchema, ROWID_COLUMN};
+use crate::semantic_analyzer::{SemanticAnalyzer, SemanticError};
+use crate::statement::{bind_condition, bind_node, Statement};
+
+/// Stored rows as they are read from a table, a view or the catalog.
+type StoredRows = Box<dyn Iterator<Item = io::Result<HashMap<String, String>>>>;
 
 pub struct ExecutionEngine {
-    data_store: DataStore 
+    data_store: DataStore,
+    /// The triggers running now, innermost last.
+    firing: Vec<String>
 }
 
 impl ExecutionEngine {
     pub fn new (data_store: DataStore) -> Self {
-        ExecutionEngine { data_store }
+        ExecutionEngine { data_store, firing: Vec::new() }
+    }
+
+    pub fn data_store(&self) -> &DataStore {
+        &self.data_store
+    }
+
+    pub fn last_insert_rowid(&self) -> i64 {
+        self.data_store.last_insert_rowid()
     }
 
+    /// Runs one statement. Reads hold a shared lock on the data directory
+    /// and writes the reserved one for the whole statement, so the schema
+    /// it is checked against is the one it runs against.
     pub fn execute(&mut self, ast: &ASTNode) -> Result<QueryResult, ExecutionError> {
+        let lock_mode = match ast {
+            ASTNode::Select(_) | ASTNode::Explain(_) => Some(LockMode::Shared),
+            ASTNode::Insert(_) | ASTNode::Update(_) | ASTNode::Delete(_) | ASTNode::CreateTable(_) | ASTNode::Analyze(_) => Some(LockMode::Reserved),
+            ASTNode::CreateView(_) | ASTNode::DropView(_) | ASTNode::CreateTrigger(_) | ASTNode::DropTrigger(_) => Some(LockMode::Reserved),
+            ASTNode::Pragma(_) | ASTNode::Begin(_) | ASTNode::Commit | ASTNode::Rollback => None
+        };
+        let Some(lock_mode) = lock_mode else { return self.execute_statement(ast) };
+
+        self.data_store.lock(lock_mode)?;
+        let result = self.execute_statement(ast);
+        let unlocked = self.data_store.unlock();
+        let result = result?;
+        unlocked?;
+        Ok(result)
+    }
+
+    /// Runs a SELECT and hands back its rows as they are read rather than
+    /// all at once. Without ORDER BY only the row in hand is kept in memory,
+    /// and dropping the cursor stops the read early. The shared lock is held
+    /// until the cursor is dropped.
+    pub fn query(&mut self, ast: &ASTNode) -> Result<Cursor<'_>, ExecutionError> {
+        let ASTNode::Select(stmt) = ast else {
+            return Err(ExecutionError::Storage(io::Error::new(io::ErrorKind::InvalidInput, "only SELECT statements return rows")));
+        };
+
+        self.data_store.lock(LockMode::Shared)?;
+        match self.select_rows(stmt) {
+            Ok((columns, rows)) => Ok(Cursor { engine: self, columns, rows }),
+            Err(err) => {
+                let _ = self.data_store.unlock();
+                Err(err)
+            }
+        }
+    }
+
+    /// Parses `sql` and checks it against the current schema once, for it
+    /// to be run as often as needed with `Statement::execute`.
+    pub fn prepare(&mut self, sql: &str) -> Result<Statement, ExecutionError> {
+        let mut parser = Parser::new(Lexer::new(sql));
+        let ast = parser.parse().map_err(ExecutionError::Parse)?;
+
+        self.data_store.lock(LockMode::Shared)?;
+        let analyzer = self.analyzer();
+        self.data_store.unlock()?;
+        analyzer.analyze(&ast).map_err(ExecutionError::Semantic)?;
+
+        let columns = analyzer.parameter_columns(&ast);
+        Ok(Statement::new(ast, parser.parameters().to_vec(), columns))
+    }
+
+    /// An analyzer for the schema as it is now, which sees views and the
+    /// catalog table as tables.
+    fn analyzer(&self) -> SemanticAnalyzer {
+        let mut schema = self.data_store.schema();
+        for name in [SCHEMA_TABLE, "sqlite_master"] {
+            schema.tables.insert(name.to_string(), schema_table().clone());
+        }
+        SemanticAnalyzer::new(schema)
+    }
+
+    fn execute_statement(&mut self, ast: &ASTNode) -> Result<QueryResult, ExecutionError> {
         match ast {
             ASTNode::Select(stmt) => self.execute_select(stmt),
             ASTNode::Insert(stmt) => self.execute_insert(stmt),
             ASTNode::Update(stmt) => self.execute_update(stmt),
-            ASTNode::Delete(stmt) => self.execute_delete(stmt)
+            ASTNode::Delete(stmt) => self.execute_delete(stmt),
+            ASTNode::CreateTable(stmt) => self.execute_create_table(stmt),
+            ASTNode::CreateView(stmt) => self.execute_create_view(stmt),
+            ASTNode::DropView(stmt) => self.execute_drop_view(stmt),
+            ASTNode::CreateTrigger(stmt) => self.execute_create_trigger(stmt),
+            ASTNode::DropTrigger(stmt) => self.execute_drop_trigger(stmt),
+            ASTNode::Pragma(stmt) => self.execute_pragma(stmt),
+            ASTNode::Explain(stmt) => self.execute_explain(stmt),
+            ASTNode::Analyze(table) => {
+                if let Some(table) = table {
+                    self.table_schema(table)?;
+                }
+                self.data_store.analyze(table.as_deref())?;
+                Ok(QueryResult::Analyze)
+            }
+            ASTNode::Begin(mode) => {
+                self.begin(*mode)?;
+                Ok(QueryResult::Begin)
+            }
+            ASTNode::Commit => {
+                self.commit()?;
+                Ok(QueryResult::Commit)
+            }
+            ASTNode::Rollback => {
+                self.rollback()?;
+                Ok(QueryResult::Rollback)
+            }
         }
     }
 
-    fn execute_select(&self, stmt: &SelectStatement) -> Result<QueryResult, ExecutionError> {
-        let table = self.data_store.get_table(&stmt.table)
-            .ok_or_else(|| ExecutionError::TableNotFound(stmt.table.clone()));
+    pub fn begin(&mut self, mode: TransactionMode) -> Result<(), ExecutionError> {
+        Ok(self.data_store.begin(mode)?)
+    }
+
+    pub fn commit(&mut self) -> Result<(), ExecutionError> {
+        Ok(self.data_store.commit()?)
+    }
+
+    pub fn rollback(&mut self) -> Result<(), ExecutionError> {
+        Ok(self.data_store.rollback()?)
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.data_store.in_transaction()
+    }
+
+    fn execute_pragma(&mut self, stmt: &PragmaStatement) -> Result<QueryResult, ExecutionError> {
+        match stmt.name.as_str() {
+            "foreign_keys" => {
+                if let Some(value) = &stmt.value {
+                    let enabled = Self::pragma_flag(value)
+                        .ok_or_else(|| ExecutionError::InvalidPragmaValue(stmt.name.clone()))?;
+                    self.data_store.set_foreign_keys(enabled);
+                }
+                let enabled = self.data_store.foreign_keys_enabled();
+                Ok(QueryResult::Select {
+                    columns: vec![stmt.name.clone()],
+                    rows: vec![Row { values: vec![Value::Number(if enabled { 1.0 } else { 0.0 })] }]
+                })
+            }
+            "journal_mode" => {
+                if let Some(value) = &stmt.value {
+                    let mode = match value {
+                        Value::String(s) if s.eq_ignore_ascii_case("wal") => JournalMode::Wal,
+                        Value::String(s) if s.eq_ignore_ascii_case("delete") => JournalMode::Delete,
+                        _ => return Err(ExecutionError::InvalidPragmaValue(stmt.name.clone()))
+                    };
+                    self.data_store.set_journal_mode(mode)?;
+                }
+                let mode = match self.data_store.journal_mode() {
+                    JournalMode::Wal => "wal",
+                    JournalMode::Delete => "delete"
+                };
+                Ok(QueryResult::Select {
+                    columns: vec![stmt.name.clone()],
+                    rows: vec![Row { values: vec![Value::String(mode.to_string())] }]
+                })
+            }
+            "busy_timeout" => {
+                if let Some(value) = &stmt.value {
+                    let Value::Number(millis) = value else {
+                        return Err(ExecutionError::InvalidPragmaValue(stmt.name.clone()));
+                    };
+                    self.data_store.set_busy_timeout(Duration::from_millis(millis.max(0.0) as u64));
+                }
+                let millis = self.data_store.busy_timeout().as_millis() as f64;
+                Ok(QueryResult::Select {
+                    columns: vec!["timeout".to_string()],
+                    rows: vec![Row { values: vec![Value::Number(millis)] }]
+                })
+            }
+            "table_info" | "index_list" => {
+                let Some(Value::String(table)) = &stmt.value else {
+                    return Err(ExecutionError::InvalidPragmaValue(stmt.name.clone()));
+                };
+                self.data_store.lock(LockMode::Shared)?;
+                let rows = self.readable_schema(table).map(|schema| match stmt.name.as_str() {
+                    "table_info" => table_info(&schema),
+                    _ => index_list(table, &schema)
+                });
+                self.data_store.unlock()?;
+                let columns = if stmt.name == "table_info" { &TABLE_INFO_COLUMNS[..] } else { &INDEX_LIST_COLUMNS[..] };
+                Ok(QueryResult::Select {
+                    columns: columns.iter().map(|column| column.to_string()).collect(),
+                    rows: rows?
+                })
+            }
+            "wal_checkpoint" => {
+                let frames = self.data_store.checkpoint()? as f64;
+                Ok(QueryResult::Select {
+                    columns: vec!["busy".to_string(), "log".to_string(), "checkpointed".to_string()],
+                    rows: vec![Row { values: vec![Value::Number(0.0), Value::Number(frames), Value::Number(frames)] }]
+                })
+            }
+            _ => Err(ExecutionError::UnknownPragma(stmt.name.clone()))
+        }
+    }
+
+    /// Describes how a statement would run, without running it.
+    fn execute_explain(&self, stmt: &ExplainStatement) -> Result<QueryResult, ExecutionError> {
+        let (columns, rows) = match stmt.mode {
+            ExplainMode::QueryPlan => (&QUERY_PLAN_COLUMNS[..], plan_rows(&query_plan(&stmt.statement, &self.data_store)?)),
+            ExplainMode::Program => (&PROGRAM_COLUMNS[..], program_rows(&program(&stmt.statement, &self.data_store)?))
+        };
+        Ok(QueryResult::Select {
+            columns: columns.iter().map(|column| column.to_string()).collect(),
+            rows
+        })
+    }
 
-        let mut result_rows = Vec::new();
+    fn pragma_flag(value: &Value) -> Option<bool> {
+        match value {
+            Value::Number(n) => Some(*n != 0.0),
+            Value::Boolean(b) => Some(*b),
+            Value::Blob(_) => None,
+            Value::String(s) => match s.to_uppercase().as_str() {
+                "ON" | "TRUE" | "YES" => Some(true),
+                "OFF" | "FALSE" | "NO" => Some(false),
+                _ => None
+            },
+            Value::Null | Value::Parameter(_) => None
+        }
+    }
 
-        for row in table.rows() {
-            if self.evaluate_condition(&stmt.condition, row)? {
-                let selected_values = self.project_columns(&stmt.columns, row)?;
-                result_rows.push(Row { values: selected_values });
+    fn execute_create_table(&mut self, stmt: &CreateTableStatement) -> Result<QueryResult, ExecutionError> {
+        if self.data_store.table_exists(&stmt.table) || self.data_store.get_view(&stmt.table).is_some() || is_schema_table(&stmt.table) {
+            return Err(ExecutionError::TableAlreadyExists(stmt.table.clone()));
+        }
+
+        let mut schema = TableSchema::default();
+        for column in &stmt.columns {
+            schema.columns.insert(column.name.clone(), column.column_type.clone());
+            schema.column_order.push(column.name.clone());
+            if !column.constraints.is_empty() {
+                schema.column_constraints.insert(column.name.clone(), column.constraints.clone());
             }
         }
+        schema.table_constraints = stmt.constraints.clone();
+        schema.strict = stmt.strict;
+
+        self.data_store.create_table(stmt.table.clone(), schema)?;
+
+        Ok(QueryResult::CreateTable)
+    }
+
+    /// Views are checked by the analyzer when they are created, since
+    /// their SELECT is only run when they are read.
+    fn execute_create_view(&mut self, stmt: &CreateViewStatement) -> Result<QueryResult, ExecutionError> {
+        if is_schema_table(&stmt.name) {
+            return Err(ExecutionError::TableAlreadyExists(stmt.name.clone()));
+        }
+        self.analyzer().analyze_create_view(stmt).map_err(ExecutionError::Semantic)?;
+
+        let columns = if !stmt.columns.is_empty() {
+            stmt.columns.clone()
+        } else if stmt.query.columns.contains(&"*".to_string()) {
+            self.readable_schema(&stmt.query.table)?.column_names()
+        } else {
+            stmt.query.columns.clone()
+        };
+        self.data_store.create_view(stmt.name.clone(), ViewSchema { columns, query: stmt.query.clone() })?;
+
+        Ok(QueryResult::CreateView)
+    }
+
+    fn execute_drop_view(&mut self, stmt: &DropViewStatement) -> Result<QueryResult, ExecutionError> {
+        if self.data_store.get_view(&stmt.name).is_none() {
+            return if stmt.if_exists { Ok(QueryResult::DropView) } else { Err(ExecutionError::TableNotFound(stmt.name.clone())) };
+        }
+        self.data_store.drop_view(&stmt.name)?;
+        Ok(QueryResult::DropView)
+    }
+
+    /// Triggers are checked by the analyzer when they are created, like
+    /// views. INSTEAD OF triggers are only for views, which cannot be
+    /// written otherwise, and the others only for tables.
+    fn execute_create_trigger(&mut self, stmt: &CreateTriggerStatement) -> Result<QueryResult, ExecutionError> {
+        if self.data_store.get_trigger(&stmt.name).is_some() {
+            return Err(ExecutionError::TriggerAlreadyExists(stmt.name.clone()));
+        }
+        if is_schema_table(&stmt.table) {
+            return Err(ExecutionError::ReadOnlyTable(stmt.table.clone()));
+        }
+        self.analyzer().analyze_create_trigger(stmt).map_err(ExecutionError::Semantic)?;
+        let on_view = self.data_store.get_view(&stmt.table).is_some();
+        if on_view != (stmt.timing == TriggerTiming::InsteadOf) {
+            return Err(ExecutionError::InvalidTrigger(stmt.name.clone()));
+        }
+
+        self.data_store.create_trigger(stmt.name.clone(), TriggerSchema {
+            table: stmt.table.clone(),
+            timing: stmt.timing,
+            event: stmt.event.clone(),
+            when: stmt.when.clone(),
+            body: stmt.body.clone(),
+            references: stmt.references.clone()
+        })?;
+        Ok(QueryResult::CreateTrigger)
+    }
+
+    fn execute_drop_trigger(&mut self, stmt: &DropTriggerStatement) -> Result<QueryResult, ExecutionError> {
+        if self.data_store.get_trigger(&stmt.name).is_none() {
+            return if stmt.if_exists { Ok(QueryResult::DropTrigger) } else { Err(ExecutionError::TriggerNotFound(stmt.name.clone())) };
+        }
+        self.data_store.drop_trigger(&stmt.name)?;
+        Ok(QueryResult::DropTrigger)
+    }
+
+    fn execute_select(&self, stmt: &SelectStatement) -> Result<QueryResult, ExecutionError> {
+        let (columns, rows) = self.select_rows(stmt)?;
+        let rows = rows.collect::<Result<_, _>>()?;
+        Ok(QueryResult::Select { columns, rows })
+    }
+
+    /// The columns a SELECT returns and an iterator reading its rows. Rows
+    /// are filtered as they are read; only ORDER BY has to gather them all.
+    fn select_rows(&self, stmt: &SelectStatement) -> Result<(Vec<String>, SelectRows), ExecutionError> {
+        let schema = self.readable_schema(&stmt.table)?;
+
+        let columns = if stmt.columns.contains(&"*".to_string()) {
+            schema.column_names()
+        } else {
+            stmt.columns.clone()
+        };
 
-        if !stmt.order_by.is_empty() {
-            self.apply_order_by(&mut result_rows, &stmt.order_by)?;
+        for column in columns.iter().chain(stmt.order_by.iter().map(|clause| &clause.column)) {
+            if !schema.has_column(column) {
+                return Err(ExecutionError::ColumnNotFound(column.clone()));
+            }
         }
 
-        Ok(QueryResult::Select(result_rows))
+        let condition = self.planned_condition(&stmt.table, &stmt.condition, &schema);
+        let scanned = self.stored_rows(&stmt.table, &schema)?;
+        let schema = Rc::new(schema.into_owned());
+        let filter_schema = Rc::clone(&schema);
+        let matching = scanned.filter(move |row| match row {
+            Ok(row) => Self::matches(&condition, row, &filter_schema),
+            Err(_) => true
+        });
+
+        let stored: StoredRows = if stmt.order_by.is_empty() {
+            Box::new(matching)
+        } else {
+            let mut rows = matching.collect::<io::Result<Vec<_>>>()?;
+            Self::apply_order_by(&mut rows, &stmt.order_by, &schema);
+            Box::new(rows.into_iter().map(Ok))
+        };
+
+        let selected = columns.clone();
+        let rows = stored.map(move |row| {
+            let row = row?;
+            Ok(Row { values: selected.iter().map(|col| column_value(&row, &schema, col)).collect() })
+        });
+        Ok((columns, Box::new(rows)))
     }
 
-    fn execute_insert(&self, stmt: &InsertStatement) -> Result<QueryResult, ExecutionError> {
-        let table = self.data_store.get_table_mut(&stmt.table)
-            .ok_or_else(|| ExecutionError::TableNotFound(stmt.table.clone()));
-        
-        let new_row = Row { values: stmt.values.clone() };
-        table.insert(new_row);
+    /// The rows of a table, a view or the catalog table in stored form,
+    /// read as the iterator is advanced.
+    fn stored_rows(&self, table: &str, schema: &TableSchema) -> Result<StoredRows, ExecutionError> {
+        if is_schema_table(table) {
+            return Ok(Box::new(schema_rows(&self.data_store).into_iter().map(Ok)));
+        }
+        let Some(view) = self.data_store.get_view(table) else {
+            return Ok(Box::new(self.data_store.scan(table)?));
+        };
 
-        Ok(QueryResult::Insert(1))
+        // The view's rows are read as its SELECT returns them and put in
+        // the stored form, so they are filtered and sorted like a table's.
+        let (_, rows) = self.select_rows(&view.query)?;
+        let columns: Vec<_> = view.columns.iter().map(|column| (column.clone(), schema.columns[column].clone())).collect();
+        Ok(Box::new(rows.map(move |row| {
+            let row = row.map_err(into_io_error)?;
+            Ok(columns.iter().zip(&row.values)
+                .filter_map(|((column, column_type), value)| Some((column.clone(), value_to_field(value, column_type)?)))
+                .collect())
+        })))
     }
 
-    fn execute_update(&self, stmt: &UpdateStatement) -> Result<QueryResult, ExecutionError> {
-        let table = self.data_store.get_table_mut(&stmt.table)
-            .ok_or_else(|| ExecutionError::TableNotFound(stmt.table.clone()))?;
+    fn execute_insert(&mut self, stmt: &InsertStatement) -> Result<QueryResult, ExecutionError> {
+        let triggers = self.triggers_for(&stmt.table, &TriggerEvent::Insert);
+        let schema = self.writable_schema(&stmt.table, &triggers)?;
+        let schema = schema.as_ref();
 
-        let mut updated_count = 0;
-        for row in table.rows_mut() {
-            if self.evaluate_condition(&stmt.condition, row)? {
-                for (column, value) in &stmt.updates {
-                    let column_index = self.get_column.index(&stmt.table, column)?;
-                    row.values[column_index] = value.clone();
+        let columns = if stmt.columns.is_empty() {
+            schema.column_names()
+        } else {
+            stmt.columns.clone()
+        };
+
+        let rows = match &stmt.source {
+            InsertSource::Values(rows) => rows.iter()
+                .map(|values| Self::insert_fields(&stmt.table, &columns, schema, values, false))
+                .collect::<Result<Vec<_>, _>>()?,
+            // Every selected row is read before any is written, so a table
+            // can be copied into itself.
+            InsertSource::Select(select) => {
+                let (selected, rows) = self.select_rows(select)?;
+                if selected.len() != columns.len() {
+                    return Err(ExecutionError::ValueCountMismatch(stmt.table.clone()));
                 }
-                updated_count += 1;
+                rows.map(|row| Self::insert_fields(&stmt.table, &columns, schema, &row?.values, true))
+                    .collect::<Result<Vec<_>, _>>()?
             }
+        };
+
+        if triggers.is_empty() {
+            let inserted = self.data_store.insert_rows(&stmt.table, rows)?;
+            return Ok(QueryResult::Insert(inserted));
+        }
+
+        // With triggers, the BEFORE triggers run for every row, then the
+        // rows are written in one go and the AFTER triggers run for each.
+        let schema = schema.clone();
+        self.in_statement_transaction(|engine| {
+            let mut inserted = 0;
+            let mut pending = Vec::new();
+            for row in rows {
+                let new = DataStore::complete_row(&stmt.table, &schema, &row)?;
+                if engine.fire_triggers(&triggers, TriggerTiming::InsteadOf, None, Some(&new), &schema)? {
+                    inserted += 1;
+                    continue;
+                }
+                engine.fire_triggers(&triggers, TriggerTiming::Before, None, Some(&new), &schema)?;
+                pending.push(row);
+            }
+            if pending.is_empty() {
+                return Ok(QueryResult::Insert(inserted));
+            }
+
+            let stored = engine.data_store.insert_rows_returning(&stmt.table, pending)?;
+            inserted += stored.len();
+            for new in &stored {
+                engine.fire_triggers(&triggers, TriggerTiming::After, None, Some(new), &schema)?;
+            }
+            Ok(QueryResult::Insert(inserted))
+        })
+    }
+
+    /// The stored fields for one row of values, keyed by column.
+    fn insert_fields(table: &str, columns: &[String], schema: &TableSchema, values: &[Value], selected: bool) -> Result<HashMap<String, Option<String>>, ExecutionError> {
+        if columns.len() != values.len() {
+            return Err(ExecutionError::ValueCountMismatch(table.to_string()));
+        }
+
+        let mut row = HashMap::new();
+        for (column, value) in columns.iter().zip(values) {
+            row.insert(schema.resolve_column(column).to_string(), Self::stored_field(column, schema, value, selected)?);
+        }
+        Ok(row)
+    }
+
+    /// The field a value is stored as in a column, refusing one the type
+    /// rules do not let in. Outside STRICT tables, values selected by an
+    /// INSERT ... SELECT are stored as their affinity leaves them instead,
+    /// except blobs, which would be stored as text that only looks like them.
+    fn stored_field(column: &str, schema: &TableSchema, value: &Value, selected: bool) -> Result<Option<String>, ExecutionError> {
+        let column_type = schema.column_type(column)
+            .ok_or_else(|| ExecutionError::ColumnNotFound(column.to_string()))?;
+        let checked = schema.strict || !selected || matches!(value, Value::Blob(_));
+        if checked && !affinity::fits(value, column_type, schema.strict) {
+            return Err(ExecutionError::TypeMismatch(column.to_string()));
         }
+        Ok(value_to_field(value, column_type))
+    }
+
+    fn execute_update(&mut self, stmt: &UpdateStatement) -> Result<QueryResult, ExecutionError> {
+        let updated_columns = stmt.updates.iter().map(|(column, _)| column.clone()).collect();
+        let triggers = self.triggers_for(&stmt.table, &TriggerEvent::Update(updated_columns));
+        let schema = self.writable_schema(&stmt.table, &triggers)?.into_owned();
+
+        let mut updates = Vec::new();
+        for (column, value) in &stmt.updates {
+            updates.push((schema.resolve_column(column).to_string(), Self::stored_field(column, &schema, value, false)?));
+        }
+
+        let condition = self.planned_condition(&stmt.table, &stmt.condition, &schema);
+        if !triggers.is_empty() {
+            return self.update_with_triggers(&stmt.table, &updates, &condition, &schema, &triggers);
+        }
+        let updated_count = self.data_store.update_with(&stmt.table, |row| {
+            if !Self::matches(&condition, row, &schema) {
+                return false;
+            }
+            Self::apply_updates(row, &updates);
+            true
+        })?;
 
         Ok(QueryResult::Update(updated_count))
     }
 
-    fn execute_delete(&self, stmt: &DeleteStatement) -> Result<QueryResult, ExecutionError> {
-        let table = self.data_store.get_table_mut(&stmt.table)
-            .ok_or_else(|| ExecutionError::TableNotFound(stmt.table.clone()))?;
+    /// Runs an UPDATE with its triggers, or in place of it for a view. The
+    /// rows to change are all found first, so rows a trigger adds are not
+    /// changed too. The BEFORE triggers run for every row, then the table
+    /// is rewritten once and the AFTER triggers run for each changed row.
+    fn update_with_triggers(&mut self, table: &str, updates: &[(String, Option<String>)], condition: &Option<Condition>, schema: &TableSchema, triggers: &[(String, TriggerSchema)]) -> Result<QueryResult, ExecutionError> {
+        self.in_statement_transaction(|engine| {
+            let old_rows = engine.matching_rows(table, condition, schema)?;
+            let mut updated_count = 0;
+            let mut pending = Vec::new();
+            for old in old_rows {
+                let mut new = old.clone();
+                Self::apply_updates(&mut new, updates);
+                if engine.fire_triggers(triggers, TriggerTiming::InsteadOf, Some(&old), Some(&new), schema)? {
+                    updated_count += 1;
+                    continue;
+                }
+                engine.fire_triggers(triggers, TriggerTiming::Before, Some(&old), Some(&new), schema)?;
+                pending.push(old);
+            }
+            if pending.is_empty() {
+                return Ok(QueryResult::Update(updated_count));
+            }
 
-        let initial_count = table.row_count();
-        table.retain(|row| !self.evaluate_condition(&stmt.condition, row).unwrap_or(false));
-        let deleted_count = initial_count - table.row_count();
+            let rowids: HashSet<&String> = pending.iter().filter_map(|old| old.get(ROWID_COLUMN)).collect();
+            let mut written = HashMap::new();
+            engine.data_store.update_with(table, |row| {
+                let Some(rowid) = row.get(ROWID_COLUMN).filter(|rowid| rowids.contains(rowid)).cloned() else { return false };
+                Self::apply_updates(row, updates);
+                written.insert(rowid, row.clone());
+                true
+            })?;
+            for old in &pending {
+                // A BEFORE trigger may have deleted the row.
+                let Some(new) = old.get(ROWID_COLUMN).and_then(|rowid| written.get(rowid)) else { continue };
+                updated_count += 1;
+                engine.fire_triggers(triggers, TriggerTiming::After, Some(old), Some(new), schema)?;
+            }
+            Ok(QueryResult::Update(updated_count))
+        })
+    }
 
-        Ok(QueryResult::Delete(deleted_count))
+    /// Sets the columns of a stored row; a `None` field sets it to NULL.
+    fn apply_updates(row: &mut HashMap<String, String>, updates: &[(String, Option<String>)]) {
+        for (column, field) in updates {
+            match field {
+                Some(field) => row.insert(column.clone(), field.clone()),
+                None => row.remove(column)
+            };
+        }
     }
 
-    fn evaluate_condition(&self, condition: &Option<Condition>, row: &Row) -> Result<bool, ExecutionError> {
-        match condition {
-            Some(Condition::Comparison(column, op, value)) => {
-                let column_index = self.get_column_index(&stmt.table, column)?;
-                let row_value = &row.values[column_index];
-                match op {
-                    ComparisonOperator::Equals => Ok(row_value == value),
-                    ComparisonOperator::NotEquals => Ok(row_value != value),
-                    ComparisonOperator::GreaterThan => Ok(row_value > value),
-                    ComparisonOperator::GreaterEqualThan => Ok(row_value >= value),
-                    ComparisonOperator::LessThan => Ok(row_value < value),
-                    ComparisonOperator::LessEqualThan => Ok(row_value <= value)
+    fn execute_delete(&mut self, stmt: &DeleteStatement) -> Result<QueryResult, ExecutionError> {
+        let triggers = self.triggers_for(&stmt.table, &TriggerEvent::Delete);
+        let schema = self.writable_schema(&stmt.table, &triggers)?.into_owned();
+
+        let condition = self.planned_condition(&stmt.table, &stmt.condition, &schema);
+        if triggers.is_empty() {
+            let deleted_count = self.data_store.delete(&stmt.table, |row| Self::matches(&condition, row, &schema))?;
+            return Ok(QueryResult::Delete(deleted_count));
+        }
+
+        // Like an UPDATE with triggers, the rows go in one rewrite between
+        // the BEFORE and the AFTER triggers.
+        self.in_statement_transaction(|engine| {
+            let old_rows = engine.matching_rows(&stmt.table, &condition, &schema)?;
+            let mut deleted_count = 0;
+            let mut pending = Vec::new();
+            for old in old_rows {
+                if engine.fire_triggers(&triggers, TriggerTiming::InsteadOf, Some(&old), None, &schema)? {
+                    deleted_count += 1;
+                    continue;
+                }
+                engine.fire_triggers(&triggers, TriggerTiming::Before, Some(&old), None, &schema)?;
+                pending.push(old);
+            }
+            if pending.is_empty() {
+                return Ok(QueryResult::Delete(deleted_count));
+            }
+
+            let rowids: HashSet<&String> = pending.iter().filter_map(|old| old.get(ROWID_COLUMN)).collect();
+            let mut deleted = HashSet::new();
+            engine.data_store.delete(&stmt.table, |row| {
+                let Some(rowid) = row.get(ROWID_COLUMN).filter(|rowid| rowids.contains(rowid)) else { return false };
+                deleted.insert(rowid.clone());
+                true
+            })?;
+            for old in &pending {
+                if !old.get(ROWID_COLUMN).is_some_and(|rowid| deleted.contains(rowid)) {
+                    continue;
+                }
+                deleted_count += 1;
+                engine.fire_triggers(&triggers, TriggerTiming::After, Some(old), None, &schema)?;
+            }
+            Ok(QueryResult::Delete(deleted_count))
+        })
+    }
+
+    /// The triggers on `table` that a write of `event` sets off. None are
+    /// while one of them is running, as SQLite's triggers do not set
+    /// themselves off again.
+    fn triggers_for(&self, table: &str, event: &TriggerEvent) -> Vec<(String, TriggerSchema)> {
+        let Ok(schema) = self.readable_schema(table) else { return Vec::new() };
+        self.data_store.triggers_on(table).into_iter()
+            .filter(|(name, trigger)| trigger.fires_on(event, &schema) && !self.firing.contains(name))
+            .collect()
+    }
+
+    /// Runs the triggers among `triggers` for `timing` on one row, passing
+    /// its `old` and `new` values, and says whether there were any. A
+    /// trigger whose WHEN clause does not hold for the row is skipped.
+    fn fire_triggers(&mut self, triggers: &[(String, TriggerSchema)], timing: TriggerTiming, old: Option<&HashMap<String, String>>, new: Option<&HashMap<String, String>>, schema: &TableSchema) -> Result<bool, ExecutionError> {
+        let mut fired = false;
+        for (name, trigger) in triggers.iter().filter(|(_, trigger)| trigger.timing == timing) {
+            fired = true;
+            let values: Vec<Value> = trigger.references.iter().map(|reference| {
+                let (row, column) = reference.split_once('.').unwrap_or_default();
+                let row = if row == "NEW" { new } else { old };
+                row.map_or(Value::Null, |row| column_value(row, schema, column))
+            }).collect();
+
+            if let Some(when) = &trigger.when {
+                let mut when = when.clone();
+                bind_condition(&mut when, &values);
+                if evaluate_condition(&when, &HashMap::new(), schema) != Some(true) {
+                    continue;
                 }
             }
+
+            self.firing.push(name.clone());
+            let result = trigger.body.iter().try_for_each(|statement| {
+                let mut statement = statement.clone();
+                bind_node(&mut statement, &values);
+                self.execute_statement(&statement).map(|_| ())
+            });
+            self.firing.pop();
+            result?;
         }
+        Ok(fired)
     }
 
-    fn project_columns(&self, columns: &[String], row: &Row) -> Result<Vec<Value>, ExecutionError> {
-        if columns.contains(&"*".to_string()) {
-            Ok(row_values.clone())
-        } else {
-            columns.iter()
-                .map(|col| self.get_column_value(&stmt.table, col, row))
-                .collect()
+    /// Runs a write that sets off triggers as a transaction of its own, so
+    /// that a trigger that fails undoes the whole statement. Inside an open
+    /// transaction it is part of that one, and what the statement changed
+    /// before the failure stays until the transaction ends.
+    fn in_statement_transaction(&mut self, write: impl FnOnce(&mut Self) -> Result<QueryResult, ExecutionError>) -> Result<QueryResult, ExecutionError> {
+        if self.data_store.in_transaction() {
+            return write(self);
+        }
+        self.data_store.begin(TransactionMode::Deferred)?;
+        match write(self) {
+            Ok(result) => {
+                self.data_store.commit()?;
+                Ok(result)
+            }
+            Err(err) => {
+                self.data_store.rollback()?;
+                Err(err)
+            }
+        }
+    }
+
+    /// Every stored row of `table` that `condition` holds for.
+    fn matching_rows(&self, table: &str, condition: &Option<Condition>, schema: &TableSchema) -> Result<Vec<HashMap<String, String>>, ExecutionError> {
+        let rows = self.stored_rows(table, schema)?
+            .filter(|row| row.as_ref().map_or(true, |row| Self::matches(condition, row, schema)))
+            .collect::<io::Result<_>>()?;
+        Ok(rows)
+    }
+
+    /// The schema of a stored table. The catalog table and views are only
+    /// ever read, through `readable_schema`, so writes to them fail here.
+    fn table_schema(&self, table: &str) -> Result<&TableSchema, ExecutionError> {
+        if is_schema_table(table) || self.data_store.get_view(table).is_some() {
+            return Err(ExecutionError::ReadOnlyTable(table.to_string()));
+        }
+        self.data_store.get_table_schema(table)
+            .ok_or_else(|| ExecutionError::TableNotFound(table.to_string()))
+    }
+
+    /// The schema of what a write goes to: a table, or a view with INSTEAD
+    /// OF triggers for the write, which run in its place.
+    fn writable_schema(&self, table: &str, triggers: &[(String, TriggerSchema)]) -> Result<Cow<'_, TableSchema>, ExecutionError> {
+        if triggers.iter().any(|(_, trigger)| trigger.timing == TriggerTiming::InsteadOf) {
+            return self.readable_schema(table);
+        }
+        self.table_schema(table).map(Cow::Borrowed)
+    }
+
+    /// The columns of anything that can be selected from: a table, a view
+    /// or the catalog table.
+    fn readable_schema(&self, table: &str) -> Result<Cow<'_, TableSchema>, ExecutionError> {
+        if is_schema_table(table) {
+            return Ok(Cow::Borrowed(schema_table()));
+        }
+        if self.data_store.get_view(table).is_some() {
+            return self.data_store.view_schema(table).map(Cow::Owned).ok_or_else(|| ExecutionError::TableNotFound(table.to_string()));
         }
+        self.table_schema(table).map(Cow::Borrowed)
     }
 
-    fn apply_order_by(&self, rows: &mut [Row], order_by: &[OrderByClause]) -> Result<(), ExecutionError> {
+    /// A WHERE clause on `table` with its terms in the order the planner
+    /// picked from the table's statistics.
+    fn planned_condition(&self, table: &str, condition: &Option<Condition>, schema: &TableSchema) -> Option<Condition> {
+        condition.as_ref().map(|condition| order_condition(condition, self.data_store.table_statistics(table), schema))
+    }
+
+    fn matches(condition: &Option<Condition>, row: &HashMap<String, String>, schema: &TableSchema) -> bool {
+        match condition {
+            Some(condition) => evaluate_condition(condition, row, schema) == Some(true),
+            None => true
+        }
+    }
+
+    fn apply_order_by(rows: &mut [HashMap<String, String>], order_by: &[OrderByClause], schema: &TableSchema) {
         rows.sort_by(|a, b| {
             for clause in order_by {
-                let column_index = self.get_column_index(&stmt.table, &clause.column).unwrap();
-                let cmp = a.values[column_index].cmp(&b.values[column_index]);
-                if cmp != std::cmp::Ordering::Equal {
+                let (a, b) = (column_value(a, schema, &clause.column), column_value(b, schema, &clause.column));
+                // NULLs sort first, as in SQLite.
+                let cmp = match (&a, &b) {
+                    (Value::Null, Value::Null) => Ordering::Equal,
+                    (Value::Null, _) => Ordering::Less,
+                    (_, Value::Null) => Ordering::Greater,
+                    _ => compare_values(&a, &b).unwrap_or(Ordering::Equal)
+                };
+                if cmp != Ordering::Equal {
                     return if clause.order == OrderDirection::Asc { cmp } else { cmp.reverse() };
                 }
             }
-            std::cmp::Ordering::Equal 
+            Ordering::Equal
         });
-        Ok(())
     }
 }
 
+#[derive(Debug, PartialEq)]
 pub enum QueryResult {
-    Select(Vec<Row>),
+    Select { columns: Vec<String>, rows: Vec<Row> },
     Insert(usize),
     Update(usize),
-    Delete(usize)
+    Delete(usize),
+    CreateTable,
+    CreateView,
+    DropView,
+    CreateTrigger,
+    DropTrigger,
+    Analyze,
+    Begin,
+    Commit,
+    Rollback
 }
 
+#[derive(Debug, PartialEq)]
 pub struct Row {
     pub values: Vec<Value>
 }
 
+type SelectRows = Box<dyn Iterator<Item = Result<Row, ExecutionError>>>;
+
+/// The rows of a SELECT run with `ExecutionEngine::query`, read from the
+/// table as the cursor is advanced.
+pub struct Cursor<'a> {
+    engine: &'a mut ExecutionEngine,
+    columns: Vec<String>,
+    rows: SelectRows
+}
+
+impl Cursor<'_> {
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+}
+
+impl Iterator for Cursor<'_> {
+    type Item = Result<Row, ExecutionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rows.next()
+    }
+}
+
+impl Drop for Cursor<'_> {
+    fn drop(&mut self) {
+        let _ = self.engine.data_store.unlock();
+    }
+}
+
+#[derive(Debug)]
 pub enum ExecutionError {
     TableNotFound(String),
     ColumnNotFound(String),
-    TypeMismatch(String)
-}
\ No newline at end of file
+    TypeMismatch(String),
+    TableAlreadyExists(String),
+    ValueCountMismatch(String),
+    UnknownPragma(String),
+    InvalidPragmaValue(String),
+    Parse(String),
+    Semantic(SemanticError),
+    /// A parameter index or name the prepared statement does not have.
+    UnknownParameter(String),
+    ReadOnlyTable(String),
+    TriggerAlreadyExists(String),
+    TriggerNotFound(String),
+    /// An INSTEAD OF trigger on a table, or another trigger on a view.
+    InvalidTrigger(String),
+    Storage(io::Error)
+}
+
+impl From<io::Error> for ExecutionError {
+    fn from(error: io::Error) -> Self {
+        ExecutionError::Storage(error)
+    }
+}
+
+/// Passes an error from reading a view's rows on through the row iterator
+/// of the SELECT reading the view.
+fn into_io_error(error: ExecutionError) -> io::Error {
+    match error {
+        ExecutionError::Storage(error) => error,
+        other => io::Error::other(format!("{other:?}"))
+    }
+}
@@ -0,0 +1,470 @@
+use std::collections::HashMap;
+
+use crate::ast::{
+    ASTNode, ArithmeticOperator, ComparisonOperator, Condition, Expression, InsertSource, OrderDirection, SelectStatement, TransactionMode, TriggerEvent,
+    TriggerTiming, Value
+};
+use crate::blob;
+use crate::catalog::{is_schema_table, schema_table};
+use crate::datastore::DataStore;
+use crate::evaluator::to_text;
+use crate::execution_engine::{ExecutionError, Row};
+use crate::planner::{estimated_rows, order_condition};
+use crate::schema::{ForeignKeyAction, TableSchema};
+
+/// The columns of `EXPLAIN QUERY PLAN`, as in SQLite.
+pub const QUERY_PLAN_COLUMNS: [&str; 4] = ["id", "parent", "notused", "detail"];
+
+/// The columns of `EXPLAIN`. `p1` names what an operation works on and
+/// `p2` is the address a jump goes to.
+pub const PROGRAM_COLUMNS: [&str; 5] = ["addr", "opcode", "p1", "p2", "comment"];
+
+/// One step of a query plan, with the steps that feed it as children.
+#[derive(Debug, PartialEq)]
+pub struct PlanNode {
+    pub detail: String,
+    pub children: Vec<PlanNode>
+}
+
+/// One operation of the program a statement runs as.
+#[derive(Debug, PartialEq)]
+pub struct Instruction {
+    pub opcode: &'static str,
+    pub p1: Option<String>,
+    pub p2: Option<usize>,
+    pub comment: String
+}
+
+/// The scans and sorts a statement makes, with the number of rows each
+/// scan is expected to keep once its table has been analyzed. Statements
+/// that read no table, such as an INSERT of VALUES, have an empty plan.
+pub fn query_plan(ast: &ASTNode, data_store: &DataStore) -> Result<Vec<PlanNode>, ExecutionError> {
+    match ast {
+        ASTNode::Select(stmt) => select_plan(stmt, data_store),
+        ASTNode::Insert(stmt) => {
+            table_schema(data_store, &stmt.table)?;
+            match &stmt.source {
+                InsertSource::Values(_) => Ok(Vec::new()),
+                InsertSource::Select(select) => select_plan(select, data_store)
+            }
+        }
+        ASTNode::Update(stmt) => scan_plan(&stmt.table, &stmt.condition, data_store),
+        ASTNode::Delete(stmt) => scan_plan(&stmt.table, &stmt.condition, data_store),
+        ASTNode::Analyze(table) => {
+            let mut plan = Vec::new();
+            for table in analyzed_tables(table, data_store)? {
+                plan.extend(scan_plan(&table, &None, data_store)?);
+            }
+            Ok(plan)
+        }
+        ASTNode::Explain(_) => Err(nested_explain()),
+        ASTNode::CreateTable(_) | ASTNode::CreateView(_) | ASTNode::DropView(_) => Ok(Vec::new()),
+        ASTNode::CreateTrigger(_) | ASTNode::DropTrigger(_) => Ok(Vec::new()),
+        ASTNode::Pragma(_) | ASTNode::Begin(_) | ASTNode::Commit | ASTNode::Rollback => Ok(Vec::new())
+    }
+}
+
+/// A query plan as `EXPLAIN QUERY PLAN` rows, numbered depth first. Steps
+/// at the top have parent 0.
+pub fn plan_rows(plan: &[PlanNode]) -> Vec<Row> {
+    fn add(nodes: &[PlanNode], parent: usize, rows: &mut Vec<Row>) {
+        for node in nodes {
+            let id = rows.len() + 1;
+            rows.push(Row { values: vec![
+                Value::Number(id as f64),
+                Value::Number(parent as f64),
+                Value::Number(0.0),
+                Value::String(node.detail.clone())
+            ] });
+            add(&node.children, id, rows);
+        }
+    }
+
+    let mut rows = Vec::new();
+    add(plan, 0, &mut rows);
+    rows
+}
+
+/// Draws `EXPLAIN QUERY PLAN` rows as a tree, the way the sqlite3 shell
+/// does.
+pub fn render_query_plan(rows: &[Row]) -> String {
+    let mut children: HashMap<String, Vec<(String, String)>> = HashMap::new();
+    for row in rows {
+        if let [id, parent, _, detail] = row.values.as_slice() {
+            children.entry(to_text(parent)).or_default().push((to_text(id), to_text(detail)));
+        }
+    }
+
+    fn draw(parent: &str, prefix: &str, children: &HashMap<String, Vec<(String, String)>>, out: &mut String) {
+        let Some(nodes) = children.get(parent) else { return };
+        for (i, (id, detail)) in nodes.iter().enumerate() {
+            let last = i + 1 == nodes.len();
+            out.push_str(&format!("{prefix}{}{detail}\n", if last { "`--" } else { "|--" }));
+            draw(id, &format!("{prefix}{}", if last { "   " } else { "|  " }), children, out);
+        }
+    }
+
+    let mut out = "QUERY PLAN\n".to_string();
+    draw("0", "", &children, &mut out);
+    out
+}
+
+/// The operations the engine carries out for a statement, in order:
+/// the lock it takes, the loop over the rows it reads and the checks and
+/// write it makes.
+pub fn program(ast: &ASTNode, data_store: &DataStore) -> Result<Vec<Instruction>, ExecutionError> {
+    let mut program = Program::default();
+    match ast {
+        ASTNode::Select(stmt) => {
+            program.emit("Transaction", Some("shared"), "read lock until the last row");
+            program.select_loop(stmt, data_store, "ResultRow")?;
+        }
+        ASTNode::Insert(stmt) => {
+            let schema = table_schema(data_store, &stmt.table)?;
+            program.emit("Transaction", Some("reserved"), "write lock for the statement");
+            match &stmt.source {
+                InsertSource::Values(rows) => {
+                    program.emit("Values", None, &format!("{} row(s)", rows.len()));
+                }
+                // Every row is read before any is written.
+                InsertSource::Select(select) => program.select_loop(select, data_store, "Collect")?
+            }
+            program.emit("OpenWrite", Some(&stmt.table), "");
+            program.checks(&stmt.table, schema, data_store);
+            program.emit("Append", Some(&stmt.table), "every row in one write");
+            program.triggers(&stmt.table, &TriggerEvent::Insert, schema, data_store);
+        }
+        ASTNode::Update(stmt) => {
+            let schema = table_schema(data_store, &stmt.table)?;
+            program.emit("Transaction", Some("reserved"), "write lock for the statement");
+            let assignments = stmt.updates.iter()
+                .map(|(column, value)| format!("{column} = {}", value_sql(value)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let condition = planned_condition(&stmt.table, &stmt.condition, schema, data_store);
+            program.table_loop(&stmt.table, &condition, |program| {
+                program.emit("Set", Some(&stmt.table), &assignments);
+            });
+            program.checks(&stmt.table, schema, data_store);
+            let updated: Vec<&str> = stmt.updates.iter().map(|(column, _)| schema.resolve_column(column)).collect();
+            program.referencing_keys(&stmt.table, data_store, "UPDATE", |fk_columns| fk_columns.iter().any(|column| updated.contains(&column.as_str())));
+            program.emit("Rewrite", Some(&stmt.table), "once every changed row is checked");
+            let updated = stmt.updates.iter().map(|(column, _)| column.clone()).collect();
+            program.triggers(&stmt.table, &TriggerEvent::Update(updated), schema, data_store);
+        }
+        ASTNode::Delete(stmt) => {
+            let schema = table_schema(data_store, &stmt.table)?;
+            program.emit("Transaction", Some("reserved"), "write lock for the statement");
+            let condition = planned_condition(&stmt.table, &stmt.condition, schema, data_store);
+            program.table_loop(&stmt.table, &condition, |program| {
+                program.emit("Delete", Some(&stmt.table), "");
+            });
+            program.referencing_keys(&stmt.table, data_store, "DELETE", |_| true);
+            program.emit("Rewrite", Some(&stmt.table), "without the deleted rows");
+            program.triggers(&stmt.table, &TriggerEvent::Delete, schema, data_store);
+        }
+        ASTNode::Analyze(table) => {
+            program.emit("Transaction", Some("reserved"), "write lock for the statement");
+            for table in analyzed_tables(table, data_store)? {
+                program.emit("OpenRead", Some(&table), "");
+                program.emit("Analyze", Some(&table), "row count, distinct values and histograms");
+            }
+        }
+        ASTNode::CreateTable(stmt) => {
+            program.emit("Transaction", Some("reserved"), "write lock for the statement");
+            program.emit("CreateTable", Some(&stmt.table), &format!("{} column(s)", stmt.columns.len()));
+        }
+        ASTNode::CreateView(stmt) => {
+            program.emit("Transaction", Some("reserved"), "write lock for the statement");
+            program.emit("CreateView", Some(&stmt.name), &format!("rows of SELECT from {}", stmt.query.table));
+        }
+        ASTNode::DropView(stmt) => {
+            program.emit("Transaction", Some("reserved"), "write lock for the statement");
+            program.emit("DropView", Some(&stmt.name), "");
+        }
+        ASTNode::CreateTrigger(stmt) => {
+            program.emit("Transaction", Some("reserved"), "write lock for the statement");
+            program.emit("CreateTrigger", Some(&stmt.name), &format!("{} statement(s) on {}", stmt.body.len(), stmt.table));
+        }
+        ASTNode::DropTrigger(stmt) => {
+            program.emit("Transaction", Some("reserved"), "write lock for the statement");
+            program.emit("DropTrigger", Some(&stmt.name), "");
+        }
+        ASTNode::Pragma(stmt) => {
+            let comment = stmt.value.as_ref().map(value_sql).unwrap_or_default();
+            program.emit("Pragma", Some(&stmt.name), &comment);
+        }
+        ASTNode::Begin(mode) => {
+            let (mode, comment) = match mode {
+                TransactionMode::Deferred => ("deferred", "no lock until the first statement"),
+                TransactionMode::Immediate => ("immediate", "reserved lock until the end"),
+                TransactionMode::Exclusive => ("exclusive", "exclusive lock until the end")
+            };
+            program.emit("Begin", Some(mode), comment);
+        }
+        ASTNode::Commit => {
+            program.emit("Commit", None, "exclusive lock while the changes are made visible");
+        }
+        ASTNode::Rollback => {
+            program.emit("Rollback", None, "");
+        }
+        ASTNode::Explain(_) => return Err(nested_explain())
+    }
+    program.emit("Halt", None, "");
+    Ok(program.instructions)
+}
+
+/// An `Instruction` as an `EXPLAIN` row.
+pub fn program_rows(program: &[Instruction]) -> Vec<Row> {
+    program.iter().enumerate().map(|(addr, instruction)| Row { values: vec![
+        Value::Number(addr as f64),
+        Value::String(instruction.opcode.to_string()),
+        instruction.p1.clone().map_or(Value::Null, Value::String),
+        instruction.p2.map_or(Value::Null, |p2| Value::Number(p2 as f64)),
+        Value::String(instruction.comment.clone())
+    ] }).collect()
+}
+
+fn select_plan(stmt: &SelectStatement, data_store: &DataStore) -> Result<Vec<PlanNode>, ExecutionError> {
+    let mut plan = match data_store.get_view(&stmt.table) {
+        // A view's SELECT runs alongside the outer one, handing it rows.
+        Some(view) => vec![
+            PlanNode { detail: format!("CO-ROUTINE {}", stmt.table), children: select_plan(&view.query, data_store)? },
+            PlanNode { detail: format!("SCAN {}", stmt.table), children: Vec::new() }
+        ],
+        None => scan_plan(&stmt.table, &stmt.condition, data_store)?
+    };
+    if !stmt.order_by.is_empty() {
+        plan.push(PlanNode { detail: "USE TEMP B-TREE FOR ORDER BY".to_string(), children: Vec::new() });
+    }
+    Ok(plan)
+}
+
+/// Every row is read from the table's file: there are no indexes to
+/// narrow the read down.
+fn scan_plan(table: &str, condition: &Option<Condition>, data_store: &DataStore) -> Result<Vec<PlanNode>, ExecutionError> {
+    let schema = table_schema(data_store, table)?;
+    let detail = match data_store.table_statistics(table) {
+        Some(statistics) => format!("SCAN {table} (~{} rows)", estimated_rows(condition.as_ref(), statistics, schema)),
+        None => format!("SCAN {table}")
+    };
+    Ok(vec![PlanNode { detail, children: Vec::new() }])
+}
+
+/// The WHERE clause as the engine runs it, with its terms in the order the
+/// planner picked.
+fn planned_condition(table: &str, condition: &Option<Condition>, schema: &TableSchema, data_store: &DataStore) -> Option<Condition> {
+    condition.as_ref().map(|condition| order_condition(condition, data_store.table_statistics(table), schema))
+}
+
+fn analyzed_tables(table: &Option<String>, data_store: &DataStore) -> Result<Vec<String>, ExecutionError> {
+    match table {
+        Some(table) => {
+            table_schema(data_store, table)?;
+            Ok(vec![table.clone()])
+        }
+        None => Ok(data_store.table_names())
+    }
+}
+
+fn table_schema<'a>(data_store: &'a DataStore, table: &str) -> Result<&'a TableSchema, ExecutionError> {
+    if is_schema_table(table) {
+        return Ok(schema_table());
+    }
+    data_store.get_table_schema(table).ok_or_else(|| ExecutionError::TableNotFound(table.to_string()))
+}
+
+fn nested_explain() -> ExecutionError {
+    ExecutionError::Storage(std::io::Error::new(std::io::ErrorKind::InvalidInput, "EXPLAIN cannot explain another EXPLAIN"))
+}
+
+#[derive(Default)]
+struct Program {
+    instructions: Vec<Instruction>
+}
+
+impl Program {
+    fn emit(&mut self, opcode: &'static str, p1: Option<&str>, comment: &str) -> usize {
+        self.instructions.push(Instruction { opcode, p1: p1.map(str::to_string), p2: None, comment: comment.to_string() });
+        self.instructions.len() - 1
+    }
+
+    /// Points the jump at `addr` to the next instruction to be emitted.
+    fn jump_here(&mut self, addr: usize) {
+        self.instructions[addr].p2 = Some(self.instructions.len());
+    }
+
+    /// Reads every row of `table`, running `body` for the ones that match
+    /// `condition`.
+    fn table_loop(&mut self, table: &str, condition: &Option<Condition>, body: impl FnOnce(&mut Self)) {
+        self.emit("OpenRead", Some(table), "");
+        let rewind = self.emit("Rewind", Some(table), "jump when the table is empty");
+        let top = self.instructions.len();
+        let filter = condition.as_ref().map(|condition| self.emit("Filter", Some(table), &condition_sql(condition)));
+        body(self);
+        if let Some(filter) = filter {
+            self.jump_here(filter);
+        }
+        let next = self.emit("Next", Some(table), "");
+        self.instructions[next].p2 = Some(top);
+        self.jump_here(rewind);
+    }
+
+    /// The loop of a SELECT, handing each row to `output`. With ORDER BY
+    /// every row goes into a sorter first and is handed on once all are in.
+    fn select_loop(&mut self, stmt: &SelectStatement, data_store: &DataStore, output: &'static str) -> Result<(), ExecutionError> {
+        let view_schema = data_store.view_schema(&stmt.table);
+        let schema = match &view_schema {
+            Some(schema) => schema,
+            None => table_schema(data_store, &stmt.table)?
+        };
+        if let Some(view) = data_store.get_view(&stmt.table) {
+            self.emit("InitCoroutine", Some(&stmt.table), "rows of the view's SELECT");
+            self.select_loop(&view.query, data_store, "Yield")?;
+        }
+        let columns = if stmt.columns.contains(&"*".to_string()) {
+            schema.column_names()
+        } else {
+            stmt.columns.clone()
+        };
+        let columns = columns.join(", ");
+        let condition = planned_condition(&stmt.table, &stmt.condition, schema, data_store);
+
+        if stmt.order_by.is_empty() {
+            self.table_loop(&stmt.table, &condition, |program| {
+                program.emit(output, Some(&stmt.table), &columns);
+            });
+            return Ok(());
+        }
+
+        let keys = stmt.order_by.iter()
+            .map(|clause| format!("{} {}", clause.column, if clause.order == OrderDirection::Asc { "ASC" } else { "DESC" }))
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.table_loop(&stmt.table, &condition, |program| {
+            program.emit("SorterInsert", Some("sorter"), &keys);
+        });
+        let sort = self.emit("SorterSort", Some("sorter"), "jump when there are no rows");
+        let top = self.emit(output, Some("sorter"), &columns);
+        let next = self.emit("SorterNext", Some("sorter"), "");
+        self.instructions[next].p2 = Some(top);
+        self.jump_here(sort);
+        Ok(())
+    }
+
+    /// The checks every written row of `table` goes through.
+    fn checks(&mut self, table: &str, schema: &TableSchema, data_store: &DataStore) {
+        for column in schema.column_names() {
+            if schema.is_not_null(&column) {
+                self.emit("NotNull", Some(table), &column);
+            }
+        }
+        let primary_key = schema.primary_key();
+        if !primary_key.is_empty() {
+            self.emit("PrimaryKey", Some(table), &format!("{} unique", primary_key.join(", ")));
+        }
+        for check in schema.checks() {
+            self.emit("Check", Some(table), &condition_sql(check));
+        }
+        if data_store.foreign_keys_enabled() {
+            for fk in schema.foreign_keys() {
+                self.emit("ForeignKey", Some(&fk.parent_table), &format!("{} REFERENCES {}({})", fk.columns.join(", "), fk.parent_table, fk.parent_columns.join(", ")));
+            }
+        }
+    }
+
+    /// What happens to rows of other tables that point at the changed rows
+    /// of `table`, for the foreign keys `affected` picks by parent columns.
+    fn referencing_keys(&mut self, table: &str, data_store: &DataStore, statement: &str, affected: impl Fn(&[String]) -> bool) {
+        let mut referencing = data_store.referencing_foreign_keys(table);
+        referencing.sort_by(|(a, _), (b, _)| a.cmp(b));
+        for (child, fk) in referencing {
+            if !affected(&fk.parent_columns) {
+                continue;
+            }
+            let action = if statement == "DELETE" { &fk.on_delete } else { &fk.on_update };
+            let action = match action {
+                ForeignKeyAction::NoAction => "NO ACTION",
+                ForeignKeyAction::Restrict => "RESTRICT",
+                ForeignKeyAction::Cascade => "CASCADE",
+                ForeignKeyAction::SetNull => "SET NULL",
+                ForeignKeyAction::SetDefault => "SET DEFAULT"
+            };
+            self.emit("ForeignKeyAction", Some(&child), &format!("{} ON {statement} {action}", fk.columns.join(", ")));
+        }
+    }
+
+    /// The triggers a write sets off. Once there are any, the rows are
+    /// written one at a time, each between its BEFORE and AFTER triggers.
+    fn triggers(&mut self, table: &str, event: &TriggerEvent, schema: &TableSchema, data_store: &DataStore) {
+        for (name, trigger) in data_store.triggers_on(table) {
+            if !trigger.fires_on(event, schema) {
+                continue;
+            }
+            let timing = match trigger.timing {
+                TriggerTiming::Before => "BEFORE",
+                TriggerTiming::After => "AFTER",
+                TriggerTiming::InsteadOf => "INSTEAD OF"
+            };
+            self.emit("Program", Some(&name), &format!("{timing} each row, {} statement(s)", trigger.body.len()));
+        }
+    }
+}
+
+/// A condition written back out as SQL.
+pub(crate) fn condition_sql(condition: &Condition) -> String {
+    match condition {
+        Condition::Comparison(column, op, value) => format!("{column} {} {}", operator_sql(op), value_sql(value)),
+        Condition::Compare(left, op, right) => format!("{} {} {}", expression_sql(left), operator_sql(op), expression_sql(right)),
+        Condition::IsNull(expr) => format!("{} IS NULL", expression_sql(expr)),
+        Condition::IsNotNull(expr) => format!("{} IS NOT NULL", expression_sql(expr)),
+        Condition::IsTrue(expr) => format!("{} IS TRUE", expression_sql(expr)),
+        Condition::IsFalse(expr) => format!("{} IS FALSE", expression_sql(expr)),
+        Condition::Truth(expr) => expression_sql(expr),
+        Condition::And(left, right) => format!("({} AND {})", condition_sql(left), condition_sql(right)),
+        Condition::Or(left, right) => format!("({} OR {})", condition_sql(left), condition_sql(right)),
+        Condition::Not(inner) => format!("NOT {}", condition_sql(inner))
+    }
+}
+
+pub(crate) fn expression_sql(expr: &Expression) -> String {
+    match expr {
+        Expression::Literal(value) => value_sql(value),
+        Expression::Column(column) => column.clone(),
+        Expression::Negate(inner) => format!("-{}", expression_sql(inner)),
+        Expression::Function(name, args) => format!("{name}({})", args.iter().map(expression_sql).collect::<Vec<_>>().join(", ")),
+        Expression::Binary(left, op, right) => {
+            let op = match op {
+                ArithmeticOperator::Add => "+",
+                ArithmeticOperator::Subtract => "-",
+                ArithmeticOperator::Multiply => "*",
+                ArithmeticOperator::Divide => "/",
+                ArithmeticOperator::Modulo => "%",
+                ArithmeticOperator::Concat => "||"
+            };
+            format!("({} {op} {})", expression_sql(left), expression_sql(right))
+        }
+    }
+}
+
+fn operator_sql(op: &ComparisonOperator) -> &'static str {
+    match op {
+        ComparisonOperator::Equals => "=",
+        ComparisonOperator::NotEquals => "<>",
+        ComparisonOperator::GreaterThan => ">",
+        ComparisonOperator::LessThan => "<",
+        ComparisonOperator::GreaterEqualThan => ">=",
+        ComparisonOperator::LessEqualThan => "<="
+    }
+}
+
+pub(crate) fn value_sql(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("'{}'", s.replace('\'', "''")),
+        Value::Null => "NULL".to_string(),
+        Value::Boolean(b) => if *b { "TRUE" } else { "FALSE" }.to_string(),
+        Value::Blob(bytes) => format!("X'{}'", blob::to_hex(bytes)),
+        Value::Parameter(index) => format!("?{index}"),
+        number => to_text(number)
+    }
+}
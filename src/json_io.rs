@@ -0,0 +1,197 @@
+use std::io::{self, BufRead, Write};
+
+use serde_json::{Map, Value as JsonValue};
+
+use crate::ast::{ASTNode, Value};
+use crate::csv_io::{create_table, find_column, import_rows, ImportReport};
+use crate::{blob, datetime};
+use crate::execution_engine::{ExecutionEngine, ExecutionError};
+use crate::schema::{ColumnType, TableSchema};
+
+/// Records read to work out the column types of a table created on import.
+const INFERENCE_SAMPLE: usize = 1000;
+
+/// How exported rows are laid out.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum JsonFormat {
+    /// The whole export is one JSON array, so it can be parsed in one go.
+    Array,
+    /// One object per line, as in NDJSON.
+    Lines
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct JsonImportOptions {
+    /// Whether a missing table is created, with a column for every key in
+    /// the first records and types inferred from their values.
+    pub create_table: bool
+}
+
+/// Writes the rows of a SELECT as JSON objects keyed by column, in column
+/// order, and returns how many rows were written. Numbers stay numbers and
+/// NULL is `null`.
+pub fn export_json<W: Write>(engine: &mut ExecutionEngine, ast: &ASTNode, mut output: W, format: JsonFormat) -> Result<usize, ExecutionError> {
+    let cursor = engine.query(ast)?;
+    let keys = cursor.columns().iter()
+        .map(|column| serde_json::to_string(column).map_err(io::Error::from))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if format == JsonFormat::Array {
+        output.write_all(b"[")?;
+    }
+    let mut written = 0;
+    for row in cursor {
+        let row = row?;
+        if format == JsonFormat::Array {
+            output.write_all(if written == 0 { b"\n" } else { b",\n" })?;
+        }
+
+        write_object(&mut output, &keys, &row.values)?;
+
+        if format == JsonFormat::Lines {
+            output.write_all(b"\n")?;
+        }
+        written += 1;
+    }
+    if format == JsonFormat::Array {
+        output.write_all(if written == 0 { b"]\n" } else { b"\n]\n" })?;
+    }
+    output.flush()?;
+    Ok(written)
+}
+
+/// Loads newline-delimited JSON objects into `table`. Keys are matched to
+/// columns regardless of case, and a key left out takes the column's
+/// default. Values must already have their column's type: a record with
+/// an unknown key, a value of the wrong type or one that breaks a
+/// constraint is left out and reported. Blank lines are skipped.
+pub fn import_ndjson<R: BufRead>(engine: &mut ExecutionEngine, input: R, table: &str, options: &JsonImportOptions) -> Result<ImportReport, ExecutionError> {
+    let mut lines = input.lines()
+        .enumerate()
+        .map(|(i, line)| (i as u64 + 1, line))
+        .filter(|(_, line)| line.as_ref().map_or(true, |line| !line.trim().is_empty()));
+
+    let mut sample = Vec::new();
+    if engine.data_store().get_table_schema(table).is_none() {
+        if !options.create_table {
+            return Err(ExecutionError::TableNotFound(table.to_string()));
+        }
+        sample.extend(lines.by_ref().take(INFERENCE_SAMPLE));
+        let columns = infer_columns(&sample);
+        if columns.is_empty() {
+            return Err(ExecutionError::Storage(io::Error::new(io::ErrorKind::InvalidData, "no JSON objects to create the table from")));
+        }
+        create_table(engine, table, columns)?;
+    }
+    let schema = engine.data_store().get_table_schema(table)
+        .ok_or_else(|| ExecutionError::TableNotFound(table.to_string()))?
+        .clone();
+
+    let records = sample.into_iter().chain(lines).map(|(line, text)| {
+        let text = text?;
+        Ok((line, convert_object(&text, &schema)))
+    });
+    import_rows(engine, table, records)
+}
+
+/// Writes a row as a JSON object on one line, given its keys already
+/// encoded as JSON strings.
+pub(crate) fn write_object<W: Write>(output: &mut W, keys: &[String], values: &[Value]) -> io::Result<()> {
+    output.write_all(b"{")?;
+    for (i, (key, value)) in keys.iter().zip(values).enumerate() {
+        let separator = if i == 0 { "" } else { "," };
+        write!(output, "{separator}{key}:{}", to_json(value))?;
+    }
+    output.write_all(b"}")
+}
+
+fn to_json(value: &Value) -> JsonValue {
+    match value {
+        Value::Null | Value::Parameter(_) => JsonValue::Null,
+        Value::Number(n) if n.fract() == 0.0 && n.abs() < i64::MAX as f64 => JsonValue::from(*n as i64),
+        Value::Number(n) => JsonValue::from(*n),
+        Value::Boolean(b) => JsonValue::Bool(*b),
+        Value::Blob(bytes) => JsonValue::String(blob::to_hex(bytes)),
+        Value::String(s) => JsonValue::String(s.clone())
+    }
+}
+
+fn parse_object(text: &str) -> Result<Map<String, JsonValue>, ExecutionError> {
+    match serde_json::from_str(text).map_err(io::Error::from)? {
+        JsonValue::Object(object) => Ok(object),
+        _ => Err(ExecutionError::Storage(io::Error::new(io::ErrorKind::InvalidData, "expected a JSON object")))
+    }
+}
+
+fn convert_object(text: &str, schema: &TableSchema) -> Result<(Vec<String>, Vec<Value>), ExecutionError> {
+    let mut columns = Vec::new();
+    let mut values = Vec::new();
+    for (key, value) in parse_object(text)? {
+        let column = find_column(schema, &key).ok_or(ExecutionError::ColumnNotFound(key))?;
+        let column_type = schema.column_type(&column).unwrap_or(&ColumnType::String);
+        values.push(from_json(&value, column_type).ok_or_else(|| ExecutionError::TypeMismatch(column.clone()))?);
+        columns.push(column);
+    }
+    Ok((columns, values))
+}
+
+/// Reads a JSON value as a value of `column_type`, or `None` if it is not
+/// one. Text is never converted to a number, but a TEXT column, which is
+/// what a key with values of mixed types is inferred as, takes any other
+/// value as its JSON text.
+fn from_json(value: &JsonValue, column_type: &ColumnType) -> Option<Value> {
+    match (value, column_type) {
+        (JsonValue::Null, _) => Some(Value::Null),
+        (JsonValue::Bool(b), ColumnType::Boolean) => Some(Value::Boolean(*b)),
+        (JsonValue::Number(n), ColumnType::Integer) => n.as_i64().map(|n| n as f64)
+            .or_else(|| n.as_f64().filter(|n| n.fract() == 0.0))
+            .map(Value::Number),
+        (JsonValue::Number(n), ColumnType::Float) => n.as_f64().map(Value::Number),
+        (JsonValue::Number(n), ColumnType::Boolean) => n.as_i64().filter(|n| *n == 0 || *n == 1).map(|n| Value::Boolean(n == 1)),
+        (JsonValue::String(s), ColumnType::String) => Some(Value::String(s.clone())),
+        (value, ColumnType::String) => Some(Value::String(value.to_string())),
+        (JsonValue::String(s), ColumnType::Blob) => blob::from_hex(s, "").map(Value::Blob),
+        (JsonValue::String(s), ColumnType::Date | ColumnType::Time | ColumnType::Timestamp) => datetime::canonical(column_type, s).map(Value::String),
+        _ => None
+    }
+}
+
+/// Columns for every key in the sampled objects, typed by the values seen
+/// for them: a column whose values do not agree on a type becomes TEXT.
+/// Lines that are not JSON objects are skipped here and rejected on import.
+fn infer_columns(sample: &[(u64, io::Result<String>)]) -> Vec<(String, ColumnType)> {
+    let mut columns: Vec<(String, Option<ColumnType>)> = Vec::new();
+    for (_, text) in sample {
+        let Some(object) = text.as_ref().ok().and_then(|text| parse_object(text).ok()) else { continue };
+        for (key, value) in object {
+            let inferred = infer_type(&value);
+            match columns.iter_mut().find(|(name, _)| name.eq_ignore_ascii_case(&key)) {
+                Some((_, column_type)) => *column_type = merge_types(column_type.take(), inferred),
+                None => columns.push((key, inferred))
+            }
+        }
+    }
+    columns.into_iter()
+        .map(|(name, column_type)| (name, column_type.unwrap_or(ColumnType::String)))
+        .collect()
+}
+
+fn infer_type(value: &JsonValue) -> Option<ColumnType> {
+    match value {
+        JsonValue::Null => None,
+        JsonValue::Bool(_) => Some(ColumnType::Boolean),
+        JsonValue::Number(n) if n.is_i64() || n.is_u64() => Some(ColumnType::Integer),
+        JsonValue::Number(_) => Some(ColumnType::Float),
+        _ => Some(ColumnType::String)
+    }
+}
+
+fn merge_types(seen: Option<ColumnType>, next: Option<ColumnType>) -> Option<ColumnType> {
+    match (seen, next) {
+        (None, next) => next,
+        (seen, None) => seen,
+        (Some(seen), Some(next)) if seen == next => Some(seen),
+        (Some(ColumnType::Integer), Some(ColumnType::Float)) | (Some(ColumnType::Float), Some(ColumnType::Integer)) => Some(ColumnType::Float),
+        _ => Some(ColumnType::String)
+    }
+}
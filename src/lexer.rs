@@ -19,13 +19,41 @@ pub enum Token {
     Identifier(String),
     Number(f64),
     String(String),
+    /// The hex digits of an `X'...'` literal, as written.
+    Blob(String),
     Operator(String),
     Comma,
+    Dot,
+    Semicolon,
     Asterisk,
     LeftParen,
     RightParen,
     Asc, 
     Desc,
+    Create,
+    Table,
+    Not,
+    Null,
+    True,
+    False,
+    Default,
+    Check,
+    And,
+    Or,
+    Is,
+    References,
+    Foreign,
+    On,
+    Pragma,
+    Primary,
+    Begin,
+    Commit,
+    Rollback,
+    Explain,
+    Analyze,
+    Drop,
+    /// `?`, `?NNN`, `:name` or `$name`, as written.
+    Parameter(String),
     EOF
 }
 
@@ -46,18 +74,40 @@ impl<'a> Lexer<'a> {
         match self.input.next() {
             Some(ch) => match ch {
                 ',' => Token::Comma, 
+                '.' => Token::Dot,
+                ';' => Token::Semicolon,
                 '*' => Token::Asterisk,
                 '(' => Token::LeftParen,
                 ')' => Token::RightParen,
-                '=' | '>' | '<' => {
+                '=' | '>' | '<' | '!' => {
                     let mut op = ch.to_string();
                     if let Some(&next_ch) = self.input.peek() {
-                        if (ch == '<' && next_ch == '=') || (ch == '>' && next_ch == '=') || (ch == '<' && next_ch == '>') {
+                        if next_ch == '=' || (ch == '<' && next_ch == '>') {
                             op.push(self.input.next().unwrap());
                         }
                     }
                     Token::Operator(op)
                 }
+                '+' | '-' | '/' | '%' => Token::Operator(ch.to_string()),
+                '|' => {
+                    if self.input.peek() != Some(&'|') {
+                        panic!("Unexpected character: {ch}");
+                    }
+                    self.input.next();
+                    Token::Operator("||".to_string())
+                }
+                '?' => {
+                    let mut parameter = ch.to_string();
+                    while let Some(&digit) = self.input.peek().filter(|ch| ch.is_ascii_digit()) {
+                        parameter.push(digit);
+                        self.input.next();
+                    }
+                    Token::Parameter(parameter)
+                }
+                ':' | '$' if self.input.peek().is_some_and(|ch| ch.is_alphabetic() || *ch == '_') => {
+                    let name = self.read_identifier(ch);
+                    Token::Parameter(name)
+                }
                 '\'' | '"' => {
                     let s = self.read_string(ch);
                     Token::String(s)
@@ -66,6 +116,10 @@ impl<'a> Lexer<'a> {
                     let num = self.read_number(ch);
                     Token::Number(num)
                 }
+                'x' | 'X' if self.input.next_if_eq(&'\'').is_some() => {
+                    let digits = self.read_string('\'');
+                    Token::Blob(digits)
+                }
                 'a'..='z' | 'A'..='Z' | '_' => {
                     let ident = self.read_identifier(ch);
                     match ident.to_uppercase().as_str() {
@@ -82,6 +136,28 @@ impl<'a> Lexer<'a> {
                         "WHERE" => Token::Where, 
                         "INTO" => Token::Into, 
                         "VALUES" => Token::Values,
+                        "CREATE" => Token::Create,
+                        "TABLE" => Token::Table,
+                        "NOT" => Token::Not,
+                        "NULL" => Token::Null,
+                        "TRUE" => Token::True,
+                        "FALSE" => Token::False,
+                        "DEFAULT" => Token::Default,
+                        "CHECK" => Token::Check,
+                        "AND" => Token::And,
+                        "OR" => Token::Or,
+                        "IS" => Token::Is,
+                        "REFERENCES" => Token::References,
+                        "FOREIGN" => Token::Foreign,
+                        "ON" => Token::On,
+                        "PRAGMA" => Token::Pragma,
+                        "PRIMARY" => Token::Primary,
+                        "BEGIN" => Token::Begin,
+                        "COMMIT" => Token::Commit,
+                        "ROLLBACK" => Token::Rollback,
+                        "EXPLAIN" => Token::Explain,
+                        "ANALYZE" => Token::Analyze,
+                        "DROP" => Token::Drop,
                         _ => Token::Identifier(ident)
                     }
                 }
@@ -100,10 +176,12 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    /// Reads up to the closing quote. A quote written twice stands for
+    /// itself, as in 'it''s'.
     fn read_string(&mut self, quote: char) -> String {
         let mut s = String::new();
         while let Some(ch) = self.input.next() {
-            if ch == quote {
+            if ch == quote && self.input.next_if_eq(&quote).is_none() {
                 break;
             }
             s.push(ch);
@@ -114,7 +192,7 @@ impl<'a> Lexer<'a> {
     fn read_number(&mut self, first_digit: char) -> f64 {
         let mut num = String::from(first_digit);
         while let Some(&ch) = self.input.peek() {
-            if !ch.is_digit(10) && ch != '.' {
+            if !ch.is_ascii_digit() && ch != '.' {
                 break;
             }
             num.push(self.input.next().unwrap());
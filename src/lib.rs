@@ -3,4 +3,21 @@ pub mod ast;
 pub mod parser;
 pub mod semantic_analyzer;
 pub mod schema;
-pub mod datastore;
\ No newline at end of file
+pub mod datastore;
+pub mod wal;
+pub mod lock;
+pub mod affinity;
+pub mod evaluator;
+pub mod datetime;
+pub mod blob;
+pub mod execution_engine;
+pub mod explain;
+pub mod statistics;
+pub mod planner;
+pub mod catalog;
+pub mod statement;
+pub mod database;
+pub mod csv_io;
+pub mod json_io;
+pub mod sqlite_file;
+pub mod output;
\ No newline at end of file
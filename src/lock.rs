@@ -0,0 +1,158 @@
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How much of the data directory a lock holder may touch: any number of
+/// stores can read together, and one of them may be preparing a write
+/// alongside them, but making a write visible needs the directory to itself.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum LockMode {
+    Shared,
+    /// Held by the one store allowed to write. Its changes stay out of what
+    /// readers look at, so they can carry on reading.
+    Reserved,
+    Exclusive
+}
+
+/// An advisory lock on a data directory, held on two lock files inside it:
+/// one that readers share and a store publishing a write holds exclusively,
+/// and one only the writer holds.
+///
+/// The first file also holds a change counter that every writer bumps
+/// before giving up its lock, so other stores can tell whether what they
+/// keep in memory is still current.
+pub struct DirectoryLock {
+    file: File,
+    reserved: File,
+    mode: Option<LockMode>
+}
+
+impl DirectoryLock {
+    pub fn open(path: &Path, reserved_path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        let reserved = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(reserved_path)?;
+        Ok(DirectoryLock { file, reserved, mode: None })
+    }
+
+    /// Takes the lock in `mode`, or switches to it if already held, waiting
+    /// up to `timeout` for other holders to let go.
+    pub fn lock(&mut self, mode: LockMode, timeout: Duration) -> io::Result<()> {
+        let deadline = Instant::now() + timeout;
+        let mut delay = Duration::from_millis(1);
+        while !self.try_lock(mode)? {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(io::Error::new(io::ErrorKind::ResourceBusy, "database is locked"));
+            }
+            thread::sleep(delay.min(deadline - now));
+            delay = (delay * 2).min(Duration::from_millis(50));
+        }
+        Ok(())
+    }
+
+    /// Takes the lock in `mode` if nobody is in the way, leaving what is
+    /// held as it was otherwise.
+    pub fn try_lock(&mut self, mode: LockMode) -> io::Result<bool> {
+        let held = self.mode;
+        if held == Some(mode) {
+            return Ok(true);
+        }
+
+        let had_reserved = held >= Some(LockMode::Reserved);
+        let wants_reserved = mode >= LockMode::Reserved;
+        if wants_reserved && !had_reserved && !Self::try_flock(&self.reserved, true)? {
+            return Ok(false);
+        }
+
+        // Converting a lock can drop the one already held when it fails, so
+        // it is let go first and taken back if the new one is not granted.
+        let had_main = held.and_then(Self::main_lock);
+        let wants_main = Self::main_lock(mode);
+        if had_main != wants_main {
+            if had_main.is_some() {
+                self.file.unlock()?;
+            }
+            if let Some(exclusive) = wants_main {
+                if !Self::try_flock(&self.file, exclusive)? {
+                    if had_main == Some(false) {
+                        self.file.lock_shared()?;
+                    }
+                    if wants_reserved && !had_reserved {
+                        self.reserved.unlock()?;
+                    }
+                    return Ok(false);
+                }
+            }
+        }
+
+        if had_reserved && !wants_reserved {
+            self.reserved.unlock()?;
+        }
+        self.mode = Some(mode);
+        Ok(true)
+    }
+
+    pub fn unlock(&mut self) -> io::Result<()> {
+        if let Some(mode) = self.mode.take() {
+            if Self::main_lock(mode).is_some() {
+                self.file.unlock()?;
+            }
+            if mode >= LockMode::Reserved {
+                self.reserved.unlock()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether another store holds the reserved lock, that is, is part way
+    /// through a write.
+    pub fn reserved_elsewhere(&mut self) -> io::Result<bool> {
+        if self.mode >= Some(LockMode::Reserved) {
+            return Ok(false);
+        }
+        if Self::try_flock(&self.reserved, true)? {
+            self.reserved.unlock()?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    pub fn mode(&self) -> Option<LockMode> {
+        self.mode
+    }
+
+    pub fn change_counter(&mut self) -> io::Result<u64> {
+        let mut bytes = [0; 8];
+        self.file.seek(SeekFrom::Start(0))?;
+        let read = self.file.read(&mut bytes)?;
+        Ok(if read == bytes.len() { u64::from_le_bytes(bytes) } else { 0 })
+    }
+
+    pub fn bump_change_counter(&mut self) -> io::Result<u64> {
+        let counter = self.change_counter()?.wrapping_add(1);
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&counter.to_le_bytes())?;
+        Ok(counter)
+    }
+
+    /// Which lock `mode` needs on the main file: shared, exclusive, or none
+    /// at all for a writer that is not publishing yet.
+    fn main_lock(mode: LockMode) -> Option<bool> {
+        match mode {
+            LockMode::Shared => Some(false),
+            LockMode::Reserved => None,
+            LockMode::Exclusive => Some(true)
+        }
+    }
+
+    fn try_flock(file: &File, exclusive: bool) -> io::Result<bool> {
+        let attempt = if exclusive { file.try_lock() } else { file.try_lock_shared() };
+        match attempt {
+            Ok(()) => Ok(true),
+            Err(TryLockError::WouldBlock) => Ok(false),
+            Err(TryLockError::Error(err)) => Err(err)
+        }
+    }
+}
@@ -1,22 +1,306 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::panic;
+
 use rust_sqlite::{
+    ast::{ASTNode, ExplainMode, Value},
+    csv_io::{export_csv, import_csv, CsvOptions},
+    datastore::DataStore,
+    execution_engine::{ExecutionEngine, ExecutionError, QueryResult},
+    explain::render_query_plan,
+    json_io::{export_json, import_ndjson, JsonFormat, JsonImportOptions},
     lexer::Lexer,
-    lexer::Token,
-    parser::Parser
+    output::{write_rows, OutputMode, OutputOptions},
+    parser::Parser,
+    sqlite_file::export_sqlite_file,
+    statement::Statement
 };
 
+const USAGE: &str = "usage: rust-sqlite [DATA_DIRECTORY]";
+
+const HELP: &str = "\
+.export [OPTIONS] FILE SELECT ...   Write the rows of a query to a file
+.headers on|off                     Show column names in list, CSV and TSV output
+.help                               Show this message
+.import [OPTIONS] FILE TABLE        Load a file into a table, creating it if needed
+.mode [MODE] [TABLE]                Show or set how query results are written
+.nullvalue STRING                   Show NULL as STRING
+.quit                               Exit
+.save FILE                          Write the whole database to FILE as a SQLite database
+.schema [TABLE]                     Show the CREATE statements of every table, view and trigger, or of TABLE
+.tables                             List the tables and views
+
+Files are CSV unless --json (export only) or --ndjson is given. CSV files
+take --delimiter C and --no-header. Quote a FILE whose name has spaces.
+
+Modes are list (the default), box, csv, tsv, json, ndjson, markdown, line
+and insert, which writes INSERT statements into TABLE.";
+
+/// The file formats `.import` and `.export` read and write.
+enum Format {
+    Csv(CsvOptions),
+    Json,
+    Ndjson
+}
+
+/// Reads statements from standard input and runs them against the data
+/// directory given on the command line, or the current directory.
+/// Statements end with a semicolon and may span lines; lines starting with
+/// a dot are commands of the shell itself.
 fn main() {
-    let query = "SELECT id, name FROM users";
+    let mut args = std::env::args().skip(1);
+    let data_directory = args.next().unwrap_or_else(|| ".".to_string());
+    if data_directory == "-h" || data_directory == "--help" || args.next().is_some() {
+        eprintln!("{USAGE}");
+        std::process::exit(2);
+    }
+
+    let mut engine = match DataStore::new(&data_directory) {
+        Ok(data_store) => ExecutionEngine::new(data_store),
+        Err(err) => {
+            eprintln!("Error: cannot open {data_directory}: {err}");
+            std::process::exit(1);
+        }
+    };
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut output = OutputOptions::default();
+    let mut statement = String::new();
+    for line in io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                eprintln!("Error: {err}");
+                std::process::exit(1);
+            }
+        };
+
+        if statement.is_empty() && line.trim_start().starts_with('.') {
+            match run_command(&mut engine, line.trim(), &mut output, &mut out) {
+                Ok(true) => {}
+                Ok(false) => return,
+                Err(err) => eprintln!("Error: {err}")
+            }
+            continue;
+        }
+
+        statement.push_str(&line);
+        statement.push('\n');
+        if let Some(sql) = complete_statement(&statement) {
+            if let Err(err) = run_statement(&mut engine, sql, &output, &mut out) {
+                eprintln!("Error: {err}");
+            }
+            statement.clear();
+        }
+    }
+
+    if !statement.trim().is_empty() {
+        eprintln!("Error: incomplete statement, missing ';'");
+        std::process::exit(1);
+    }
+}
+
+/// The statement read so far without its final semicolon, once it is
+/// complete. The statements in the body of a CREATE TRIGGER end with
+/// semicolons of their own, so it only ends at the one after END.
+fn complete_statement(statement: &str) -> Option<&str> {
+    let sql = statement.trim_end().strip_suffix(';')?;
+    let mut words = sql.split(|ch: char| ch.is_whitespace() || ch == ';').filter(|word| !word.is_empty());
+    let is_trigger = words.clone()
+        .skip_while(|word| ["EXPLAIN", "QUERY", "PLAN"].iter().any(|keyword| word.eq_ignore_ascii_case(keyword)))
+        .take(2)
+        .map(str::to_ascii_uppercase)
+        .eq(["CREATE", "TRIGGER"]);
+    if is_trigger && !words.next_back().is_some_and(|word| word.eq_ignore_ascii_case("END")) {
+        return None;
+    }
+    Some(sql)
+}
+
+fn parse(sql: &str) -> Result<ASTNode, String> {
+    // The lexer panics on characters it does not know, which should not end
+    // the session.
+    let previous_hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let parsed = panic::catch_unwind(|| Parser::new(Lexer::new(sql)).parse());
+    panic::set_hook(previous_hook);
+
+    match parsed {
+        Ok(result) => result,
+        Err(payload) => Err(payload.downcast_ref::<String>().cloned().unwrap_or_else(|| "syntax error".to_string()))
+    }
+}
+
+fn run_statement(engine: &mut ExecutionEngine, sql: &str, output: &OutputOptions, out: &mut impl Write) -> Result<(), String> {
+    let ast = parse(sql)?;
+    match engine.execute(&ast).map_err(|err| describe(&err))? {
+        QueryResult::Select { rows, .. } if matches!(&ast, ASTNode::Explain(explain) if explain.mode == ExplainMode::QueryPlan) => {
+            write!(out, "{}", render_query_plan(&rows)).map_err(|err| err.to_string())?;
+        }
+        QueryResult::Select { columns, rows } => write_rows(out, &columns, &rows, output).map_err(|err| err.to_string())?,
+        QueryResult::Insert(_) | QueryResult::Update(_) | QueryResult::Delete(_) => {}
+        QueryResult::CreateTable | QueryResult::CreateView | QueryResult::DropView | QueryResult::CreateTrigger | QueryResult::DropTrigger => {}
+        QueryResult::Analyze | QueryResult::Begin | QueryResult::Commit | QueryResult::Rollback => {}
+    }
+    Ok(())
+}
+
+/// Runs a dot command, returning false once the shell should exit.
+fn run_command(engine: &mut ExecutionEngine, line: &str, output: &mut OutputOptions, out: &mut impl Write) -> Result<bool, String> {
+    let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    match command {
+        ".quit" | ".exit" => return Ok(false),
+        ".help" => writeln!(out, "{HELP}").map_err(|err| err.to_string())?,
+        ".mode" => {
+            let arguments: Vec<&str> = rest.split_whitespace().collect();
+            match arguments.as_slice() {
+                [] => match &output.mode {
+                    OutputMode::Insert(table) => writeln!(out, "current output mode: insert {table}"),
+                    mode => writeln!(out, "current output mode: {}", mode.name())
+                }.map_err(|err| err.to_string())?,
+                [name, table @ ..] if table.len() <= 1 => {
+                    output.mode = OutputMode::from_name(name, table.first().copied())
+                        .ok_or_else(|| format!("unknown mode {}, see .help", arguments.join(" ")))?;
+                }
+                _ => return Err("usage: .mode [MODE] [TABLE]".to_string())
+            }
+        }
+        ".headers" => {
+            output.headers = match rest.trim().to_ascii_lowercase().as_str() {
+                "on" | "yes" | "1" => true,
+                "off" | "no" | "0" => false,
+                _ => return Err("usage: .headers on|off".to_string())
+            };
+        }
+        ".nullvalue" => output.null_value = rest.trim().to_string(),
+        ".tables" => {
+            let tables = engine.prepare("SELECT name FROM sqlite_schema WHERE type = 'table' OR type = 'view' ORDER BY name").map_err(|err| describe(&err))?;
+            for name in catalog_text(engine, &tables)? {
+                writeln!(out, "{name}").map_err(|err| err.to_string())?;
+            }
+        }
+        ".schema" => {
+            // The catalog lists each table's triggers after it.
+            let mut schema = engine.prepare("SELECT sql FROM sqlite_schema WHERE type <> 'index' AND (tbl_name = ?1 OR ?1 IS NULL)")
+                .map_err(|err| describe(&err))?;
+            let table = rest.trim();
+            if !table.is_empty() {
+                schema.bind(1, table).map_err(|err| describe(&err))?;
+            }
+            for sql in catalog_text(engine, &schema)? {
+                writeln!(out, "{sql};").map_err(|err| err.to_string())?;
+            }
+        }
+        ".import" => {
+            let (format, arguments) = parse_format_options(rest)?;
+            let Some((path, table)) = split_path(arguments).filter(|(path, table)| !path.is_empty() && !table.is_empty() && !table.contains(char::is_whitespace)) else {
+                return Err("usage: .import [OPTIONS] FILE TABLE".to_string());
+            };
 
-    let lexer = Lexer::new(query);
-    let mut parser = Parser::new(lexer);
+            let file = File::open(path).map_err(|err| format!("cannot open {path}: {err}"))?;
+            let report = match format {
+                Format::Csv(options) => import_csv(engine, BufReader::new(file), table, &options),
+                Format::Ndjson => import_ndjson(engine, BufReader::new(file), table, &JsonImportOptions { create_table: true }),
+                Format::Json => return Err("only NDJSON can be imported, use --ndjson".to_string())
+            }.map_err(|err| describe(&err))?;
+            for rejected in &report.rejected {
+                eprintln!("{path}:{}: rejected: {}", rejected.line, describe(&rejected.error));
+            }
+            writeln!(out, "imported {} rows, rejected {}", report.imported, report.rejected.len()).map_err(|err| err.to_string())?;
+        }
+        ".export" => {
+            let (format, arguments) = parse_format_options(rest)?;
+            let Some((path, query)) = split_path(arguments).filter(|(path, query)| !path.is_empty() && !query.is_empty()) else {
+                return Err("usage: .export [OPTIONS] FILE SELECT ...".to_string());
+            };
 
-    println!("Passed!");
+            let ast = parse(query.trim_end().trim_end_matches(';'))?;
+            let file = BufWriter::new(File::create(path).map_err(|err| format!("cannot create {path}: {err}"))?);
+            let written = match format {
+                Format::Csv(options) => export_csv(engine, &ast, file, &options),
+                Format::Json => export_json(engine, &ast, file, JsonFormat::Array),
+                Format::Ndjson => export_json(engine, &ast, file, JsonFormat::Lines)
+            }.map_err(|err| describe(&err))?;
+            writeln!(out, "exported {written} rows").map_err(|err| err.to_string())?;
+        }
+        ".save" => {
+            let path = rest.trim();
+            if path.is_empty() {
+                return Err("usage: .save FILE".to_string());
+            }
+            let file = BufWriter::new(File::create(path).map_err(|err| format!("cannot create {path}: {err}"))?);
+            let written = export_sqlite_file(engine, file).map_err(|err| describe(&err))?;
+            writeln!(out, "saved {written} rows").map_err(|err| err.to_string())?;
+        }
+        _ => return Err(format!("unknown command {command}, see .help"))
+    }
+    Ok(true)
+}
+
+/// The first column of each row a query on the catalog returns.
+fn catalog_text(engine: &mut ExecutionEngine, query: &Statement) -> Result<Vec<String>, String> {
+    let rows = query.query(engine).map_err(|err| describe(&err))?;
+    rows.map(|row| match row.map_err(|err| describe(&err))?.values.first() {
+        Some(Value::String(text)) => Ok(text.clone()),
+        _ => Ok(String::new())
+    }).collect()
+}
 
-    // let parser = Parser::new(tokens);
-    // let ast = parser.parse();
+/// Splits the leading format options off a command's arguments, returning
+/// the rest of the arguments as written.
+fn parse_format_options(arguments: &str) -> Result<(Format, &str), String> {
+    let mut options = CsvOptions::default();
+    let mut format = None;
+    let mut rest = arguments.trim_start();
+    while let Some((word, after)) = next_word(rest) {
+        match word {
+            "--json" => format = Some(Format::Json),
+            "--ndjson" => format = Some(Format::Ndjson),
+            "--delimiter" => {
+                let (delimiter, after) = next_word(after).ok_or("--delimiter needs a character")?;
+                options.delimiter = match delimiter {
+                    "\\t" | "tab" => b'\t',
+                    _ if delimiter.len() == 1 => delimiter.as_bytes()[0],
+                    _ => return Err(format!("delimiter must be a single character, not {delimiter}"))
+                };
+                rest = after;
+                continue;
+            }
+            "--no-header" => options.has_header = false,
+            _ => break
+        }
+        rest = after;
+    }
+    Ok((format.unwrap_or(Format::Csv(options)), rest))
+}
 
-    // let interpreter = Interpreter::new();
-    // let result = interpreter.execute();
+/// Splits the first whitespace separated word off `text`.
+fn next_word(text: &str) -> Option<(&str, &str)> {
+    let text = text.trim_start();
+    if text.is_empty() {
+        return None;
+    }
+    let (word, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    Some((word, rest.trim_start()))
+}
+
+/// Splits a file name off the front of a command's arguments. A name in
+/// single or double quotes may contain spaces.
+fn split_path(arguments: &str) -> Option<(&str, &str)> {
+    let arguments = arguments.trim_start();
+    match arguments.chars().next()? {
+        quote @ ('\'' | '"') => {
+            let (path, rest) = arguments[1..].split_once(quote)?;
+            Some((path, rest.trim_start()))
+        }
+        _ => next_word(arguments)
+    }
+}
 
-    // println!("{:?}", result);
+fn describe(error: &ExecutionError) -> String {
+    match error {
+        ExecutionError::Storage(err) => err.to_string(),
+        other => format!("{other:?}")
+    }
 }
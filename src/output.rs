@@ -0,0 +1,209 @@
+use std::io::{self, Write};
+
+use csv::WriterBuilder;
+
+use crate::ast::Value;
+use crate::blob;
+use crate::evaluator::to_text;
+use crate::execution_engine::Row;
+use crate::json_io::write_object;
+
+/// How the shell lays out the rows of a query, after sqlite3's `.mode`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum OutputMode {
+    /// Fields separated by `|`, a row per line.
+    List,
+    /// An aligned table drawn with box characters.
+    Box,
+    Csv,
+    Tsv,
+    /// The rows printed as a JSON array of objects keyed by column name.
+    Json,
+    /// An object per line.
+    Ndjson,
+    /// A table in Markdown's pipe syntax.
+    Markdown,
+    /// A `column = value` line per column, with a blank line between rows.
+    Line,
+    /// An INSERT statement per row into the named table.
+    Insert(String)
+}
+
+impl OutputMode {
+    /// The mode called `name` in `.mode`. Only INSERT takes an argument,
+    /// the table to insert into, which defaults to "table" as in sqlite3.
+    pub fn from_name(name: &str, table: Option<&str>) -> Option<Self> {
+        let mode = match name.to_ascii_lowercase().as_str() {
+            "list" => OutputMode::List,
+            "box" => OutputMode::Box,
+            "csv" => OutputMode::Csv,
+            "tsv" | "tabs" => OutputMode::Tsv,
+            "json" => OutputMode::Json,
+            "ndjson" => OutputMode::Ndjson,
+            "markdown" => OutputMode::Markdown,
+            "line" => OutputMode::Line,
+            "insert" => return Some(OutputMode::Insert(table.unwrap_or("table").to_string())),
+            _ => return None
+        };
+        table.is_none().then_some(mode)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputMode::List => "list",
+            OutputMode::Box => "box",
+            OutputMode::Csv => "csv",
+            OutputMode::Tsv => "tsv",
+            OutputMode::Json => "json",
+            OutputMode::Ndjson => "ndjson",
+            OutputMode::Markdown => "markdown",
+            OutputMode::Line => "line",
+            OutputMode::Insert(_) => "insert"
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OutputOptions {
+    pub mode: OutputMode,
+    /// Whether list, CSV and TSV output starts with the column names, and
+    /// INSERT statements name their columns. Box and Markdown tables
+    /// always have a header, and the other modes never do.
+    pub headers: bool,
+    /// What NULL is shown as in every mode but JSON, NDJSON and INSERT,
+    /// which have their own way to write it.
+    pub null_value: String
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        OutputOptions { mode: OutputMode::List, headers: false, null_value: String::new() }
+    }
+}
+
+/// Writes the result of a query in the chosen mode.
+pub fn write_rows<W: Write>(output: &mut W, columns: &[String], rows: &[Row], options: &OutputOptions) -> io::Result<()> {
+    let text = |value: &Value| match value {
+        Value::Null => options.null_value.clone(),
+        value => to_text(value)
+    };
+    let text_rows = || rows.iter().map(|row| row.values.iter().map(text).collect::<Vec<String>>());
+
+    match &options.mode {
+        OutputMode::List => {
+            if options.headers {
+                writeln!(output, "{}", columns.join("|"))?;
+            }
+            for fields in text_rows() {
+                writeln!(output, "{}", fields.join("|"))?;
+            }
+        }
+        OutputMode::Csv | OutputMode::Tsv => {
+            let delimiter = if options.mode == OutputMode::Csv { b',' } else { b'\t' };
+            let mut writer = WriterBuilder::new().delimiter(delimiter).from_writer(&mut *output);
+            if options.headers {
+                writer.write_record(columns)?;
+            }
+            for fields in text_rows() {
+                writer.write_record(fields)?;
+            }
+            writer.flush()?;
+        }
+        OutputMode::Box => {
+            let rows: Vec<Vec<String>> = text_rows().collect();
+            let widths = column_widths(columns, &rows);
+            let rule = |left: &str, middle: &str, right: &str| {
+                let lines: Vec<String> = widths.iter().map(|width| "─".repeat(width + 2)).collect();
+                format!("{left}{}{right}", lines.join(middle))
+            };
+
+            writeln!(output, "{}", rule("┌", "┬", "┐"))?;
+            writeln!(output, "{}", boxed_row(columns, &widths))?;
+            writeln!(output, "{}", rule("├", "┼", "┤"))?;
+            for fields in &rows {
+                writeln!(output, "{}", boxed_row(fields, &widths))?;
+            }
+            writeln!(output, "{}", rule("└", "┴", "┘"))?;
+        }
+        OutputMode::Markdown => {
+            let escape = |field: &String| field.replace('|', "\\|");
+            let header: Vec<String> = columns.iter().map(escape).collect();
+            let rows: Vec<Vec<String>> = text_rows().map(|fields| fields.iter().map(escape).collect()).collect();
+            let widths = column_widths(&header, &rows);
+
+            writeln!(output, "{}", markdown_row(&header, &widths))?;
+            let rules: Vec<String> = widths.iter().map(|width| "-".repeat(width + 2)).collect();
+            writeln!(output, "|{}|", rules.join("|"))?;
+            for fields in &rows {
+                writeln!(output, "{}", markdown_row(fields, &widths))?;
+            }
+        }
+        OutputMode::Line => {
+            let width = columns.iter().map(|column| column.chars().count()).max().unwrap_or(0);
+            for (i, fields) in text_rows().enumerate() {
+                if i > 0 {
+                    writeln!(output)?;
+                }
+                for (column, field) in columns.iter().zip(fields) {
+                    writeln!(output, "{column:>width$} = {field}")?;
+                }
+            }
+        }
+        OutputMode::Json | OutputMode::Ndjson => {
+            let keys = columns.iter()
+                .map(|column| serde_json::to_string(column).map_err(io::Error::from))
+                .collect::<io::Result<Vec<_>>>()?;
+            let array = options.mode == OutputMode::Json;
+            if array {
+                output.write_all(b"[")?;
+            }
+            for (i, row) in rows.iter().enumerate() {
+                if array {
+                    output.write_all(if i == 0 { b"\n" } else { b",\n" })?;
+                }
+                write_object(output, &keys, &row.values)?;
+                if !array {
+                    output.write_all(b"\n")?;
+                }
+            }
+            if array {
+                output.write_all(if rows.is_empty() { b"]\n" } else { b"\n]\n" })?;
+            }
+        }
+        OutputMode::Insert(table) => {
+            let target = if options.headers { format!("{table}({})", columns.join(",")) } else { table.clone() };
+            for row in rows {
+                let values: Vec<String> = row.values.iter().map(sql_literal).collect();
+                writeln!(output, "INSERT INTO {target} VALUES({});", values.join(","))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The widest of each column's name and fields, in characters.
+fn column_widths(columns: &[String], rows: &[Vec<String>]) -> Vec<usize> {
+    columns.iter().enumerate()
+        .map(|(i, column)| rows.iter().map(|fields| fields[i].chars().count()).fold(column.chars().count(), usize::max))
+        .collect()
+}
+
+fn boxed_row(fields: &[String], widths: &[usize]) -> String {
+    let cells: Vec<String> = fields.iter().zip(widths).map(|(field, width)| format!(" {field:<width$} ")).collect();
+    format!("│{}│", cells.join("│"))
+}
+
+fn markdown_row(fields: &[String], widths: &[usize]) -> String {
+    let cells: Vec<String> = fields.iter().zip(widths).map(|(field, width)| format!(" {field:<width$} ")).collect();
+    format!("|{}|", cells.join("|"))
+}
+
+/// A value as it would be written in SQL, with quotes in strings doubled.
+fn sql_literal(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("'{}'", s.replace('\'', "''")),
+        Value::Null | Value::Parameter(_) => "NULL".to_string(),
+        Value::Blob(bytes) => format!("X'{}'", blob::to_hex(bytes)),
+        number => to_text(number)
+    }
+}
@@ -7,41 +7,104 @@ use crate::ast::{
     ASTNode, 
     SelectStatement,
     InsertStatement,
+    InsertSource,
     UpdateStatement,
     DeleteStatement,
+    CreateTableStatement,
+    ColumnDefinition,
+    PragmaStatement,
+    ExplainStatement,
+    ExplainMode,
+    CreateViewStatement,
+    DropViewStatement,
+    CreateTriggerStatement,
+    DropTriggerStatement,
+    TriggerTiming,
+    TriggerEvent,
+    TransactionMode,
     Condition,
+    Expression,
+    ArithmeticOperator,
     ComparisonOperator,
     Value,
     OrderByClause,
     OrderDirection
 };
 
+use crate::schema::{
+    ColumnType,
+    ColumnConstraint,
+    TableConstraint,
+    ForeignKey,
+    ForeignKeyAction
+};
+use crate::{blob, datetime};
+
+/// The largest parameter index a statement can use, SQLite's default
+/// `SQLITE_MAX_VARIABLE_NUMBER`.
+const MAX_PARAMETERS: usize = 32766;
+
 pub struct Parser<'a> {
     lexer: Lexer<'a>,
-    current_token: Token
+    current_token: Token,
+    /// The parameters seen so far by index, less one, with their names.
+    parameters: Vec<Option<String>>,
+    /// Whether `NEW.column` and `OLD.column` can be used, as they can in
+    /// the WHEN clause and body of a trigger.
+    in_trigger: bool
 }
 
 impl<'a> Parser<'a> {
     pub fn new(lexer: Lexer<'a>) -> Self {
         let mut parser = Parser {
             lexer, 
-            current_token: Token::EOF
+            current_token: Token::EOF,
+            parameters: Vec::new(),
+            in_trigger: false
         };
 
         parser.advance();
         parser 
     }
 
+    /// The name of each parameter of the statement parsed, by index less
+    /// one, or `None` for a `?` or `?NNN` one. Indexes skipped by `?NNN`
+    /// have no name either.
+    pub fn parameters(&self) -> &[Option<String>] {
+        &self.parameters
+    }
+
     fn advance(&mut self) {
         self.current_token = self.lexer.next().unwrap_or(Token::EOF);
     }
 
     pub fn parse(&mut self) -> Result<ASTNode, String> {
+        let ast = self.parse_statement()?;
+        let defines_schema = match &ast {
+            ASTNode::Explain(stmt) => matches!(*stmt.statement, ASTNode::CreateTable(_) | ASTNode::CreateView(_) | ASTNode::Pragma(_)),
+            ast => matches!(ast, ASTNode::CreateTable(_) | ASTNode::CreateView(_) | ASTNode::Pragma(_))
+        };
+        if defines_schema && !self.parameters.is_empty() {
+            return Err("Parameters are not allowed in CREATE TABLE, CREATE VIEW or PRAGMA".to_string());
+        }
+        Ok(ast)
+    }
+
+    fn parse_statement(&mut self) -> Result<ASTNode, String> {
         match self.current_token {
             Token::Select => self.parse_select(),
             Token::Insert => self.parse_insert(),
             Token::Delete => self.parse_delete(),
             Token::Update => self.parse_update(),
+            Token::Create => self.parse_create_table(),
+            Token::Drop => self.parse_drop(),
+            Token::Pragma => self.parse_pragma(),
+            Token::Begin => self.parse_begin(),
+            Token::Commit => self.parse_end_transaction(ASTNode::Commit),
+            Token::Rollback => self.parse_end_transaction(ASTNode::Rollback),
+            Token::Explain => self.parse_explain(),
+            Token::Analyze => self.parse_analyze(),
+            _ if self.current_identifier_is("END") => self.parse_end_transaction(ASTNode::Commit),
             _ => Err("Unexpected token".to_string())
         }
     }
@@ -110,24 +173,46 @@ impl<'a> Parser<'a> {
             Vec::new()
         };
 
-        if self.current_token != Token::Values {
-            return Err("Expected values keyword".to_string());
-        }
-        self.advance();
-
-        let values = self.parse_value_list()?;
-
-        if columns.len() > 0 && columns.len() != values.len() {
-            return Err("Number of columns doesn't match number of values".to_string());
-        }
+        let source = match self.current_token {
+            Token::Values => {
+                self.advance();
+                InsertSource::Values(self.parse_value_rows(&columns)?)
+            }
+            Token::Select => match self.parse_select()? {
+                ASTNode::Select(select) => InsertSource::Select(Box::new(select)),
+                _ => return Err("Expected SELECT".to_string())
+            },
+            _ => return Err("Expected values keyword".to_string())
+        };
 
         Ok(ASTNode::Insert(InsertStatement {
             table, 
             columns, 
-            values, 
+            source, 
         }))
     }
 
+    /// One or more comma separated value lists, all the same length.
+    fn parse_value_rows(&mut self, columns: &[String]) -> Result<Vec<Vec<Value>>, String> {
+        let mut rows = Vec::new();
+        loop {
+            let values = self.parse_value_list()?;
+
+            if !columns.is_empty() && columns.len() != values.len() {
+                return Err("Number of columns doesn't match number of values".to_string());
+            }
+            if rows.first().is_some_and(|first: &Vec<Value>| first.len() != values.len()) {
+                return Err("All VALUES must have the same number of terms".to_string());
+            }
+            rows.push(values);
+
+            if self.current_token != Token::Comma {
+                return Ok(rows);
+            }
+            self.advance();
+        }
+    }
+
     pub fn parse_update(&mut self) -> Result<ASTNode, String> {
         self.advance();
 
@@ -179,19 +264,8 @@ impl<'a> Parser<'a> {
             }
             self.advance();
 
-            let value = match &self.current_token {
-                Token::Number(n) => {
-                    let num = *n;
-                    self.advance();
-                    Value::Number(num)
-                }
-                Token::String(s) => {
-                    let string_val = s.clone();
-                    self.advance();
-                    Value::String(string_val)
-                }
-                _ => return Err("Expected value in UPDATE statement.".to_string())
-            };
+            let value = self.parse_literal()
+                .map_err(|_| "Expected value in UPDATE statement.".to_string())?;
 
             updates.push((column, value));
 
@@ -303,17 +377,7 @@ impl<'a> Parser<'a> {
         let mut values = Vec::new();
 
         loop {
-            match &self.current_token {
-                Token::Number(n) => {
-                    values.push(Value::Number(*n));
-                    self.advance();
-                }
-                Token::String(s) => {
-                    values.push(Value::String(s.clone()));
-                    self.advance();
-                }
-                _ => return Err("Expected value".to_string())
-            }
+            values.push(self.parse_literal()?);
 
             match self.current_token {
                 Token::Comma => {
@@ -332,20 +396,90 @@ impl<'a> Parser<'a> {
     }
 
     pub fn parse_condition(&mut self) -> Result<Condition, String> {
-        let column = match self.current_token {
-            Token::Identifier(ref name) => {
-                let column_name = name.clone();
-                self.advance();
-                column_name 
+        let mut condition = self.parse_and_condition()?;
+
+        while self.current_token == Token::Or {
+            self.advance();
+            let right = self.parse_and_condition()?;
+            condition = Condition::Or(Box::new(condition), Box::new(right));
+        }
+
+        Ok(condition)
+    }
+
+    fn parse_and_condition(&mut self) -> Result<Condition, String> {
+        let mut condition = self.parse_not_condition()?;
+
+        while self.current_token == Token::And {
+            self.advance();
+            let right = self.parse_not_condition()?;
+            condition = Condition::And(Box::new(condition), Box::new(right));
+        }
+
+        Ok(condition)
+    }
+
+    fn parse_not_condition(&mut self) -> Result<Condition, String> {
+        if self.current_token == Token::Not {
+            self.advance();
+            let inner = self.parse_not_condition()?;
+            return Ok(Condition::Not(Box::new(inner)));
+        }
+
+        if self.current_token == Token::LeftParen {
+            self.advance();
+            let inner = self.parse_condition()?;
+            if self.current_token != Token::RightParen {
+                return Err("Expected right parens after condition".to_string());
             }
+            self.advance();
+            return Ok(inner);
+        }
+
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> Result<Condition, String> {
+        let left = match self.current_token {
+            Token::Identifier(_) | Token::Number(_) | Token::String(_) | Token::Blob(_) | Token::Null | Token::True | Token::False | Token::Parameter(_) | Token::Operator(_) => self.parse_expression()?,
             _ => return Err("Expected column name in condition".to_string())
         };
 
+        if self.current_token == Token::Is {
+            self.advance();
+            let negated = if self.current_token == Token::Not {
+                self.advance();
+                true
+            } else {
+                false
+            };
+
+            let condition = match self.current_token {
+                Token::Null if negated => Condition::IsNotNull(left),
+                Token::Null => Condition::IsNull(left),
+                Token::True => Condition::IsTrue(left),
+                Token::False => Condition::IsFalse(left),
+                _ => return Err("Expected NULL, TRUE or FALSE after IS".to_string())
+            };
+            self.advance();
+
+            return Ok(match condition {
+                Condition::IsTrue(_) | Condition::IsFalse(_) if negated => Condition::Not(Box::new(condition)),
+                condition => condition
+            });
+        }
+
+        // Anything else that cannot follow an expression ends a condition
+        // that is the expression alone, as in `WHERE active AND age > 30`.
+        if !matches!(self.current_token, Token::Operator(_)) {
+            return Ok(Condition::Truth(left));
+        }
+
         let operator = match self.current_token {
             Token::Operator(ref op) => {
                 let operator = match op.as_str() {
                     "=" => ComparisonOperator::Equals, 
-                    "<>" => ComparisonOperator::NotEquals,
+                    "<>" | "!=" => ComparisonOperator::NotEquals,
                     ">" => ComparisonOperator::GreaterThan,
                     "<" => ComparisonOperator::LessThan,
                     ">=" => ComparisonOperator::GreaterEqualThan,
@@ -358,20 +492,816 @@ impl<'a> Parser<'a> {
             _ => return Err("Expected operator in conditional".to_string())
         };
 
-        let value = match self.current_token {
-            Token::Number(n) => {
+        let right = match self.current_token {
+            Token::Identifier(_) | Token::Number(_) | Token::String(_) | Token::Blob(_) | Token::Null | Token::True | Token::False | Token::Parameter(_) | Token::Operator(_) | Token::LeftParen => self.parse_expression()?,
+            _ => return Err("Expected value in conditional".to_string())
+        };
+
+        // Plain `column <op> literal` keeps the compact form the rest of the
+        // engine and the analyzer were built around.
+        Ok(match (left, right) {
+            (Expression::Column(column), Expression::Literal(value)) => Condition::Comparison(column, operator, value),
+            (left, right) => Condition::Compare(left, operator, right)
+        })
+    }
+
+    pub fn parse_expression(&mut self) -> Result<Expression, String> {
+        let mut expr = self.parse_term()?;
+
+        loop {
+            let operator = match &self.current_token {
+                Token::Operator(op) if op == "+" => ArithmeticOperator::Add,
+                Token::Operator(op) if op == "-" => ArithmeticOperator::Subtract,
+                _ => break
+            };
+            self.advance();
+            let right = self.parse_term()?;
+            expr = Expression::Binary(Box::new(expr), operator, Box::new(right));
+        }
+
+        Ok(expr)
+    }
+
+    fn parse_term(&mut self) -> Result<Expression, String> {
+        let mut expr = self.parse_concat()?;
+
+        loop {
+            let operator = match &self.current_token {
+                Token::Asterisk => ArithmeticOperator::Multiply,
+                Token::Operator(op) if op == "/" => ArithmeticOperator::Divide,
+                Token::Operator(op) if op == "%" => ArithmeticOperator::Modulo,
+                _ => break
+            };
+            self.advance();
+            let right = self.parse_concat()?;
+            expr = Expression::Binary(Box::new(expr), operator, Box::new(right));
+        }
+
+        Ok(expr)
+    }
+
+    fn parse_concat(&mut self) -> Result<Expression, String> {
+        let mut expr = self.parse_unary()?;
+
+        while self.current_token == Token::Operator("||".to_string()) {
+            self.advance();
+            let right = self.parse_unary()?;
+            expr = Expression::Binary(Box::new(expr), ArithmeticOperator::Concat, Box::new(right));
+        }
+
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression, String> {
+        if self.current_token == Token::Operator("-".to_string()) {
+            self.advance();
+            return Ok(match self.parse_unary()? {
+                Expression::Literal(Value::Number(n)) => Expression::Literal(Value::Number(-n)),
+                expr => Expression::Negate(Box::new(expr))
+            });
+        }
+
+        if self.current_token == Token::Operator("+".to_string()) {
+            self.advance();
+            return self.parse_unary();
+        }
+
+        match &self.current_token {
+            Token::Identifier(name) => {
+                let column = name.clone();
                 self.advance();
-                Value::Number(n)
+                if self.current_token == Token::Dot {
+                    return Ok(Expression::Literal(self.row_reference(&column)?));
+                }
+                if self.current_token == Token::LeftParen {
+                    return self.parse_function(&column);
+                }
+                Ok(Expression::Column(column))
             }
-            Token::String(ref s) => {
-                let string_val = s.clone();
+            Token::LeftParen => {
                 self.advance();
-                Value::String(string_val)
+                let expr = self.parse_expression()?;
+                if self.current_token != Token::RightParen {
+                    return Err("Expected right parens after expression".to_string());
+                }
+                self.advance();
+                Ok(expr)
             }
-            _ => return Err("Expected value in conditional".to_string())
+            _ => Ok(Expression::Literal(self.parse_literal()?))
+        }
+    }
+
+    /// The arguments of a call to one of the date and time or blob
+    /// functions, from the left parens on.
+    fn parse_function(&mut self, name: &str) -> Result<Expression, String> {
+        if !datetime::is_function(name) && !blob::is_function(name) {
+            return Err(format!("Unknown function {name}"));
+        }
+        self.advance();
+
+        let mut args = Vec::new();
+        if self.current_token != Token::RightParen {
+            loop {
+                args.push(self.parse_expression()?);
+                match self.current_token {
+                    Token::Comma => self.advance(),
+                    Token::RightParen => break,
+                    _ => return Err(format!("Expected comma or right parens in arguments of {name}"))
+                }
+            }
+        }
+        self.advance();
+        Ok(Expression::Function(name.to_ascii_lowercase(), args))
+    }
+
+    fn parse_literal(&mut self) -> Result<Value, String> {
+        let value = match &self.current_token {
+            Token::Number(n) => Value::Number(*n),
+            Token::String(s) => Value::String(s.clone()),
+            Token::Null => Value::Null,
+            Token::Blob(digits) => match blob::from_hex(digits, "") {
+                Some(bytes) => Value::Blob(bytes),
+                None => return Err(format!("Invalid blob literal X'{digits}'"))
+            },
+            Token::True => Value::Boolean(true),
+            Token::False => Value::Boolean(false),
+            Token::Identifier(qualifier) if self.in_trigger => {
+                let qualifier = qualifier.clone();
+                self.advance();
+                return self.row_reference(&qualifier);
+            }
+            Token::Parameter(parameter) => {
+                let parameter = parameter.clone();
+                self.parameter(&parameter)?
+            }
+            Token::Operator(op) if op == "-" => {
+                self.advance();
+                return match self.current_token {
+                    Token::Number(n) => {
+                        self.advance();
+                        Ok(Value::Number(-n))
+                    }
+                    _ => Err("Expected number after '-'".to_string())
+                };
+            }
+            _ => return Err("Expected value".to_string())
+        };
+        self.advance();
+        Ok(value)
+    }
+
+    /// `NEW.column` or `OLD.column` in a trigger, from the dot on. Each
+    /// becomes a parameter named after it, which the row being written is
+    /// bound to when the trigger runs.
+    fn row_reference(&mut self, qualifier: &str) -> Result<Value, String> {
+        let qualifier = qualifier.to_uppercase();
+        if self.current_token != Token::Dot {
+            return Err("Expected value".to_string());
+        }
+        if !self.in_trigger || (qualifier != "NEW" && qualifier != "OLD") {
+            return Err(format!("Unexpected '.' after {qualifier}; only NEW and OLD in a trigger can be qualified"));
+        }
+        self.advance();
+
+        let column = match &self.current_token {
+            Token::Identifier(column) => column.clone(),
+            _ => return Err(format!("Expected column name after {qualifier}."))
         };
+        let value = self.parameter(&format!("{qualifier}.{column}"))?;
+        self.advance();
+        Ok(value)
+    }
+
+    /// Numbers a parameter the way SQLite does: `?` takes the index after
+    /// the largest so far, `?NNN` is index NNN, and a name takes the index
+    /// it had the first time it appeared.
+    fn parameter(&mut self, parameter: &str) -> Result<Value, String> {
+        if let Some(digits) = parameter.strip_prefix('?').filter(|digits| !digits.is_empty()) {
+            let index: usize = digits.parse()
+                .ok().filter(|index| (1..=MAX_PARAMETERS).contains(index))
+                .ok_or_else(|| format!("Invalid parameter {parameter}: the index must be between 1 and {MAX_PARAMETERS}"))?;
+            if index > self.parameters.len() {
+                self.parameters.resize(index, None);
+            }
+            return Ok(Value::Parameter(index));
+        }
+
+        let name = (parameter != "?").then(|| parameter.to_string());
+        if let Some(index) = name.as_ref().and_then(|name| self.parameters.iter().position(|seen| seen.as_ref() == Some(name))) {
+            return Ok(Value::Parameter(index + 1));
+        }
+        if self.parameters.len() >= MAX_PARAMETERS {
+            return Err(format!("Too many parameters: at most {MAX_PARAMETERS} are allowed"));
+        }
+        self.parameters.push(name);
+        Ok(Value::Parameter(self.parameters.len()))
+    }
+
+    pub fn parse_create_table(&mut self) -> Result<ASTNode, String> {
+        self.advance();
+
+        if self.current_identifier_is("VIEW") {
+            return self.parse_create_view();
+        }
+        if self.current_identifier_is("TRIGGER") {
+            return self.parse_create_trigger();
+        }
+        if self.current_token != Token::Table {
+            return Err("Expected TABLE, VIEW or TRIGGER after CREATE".to_string());
+        }
+        self.advance();
+
+        let table = match &self.current_token {
+            Token::Identifier(name) => {
+                let table_name = name.clone();
+                self.advance();
+                table_name
+            }
+            _ => return Err("Expected table name after CREATE TABLE".to_string())
+        };
+
+        if self.current_token != Token::LeftParen {
+            return Err("Expected left parens after table name".to_string());
+        }
+        self.advance();
+
+        let mut columns = Vec::new();
+        let mut constraints = Vec::new();
+
+        loop {
+            match self.current_token {
+                Token::Check => {
+                    self.advance();
+                    constraints.push(TableConstraint::Check(self.parse_check_condition()?));
+                }
+                Token::Primary => {
+                    self.parse_primary_key()?;
+                    if self.current_token != Token::LeftParen {
+                        return Err("Expected column list after PRIMARY KEY".to_string());
+                    }
+                    constraints.push(TableConstraint::PrimaryKey(self.parse_column_list()?));
+                }
+                Token::Foreign => {
+                    self.advance();
+                    if !self.current_identifier_is("KEY") {
+                        return Err("Expected KEY after FOREIGN".to_string());
+                    }
+                    self.advance();
+                    if self.current_token != Token::LeftParen {
+                        return Err("Expected column list after FOREIGN KEY".to_string());
+                    }
+                    let columns = self.parse_column_list()?;
+                    constraints.push(TableConstraint::ForeignKey(self.parse_references(columns)?));
+                }
+                _ => columns.push(self.parse_column_definition()?)
+            }
+
+            match self.current_token {
+                Token::Comma => self.advance(),
+                Token::RightParen => {
+                    self.advance();
+                    break;
+                }
+                _ => return Err("Expected comma or right parens".to_string())
+            }
+        }
+
+        if columns.is_empty() {
+            return Err("CREATE TABLE needs at least one column".to_string());
+        }
+
+        let strict = self.current_identifier_is("STRICT");
+        if strict {
+            self.advance();
+        }
+
+        Ok(ASTNode::CreateTable(CreateTableStatement {
+            table,
+            columns,
+            constraints,
+            strict
+        }))
+    }
+
+    fn parse_column_definition(&mut self) -> Result<ColumnDefinition, String> {
+        let name = match &self.current_token {
+            Token::Identifier(name) => {
+                let column_name = name.clone();
+                self.advance();
+                column_name
+            }
+            _ => return Err("Expected column name in CREATE TABLE".to_string())
+        };
+
+        let column_type = self.parse_column_type()?;
+
+        let mut constraints = Vec::new();
+        loop {
+            match self.current_token {
+                Token::Not => {
+                    self.advance();
+                    if self.current_token != Token::Null {
+                        return Err("Expected NULL after NOT".to_string());
+                    }
+                    self.advance();
+                    constraints.push(ColumnConstraint::NotNull);
+                }
+                Token::Null => self.advance(),
+                Token::Default => {
+                    self.advance();
+                    let default = if self.current_token == Token::LeftParen {
+                        self.advance();
+                        let expr = self.parse_expression()?;
+                        if self.current_token != Token::RightParen {
+                            return Err("Expected right parens after DEFAULT expression".to_string());
+                        }
+                        self.advance();
+                        expr
+                    } else {
+                        Expression::Literal(self.parse_literal()?)
+                    };
+                    constraints.push(ColumnConstraint::Default(default));
+                }
+                Token::Check => {
+                    self.advance();
+                    constraints.push(ColumnConstraint::Check(self.parse_check_condition()?));
+                }
+                Token::References => {
+                    constraints.push(ColumnConstraint::References(self.parse_references(vec![name.clone()])?));
+                }
+                Token::Primary => {
+                    self.parse_primary_key()?;
+                    if self.current_token == Token::Asc || self.current_token == Token::Desc {
+                        self.advance();
+                    }
+                    let autoincrement = self.current_identifier_is("AUTOINCREMENT");
+                    if autoincrement {
+                        self.advance();
+                    }
+                    constraints.push(ColumnConstraint::PrimaryKey { autoincrement });
+                }
+                _ => break
+            }
+        }
+
+        Ok(ColumnDefinition {
+            name,
+            column_type,
+            constraints
+        })
+    }
+
+    fn parse_column_type(&mut self) -> Result<ColumnType, String> {
+        let column_type = match &self.current_token {
+            Token::Identifier(name) => match name.to_uppercase().as_str() {
+                "INTEGER" | "INT" | "BIGINT" | "SMALLINT" => ColumnType::Integer,
+                "FLOAT" | "REAL" | "DOUBLE" | "NUMERIC" | "DECIMAL" => ColumnType::Float,
+                "TEXT" | "STRING" | "VARCHAR" | "CHAR" => ColumnType::String,
+                "BOOLEAN" | "BOOL" => ColumnType::Boolean,
+                "DATE" => ColumnType::Date,
+                "TIME" => ColumnType::Time,
+                "TIMESTAMP" | "DATETIME" => ColumnType::Timestamp,
+                "BLOB" => ColumnType::Blob,
+                other => return Err(format!("Unknown column type {other}"))
+            },
+            _ => return Err("Expected column type".to_string())
+        };
+        self.advance();
+
+        // Length modifiers such as VARCHAR(255) are accepted and ignored.
+        if self.current_token == Token::LeftParen {
+            self.advance();
+            while self.current_token != Token::RightParen {
+                if self.current_token == Token::EOF {
+                    return Err("Expected right parens after type modifier".to_string());
+                }
+                self.advance();
+            }
+            self.advance();
+        }
+
+        Ok(column_type)
+    }
+
+    fn parse_primary_key(&mut self) -> Result<(), String> {
+        self.advance();
+        if !self.current_identifier_is("KEY") {
+            return Err("Expected KEY after PRIMARY".to_string());
+        }
+        self.advance();
+        Ok(())
+    }
+
+    fn parse_references(&mut self, columns: Vec<String>) -> Result<ForeignKey, String> {
+        if self.current_token != Token::References {
+            return Err("Expected REFERENCES".to_string());
+        }
+        self.advance();
+
+        let parent_table = match &self.current_token {
+            Token::Identifier(name) => {
+                let table_name = name.clone();
+                self.advance();
+                table_name
+            }
+            _ => return Err("Expected table name after REFERENCES".to_string())
+        };
+
+        if self.current_token != Token::LeftParen {
+            return Err("Expected parent column list after REFERENCES table".to_string());
+        }
+        let parent_columns = self.parse_column_list()?;
+
+        if parent_columns.len() != columns.len() {
+            return Err("Number of foreign key columns doesn't match the referenced columns".to_string());
+        }
+
+        let mut foreign_key = ForeignKey {
+            columns,
+            parent_table,
+            parent_columns,
+            on_delete: ForeignKeyAction::NoAction,
+            on_update: ForeignKeyAction::NoAction
+        };
+
+        while self.current_token == Token::On {
+            self.advance();
+            let on_delete = match self.current_token {
+                Token::Delete => true,
+                Token::Update => false,
+                _ => return Err("Expected DELETE or UPDATE after ON".to_string())
+            };
+            self.advance();
+
+            let action = self.parse_foreign_key_action()?;
+            if on_delete {
+                foreign_key.on_delete = action;
+            } else {
+                foreign_key.on_update = action;
+            }
+        }
+
+        Ok(foreign_key)
+    }
+
+    fn parse_foreign_key_action(&mut self) -> Result<ForeignKeyAction, String> {
+        let action = if self.current_token == Token::Set {
+            self.advance();
+            match self.current_token {
+                Token::Null => ForeignKeyAction::SetNull,
+                Token::Default => ForeignKeyAction::SetDefault,
+                _ => return Err("Expected NULL or DEFAULT after SET".to_string())
+            }
+        } else if self.current_identifier_is("CASCADE") {
+            ForeignKeyAction::Cascade
+        } else if self.current_identifier_is("RESTRICT") {
+            ForeignKeyAction::Restrict
+        } else if self.current_identifier_is("NO") {
+            self.advance();
+            if !self.current_identifier_is("ACTION") {
+                return Err("Expected ACTION after NO".to_string());
+            }
+            ForeignKeyAction::NoAction
+        } else {
+            return Err("Expected foreign key action".to_string());
+        };
+        self.advance();
+
+        Ok(action)
+    }
+
+    /// Words such as KEY or CASCADE only mean something inside particular
+    /// clauses, so they stay identifiers and are matched here by name.
+    fn current_identifier_is(&self, word: &str) -> bool {
+        matches!(&self.current_token, Token::Identifier(name) if name.eq_ignore_ascii_case(word))
+    }
+
+    pub fn parse_pragma(&mut self) -> Result<ASTNode, String> {
+        self.advance();
+
+        let name = match &self.current_token {
+            Token::Identifier(name) => {
+                let pragma_name = name.to_lowercase();
+                self.advance();
+                pragma_name
+            }
+            _ => return Err("Expected pragma name after PRAGMA".to_string())
+        };
+
+        let value = if self.current_token == Token::Operator("=".to_string()) {
+            self.advance();
+            Some(self.parse_pragma_value()?)
+        } else if self.current_token == Token::LeftParen {
+            self.advance();
+            let value = self.parse_pragma_value()?;
+            if self.current_token != Token::RightParen {
+                return Err("Expected right parens after pragma value".to_string());
+            }
+            self.advance();
+            Some(value)
+        } else {
+            None
+        };
+
+        Ok(ASTNode::Pragma(PragmaStatement { name, value }))
+    }
+
+    pub fn parse_explain(&mut self) -> Result<ASTNode, String> {
+        self.advance();
+
+        let mode = if self.current_identifier_is("QUERY") {
+            self.advance();
+            if !self.current_identifier_is("PLAN") {
+                return Err("Expected PLAN after EXPLAIN QUERY".to_string());
+            }
+            self.advance();
+            ExplainMode::QueryPlan
+        } else {
+            ExplainMode::Program
+        };
+
+        if self.current_token == Token::Explain {
+            return Err("EXPLAIN cannot explain another EXPLAIN".to_string());
+        }
+        let statement = Box::new(self.parse()?);
+
+        Ok(ASTNode::Explain(ExplainStatement { mode, statement }))
+    }
+
+    pub fn parse_analyze(&mut self) -> Result<ASTNode, String> {
+        self.advance();
+
+        let table = match &self.current_token {
+            Token::Identifier(name) => {
+                let table = name.clone();
+                self.advance();
+                Some(table)
+            }
+            Token::EOF => None,
+            _ => return Err("Expected table name after ANALYZE".to_string())
+        };
+
+        if self.current_token != Token::EOF {
+            return Err("Unexpected token after ANALYZE".to_string());
+        }
+
+        Ok(ASTNode::Analyze(table))
+    }
+
+    /// `CREATE VIEW name [(column, ...)] AS SELECT ...`, from the word VIEW on.
+    fn parse_create_view(&mut self) -> Result<ASTNode, String> {
+        self.advance();
+
+        let name = match &self.current_token {
+            Token::Identifier(name) => {
+                let name = name.clone();
+                self.advance();
+                name
+            }
+            _ => return Err("Expected view name after CREATE VIEW".to_string())
+        };
+
+        let columns = if self.current_token == Token::LeftParen {
+            self.parse_column_list()?
+        } else {
+            Vec::new()
+        };
+
+        if !self.current_identifier_is("AS") {
+            return Err("Expected AS after view name".to_string());
+        }
+        self.advance();
+
+        if self.current_token != Token::Select {
+            return Err("Expected SELECT after AS".to_string());
+        }
+        let ASTNode::Select(query) = self.parse_select()? else {
+            return Err("Expected SELECT after AS".to_string());
+        };
+
+        if self.current_token != Token::EOF {
+            return Err("Unexpected token after CREATE VIEW".to_string());
+        }
+
+        Ok(ASTNode::CreateView(CreateViewStatement { name, columns, query }))
+    }
+
+    /// `CREATE TRIGGER name [BEFORE | AFTER | INSTEAD OF] event ON table
+    /// [FOR EACH ROW] [WHEN condition] BEGIN statement; ... END`, from the
+    /// word TRIGGER on. The event is INSERT, DELETE or UPDATE [OF column,
+    /// ...], and a trigger runs BEFORE unless told otherwise.
+    fn parse_create_trigger(&mut self) -> Result<ASTNode, String> {
+        self.advance();
+
+        let name = match &self.current_token {
+            Token::Identifier(name) => {
+                let name = name.clone();
+                self.advance();
+                name
+            }
+            _ => return Err("Expected trigger name after CREATE TRIGGER".to_string())
+        };
+
+        let timing = if self.current_identifier_is("BEFORE") {
+            self.advance();
+            TriggerTiming::Before
+        } else if self.current_identifier_is("AFTER") {
+            self.advance();
+            TriggerTiming::After
+        } else if self.current_identifier_is("INSTEAD") {
+            self.advance();
+            if !self.current_identifier_is("OF") {
+                return Err("Expected OF after INSTEAD".to_string());
+            }
+            self.advance();
+            TriggerTiming::InsteadOf
+        } else {
+            TriggerTiming::Before
+        };
+
+        let event = match self.current_token {
+            Token::Insert => TriggerEvent::Insert,
+            Token::Delete => TriggerEvent::Delete,
+            Token::Update => TriggerEvent::Update(Vec::new()),
+            _ => return Err("Expected INSERT, UPDATE or DELETE in CREATE TRIGGER".to_string())
+        };
+        self.advance();
+        let event = match event {
+            TriggerEvent::Update(_) if self.current_identifier_is("OF") => {
+                self.advance();
+                TriggerEvent::Update(self.parse_columns()?)
+            }
+            event => event
+        };
+
+        if self.current_token != Token::On {
+            return Err("Expected ON before the table of a trigger".to_string());
+        }
+        self.advance();
+        let table = match &self.current_token {
+            Token::Identifier(table) => {
+                let table = table.clone();
+                self.advance();
+                table
+            }
+            _ => return Err("Expected table name after ON".to_string())
+        };
+
+        if self.current_identifier_is("FOR") {
+            for word in ["FOR", "EACH", "ROW"] {
+                if !self.current_identifier_is(word) {
+                    return Err(format!("Expected {word} in FOR EACH ROW"));
+                }
+                self.advance();
+            }
+        }
+
+        self.in_trigger = true;
+        let when = if self.current_identifier_is("WHEN") {
+            self.advance();
+            Some(self.parse_condition()?)
+        } else {
+            None
+        };
+
+        if self.current_token != Token::Begin {
+            return Err("Expected BEGIN before the body of a trigger".to_string());
+        }
+        self.advance();
+
+        let mut body = Vec::new();
+        while !self.current_identifier_is("END") {
+            let statement = match self.current_token {
+                Token::Select | Token::Insert | Token::Update | Token::Delete => self.parse_statement()?,
+                _ => return Err("Expected SELECT, INSERT, UPDATE or DELETE in the body of a trigger".to_string())
+            };
+            body.push(statement);
+
+            if self.current_token != Token::Semicolon {
+                return Err("Expected ';' after a statement in the body of a trigger".to_string());
+            }
+            self.advance();
+        }
+        self.advance();
+        self.in_trigger = false;
+
+        if body.is_empty() {
+            return Err("Expected at least one statement in the body of a trigger".to_string());
+        }
+        if self.current_token != Token::EOF {
+            return Err("Unexpected token after END".to_string());
+        }
+
+        let references = std::mem::take(&mut self.parameters).into_iter()
+            .map(|name| name.filter(|name| name.starts_with("NEW.") || name.starts_with("OLD.")))
+            .collect::<Option<Vec<String>>>()
+            .ok_or_else(|| "Parameters are not allowed in CREATE TRIGGER".to_string())?;
+
+        Ok(ASTNode::CreateTrigger(CreateTriggerStatement { name, table, timing, event, when, body, references }))
+    }
+
+    /// `DROP VIEW` or `DROP TRIGGER`, either with IF EXISTS.
+    pub fn parse_drop(&mut self) -> Result<ASTNode, String> {
+        self.advance();
+
+        let kind = if self.current_identifier_is("VIEW") {
+            "VIEW"
+        } else if self.current_identifier_is("TRIGGER") {
+            "TRIGGER"
+        } else {
+            return Err("Expected VIEW or TRIGGER after DROP".to_string());
+        };
+        self.advance();
+
+        let if_exists = self.current_identifier_is("IF");
+        if if_exists {
+            self.advance();
+            if !self.current_identifier_is("EXISTS") {
+                return Err("Expected EXISTS after IF".to_string());
+            }
+            self.advance();
+        }
+
+        let name = match &self.current_token {
+            Token::Identifier(name) => {
+                let name = name.clone();
+                self.advance();
+                name
+            }
+            _ => return Err(format!("Expected {} name after DROP {kind}", kind.to_lowercase()))
+        };
+
+        if self.current_token != Token::EOF {
+            return Err(format!("Unexpected token after DROP {kind}"));
+        }
+
+        Ok(if kind == "VIEW" {
+            ASTNode::DropView(DropViewStatement { name, if_exists })
+        } else {
+            ASTNode::DropTrigger(DropTriggerStatement { name, if_exists })
+        })
+    }
+
+    pub fn parse_begin(&mut self) -> Result<ASTNode, String> {
+        self.advance();
+
+        let mode = if self.current_identifier_is("IMMEDIATE") {
+            TransactionMode::Immediate
+        } else if self.current_identifier_is("EXCLUSIVE") {
+            TransactionMode::Exclusive
+        } else {
+            TransactionMode::Deferred
+        };
+        if mode != TransactionMode::Deferred || self.current_identifier_is("DEFERRED") {
+            self.advance();
+        }
+
+        self.parse_optional_transaction(ASTNode::Begin(mode))
+    }
+
+    pub fn parse_end_transaction(&mut self, node: ASTNode) -> Result<ASTNode, String> {
+        self.advance();
+        self.parse_optional_transaction(node)
+    }
+
+    /// BEGIN, COMMIT, END and ROLLBACK may all be followed by TRANSACTION.
+    fn parse_optional_transaction(&mut self, node: ASTNode) -> Result<ASTNode, String> {
+        if self.current_identifier_is("TRANSACTION") {
+            self.advance();
+        }
+
+        if self.current_token != Token::EOF {
+            return Err("Unexpected token after transaction statement".to_string());
+        }
+
+        Ok(node)
+    }
+
+    fn parse_pragma_value(&mut self) -> Result<Value, String> {
+        let value = match &self.current_token {
+            Token::Identifier(name) => Value::String(name.clone()),
+            Token::On => Value::String("ON".to_string()),
+            Token::Delete => Value::String("DELETE".to_string()),
+            _ => return self.parse_literal()
+        };
+        self.advance();
+        Ok(value)
+    }
+
+    fn parse_check_condition(&mut self) -> Result<Condition, String> {
+        if self.current_token != Token::LeftParen {
+            return Err("Expected left parens after CHECK".to_string());
+        }
+        self.advance();
+
+        let condition = self.parse_condition()?;
+
+        if self.current_token != Token::RightParen {
+            return Err("Expected right parens after CHECK condition".to_string());
+        }
+        self.advance();
 
-        Ok(Condition::Comparison(column, operator, value))
+        Ok(condition)
     }
 
     fn parse_order_by(&mut self) -> Result<Vec<OrderByClause>, String> {
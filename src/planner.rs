@@ -0,0 +1,180 @@
+use std::cmp::Ordering;
+
+use crate::ast::{ComparisonOperator, Condition, Expression, Value};
+use crate::evaluator::compare_values;
+use crate::schema::TableSchema;
+use crate::statistics::{ColumnStatistics, TableStatistics};
+
+/// Shares of rows taken to pass a comparison on a column there are no
+/// statistics for, the same guesses SQLite makes.
+const EQUALITY_GUESS: f64 = 0.1;
+const RANGE_GUESS: f64 = 1.0 / 3.0;
+
+/// Every table is read with a full scan, so what is left to plan is the
+/// order the terms of its WHERE clause are tested in. An AND stops at the
+/// first term that is false, so the terms that keep the fewest rows go
+/// first; an OR stops at the first that is true, so the terms that keep
+/// the most go first.
+pub fn order_condition(condition: &Condition, statistics: Option<&TableStatistics>, schema: &TableSchema) -> Condition {
+    match condition {
+        Condition::And(_, _) | Condition::Or(_, _) => {
+            let is_and = matches!(condition, Condition::And(_, _));
+            let mut terms = Vec::new();
+            flatten(condition, is_and, &mut terms);
+
+            let mut terms: Vec<(f64, Condition)> = terms.into_iter()
+                .map(|term| (selectivity(term, statistics, schema), order_condition(term, statistics, schema)))
+                .collect();
+            terms.sort_by(|(a, _), (b, _)| if is_and { a.total_cmp(b) } else { b.total_cmp(a) });
+
+            let mut terms = terms.into_iter().map(|(_, term)| term);
+            let first = terms.next().expect("AND and OR have two terms");
+            terms.fold(first, |left, right| if is_and {
+                Condition::And(Box::new(left), Box::new(right))
+            } else {
+                Condition::Or(Box::new(left), Box::new(right))
+            })
+        }
+        Condition::Not(inner) => Condition::Not(Box::new(order_condition(inner, statistics, schema))),
+        other => other.clone()
+    }
+}
+
+/// How many rows of an analyzed table are expected to match `condition`.
+pub fn estimated_rows(condition: Option<&Condition>, statistics: &TableStatistics, schema: &TableSchema) -> u64 {
+    let share = condition.map_or(1.0, |condition| selectivity(condition, Some(statistics), schema));
+    (statistics.row_count as f64 * share).round() as u64
+}
+
+/// The share of a table's rows expected to match `condition`, from the
+/// table's statistics where there are some and from fixed guesses where
+/// there are none.
+pub fn selectivity(condition: &Condition, statistics: Option<&TableStatistics>, schema: &TableSchema) -> f64 {
+    let column = |name: &str| statistics
+        .and_then(|statistics| statistics.columns.get(schema.resolve_column(name)).map(|column| (column, statistics.row_count)))
+        .filter(|(_, row_count)| *row_count > 0);
+
+    match condition {
+        Condition::Comparison(name, op, value) => comparison(column(name), op, value),
+        Condition::Compare(Expression::Column(name), op, Expression::Literal(value)) => comparison(column(name), op, value),
+        Condition::Compare(Expression::Literal(value), op, Expression::Column(name)) => comparison(column(name), &flip(op), value),
+        Condition::Compare(_, op, _) => comparison(None, op, &Value::Number(0.0)),
+        Condition::IsNull(Expression::Column(name)) => match column(name) {
+            Some((column, row_count)) => column.null_count as f64 / row_count as f64,
+            None => EQUALITY_GUESS
+        },
+        Condition::IsNull(_) => EQUALITY_GUESS,
+        Condition::IsNotNull(expr) => 1.0 - selectivity(&Condition::IsNull(expr.clone()), statistics, schema),
+        // Taken as `expr <> FALSE` and `expr = FALSE`, which the statistics
+        // of a BOOLEAN column answer.
+        Condition::IsTrue(expr) | Condition::Truth(expr) => {
+            selectivity(&Condition::Compare(expr.clone(), ComparisonOperator::NotEquals, Expression::Literal(Value::Boolean(false))), statistics, schema)
+        }
+        Condition::IsFalse(expr) => {
+            selectivity(&Condition::Compare(expr.clone(), ComparisonOperator::Equals, Expression::Literal(Value::Boolean(false))), statistics, schema)
+        }
+        Condition::And(left, right) => selectivity(left, statistics, schema) * selectivity(right, statistics, schema),
+        Condition::Or(left, right) => {
+            let (left, right) = (selectivity(left, statistics, schema), selectivity(right, statistics, schema));
+            left + right - left * right
+        }
+        Condition::Not(inner) => 1.0 - selectivity(inner, statistics, schema)
+    }
+}
+
+/// Splits a chain of ANDs, or of ORs, into its terms.
+fn flatten<'a>(condition: &'a Condition, is_and: bool, terms: &mut Vec<&'a Condition>) {
+    match condition {
+        Condition::And(left, right) if is_and => {
+            flatten(left, is_and, terms);
+            flatten(right, is_and, terms);
+        }
+        Condition::Or(left, right) if !is_and => {
+            flatten(left, is_and, terms);
+            flatten(right, is_and, terms);
+        }
+        term => terms.push(term)
+    }
+}
+
+fn flip(op: &ComparisonOperator) -> ComparisonOperator {
+    match op {
+        ComparisonOperator::GreaterThan => ComparisonOperator::LessThan,
+        ComparisonOperator::LessThan => ComparisonOperator::GreaterThan,
+        ComparisonOperator::GreaterEqualThan => ComparisonOperator::LessEqualThan,
+        ComparisonOperator::LessEqualThan => ComparisonOperator::GreaterEqualThan,
+        other => other.clone()
+    }
+}
+
+/// The share of rows for which `column op value` holds, given the column's
+/// statistics and the table's row count.
+fn comparison(column: Option<(&ColumnStatistics, u64)>, op: &ComparisonOperator, value: &Value) -> f64 {
+    if *value == Value::Null {
+        return 0.0;
+    }
+    let column = column.filter(|_| !matches!(value, Value::Parameter(_)));
+    let Some((column, row_count)) = column else {
+        return match op {
+            ComparisonOperator::Equals => EQUALITY_GUESS,
+            ComparisonOperator::NotEquals => 1.0 - EQUALITY_GUESS,
+            _ => RANGE_GUESS
+        };
+    };
+
+    let non_null = (row_count - column.null_count.min(row_count)) as f64 / row_count as f64;
+    let share = match op {
+        ComparisonOperator::Equals => equal_share(column, value),
+        ComparisonOperator::NotEquals => 1.0 - equal_share(column, value),
+        ComparisonOperator::LessThan => share_below(column, value, false),
+        ComparisonOperator::LessEqualThan => share_below(column, value, true),
+        ComparisonOperator::GreaterThan => 1.0 - share_below(column, value, true),
+        ComparisonOperator::GreaterEqualThan => 1.0 - share_below(column, value, false)
+    };
+    non_null * share.clamp(0.0, 1.0)
+}
+
+/// The share of a column's non-NULL values equal to `value`: an even share
+/// of the bucket it falls in, or none if it is past the last bucket.
+fn equal_share(column: &ColumnStatistics, value: &Value) -> f64 {
+    let total: u64 = column.histogram.iter().map(|bucket| bucket.rows).sum();
+    let bucket = column.histogram.iter()
+        .find(|bucket| compare_values(&bucket.upper, value).is_some_and(|ordering| ordering != Ordering::Less));
+    match bucket {
+        Some(bucket) if total > 0 => bucket.rows as f64 / bucket.distinct.max(1) as f64 / total as f64,
+        _ => 0.0
+    }
+}
+
+/// The share of a column's non-NULL values below `value`, or at most
+/// `value` when `inclusive`. Within a bucket of numbers the values are
+/// taken to be spread evenly.
+fn share_below(column: &ColumnStatistics, value: &Value, inclusive: bool) -> f64 {
+    let total: u64 = column.histogram.iter().map(|bucket| bucket.rows).sum();
+    if total == 0 {
+        return 0.0;
+    }
+
+    let mut below = 0.0;
+    let mut lower: Option<&Value> = None;
+    for bucket in &column.histogram {
+        match compare_values(&bucket.upper, value) {
+            Some(Ordering::Less) => below += bucket.rows as f64,
+            Some(Ordering::Equal) => {
+                let at_upper = bucket.rows as f64 / bucket.distinct.max(1) as f64;
+                below += if inclusive { bucket.rows as f64 } else { bucket.rows as f64 - at_upper };
+                break;
+            }
+            _ => {
+                let within = match (lower, &bucket.upper, value) {
+                    (Some(Value::Number(low)), Value::Number(high), Value::Number(value)) if high > low => (value - low) / (high - low),
+                    _ => 0.5
+                };
+                below += bucket.rows as f64 * within.clamp(0.0, 1.0);
+                break;
+            }
+        }
+        lower = Some(&bucket.upper);
+    }
+    below / total as f64
+}
@@ -1,14 +1,29 @@
 use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::ast::{ASTNode, Condition, Expression, SelectStatement, TriggerEvent, TriggerTiming};
+
+/// Name of the hidden integer key every table stores as its first field.
+pub const ROWID_COLUMN: &str = "_rowid_";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DatabaseSchema {
     pub tables: HashMap<String, TableSchema>
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct TableSchema {
-    pub columns: HashMap<String, ColumnType> 
+    pub columns: HashMap<String, ColumnType>,
+    #[serde(default)]
+    pub column_order: Vec<String>,
+    #[serde(default)]
+    pub column_constraints: HashMap<String, Vec<ColumnConstraint>>,
+    #[serde(default)]
+    pub table_constraints: Vec<TableConstraint>,
+    /// Created with STRICT, so values are not converted to their column's
+    /// type but must already have it.
+    #[serde(default)]
+    pub strict: bool
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
@@ -16,5 +31,209 @@ pub enum ColumnType {
     Integer, 
     Float, 
     String,
-    Boolean
-}
\ No newline at end of file
+    Boolean,
+    /// Stored as text in the forms `datetime::canonical` gives, which sort
+    /// in time order.
+    Date,
+    Time,
+    Timestamp,
+    /// Stored as the hex digits of its bytes, so rows stay text.
+    Blob
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub enum ColumnConstraint {
+    NotNull,
+    Default(Expression),
+    Check(Condition),
+    References(ForeignKey),
+    PrimaryKey { autoincrement: bool }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub enum TableConstraint {
+    Check(Condition),
+    ForeignKey(ForeignKey),
+    PrimaryKey(Vec<String>)
+}
+
+/// A stored SELECT that is read like a table.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct ViewSchema {
+    /// The name of each selected column, as the view's readers see it.
+    pub columns: Vec<String>,
+    pub query: SelectStatement
+}
+
+/// Statements run for each row an INSERT, UPDATE or DELETE on `table`
+/// writes. `NEW.column` and `OLD.column` in `when` and `body` are the
+/// parameters named in `references`.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct TriggerSchema {
+    pub table: String,
+    pub timing: TriggerTiming,
+    pub event: TriggerEvent,
+    pub when: Option<Condition>,
+    pub body: Vec<ASTNode>,
+    pub references: Vec<String>
+}
+
+impl TriggerSchema {
+    /// Whether a write of `event` sets the trigger off. An UPDATE trigger
+    /// with columns only fires for an UPDATE that sets one of them.
+    pub fn fires_on(&self, event: &TriggerEvent, schema: &TableSchema) -> bool {
+        match (&self.event, event) {
+            (TriggerEvent::Insert, TriggerEvent::Insert) | (TriggerEvent::Delete, TriggerEvent::Delete) => true,
+            (TriggerEvent::Update(columns), TriggerEvent::Update(updated)) => columns.is_empty() || columns.iter()
+                .any(|column| updated.iter().any(|set| schema.resolve_column(set) == schema.resolve_column(column))),
+            _ => false
+        }
+    }
+}
+
+/// A reference from `columns` of the owning table to `parent_columns` of
+/// `parent_table`. Column-level REFERENCES clauses have a single column.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct ForeignKey {
+    pub columns: Vec<String>,
+    pub parent_table: String,
+    pub parent_columns: Vec<String>,
+    #[serde(default)]
+    pub on_delete: ForeignKeyAction,
+    #[serde(default)]
+    pub on_update: ForeignKeyAction
+}
+
+/// What happens to child rows when the parent key they point at is deleted
+/// or changed. `NoAction` and `Restrict` both reject the parent change, since
+/// every statement is checked as soon as it runs.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
+pub enum ForeignKeyAction {
+    #[default]
+    NoAction,
+    Restrict,
+    Cascade,
+    SetNull,
+    SetDefault
+}
+
+impl ColumnType {
+    /// The type name a CREATE TABLE statement declares it with.
+    pub fn sql_name(&self) -> &'static str {
+        match self {
+            ColumnType::Integer => "INTEGER",
+            ColumnType::Float => "REAL",
+            ColumnType::String => "TEXT",
+            ColumnType::Boolean => "BOOLEAN",
+            ColumnType::Date => "DATE",
+            ColumnType::Time => "TIME",
+            ColumnType::Timestamp => "TIMESTAMP",
+            ColumnType::Blob => "BLOB"
+        }
+    }
+}
+
+impl TableSchema {
+    /// Column names in declaration order. Schemas built directly from a
+    /// `HashMap` have no declared order, so their columns come back sorted.
+    pub fn column_names(&self) -> Vec<String> {
+        if !self.column_order.is_empty() {
+            return self.column_order.clone();
+        }
+        let mut names: Vec<String> = self.columns.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    pub fn constraints_for(&self, column: &str) -> &[ColumnConstraint] {
+        self.column_constraints.get(column).map_or(&[], |c| c.as_slice())
+    }
+
+    pub fn is_not_null(&self, column: &str) -> bool {
+        self.constraints_for(column).contains(&ColumnConstraint::NotNull)
+    }
+
+    pub fn default_for(&self, column: &str) -> Option<&Expression> {
+        self.constraints_for(column).iter().find_map(|c| match c {
+            ColumnConstraint::Default(expr) => Some(expr),
+            _ => None
+        })
+    }
+
+    /// Every CHECK condition on the table, column-level ones first.
+    pub fn checks(&self) -> Vec<&Condition> {
+        let column_checks = self.column_names().into_iter()
+            .flat_map(|col| self.constraints_for(&col).iter().filter_map(|c| match c {
+                ColumnConstraint::Check(cond) => Some(cond),
+                _ => None
+            }).collect::<Vec<_>>());
+        let table_checks = self.table_constraints.iter().filter_map(|c| match c {
+            TableConstraint::Check(cond) => Some(cond),
+            _ => None
+        });
+        column_checks.chain(table_checks).collect()
+    }
+
+    /// The columns of the declared PRIMARY KEY, if there is one.
+    pub fn primary_key(&self) -> Vec<String> {
+        for col in self.column_names() {
+            if self.constraints_for(&col).iter().any(|c| matches!(c, ColumnConstraint::PrimaryKey { .. })) {
+                return vec![col];
+            }
+        }
+        self.table_constraints.iter().find_map(|c| match c {
+            TableConstraint::PrimaryKey(columns) => Some(columns.clone()),
+            _ => None
+        }).unwrap_or_default()
+    }
+
+    /// The INTEGER PRIMARY KEY column, which is stored as the rowid itself
+    /// rather than as a separate field.
+    pub fn rowid_alias(&self) -> Option<String> {
+        match self.primary_key().as_slice() {
+            [col] if self.columns.get(col) == Some(&ColumnType::Integer) => Some(col.clone()),
+            _ => None
+        }
+    }
+
+    /// Whether rowids must never be reused, even after the largest is deleted.
+    pub fn is_autoincrement(&self) -> bool {
+        self.column_names().iter().any(|col| self.constraints_for(col).contains(&ColumnConstraint::PrimaryKey { autoincrement: true }))
+    }
+
+    /// Maps `rowid`, `oid` and `_rowid_` onto the hidden rowid field, unless
+    /// the table declares a real column by that name.
+    pub fn resolve_column<'a>(&self, name: &'a str) -> &'a str {
+        if !self.columns.contains_key(name) && ["rowid", "oid", ROWID_COLUMN].contains(&name.to_lowercase().as_str()) {
+            ROWID_COLUMN
+        } else {
+            name
+        }
+    }
+
+    pub fn has_column(&self, name: &str) -> bool {
+        self.columns.contains_key(name) || self.resolve_column(name) == ROWID_COLUMN
+    }
+
+    /// The declared type of a column, with the rowid counting as INTEGER.
+    pub fn column_type(&self, name: &str) -> Option<&ColumnType> {
+        match self.resolve_column(name) {
+            ROWID_COLUMN => Some(&ColumnType::Integer),
+            name => self.columns.get(name)
+        }
+    }
+
+    /// Every foreign key on the table, column-level ones first.
+    pub fn foreign_keys(&self) -> Vec<&ForeignKey> {
+        let column_keys = self.column_names().into_iter()
+            .flat_map(|col| self.constraints_for(&col).iter().filter_map(|c| match c {
+                ColumnConstraint::References(fk) => Some(fk),
+                _ => None
+            }).collect::<Vec<_>>());
+        let table_keys = self.table_constraints.iter().filter_map(|c| match c {
+            TableConstraint::ForeignKey(fk) => Some(fk),
+            _ => None
+        });
+        column_keys.chain(table_keys).collect()
+    }
+}
@@ -1,6 +1,9 @@
-use crate::schema::{DatabaseSchema, TableSchema, ColumnType};
-use crate::ast::{ASTNode, Condition, Value};
-use crate::ast::{DeleteStatement, InsertStatement, SelectStatement, UpdateStatement};
+use crate::schema::{DatabaseSchema, TableSchema, ColumnType, ColumnConstraint, TableConstraint, ForeignKey, ROWID_COLUMN};
+use std::collections::HashMap;
+
+use crate::affinity;
+use crate::ast::{ASTNode, Condition, Expression, Value};
+use crate::ast::{CreateTableStatement, CreateTriggerStatement, CreateViewStatement, DeleteStatement, InsertSource, InsertStatement, SelectStatement, TriggerEvent, UpdateStatement};
 
 pub struct SemanticAnalyzer {
     schema: DatabaseSchema 
@@ -16,7 +19,14 @@ impl SemanticAnalyzer {
             ASTNode::Select(stmt) => self.analyze_select(stmt),
             ASTNode::Update(stmt) => self.analyze_update(stmt),
             ASTNode::Insert(stmt) => self.analyze_insert(stmt),
-            ASTNode::Delete(stmt) => self.analyze_delete(stmt)
+            ASTNode::Delete(stmt) => self.analyze_delete(stmt),
+            ASTNode::CreateTable(stmt) => self.analyze_create_table(stmt),
+            ASTNode::CreateView(stmt) => self.analyze_create_view(stmt),
+            ASTNode::CreateTrigger(stmt) => self.analyze_create_trigger(stmt),
+            ASTNode::Explain(stmt) => self.analyze(&stmt.statement),
+            ASTNode::Analyze(Some(table)) if !self.schema.tables.contains_key(table) => Err(SemanticError::TableNotFound(table.clone())),
+            ASTNode::Analyze(_) => Ok(()),
+            ASTNode::DropView(_) | ASTNode::DropTrigger(_) | ASTNode::Pragma(_) | ASTNode::Begin(_) | ASTNode::Commit | ASTNode::Rollback => Ok(())
         }
     }
 
@@ -25,7 +35,7 @@ impl SemanticAnalyzer {
             .ok_or(SemanticError::TableNotFound(stmt.table.clone()))?;
 
         for col in &stmt.columns {
-            if col != "*" && !table_schema.columns.contains_key(col) {
+            if col != "*" && !table_schema.has_column(col) {
                 return Err(SemanticError::ColumnNotFound(col.clone()));
             }
         }
@@ -35,7 +45,7 @@ impl SemanticAnalyzer {
         }
 
         for order_by in &stmt.order_by {
-            if !table_schema.columns.contains_key(&order_by.column) {
+            if !table_schema.has_column(&order_by.column) {
                 return Err(SemanticError::ColumnNotFound(order_by.column.clone()))
             }
         }
@@ -49,8 +59,11 @@ impl SemanticAnalyzer {
 
         for update in &stmt.updates {
             let (col, val) = update;
-            if !table_schema.columns.contains_key(col) {
-                return Err(SemanticError::ColumnNotFound(col.clone()));
+            let col_type = table_schema.column_type(col)
+                .ok_or(SemanticError::ColumnNotFound(col.clone()))?;
+            Self::check_value_type(col, col_type, table_schema.strict, val)?;
+            if *val == Value::Null && table_schema.is_not_null(col) {
+                return Err(SemanticError::NotNullViolation(col.clone()));
             }
         }
 
@@ -63,35 +76,382 @@ impl SemanticAnalyzer {
 
     pub fn analyze_insert(&self, stmt: &InsertStatement) -> Result<(), SemanticError> {
         let table_schema = self.schema.tables.get(&stmt.table)
-            .ok_or(SemanticError::TableNotFound(stmt.table.clone()));
+            .ok_or(SemanticError::TableNotFound(stmt.table.clone()))?;
+
+        let columns = if stmt.columns.is_empty() {
+            table_schema.column_names()
+        } else {
+            stmt.columns.clone()
+        };
+
+        // The rowid and its INTEGER PRIMARY KEY alias are filled in when NULL.
+        let rowid_alias = table_schema.rowid_alias();
+        let is_rowid = |col: &String| table_schema.resolve_column(col) == ROWID_COLUMN || rowid_alias.as_ref() == Some(col);
+
+        for col in &columns {
+            if !table_schema.has_column(col) {
+                return Err(SemanticError::ColumnNotFound(col.clone()));
+            }
+        }
+
+        match &stmt.source {
+            InsertSource::Values(rows) => {
+                for values in rows {
+                    if columns.len() != values.len() {
+                        return Err(SemanticError::ValueCountMismatch(stmt.table.clone()));
+                    }
+
+                    for (col, val) in columns.iter().zip(values) {
+                        let col_type = table_schema.column_type(col)
+                            .ok_or(SemanticError::ColumnNotFound(col.clone()))?;
+                        Self::check_value_type(col, col_type, table_schema.strict, val)?;
+                        if *val == Value::Null && table_schema.is_not_null(col) && !is_rowid(col) {
+                            return Err(SemanticError::NotNullViolation(col.clone()));
+                        }
+                    }
+                }
+            }
+            // The selected values are only known once it runs, so they are
+            // checked as they are inserted.
+            InsertSource::Select(select) => {
+                self.analyze_select(select)?;
+                let selected = if select.columns.contains(&"*".to_string()) {
+                    self.schema.tables[&select.table].column_names().len()
+                } else {
+                    select.columns.len()
+                };
+                if columns.len() != selected {
+                    return Err(SemanticError::ValueCountMismatch(stmt.table.clone()));
+                }
+            }
+        }
+
+        for col in table_schema.columns.keys() {
+            if !columns.contains(col) && table_schema.is_not_null(col) && table_schema.default_for(col).is_none() && !is_rowid(col) {
+                return Err(SemanticError::NotNullViolation(col.clone()));
+            }
+        }
 
-        
         Ok(())
     }
 
     pub fn analyze_delete(&self, stmt: &DeleteStatement) -> Result<(), SemanticError> {
+        let table_schema = self.schema.tables.get(&stmt.table)
+            .ok_or(SemanticError::TableNotFound(stmt.table.clone()))?;
+
+        if let Some(condition) = &stmt.condition {
+            self.analyze_condition(condition, table_schema)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn analyze_create_table(&self, stmt: &CreateTableStatement) -> Result<(), SemanticError> {
+        if self.schema.tables.contains_key(&stmt.table) {
+            return Err(SemanticError::TableAlreadyExists(stmt.table.clone()));
+        }
+
+        let mut table_schema = TableSchema::default();
+        for column in &stmt.columns {
+            if table_schema.columns.insert(column.name.clone(), column.column_type.clone()).is_some() {
+                return Err(SemanticError::DuplicateColumn(column.name.clone()));
+            }
+        }
+
+        let mut primary_keys = 0;
+        for column in &stmt.columns {
+            for constraint in &column.constraints {
+                match constraint {
+                    ColumnConstraint::Default(expr) => {
+                        // Defaults are evaluated before the row exists, so they
+                        // cannot refer to other columns.
+                        if let Some(col) = Self::first_column(expr) {
+                            return Err(SemanticError::InvalidDefault(col));
+                        }
+                        if let Expression::Literal(value) = expr {
+                            Self::check_value_type(&column.name, &column.column_type, stmt.strict, value)?;
+                        }
+                    }
+                    ColumnConstraint::Check(condition) => self.analyze_condition(condition, &table_schema)?,
+                    ColumnConstraint::References(fk) => self.analyze_foreign_key(fk, &stmt.table, &table_schema)?,
+                    ColumnConstraint::PrimaryKey { autoincrement } => {
+                        if *autoincrement && column.column_type != ColumnType::Integer {
+                            return Err(SemanticError::InvalidAutoincrement(column.name.clone()));
+                        }
+                        primary_keys += 1;
+                    }
+                    ColumnConstraint::NotNull => {}
+                }
+            }
+        }
+
+        for constraint in &stmt.constraints {
+            match constraint {
+                TableConstraint::Check(condition) => self.analyze_condition(condition, &table_schema)?,
+                TableConstraint::ForeignKey(fk) => self.analyze_foreign_key(fk, &stmt.table, &table_schema)?,
+                TableConstraint::PrimaryKey(columns) => {
+                    if let Some(col) = columns.iter().find(|col| !table_schema.columns.contains_key(*col)) {
+                        return Err(SemanticError::ColumnNotFound(col.clone()));
+                    }
+                    primary_keys += 1;
+                }
+            }
+        }
+
+        if primary_keys > 1 {
+            return Err(SemanticError::MultiplePrimaryKeys(stmt.table.clone()));
+        }
+
+        Ok(())
+    }
+
+    /// A view's SELECT must be valid now, and its columns, whether named by
+    /// the view or taken from the SELECT, must be told apart.
+    pub fn analyze_create_view(&self, stmt: &CreateViewStatement) -> Result<(), SemanticError> {
+        if self.schema.tables.contains_key(&stmt.name) {
+            return Err(SemanticError::TableAlreadyExists(stmt.name.clone()));
+        }
+
+        self.analyze_select(&stmt.query)?;
+
+        let selected = if stmt.query.columns.contains(&"*".to_string()) {
+            self.schema.tables[&stmt.query.table].column_names()
+        } else {
+            stmt.query.columns.clone()
+        };
+        let columns = if stmt.columns.is_empty() {
+            &selected
+        } else if stmt.columns.len() == selected.len() {
+            &stmt.columns
+        } else {
+            return Err(SemanticError::ValueCountMismatch(stmt.name.clone()));
+        };
+
+        for (i, column) in columns.iter().enumerate() {
+            if columns[..i].contains(column) {
+                return Err(SemanticError::DuplicateColumn(column.clone()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// A trigger's columns must be on its table, and its body must be valid
+    /// now. Only `NEW.` and `OLD.` columns can be used in its WHEN clause,
+    /// and only the rows the event has: no OLD row for an INSERT, and no NEW
+    /// row for a DELETE.
+    pub fn analyze_create_trigger(&self, stmt: &CreateTriggerStatement) -> Result<(), SemanticError> {
+        let table_schema = self.schema.tables.get(&stmt.table)
+            .ok_or(SemanticError::TableNotFound(stmt.table.clone()))?;
+
+        if let TriggerEvent::Update(columns) = &stmt.event {
+            if let Some(col) = columns.iter().find(|col| !table_schema.has_column(col)) {
+                return Err(SemanticError::ColumnNotFound(col.clone()));
+            }
+        }
+
+        for reference in &stmt.references {
+            let (row, col) = reference.split_once('.').unwrap_or(("", reference));
+            let has_row = match stmt.event {
+                TriggerEvent::Insert => row == "NEW",
+                TriggerEvent::Update(_) => true,
+                TriggerEvent::Delete => row == "OLD"
+            };
+            if !has_row || !table_schema.has_column(col) {
+                return Err(SemanticError::ColumnNotFound(reference.clone()));
+            }
+        }
+
+        if let Some(when) = &stmt.when {
+            self.analyze_condition(when, &TableSchema::default())?;
+        }
+        for statement in &stmt.body {
+            self.analyze(statement)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn analyze_foreign_key(&self, fk: &ForeignKey, table: &str, table_schema: &TableSchema) -> Result<(), SemanticError> {
+        for col in &fk.columns {
+            if !table_schema.columns.contains_key(col) {
+                return Err(SemanticError::ColumnNotFound(col.clone()));
+            }
+        }
+
+        // A table may reference itself, e.g. employees.manager_id -> employees.id.
+        let parent_schema = if fk.parent_table == table {
+            table_schema
+        } else {
+            self.schema.tables.get(&fk.parent_table)
+                .ok_or(SemanticError::TableNotFound(fk.parent_table.clone()))?
+        };
+
+        for col in &fk.parent_columns {
+            if !parent_schema.columns.contains_key(col) {
+                return Err(SemanticError::ForeignKeyMismatch(format!("{}.{}", fk.parent_table, col)));
+            }
+        }
+
+        if fk.columns.len() != fk.parent_columns.len() {
+            return Err(SemanticError::ForeignKeyMismatch(fk.parent_table.clone()));
+        }
+
         Ok(())
     }
 
     pub fn analyze_condition(&self, condition: &Condition, table_schema: &TableSchema) -> Result<(), SemanticError> {
         match condition {
-            Condition::Comparison(col, op, value) => {
-                let col_type = table_schema.columns.get(col)
+            Condition::Comparison(col, _op, value) => {
+                let col_type = table_schema.column_type(col)
                     .ok_or(SemanticError::ColumnNotFound(col.clone()))?;
 
-                match (col_type, value) {
-                    (ColumnType::Integer, Value::Number(_)) => Ok(()),
-                    (ColumnType::Float, Value::Number(_)) => Ok(()),
-                    (ColumnType::String, Value::String(_)) => Ok(()),
-                    _ => Err(SemanticError::TypeMismatch(col.clone()))
+                // The value is converted to the column's type to compare,
+                // in STRICT tables too.
+                Self::check_value_type(col, col_type, false, value)
+            }
+            Condition::Compare(left, _op, right) => {
+                self.analyze_expression(left, table_schema)?;
+                self.analyze_expression(right, table_schema)
+            }
+            Condition::IsNull(expr) | Condition::IsNotNull(expr) | Condition::IsTrue(expr) | Condition::IsFalse(expr) | Condition::Truth(expr) => {
+                self.analyze_expression(expr, table_schema)
+            }
+            Condition::And(left, right) | Condition::Or(left, right) => {
+                self.analyze_condition(left, table_schema)?;
+                self.analyze_condition(right, table_schema)
+            }
+            Condition::Not(inner) => self.analyze_condition(inner, table_schema)
+        }
+    }
+
+    pub fn analyze_expression(&self, expr: &Expression, table_schema: &TableSchema) -> Result<(), SemanticError> {
+        match expr {
+            Expression::Literal(_) => Ok(()),
+            Expression::Column(col) => {
+                if table_schema.has_column(col) {
+                    Ok(())
+                } else {
+                    Err(SemanticError::ColumnNotFound(col.clone()))
                 }
             }
+            Expression::Negate(inner) => self.analyze_expression(inner, table_schema),
+            Expression::Binary(left, _op, right) => {
+                self.analyze_expression(left, table_schema)?;
+                self.analyze_expression(right, table_schema)
+            }
+            Expression::Function(_, args) => args.iter().try_for_each(|arg| self.analyze_expression(arg, table_schema))
+        }
+    }
+
+    /// The column each parameter of `ast` is compared with or stored in,
+    /// its type, and whether it must be matched exactly, so that a value
+    /// bound to it can be checked the way a literal in its place would be.
+    /// Parameters used anywhere else take any value. Expects `ast` to have
+    /// passed `analyze`.
+    pub fn parameter_columns(&self, ast: &ASTNode) -> HashMap<usize, (String, ColumnType, bool)> {
+        let mut parameters = HashMap::new();
+        self.collect_parameters(ast, &mut parameters);
+        parameters
+    }
+
+    fn collect_parameters(&self, ast: &ASTNode, parameters: &mut HashMap<usize, (String, ColumnType, bool)>) {
+        // Only values written to a STRICT table are matched exactly.
+        let mut add = |table_schema: &TableSchema, col: &str, value: &Value, strict: bool| {
+            if let (Value::Parameter(index), Some(col_type)) = (value, table_schema.column_type(col)) {
+                parameters.entry(*index).or_insert_with(|| (col.to_string(), col_type.clone(), strict));
+            }
+        };
+
+        match ast {
+            ASTNode::Select(stmt) => {
+                if let (Some(table_schema), Some(condition)) = (self.schema.tables.get(&stmt.table), &stmt.condition) {
+                    Self::condition_parameters(condition, table_schema, &mut add);
+                }
+            }
+            ASTNode::Insert(stmt) => {
+                let Some(table_schema) = self.schema.tables.get(&stmt.table) else { return };
+                match &stmt.source {
+                    InsertSource::Values(rows) => {
+                        let columns = if stmt.columns.is_empty() { table_schema.column_names() } else { stmt.columns.clone() };
+                        for values in rows {
+                            for (col, value) in columns.iter().zip(values) {
+                                add(table_schema, col, value, table_schema.strict);
+                            }
+                        }
+                    }
+                    InsertSource::Select(select) => {
+                        if let (Some(table_schema), Some(condition)) = (self.schema.tables.get(&select.table), &select.condition) {
+                            Self::condition_parameters(condition, table_schema, &mut add);
+                        }
+                    }
+                }
+            }
+            ASTNode::Update(stmt) => {
+                let Some(table_schema) = self.schema.tables.get(&stmt.table) else { return };
+                for (col, value) in &stmt.updates {
+                    add(table_schema, col, value, table_schema.strict);
+                }
+                if let Some(condition) = &stmt.condition {
+                    Self::condition_parameters(condition, table_schema, &mut add);
+                }
+            }
+            ASTNode::Delete(stmt) => {
+                if let (Some(table_schema), Some(condition)) = (self.schema.tables.get(&stmt.table), &stmt.condition) {
+                    Self::condition_parameters(condition, table_schema, &mut add);
+                }
+            }
+            ASTNode::Explain(stmt) => self.collect_parameters(&stmt.statement, parameters),
+            _ => {}
+        }
+    }
+
+    fn condition_parameters(condition: &Condition, table_schema: &TableSchema, add: &mut impl FnMut(&TableSchema, &str, &Value, bool)) {
+        match condition {
+            Condition::Comparison(col, _op, value) => add(table_schema, col, value, false),
+            Condition::Compare(Expression::Column(col), _op, Expression::Literal(value))
+            | Condition::Compare(Expression::Literal(value), _op, Expression::Column(col)) => add(table_schema, col, value, false),
+            Condition::And(left, right) | Condition::Or(left, right) => {
+                Self::condition_parameters(left, table_schema, add);
+                Self::condition_parameters(right, table_schema, add);
+            }
+            Condition::Not(inner) => Self::condition_parameters(inner, table_schema, add),
+            Condition::Compare(..) | Condition::IsNull(_) | Condition::IsNotNull(_) | Condition::IsTrue(_) | Condition::IsFalse(_) | Condition::Truth(_) => {}
+        }
+    }
+
+    /// Refuses a value the type rules in `affinity::fits` do not let into
+    /// the column, exactly matched or not.
+    pub fn check_value_type(col: &str, col_type: &ColumnType, strict: bool, value: &Value) -> Result<(), SemanticError> {
+        if affinity::fits(value, col_type, strict) {
+            Ok(())
+        } else {
+            Err(SemanticError::TypeMismatch(col.to_string()))
+        }
+    }
+
+    fn first_column(expr: &Expression) -> Option<String> {
+        match expr {
+            Expression::Literal(_) => None,
+            Expression::Column(col) => Some(col.clone()),
+            Expression::Negate(inner) => Self::first_column(inner),
+            Expression::Binary(left, _op, right) => Self::first_column(left).or_else(|| Self::first_column(right)),
+            Expression::Function(_, args) => args.iter().find_map(Self::first_column)
         }
     }
 }
 
+#[derive(Debug)]
 pub enum SemanticError {
     TableNotFound(String),
     ColumnNotFound(String),
-    TypeMismatch(String)
-}
\ No newline at end of file
+    TypeMismatch(String),
+    TableAlreadyExists(String),
+    DuplicateColumn(String),
+    ValueCountMismatch(String),
+    NotNullViolation(String),
+    InvalidDefault(String),
+    ForeignKeyMismatch(String),
+    MultiplePrimaryKeys(String),
+    InvalidAutoincrement(String)
+}
@@ -0,0 +1,1043 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::Path;
+
+use crate::ast::{ASTNode, Expression, SelectStatement, TransactionMode, Value};
+use crate::catalog::{primary_key_index, schema_rows};
+use crate::evaluator::{compare_values, value_to_field};
+use crate::execution_engine::{ExecutionEngine, ExecutionError};
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::schema::{ColumnConstraint, ColumnType, TableSchema, ROWID_COLUMN};
+
+/// The first 16 bytes of every SQLite 3 database file.
+const MAGIC: &[u8; 16] = b"SQLite format 3\0";
+const HEADER_SIZE: usize = 100;
+
+/// B-tree page types, from the first byte of the page header.
+const INTERIOR_INDEX_PAGE: u8 = 0x02;
+const INTERIOR_TABLE_PAGE: u8 = 0x05;
+const LEAF_INDEX_PAGE: u8 = 0x0A;
+const LEAF_TABLE_PAGE: u8 = 0x0D;
+
+/// The page size files are written with, SQLite's default. No bytes are
+/// reserved at the end of a page, so all of it is usable.
+const PAGE_SIZE: usize = 4096;
+
+/// The SQLite release written files claim to come from, at offset 96.
+const SQLITE_VERSION_NUMBER: u32 = 3_046_000;
+
+/// How text is encoded in the file, from offset 56 of the header.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be
+}
+
+/// A row of the file's `sqlite_schema` table.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SchemaEntry {
+    /// 'table', 'index', 'view' or 'trigger'.
+    pub kind: String,
+    pub name: String,
+    pub table: String,
+    /// The first page of the object's B-tree, or 0 for views and triggers.
+    pub root_page: u32,
+    pub sql: Option<String>
+}
+
+/// A table read from the file: its name, its schema and its rows as fields
+/// ready to insert, rowids included.
+pub type Table = (String, TableSchema, Vec<HashMap<String, Option<String>>>);
+
+/// A value of a record. Integers keep all 64 bits, which a `Value::Number`
+/// loses for those past 2^53.
+enum RecordValue {
+    Integer(i64),
+    Other(Value)
+}
+
+impl RecordValue {
+    fn into_value(self) -> Value {
+        match self {
+            RecordValue::Integer(n) => Value::Number(n as f64),
+            RecordValue::Other(value) => value
+        }
+    }
+
+    /// The field the value is stored as in a column of `column_type`. An
+    /// integer too big for an `f64` is written out exactly unless the column
+    /// turns it into a real number anyway, and a BLOB column holds the bytes
+    /// of a number's text, as a cast to BLOB gives.
+    fn to_field(&self, column_type: &ColumnType) -> Option<String> {
+        match self {
+            RecordValue::Integer(n) if matches!(column_type, ColumnType::Blob) => value_to_field(&Value::String(n.to_string()), column_type),
+            RecordValue::Other(Value::Number(n)) if matches!(column_type, ColumnType::Blob) => value_to_field(&Value::String(n.to_string()), column_type),
+            RecordValue::Integer(n) if n.unsigned_abs() > 1 << 53 && matches!(column_type, ColumnType::Integer | ColumnType::String) => Some(n.to_string()),
+            RecordValue::Integer(n) => value_to_field(&Value::Number(*n as f64), column_type),
+            RecordValue::Other(value) => value_to_field(value, column_type)
+        }
+    }
+}
+
+/// A SQLite 3 database file, read into memory and only ever read.
+///
+/// The reader follows the documented file format: the 100-byte header,
+/// table B-tree pages, overflow pages and the record format. Rows are read
+/// from the main file alone, so anything still in a `-wal` file beside it
+/// is not seen. Tables declared WITHOUT ROWID are kept in index B-trees,
+/// which are not read.
+pub struct SqliteFile {
+    data: Vec<u8>,
+    page_size: usize,
+    /// The bytes of each page in use, after any reserved at the end.
+    usable_size: usize,
+    page_count: u32,
+    encoding: Encoding
+}
+
+impl SqliteFile {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::from_bytes(fs::read(path)?)
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> io::Result<Self> {
+        if data.len() < HEADER_SIZE || &data[..16] != MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "file is not a database"));
+        }
+
+        let page_size = match u16::from_be_bytes([data[16], data[17]]) {
+            1 => 65536,
+            size if size >= 512 && size.is_power_of_two() => size as usize,
+            _ => return Err(malformed("bad page size"))
+        };
+        let usable_size = page_size - data[20] as usize;
+        if usable_size < 480 {
+            return Err(malformed("too many reserved bytes"));
+        }
+
+        // The page count in the header is only trusted when it was written
+        // by the same change as the rest of it, as SQLite does.
+        let header_count = read_u32(&data, 28);
+        let counted = read_u32(&data, 24) == read_u32(&data, 92) && header_count > 0;
+        let page_count = if counted { header_count } else { (data.len() / page_size) as u32 };
+        if data.len() < page_count as usize * page_size {
+            return Err(malformed("file is shorter than its page count"));
+        }
+
+        let encoding = match read_u32(&data, 56) {
+            0 | 1 => Encoding::Utf8,
+            2 => Encoding::Utf16Le,
+            3 => Encoding::Utf16Be,
+            _ => return Err(malformed("unknown text encoding"))
+        };
+
+        Ok(SqliteFile { data, page_size, usable_size, page_count, encoding })
+    }
+
+    /// The rows of `sqlite_schema`, which is always the table on page 1.
+    pub fn schema(&self) -> io::Result<Vec<SchemaEntry>> {
+        self.table_rows(1)?.into_iter().map(|(_, values)| {
+            let text = |i: usize| match values.get(i) {
+                Some(Value::String(s)) => Ok(s.clone()),
+                _ => Err(malformed("bad sqlite_schema row"))
+            };
+            Ok(SchemaEntry {
+                kind: text(0)?,
+                name: text(1)?,
+                table: text(2)?,
+                root_page: match values.get(3) {
+                    Some(Value::Number(n)) if *n >= 0.0 => *n as u32,
+                    _ => 0
+                },
+                sql: text(4).ok()
+            })
+        }).collect()
+    }
+
+    /// The tables a query can read, each with a schema built from its
+    /// CREATE TABLE statement. Internal `sqlite_` tables, virtual tables and
+    /// WITHOUT ROWID tables are left out.
+    ///
+    /// SQLite lets a TEXT column, or one with no type, hold blobs, but a
+    /// column here holds one kind of value. One that holds any blob is
+    /// read as a BLOB column, which keeps their bytes, and its text as the
+    /// bytes of that text.
+    pub fn tables(&self) -> io::Result<Vec<Table>> {
+        let mut tables = Vec::new();
+        for entry in self.schema()? {
+            let Some(sql) = &entry.sql else { continue };
+            let without_rowid = table_options(sql).iter().any(|option| option == "WITHOUT ROWID");
+            if entry.kind != "table" || entry.root_page == 0 || entry.name.to_lowercase().starts_with("sqlite_") || without_rowid {
+                continue;
+            }
+
+            let mut schema = table_schema(sql)?;
+            let columns = schema.column_names();
+            let alias = schema.rowid_alias();
+            let records = self.records(entry.root_page)?;
+            for (at, column) in columns.iter().enumerate() {
+                let holds_blobs = records.iter().any(|(_, values)| matches!(values.get(at), Some(RecordValue::Other(Value::Blob(_)))));
+                if holds_blobs && matches!(schema.columns[column], ColumnType::String) {
+                    schema.columns.insert(column.clone(), ColumnType::Blob);
+                }
+            }
+
+            // A record written before columns were added ends early, and
+            // the columns it leaves out take their defaults on insert.
+            let rows = records.into_iter().map(|(rowid, values)| {
+                let mut row: HashMap<String, Option<String>> = columns.iter().zip(&values)
+                    .filter(|(column, _)| Some(*column) != alias.as_ref())
+                    .map(|(column, value)| (column.clone(), value.to_field(&schema.columns[column])))
+                    .collect();
+                row.insert(ROWID_COLUMN.to_string(), Some(rowid.to_string()));
+                row
+            }).collect();
+            tables.push((entry.name, schema, rows));
+        }
+        Ok(tables)
+    }
+
+    /// Every row of the table B-tree rooted at `root`, as its rowid and the
+    /// values of its record, in rowid order.
+    pub fn table_rows(&self, root: u32) -> io::Result<Vec<(i64, Vec<Value>)>> {
+        let rows = self.records(root)?.into_iter()
+            .map(|(rowid, values)| (rowid, values.into_iter().map(RecordValue::into_value).collect()))
+            .collect();
+        Ok(rows)
+    }
+
+    fn records(&self, root: u32) -> io::Result<Vec<(i64, Vec<RecordValue>)>> {
+        let mut rows = Vec::new();
+        let mut pages = vec![root];
+        let mut visited = 0;
+        while let Some(number) = pages.pop() {
+            // A B-tree has no more pages than the file, so more means a loop.
+            visited += 1;
+            if visited > self.page_count {
+                return Err(malformed("B-tree pages form a loop"));
+            }
+
+            let (page, header) = self.page(number)?;
+            let cells = read_u16(page, header + 3) as usize;
+            match page[header] {
+                LEAF_TABLE_PAGE => {
+                    for i in 0..cells {
+                        let cell = self.cell_offset(page, header + 8, i)?;
+                        let (payload_size, size_length) = read_varint(page, cell)?;
+                        let (rowid, rowid_length) = read_varint(page, cell + size_length)?;
+                        let payload = self.payload(page, cell + size_length + rowid_length, payload_size as usize)?;
+                        rows.push((rowid as i64, self.record(&payload)?));
+                    }
+                }
+                INTERIOR_TABLE_PAGE => {
+                    // Children are pushed right to left so they come off the
+                    // stack in rowid order.
+                    pages.push(read_u32(page, header + 8));
+                    for i in (0..cells).rev() {
+                        let cell = self.cell_offset(page, header + 12, i)?;
+                        pages.push(read_u32(page, cell));
+                    }
+                }
+                _ => return Err(malformed("expected a table B-tree page"))
+            }
+        }
+        Ok(rows)
+    }
+
+    /// A page by its 1-based number, with the offset of its B-tree page
+    /// header: past the file header on page 1.
+    fn page(&self, number: u32) -> io::Result<(&[u8], usize)> {
+        if number == 0 || number > self.page_count {
+            return Err(malformed("page number out of range"));
+        }
+        let start = (number as usize - 1) * self.page_size;
+        let header = if number == 1 { HEADER_SIZE } else { 0 };
+        Ok((&self.data[start..start + self.page_size], header))
+    }
+
+    /// Where the `i`th cell of a page starts, from the cell pointer array
+    /// beginning at `pointers`.
+    fn cell_offset(&self, page: &[u8], pointers: usize, i: usize) -> io::Result<usize> {
+        let pointer = pointers + 2 * i;
+        if pointer + 2 > self.usable_size {
+            return Err(malformed("too many cells on a page"));
+        }
+        // No cell is shorter than four bytes, so that much can always be read.
+        match read_u16(page, pointer) as usize {
+            offset if offset + 4 <= self.usable_size => Ok(offset),
+            _ => Err(malformed("cell pointer out of range"))
+        }
+    }
+
+    /// A cell's payload of `size` bytes starting at `start`, followed into
+    /// its overflow pages when it does not fit on the page.
+    fn payload(&self, page: &[u8], start: usize, size: usize) -> io::Result<Vec<u8>> {
+        let usable = self.usable_size;
+        let local = local_size(size, usable, usable - 35);
+
+        let end = start + local;
+        let pointer_size = if local < size { 4 } else { 0 };
+        if end + pointer_size > usable {
+            return Err(malformed("cell runs past the end of its page"));
+        }
+        let mut payload = page[start..end].to_vec();
+        if local == size {
+            return Ok(payload);
+        }
+
+        let mut next = read_u32(page, end);
+        let mut visited = 0;
+        while payload.len() < size {
+            visited += 1;
+            if next == 0 || visited > self.page_count {
+                return Err(malformed("overflow chain ends early"));
+            }
+            let (overflow, _) = self.page(next)?;
+            let take = (size - payload.len()).min(usable - 4);
+            payload.extend_from_slice(&overflow[4..4 + take]);
+            next = read_u32(overflow, 0);
+        }
+        Ok(payload)
+    }
+
+    /// The values of a record: a header of serial types, then the values.
+    fn record(&self, payload: &[u8]) -> io::Result<Vec<RecordValue>> {
+        let (header_size, mut offset) = read_varint(payload, 0)?;
+        let header_size = header_size as usize;
+        let mut body = header_size;
+        let mut values = Vec::new();
+        while offset < header_size {
+            let (serial_type, length) = read_varint(payload, offset)?;
+            offset += length;
+
+            let size = match serial_type {
+                0 | 8 | 9 => 0,
+                1..=4 => serial_type as usize,
+                5 => 6,
+                6 | 7 => 8,
+                10 | 11 => return Err(malformed("reserved serial type")),
+                n => (n as usize - 12) / 2
+            };
+            let end = body.checked_add(size).ok_or_else(|| malformed("record runs past its payload"))?;
+            let bytes = payload.get(body..end).ok_or_else(|| malformed("record runs past its payload"))?;
+            body = end;
+
+            values.push(match serial_type {
+                1..=6 => {
+                    // Big-endian two's complement, sign-extended from its
+                    // first byte.
+                    let first = if bytes[0] & 0x80 != 0 { -1i64 } else { 0 };
+                    RecordValue::Integer(bytes.iter().fold(first, |n, byte| (n << 8) | *byte as i64))
+                }
+                8 => RecordValue::Integer(0),
+                9 => RecordValue::Integer(1),
+                _ => RecordValue::Other(match serial_type {
+                    0 => Value::Null,
+                    7 => Value::Number(f64::from_be_bytes(bytes.try_into().unwrap())),
+                    n if n % 2 == 0 => Value::Blob(bytes.to_vec()),
+                    _ => Value::String(self.text(bytes))
+                })
+            });
+        }
+        Ok(values)
+    }
+
+    fn text(&self, bytes: &[u8]) -> String {
+        let units = |from: fn([u8; 2]) -> u16| bytes.chunks_exact(2).map(|pair| from([pair[0], pair[1]])).collect::<Vec<u16>>();
+        match self.encoding {
+            Encoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            Encoding::Utf16Le => String::from_utf16_lossy(&units(u16::from_le_bytes)),
+            Encoding::Utf16Be => String::from_utf16_lossy(&units(u16::from_be_bytes))
+        }
+    }
+}
+
+/// How much of a payload of `size` bytes a cell keeps on its page, by the
+/// file format's rules: all of it up to `max_local`, and otherwise as much
+/// as leaves the rest filling whole overflow pages, as long as that is
+/// between the minimum and `max_local`.
+fn local_size(size: usize, usable: usize, max_local: usize) -> usize {
+    let min_local = (usable - 12) * 32 / 255 - 23;
+    if size <= max_local {
+        return size;
+    }
+    match min_local + (size - min_local) % (usable - 4) {
+        local if local <= max_local => local,
+        _ => min_local
+    }
+}
+
+/// Writes every table, view and trigger to `output` as a SQLite 3 database
+/// file that SQLite itself, or `SqliteFile`, can open, and returns how many
+/// rows were written.
+///
+/// Each table becomes a table B-tree keyed by its rowids, with an index
+/// B-tree for a PRIMARY KEY that is not the rowid, and `sqlite_schema`
+/// holds the CREATE statements the catalog shows. Values keep their types:
+/// numbers in REAL columns are written as floats and other whole numbers
+/// as integers, TRUE and FALSE as 1 and 0, and text as UTF-8. AUTOINCREMENT
+/// sequences go in `sqlite_sequence`. The tables are read in one
+/// transaction, unless one is open already.
+pub fn export_sqlite_file<W: Write>(engine: &mut ExecutionEngine, mut output: W) -> Result<usize, ExecutionError> {
+    let own_transaction = !engine.in_transaction();
+    if own_transaction {
+        engine.begin(TransactionMode::Deferred)?;
+    }
+    let written = write_pages(engine);
+    if own_transaction {
+        engine.commit()?;
+    }
+
+    let (pages, rows) = written?;
+    for page in pages {
+        output.write_all(&page)?;
+    }
+    output.flush()?;
+    Ok(rows)
+}
+
+/// The pages of the file, in order, and how many rows they hold.
+fn write_pages(engine: &mut ExecutionEngine) -> Result<(Vec<Vec<u8>>, usize), ExecutionError> {
+    let mut pages = PageWriter { pages: vec![Vec::new()] };
+    let mut roots = HashMap::new();
+    let mut sequences = Vec::new();
+    let mut written = 0;
+
+    for table in engine.data_store().table_names() {
+        let Some(schema) = engine.data_store().get_table_schema(&table).cloned() else { continue };
+        let columns = schema.column_names();
+        let real: Vec<bool> = columns.iter().map(|column| schema.columns[column] == ColumnType::Float).collect();
+        let rows = table_rows(engine, &table, &schema)?;
+        written += rows.len();
+
+        let records = rows.iter().map(|(rowid, values)| (*rowid, encode_record(values, &real))).collect();
+        roots.insert(table.clone(), pages.table_tree(records, 0));
+
+        if let Some(index) = primary_key_index(&table, &schema) {
+            // Index entries are the key's values then the rowid, in that order.
+            let key: Vec<usize> = schema.primary_key().iter().filter_map(|column| columns.iter().position(|c| c == column)).collect();
+            let mut entries: Vec<Vec<Value>> = rows.iter().map(|(rowid, values)| {
+                key.iter().map(|&i| values[i].clone()).chain(std::iter::once(Value::Number(*rowid as f64))).collect()
+            }).collect();
+            entries.sort_by(|a, b| compare_entries(a, b));
+            let key_real: Vec<bool> = key.iter().map(|&i| real[i]).collect();
+            roots.insert(index, pages.index_tree(entries.iter().map(|entry| encode_record(entry, &key_real)).collect()));
+        }
+        if schema.is_autoincrement() {
+            let sequence = engine.data_store().sequence(&table).unwrap_or(0);
+            sequences.push(vec![Value::String(table.clone()), Value::Number(sequence as f64)]);
+        }
+    }
+
+    let mut catalog: Vec<Vec<Value>> = schema_rows(engine.data_store()).into_iter().map(|row| {
+        let text = |column: &str| row.get(column).map_or(Value::Null, |value| Value::String(value.clone()));
+        let root = row.get("name").and_then(|name| roots.get(name)).copied().unwrap_or(0);
+        vec![text("type"), text("name"), text("tbl_name"), Value::Number(root as f64), text("sql")]
+    }).collect();
+    if !sequences.is_empty() {
+        let root = pages.table_tree(sequences.iter().enumerate().map(|(i, row)| (i as i64 + 1, encode_record(row, &[]))).collect(), 0);
+        let name = Value::String("sqlite_sequence".to_string());
+        catalog.push(vec![Value::String("table".to_string()), name.clone(), name, Value::Number(root as f64), Value::String("CREATE TABLE sqlite_sequence(name,seq)".to_string())]);
+    }
+    let records = catalog.iter().enumerate().map(|(i, row)| (i as i64 + 1, encode_record(row, &[]))).collect();
+    pages.table_tree(records, HEADER_SIZE);
+
+    let page_count = pages.pages.len() as u32;
+    pages.pages[0][..HEADER_SIZE].copy_from_slice(&file_header(page_count));
+    Ok((pages.pages, written))
+}
+
+/// Every row of a table with its rowid, in rowid order, as values in
+/// column order. An INTEGER PRIMARY KEY is NULL, as SQLite stores it, since
+/// the rowid is its value.
+fn table_rows(engine: &mut ExecutionEngine, table: &str, schema: &TableSchema) -> Result<Vec<(i64, Vec<Value>)>, ExecutionError> {
+    let alias = schema.rowid_alias();
+    let columns = schema.column_names();
+    let stored: Vec<String> = columns.iter().filter(|column| Some(*column) != alias.as_ref()).cloned().collect();
+    let select = ASTNode::Select(SelectStatement {
+        columns: std::iter::once(ROWID_COLUMN.to_string()).chain(stored.iter().cloned()).collect(),
+        table: table.to_string(),
+        condition: None,
+        order_by: Vec::new()
+    });
+
+    let mut rows = Vec::new();
+    for row in engine.query(&select)? {
+        let mut values = row?.values.into_iter();
+        let rowid = match values.next() {
+            Some(Value::Number(n)) => n as i64,
+            _ => return Err(ExecutionError::Storage(io::Error::new(io::ErrorKind::InvalidData, format!("row of {table} without a rowid"))))
+        };
+        let mut by_column: HashMap<&String, Value> = stored.iter().zip(values).collect();
+        rows.push((rowid, columns.iter().map(|column| by_column.remove(column).unwrap_or(Value::Null)).collect()));
+    }
+    rows.sort_by_key(|(rowid, _)| *rowid);
+    Ok(rows)
+}
+
+/// Orders index entries the way SQLite's BINARY collation does: NULL
+/// first, then numbers, text and blobs, value by value.
+fn compare_entries(a: &[Value], b: &[Value]) -> Ordering {
+    a.iter().zip(b).map(|(a, b)| match (a, b) {
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Less,
+        (_, Value::Null) => Ordering::Greater,
+        (a, b) => compare_values(a, b).unwrap_or(Ordering::Equal)
+    }).find(|ordering| ordering.is_ne()).unwrap_or(Ordering::Equal)
+}
+
+/// The 100-byte header of a UTF-8 file of `page_count` pages, with no free
+/// pages, written by change 1.
+fn file_header(page_count: u32) -> [u8; HEADER_SIZE] {
+    let mut header = [0; HEADER_SIZE];
+    header[..16].copy_from_slice(MAGIC);
+    header[16..18].copy_from_slice(&(PAGE_SIZE as u16).to_be_bytes());
+    // Rollback journal for writing and reading.
+    header[18] = 1;
+    header[19] = 1;
+    // The fixed payload fractions.
+    header[21] = 64;
+    header[22] = 32;
+    header[23] = 32;
+    let fields: [(usize, u32); 6] = [(24, 1), (28, page_count), (40, 1), (44, 4), (56, 1), (92, 1)];
+    for (offset, value) in fields {
+        header[offset..offset + 4].copy_from_slice(&value.to_be_bytes());
+    }
+    header[96..100].copy_from_slice(&SQLITE_VERSION_NUMBER.to_be_bytes());
+    header
+}
+
+/// Encodes values as a record: a header of serial types, then the values.
+/// Numbers are written as 8-byte floats in columns marked `real` and when
+/// they are not whole, and otherwise as the smallest integer that holds
+/// them.
+fn encode_record(values: &[Value], real: &[bool]) -> Vec<u8> {
+    let mut types = Vec::new();
+    let mut body = Vec::new();
+    for (i, value) in values.iter().enumerate() {
+        let serial_type = match value {
+            Value::Null | Value::Parameter(_) => 0,
+            Value::Boolean(b) => if *b { 9 } else { 8 },
+            Value::Number(n) if real.get(i) == Some(&true) || n.fract() != 0.0 || n.abs() >= 9.2e18 => {
+                body.extend_from_slice(&n.to_be_bytes());
+                7
+            }
+            Value::Number(n) => encode_integer(*n as i64, &mut body),
+            Value::String(s) => {
+                body.extend_from_slice(s.as_bytes());
+                s.len() as u64 * 2 + 13
+            }
+            Value::Blob(bytes) => {
+                body.extend_from_slice(bytes);
+                bytes.len() as u64 * 2 + 12
+            }
+        };
+        write_varint(&mut types, serial_type);
+    }
+
+    // The header's size counts the varint it is written in.
+    let mut header_size = types.len() + 1;
+    while varint_length(header_size as u64) + types.len() != header_size {
+        header_size = varint_length(header_size as u64) + types.len();
+    }
+    let mut record = Vec::with_capacity(header_size + body.len());
+    write_varint(&mut record, header_size as u64);
+    record.extend(types);
+    record.extend(body);
+    record
+}
+
+/// Writes an integer in as few bytes as hold it, or none for 0 and 1, and
+/// gives its serial type.
+fn encode_integer(n: i64, body: &mut Vec<u8>) -> u64 {
+    let (serial_type, size) = match n {
+        0 => return 8,
+        1 => return 9,
+        -0x80..=0x7F => (1, 1),
+        -0x8000..=0x7FFF => (2, 2),
+        -0x80_0000..=0x7F_FFFF => (3, 3),
+        -0x8000_0000..=0x7FFF_FFFF => (4, 4),
+        -0x8000_0000_0000..=0x7FFF_FFFF_FFFF => (5, 6),
+        _ => (6, 8)
+    };
+    body.extend_from_slice(&n.to_be_bytes()[8 - size..]);
+    serial_type
+}
+
+/// The pages of a file being written, numbered from 1. Page 1 is kept for
+/// the root of `sqlite_schema`, which is written last.
+struct PageWriter {
+    pages: Vec<Vec<u8>>
+}
+
+impl PageWriter {
+    fn add(&mut self, page: Vec<u8>) -> u32 {
+        self.pages.push(page);
+        self.pages.len() as u32
+    }
+
+    /// A cell of `prefix` then as much of `payload` as its page keeps,
+    /// with the rest written to a chain of overflow pages.
+    fn cell(&mut self, mut cell: Vec<u8>, payload: &[u8], max_local: usize) -> Vec<u8> {
+        let local = local_size(payload.len(), PAGE_SIZE, max_local);
+        cell.extend_from_slice(&payload[..local]);
+        if local == payload.len() {
+            return cell;
+        }
+
+        let first = self.pages.len() as u32 + 1;
+        let chunks: Vec<&[u8]> = payload[local..].chunks(PAGE_SIZE - 4).collect();
+        for (i, chunk) in chunks.iter().enumerate() {
+            let next = if i + 1 == chunks.len() { 0 } else { first + i as u32 + 1 };
+            let mut page = vec![0; PAGE_SIZE];
+            page[..4].copy_from_slice(&next.to_be_bytes());
+            page[4..4 + chunk.len()].copy_from_slice(chunk);
+            self.add(page);
+        }
+        cell.extend_from_slice(&first.to_be_bytes());
+        cell
+    }
+
+    /// Writes a table B-tree of records by rowid, in rowid order, and gives
+    /// its root page. With a `root_offset` of the file header's size the
+    /// root is page 1, after the header.
+    fn table_tree(&mut self, records: Vec<(i64, Vec<u8>)>, root_offset: usize) -> u32 {
+        let cells: Vec<Vec<u8>> = records.iter().map(|(rowid, record)| {
+            let mut prefix = Vec::new();
+            write_varint(&mut prefix, record.len() as u64);
+            write_varint(&mut prefix, *rowid as u64);
+            self.cell(prefix, record, PAGE_SIZE - 35)
+        }).collect();
+        if fits(&cells, PAGE_SIZE - root_offset - 8) {
+            return self.root(LEAF_TABLE_PAGE, &cells, None, root_offset);
+        }
+
+        // Each child is its page and the largest rowid under it.
+        let mut children: Vec<(u32, i64)> = split_for_root(pack(&cells, PAGE_SIZE - 8)).into_iter().map(|range| {
+            (self.add(btree_page(LEAF_TABLE_PAGE, &cells[range.clone()], None, 0)), records[range.end - 1].0)
+        }).collect();
+        loop {
+            // An interior page has a cell for every child but the last,
+            // which is its right pointer.
+            let cells: Vec<Vec<u8>> = children.iter().map(|(page, key)| {
+                let mut cell = page.to_be_bytes().to_vec();
+                write_varint(&mut cell, *key as u64);
+                cell
+            }).collect();
+            let (right, _) = children[children.len() - 1];
+            if fits(&cells[..cells.len() - 1], PAGE_SIZE - root_offset - 12) {
+                return self.root(INTERIOR_TABLE_PAGE, &cells[..cells.len() - 1], Some(right), root_offset);
+            }
+
+            // A page with only its right pointer would hold nothing, so the
+            // last one takes a child from the one before.
+            let mut groups = split_for_root(pack(&cells, PAGE_SIZE - 12));
+            let last = groups.len() - 1;
+            if last > 0 && groups[last].len() == 1 {
+                groups[last - 1].end -= 1;
+                groups[last].start -= 1;
+            }
+            children = groups.into_iter().map(|range| {
+                let (right, key) = children[range.end - 1];
+                (self.add(btree_page(INTERIOR_TABLE_PAGE, &cells[range.start..range.end - 1], Some(right), 0)), key)
+            }).collect();
+        }
+    }
+
+    /// Writes an index B-tree of records already in order and gives its
+    /// root page. Unlike a table's, an index's interior pages hold records
+    /// too: the one after each full page moves up a level, with that page
+    /// as its left child.
+    fn index_tree(&mut self, records: Vec<Vec<u8>>) -> u32 {
+        let max_local = (PAGE_SIZE - 12) * 64 / 255 - 23;
+        let mut cells: Vec<Vec<u8>> = records.iter().map(|record| {
+            let mut prefix = Vec::new();
+            write_varint(&mut prefix, record.len() as u64);
+            self.cell(prefix, record, max_local)
+        }).collect();
+        // The left child of each cell, then the right pointer; none on leaves.
+        let mut children: Vec<u32> = Vec::new();
+        loop {
+            let (kind, header_size) = if children.is_empty() { (LEAF_INDEX_PAGE, 8) } else { (INTERIOR_INDEX_PAGE, 12) };
+            let page_cells: Vec<Vec<u8>> = cells.iter().enumerate().map(|(i, cell)| match children.get(i) {
+                Some(child) => [&child.to_be_bytes()[..], cell].concat(),
+                None => cell.clone()
+            }).collect();
+            let right = children.last().copied();
+            if fits(&page_cells, PAGE_SIZE - header_size) {
+                return self.add(btree_page(kind, &page_cells, right, 0));
+            }
+
+            let mut groups = pack(&page_cells, PAGE_SIZE - header_size);
+            // The cell after each page but the last moves up, so the last
+            // page must not be left with nothing once it has.
+            let mut i = 1;
+            while i < groups.len() {
+                if groups[i].len() == 1 && i + 1 == groups.len() {
+                    groups[i - 1].end -= 1;
+                    groups[i].start -= 1;
+                }
+                groups[i].start += 1;
+                i += 1;
+            }
+
+            let mut up_cells = Vec::new();
+            let mut up_children = Vec::new();
+            for (n, range) in groups.iter().enumerate() {
+                let page_right = match groups.get(n + 1) {
+                    Some(next) => children.get(next.start - 1).copied(),
+                    None => right
+                };
+                up_children.push(self.add(btree_page(kind, &page_cells[range.clone()], page_right, 0)));
+                if n + 1 < groups.len() {
+                    up_cells.push(cells[range.end].clone());
+                }
+            }
+            cells = up_cells;
+            children = up_children;
+        }
+    }
+
+    /// Writes a root page: to page 1 when it goes after the file header,
+    /// and otherwise as a new page.
+    fn root(&mut self, kind: u8, cells: &[Vec<u8>], right: Option<u32>, offset: usize) -> u32 {
+        let page = btree_page(kind, cells, right, offset);
+        if offset == 0 {
+            return self.add(page);
+        }
+        self.pages[0] = page;
+        1
+    }
+}
+
+/// A B-tree page holding `cells`, with its header at `offset`. Interior
+/// pages have a `right` pointer to their last child.
+fn btree_page(kind: u8, cells: &[Vec<u8>], right: Option<u32>, offset: usize) -> Vec<u8> {
+    let mut page = vec![0; PAGE_SIZE];
+    let header_size = if right.is_some() { 12 } else { 8 };
+    page[offset] = kind;
+    page[offset + 3..offset + 5].copy_from_slice(&(cells.len() as u16).to_be_bytes());
+    if let Some(right) = right {
+        page[offset + 8..offset + 12].copy_from_slice(&right.to_be_bytes());
+    }
+
+    // Cells fill the page from the end, their pointers from the header.
+    let mut content = PAGE_SIZE;
+    for (i, cell) in cells.iter().enumerate() {
+        content -= cell.len();
+        page[content..content + cell.len()].copy_from_slice(cell);
+        let pointer = offset + header_size + 2 * i;
+        page[pointer..pointer + 2].copy_from_slice(&(content as u16).to_be_bytes());
+    }
+    page[offset + 5..offset + 7].copy_from_slice(&(content as u16).to_be_bytes());
+    page
+}
+
+/// Cells that fit on one page but not on page 1 after the file header are
+/// split in two, so the root above them has something to point to.
+fn split_for_root(groups: Vec<Range<usize>>) -> Vec<Range<usize>> {
+    match groups.as_slice() {
+        [group] if group.len() > 1 => {
+            let middle = group.start + group.len() / 2;
+            vec![group.start..middle, middle..group.end]
+        }
+        _ => groups
+    }
+}
+
+/// Whether cells fit in `space` bytes of a page, with their pointers.
+fn fits(cells: &[Vec<u8>], space: usize) -> bool {
+    cells.iter().map(|cell| cell.len() + 2).sum::<usize>() <= space
+}
+
+/// Splits cells into runs that each fit in `space` bytes of a page,
+/// filling each before starting the next.
+fn pack(cells: &[Vec<u8>], space: usize) -> Vec<Range<usize>> {
+    let mut groups = Vec::new();
+    let mut start = 0;
+    let mut used = 0;
+    for (i, cell) in cells.iter().enumerate() {
+        if i > start && used + cell.len() + 2 > space {
+            groups.push(start..i);
+            start = i;
+            used = 0;
+        }
+        used += cell.len() + 2;
+    }
+    groups.push(start..cells.len());
+    groups
+}
+
+/// A schema for a table from the CREATE TABLE statement SQLite keeps for
+/// it. Column types are mapped onto this engine's by SQLite's rules for
+/// their affinity. Of the constraints only an INTEGER PRIMARY KEY is kept,
+/// since that is the rowid, and DEFAULT, which fills in the columns a
+/// record written before they were added leaves out.
+fn table_schema(sql: &str) -> io::Result<TableSchema> {
+    let open = sql.find('(').ok_or_else(|| malformed("bad CREATE TABLE statement"))?;
+
+    let mut schema = TableSchema::default();
+    let mut declared_types = HashMap::new();
+    let mut primary_key = None;
+    for definition in split_top_level(&sql[open + 1..]) {
+        let words = words(&definition);
+        let upper: Vec<String> = words.iter().map(|word| word.to_uppercase()).collect();
+        let Some(first) = upper.first() else { continue };
+        let declares_key = upper.windows(2).any(|pair| pair == ["PRIMARY", "KEY"]);
+
+        if ["CONSTRAINT", "PRIMARY", "UNIQUE", "CHECK", "FOREIGN"].contains(&first.as_str()) {
+            // Of the table constraints, only a PRIMARY KEY on one column
+            // can make a rowid.
+            let columns = upper.iter().position(|word| word == "KEY")
+                .and_then(|at| words.get(at + 1))
+                .filter(|group| declares_key && group.starts_with('('))
+                .map(|group| split_top_level(&group[1..]))
+                .unwrap_or_default();
+            if let [column] = columns.as_slice() {
+                primary_key = self::words(column).first().map(|name| unquote(name));
+            }
+            continue;
+        }
+
+        let name = unquote(&words[0]);
+        let declared = words[1..].iter()
+            .take_while(|word| !CONSTRAINT_KEYWORDS.contains(&word.to_uppercase().as_str()))
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(" ");
+        if declares_key {
+            primary_key = Some(name.clone());
+        }
+        if let Some(default) = upper.iter().position(|word| word == "DEFAULT").and_then(|at| default_expression(&words[at + 1..])) {
+            schema.column_constraints.entry(name.clone()).or_default().push(ColumnConstraint::Default(default));
+        }
+        schema.columns.insert(name.clone(), column_type(&declared));
+        schema.column_order.push(name.clone());
+        declared_types.insert(name, declared);
+    }
+
+    // Only a column declared exactly INTEGER is the rowid.
+    if let Some(column) = primary_key.filter(|column| declared_types.get(column).is_some_and(|declared| declared.eq_ignore_ascii_case("INTEGER"))) {
+        schema.column_constraints.entry(column).or_default().push(ColumnConstraint::PrimaryKey { autoincrement: false });
+    }
+    Ok(schema)
+}
+
+/// The expression of a DEFAULT clause from the words after DEFAULT: a
+/// literal, a signed number or an expression in parens. Names such as
+/// CURRENT_TIMESTAMP mean nothing here, so they give no default.
+fn default_expression(words: &[String]) -> Option<Expression> {
+    let text = match words {
+        [sign, number, ..] if sign == "-" || sign == "+" => format!("{sign}{number}"),
+        [word, ..] => word.clone(),
+        [] => return None
+    };
+    Parser::new(Lexer::new(&text)).parse_expression().ok()
+        .filter(|expression| !matches!(expression, Expression::Column(_)))
+}
+
+/// The options after the column definitions of a CREATE TABLE statement,
+/// such as `WITHOUT ROWID` and `STRICT`, in upper case with their words
+/// split by single spaces.
+fn table_options(sql: &str) -> Vec<String> {
+    let Some(start) = sql.find('(') else { return Vec::new() };
+    let mut depth = 0;
+    let mut quote = None;
+    for (at, ch) in sql[start..].char_indices() {
+        match (quote, ch) {
+            (Some(open), ch) if ch == closing_quote(open) => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`' | '[') => quote = Some(ch),
+            (None, '(') => depth += 1,
+            (None, ')') if depth == 1 => {
+                return sql[start + at + 1..].split(',')
+                    .map(|option| option.split_whitespace().collect::<Vec<_>>().join(" ").to_uppercase())
+                    .collect();
+            }
+            (None, ')') => depth -= 1,
+            _ => {}
+        }
+    }
+    Vec::new()
+}
+
+/// Words that end a column's declared type and start its constraints.
+const CONSTRAINT_KEYWORDS: [&str; 12] = [
+    "CONSTRAINT", "PRIMARY", "NOT", "NULL", "UNIQUE", "CHECK", "DEFAULT", "COLLATE", "REFERENCES", "GENERATED", "AS", "AUTOINCREMENT"
+];
+
+/// The column type for a declared type, by the rules SQLite uses to give a
+/// column its affinity: INT means INTEGER; CHAR, CLOB or TEXT mean TEXT;
+/// BLOB means BLOB; REAL, FLOA or DOUB mean REAL. Of the rest, which SQLite
+/// treats as NUMERIC, booleans and dates keep their own types here and
+/// anything else is REAL. A column with no type is TEXT, the closest this
+/// engine has to holding anything, unless it holds blobs.
+fn column_type(declared: &str) -> ColumnType {
+    let declared = declared.to_uppercase();
+    let has = |part: &str| declared.contains(part);
+    if has("INT") {
+        ColumnType::Integer
+    } else if has("CHAR") || has("CLOB") || has("TEXT") || declared.is_empty() {
+        ColumnType::String
+    } else if has("BLOB") {
+        ColumnType::Blob
+    } else if has("REAL") || has("FLOA") || has("DOUB") {
+        ColumnType::Float
+    } else if has("BOOL") {
+        ColumnType::Boolean
+    } else if has("DATETIME") || has("TIMESTAMP") {
+        ColumnType::Timestamp
+    } else if has("DATE") {
+        ColumnType::Date
+    } else if has("TIME") {
+        ColumnType::Time
+    } else {
+        ColumnType::Float
+    }
+}
+
+/// Splits the text inside a pair of parens on its top-level commas, up to
+/// the parens that close it.
+fn split_top_level(text: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    let mut quote = None;
+    for ch in text.chars() {
+        match (quote, ch) {
+            (Some(open), ch) if ch == closing_quote(open) => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`' | '[') => quote = Some(ch),
+            (None, '(') => depth += 1,
+            (None, ')') if depth == 0 => break,
+            (None, ')') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                parts.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(ch);
+    }
+    parts.push(current);
+    parts.into_iter().map(|part| part.trim().to_string()).filter(|part| !part.is_empty()).collect()
+}
+
+/// The words of a column or constraint definition: names, quoted names and
+/// parenthesised groups, each kept whole.
+fn words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch.is_whitespace() {
+            continue;
+        }
+        let mut word = ch.to_string();
+        match ch {
+            '\'' | '"' | '`' | '[' => {
+                for next in chars.by_ref() {
+                    word.push(next);
+                    if next == closing_quote(ch) {
+                        break;
+                    }
+                }
+            }
+            '(' => {
+                let mut depth = 1;
+                for next in chars.by_ref() {
+                    word.push(next);
+                    depth += match next { '(' => 1, ')' => -1, _ => 0 };
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            }
+            _ => {
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || next == '(' {
+                        break;
+                    }
+                    word.push(next);
+                    chars.next();
+                }
+            }
+        }
+        words.push(word);
+    }
+    words
+}
+
+fn closing_quote(open: char) -> char {
+    if open == '[' { ']' } else { open }
+}
+
+/// A name without the quotes SQL allows around it.
+fn unquote(name: &str) -> String {
+    let mut chars = name.chars();
+    match (chars.next(), name.chars().last()) {
+        (Some(open @ ('"' | '`' | '[' | '\'')), Some(close)) if name.len() > 1 && close == closing_quote(open) => {
+            let inner = &name[1..name.len() - 1];
+            inner.replace(&format!("{close}{close}"), &close.to_string())
+        }
+        _ => name.to_string()
+    }
+}
+
+/// A variable-length integer as the file format writes them: up to eight
+/// bytes of seven bits each, high bit set on all but the last, and a ninth
+/// of eight bits. Gives the value and how many bytes it took.
+fn read_varint(bytes: &[u8], offset: usize) -> io::Result<(u64, usize)> {
+    let mut value = 0u64;
+    for i in 0..9 {
+        let byte = *bytes.get(offset + i).ok_or_else(|| malformed("varint runs past its page"))?;
+        if i == 8 {
+            return Ok(((value << 8) | byte as u64, 9));
+        }
+        value = (value << 7) | (byte & 0x7F) as u64;
+        if byte & 0x80 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+    unreachable!()
+}
+
+/// Writes a variable-length integer as `read_varint` reads them.
+fn write_varint(out: &mut Vec<u8>, value: u64) {
+    if value >> 56 != 0 {
+        for i in (1..=8).rev() {
+            out.push(((value >> (8 + 7 * (i - 1))) & 0x7F) as u8 | 0x80);
+        }
+        out.push(value as u8);
+        return;
+    }
+    let mut bytes = vec![(value & 0x7F) as u8];
+    let mut rest = value >> 7;
+    while rest != 0 {
+        bytes.push((rest & 0x7F) as u8 | 0x80);
+        rest >>= 7;
+    }
+    out.extend(bytes.iter().rev());
+}
+
+fn varint_length(value: u64) -> usize {
+    let mut bytes = Vec::new();
+    write_varint(&mut bytes, value);
+    bytes.len()
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn malformed(detail: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("database disk image is malformed: {detail}"))
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+
+use crate::ast::{ASTNode, Condition, Expression, InsertSource, SelectStatement, Value};
+use crate::execution_engine::{Cursor, ExecutionEngine, ExecutionError, QueryResult};
+use crate::schema::ColumnType;
+use crate::semantic_analyzer::SemanticAnalyzer;
+
+/// A statement parsed and checked once, to be run any number of times with
+/// different values bound to its parameters. Parameters left unbound read
+/// as NULL.
+#[derive(Debug, Clone)]
+pub struct Statement {
+    ast: ASTNode,
+    names: Vec<Option<String>>,
+    columns: HashMap<usize, (String, ColumnType, bool)>,
+    values: Vec<Value>
+}
+
+impl Statement {
+    pub(crate) fn new(ast: ASTNode, names: Vec<Option<String>>, columns: HashMap<usize, (String, ColumnType, bool)>) -> Self {
+        let values = vec![Value::Null; names.len()];
+        Statement { ast, names, columns, values }
+    }
+
+    /// The largest parameter index in the statement.
+    pub fn parameter_count(&self) -> usize {
+        self.names.len()
+    }
+
+    /// The index of a named parameter, written with its prefix, as in
+    /// `:name` or `$name`.
+    pub fn parameter_index(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|seen| seen.as_deref() == Some(name)).map(|index| index + 1)
+    }
+
+    /// Binds a value to the parameter at the 1-based `index`. A value of the
+    /// wrong type for the column the parameter is compared with or stored
+    /// in is refused with the analyzer's error.
+    pub fn bind(&mut self, index: usize, value: impl Into<Value>) -> Result<(), ExecutionError> {
+        let value = value.into();
+        if index == 0 || index > self.values.len() {
+            return Err(ExecutionError::UnknownParameter(format!("?{index}")));
+        }
+        if let Some((column, column_type, strict)) = self.columns.get(&index) {
+            SemanticAnalyzer::check_value_type(column, column_type, *strict, &value).map_err(ExecutionError::Semantic)?;
+        }
+        self.values[index - 1] = value;
+        Ok(())
+    }
+
+    pub fn bind_named(&mut self, name: &str, value: impl Into<Value>) -> Result<(), ExecutionError> {
+        let index = self.parameter_index(name).ok_or_else(|| ExecutionError::UnknownParameter(name.to_string()))?;
+        self.bind(index, value)
+    }
+
+    /// Sets every parameter back to NULL.
+    pub fn clear_bindings(&mut self) {
+        self.values.fill(Value::Null);
+    }
+
+    /// The statement with the bound values in place of its parameters.
+    pub fn to_ast(&self) -> ASTNode {
+        let mut ast = self.ast.clone();
+        bind_node(&mut ast, &self.values);
+        ast
+    }
+
+    pub fn execute(&self, engine: &mut ExecutionEngine) -> Result<QueryResult, ExecutionError> {
+        engine.execute(&self.to_ast())
+    }
+
+    pub fn query<'a>(&self, engine: &'a mut ExecutionEngine) -> Result<Cursor<'a>, ExecutionError> {
+        engine.query(&self.to_ast())
+    }
+}
+
+pub(crate) fn bind_node(ast: &mut ASTNode, values: &[Value]) {
+    match ast {
+        ASTNode::Select(stmt) => bind_select(stmt, values),
+        ASTNode::Insert(stmt) => match &mut stmt.source {
+            InsertSource::Values(rows) => rows.iter_mut().flatten().for_each(|value| bind_value(value, values)),
+            InsertSource::Select(select) => bind_select(select, values)
+        },
+        ASTNode::Update(stmt) => {
+            stmt.updates.iter_mut().for_each(|(_, value)| bind_value(value, values));
+            if let Some(condition) = &mut stmt.condition {
+                bind_condition(condition, values);
+            }
+        }
+        ASTNode::Delete(stmt) => {
+            if let Some(condition) = &mut stmt.condition {
+                bind_condition(condition, values);
+            }
+        }
+        ASTNode::Explain(stmt) => bind_node(&mut stmt.statement, values),
+        _ => {}
+    }
+}
+
+fn bind_select(stmt: &mut SelectStatement, values: &[Value]) {
+    if let Some(condition) = &mut stmt.condition {
+        bind_condition(condition, values);
+    }
+}
+
+pub(crate) fn bind_condition(condition: &mut Condition, values: &[Value]) {
+    match condition {
+        Condition::Comparison(_, _, value) => bind_value(value, values),
+        Condition::Compare(left, _, right) => {
+            bind_expression(left, values);
+            bind_expression(right, values);
+        }
+        Condition::IsNull(expr) | Condition::IsNotNull(expr) | Condition::IsTrue(expr) | Condition::IsFalse(expr) | Condition::Truth(expr) => {
+            bind_expression(expr, values)
+        }
+        Condition::And(left, right) | Condition::Or(left, right) => {
+            bind_condition(left, values);
+            bind_condition(right, values);
+        }
+        Condition::Not(inner) => bind_condition(inner, values)
+    }
+}
+
+fn bind_expression(expr: &mut Expression, values: &[Value]) {
+    match expr {
+        Expression::Literal(value) => bind_value(value, values),
+        Expression::Column(_) => {}
+        Expression::Negate(inner) => bind_expression(inner, values),
+        Expression::Binary(left, _, right) => {
+            bind_expression(left, values);
+            bind_expression(right, values);
+        }
+        Expression::Function(_, args) => args.iter_mut().for_each(|arg| bind_expression(arg, values))
+    }
+}
+
+fn bind_value(value: &mut Value, values: &[Value]) {
+    if let Value::Parameter(index) = value {
+        *value = values.get(*index - 1).cloned().unwrap_or(Value::Null);
+    }
+}
@@ -0,0 +1,156 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ast::Value;
+use crate::evaluator::{column_value, compare_values};
+use crate::schema::TableSchema;
+
+/// Rows kept from each table to build histograms and count distinct values
+/// from. Smaller tables are read in full and their statistics are exact.
+const SAMPLE_SIZE: usize = 10_000;
+
+/// Buckets in each column's histogram.
+const HISTOGRAM_BUCKETS: usize = 16;
+
+/// What `ANALYZE` found in a table.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
+pub struct TableStatistics {
+    pub row_count: u64,
+    pub columns: HashMap<String, ColumnStatistics>
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
+pub struct ColumnStatistics {
+    pub null_count: u64,
+    /// Estimated number of distinct non-NULL values.
+    pub distinct: u64,
+    /// The non-NULL values in ascending order, split into buckets holding
+    /// about as many rows each.
+    pub histogram: Vec<Bucket>
+}
+
+/// The values above the previous bucket's `upper` up to and including this
+/// one's.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Bucket {
+    pub upper: Value,
+    pub rows: u64,
+    pub distinct: u64
+}
+
+impl TableStatistics {
+    /// Reads every row once. Counts are exact; distinct values and
+    /// histograms come from an evenly drawn sample of the rows when there
+    /// are more than `SAMPLE_SIZE`.
+    pub fn collect(rows: impl Iterator<Item = io::Result<HashMap<String, String>>>, schema: &TableSchema) -> io::Result<Self> {
+        let columns = schema.column_names();
+        let mut row_count = 0;
+        let mut null_counts = vec![0; columns.len()];
+        let mut sample: Vec<Vec<Value>> = Vec::new();
+        let mut random = Random(0x2545_f491_4f6c_dd1d);
+
+        for row in rows {
+            let row = row?;
+            row_count += 1;
+            let values: Vec<Value> = columns.iter().map(|column| column_value(&row, schema, column)).collect();
+            for (null_count, value) in null_counts.iter_mut().zip(&values) {
+                if *value == Value::Null {
+                    *null_count += 1;
+                }
+            }
+
+            // Reservoir sampling: every row read so far has the same chance
+            // of being in the sample.
+            if sample.len() < SAMPLE_SIZE {
+                sample.push(values);
+            } else if let Some(slot) = sample.get_mut(random.below(row_count) as usize) {
+                *slot = values;
+            }
+        }
+
+        let columns = columns.into_iter().enumerate().map(|(i, column)| {
+            let mut values: Vec<Value> = sample.iter().map(|row| row[i].clone()).filter(|value| *value != Value::Null).collect();
+            values.sort_by(|a, b| compare_values(a, b).unwrap_or(Ordering::Equal));
+            let non_null = row_count - null_counts[i];
+            (column, ColumnStatistics::from_sorted(&values, null_counts[i], non_null))
+        }).collect();
+
+        Ok(TableStatistics { row_count, columns })
+    }
+}
+
+impl ColumnStatistics {
+    /// Statistics for a column with `non_null` values in the table, of which
+    /// `values` is a sorted sample.
+    fn from_sorted(values: &[Value], null_count: u64, non_null: u64) -> Self {
+        if values.is_empty() {
+            return ColumnStatistics { null_count, distinct: 0, histogram: Vec::new() };
+        }
+
+        let runs = runs(values);
+        let sampled = values.len() as u64;
+        let sample_distinct = runs.len() as u64;
+        let distinct = if sampled == non_null {
+            sample_distinct
+        } else {
+            // Haas and Stokes' estimator, from how many values the sample
+            // saw only once.
+            let singles = runs.iter().filter(|(_, count)| *count == 1).count() as f64;
+            let (n, total, d) = (sampled as f64, non_null as f64, sample_distinct as f64);
+            let estimate = n * d / (n - singles + singles * n / total);
+            (estimate.round() as u64).clamp(sample_distinct, non_null)
+        };
+
+        let row_scale = non_null as f64 / sampled as f64;
+        let distinct_scale = distinct as f64 / sample_distinct as f64;
+        let target = values.len().div_ceil(HISTOGRAM_BUCKETS);
+        let mut histogram = Vec::new();
+        let (mut rows, mut values_in_bucket) = (0, 0);
+        // A value is never split between two buckets.
+        for (value, count) in runs {
+            rows += count;
+            values_in_bucket += 1;
+            if rows >= target {
+                histogram.push(Bucket { upper: value.clone(), rows: scale(rows, row_scale), distinct: scale(values_in_bucket, distinct_scale) });
+                (rows, values_in_bucket) = (0, 0);
+            }
+        }
+        if rows > 0 {
+            histogram.push(Bucket { upper: values[values.len() - 1].clone(), rows: scale(rows, row_scale), distinct: scale(values_in_bucket, distinct_scale) });
+        }
+
+        ColumnStatistics { null_count, distinct, histogram }
+    }
+}
+
+/// Each distinct value of a sorted list with how many times it occurs.
+fn runs(values: &[Value]) -> Vec<(&Value, usize)> {
+    let mut runs: Vec<(&Value, usize)> = Vec::new();
+    for value in values {
+        match runs.last_mut() {
+            Some((last, count)) if *last == value => *count += 1,
+            _ => runs.push((value, 1))
+        }
+    }
+    runs
+}
+
+fn scale(count: usize, factor: f64) -> u64 {
+    ((count as f64 * factor).round() as u64).max(1)
+}
+
+/// A small xorshift generator with a fixed seed, so analyzing the same
+/// table twice gives the same statistics.
+struct Random(u64);
+
+impl Random {
+    fn below(&mut self, bound: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % bound
+    }
+}
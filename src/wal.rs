@@ -0,0 +1,139 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::schema::{TableSchema, TriggerSchema, ViewSchema};
+use crate::statistics::TableStatistics;
+
+/// Changed rows of one table by rowid: the row as stored, or `None` once it
+/// has been deleted.
+pub type RowChanges = BTreeMap<i64, Option<HashMap<String, String>>>;
+
+/// Length, commit flag and checksum in front of every frame's payload.
+const FRAME_HEADER_SIZE: usize = 4 + 1 + 8;
+
+const CHECKSUM_SEED: u64 = 0xcbf29ce484222325;
+
+/// What a single frame records. A committed transaction is a run of frames
+/// whose last one carries the commit flag.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum Change {
+    Rows { table: String, rows: RowChanges },
+    Catalog {
+        schemas: HashMap<String, TableSchema>,
+        sequences: HashMap<String, i64>,
+        #[serde(default)]
+        statistics: HashMap<String, TableStatistics>,
+        #[serde(default)]
+        views: HashMap<String, ViewSchema>,
+        #[serde(default)]
+        triggers: HashMap<String, TriggerSchema>
+    }
+}
+
+/// The write-ahead log file. Each frame's checksum covers the frame and
+/// the checksum of the one before it, so a frame that was only partly
+/// written, or that is left over from an older log, ends the log.
+pub struct WriteAheadLog {
+    file: File,
+    checksum: u64,
+    frame_count: usize
+}
+
+impl WriteAheadLog {
+    /// Opens the log at `path`, creating it if needed, and returns the
+    /// changes of every committed transaction in it. Frames after the last
+    /// commit are cut off.
+    pub fn open(path: &Path) -> io::Result<(Self, Vec<Change>)> {
+        let mut file = OpenOptions::new().read(true).append(true).create(true).open(path)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+
+        let mut checksum = CHECKSUM_SEED;
+        let mut committed = Vec::new();
+        let mut uncommitted = Vec::new();
+        let (mut offset, mut committed_end, mut committed_checksum) = (0, 0, CHECKSUM_SEED);
+        let mut frame_count = 0;
+        let mut committed_frames = 0;
+
+        while let Some(header) = bytes.get(offset..offset + FRAME_HEADER_SIZE) {
+            let length = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+            let commit = header[4] == 1;
+            let expected = u64::from_le_bytes(header[5..13].try_into().unwrap());
+
+            let start = offset + FRAME_HEADER_SIZE;
+            let Some(payload) = bytes.get(start..start + length) else { break };
+            if Self::frame_checksum(checksum, commit, payload) != expected {
+                break;
+            }
+            let Ok(change) = serde_json::from_slice(payload) else { break };
+
+            checksum = expected;
+            offset = start + length;
+            frame_count += 1;
+            uncommitted.push(change);
+            if commit {
+                committed.append(&mut uncommitted);
+                committed_end = offset;
+                committed_checksum = checksum;
+                committed_frames = frame_count;
+            }
+        }
+
+        if committed_end < bytes.len() {
+            file.set_len(committed_end as u64)?;
+            file.sync_all()?;
+        }
+
+        let log = WriteAheadLog { file, checksum: committed_checksum, frame_count: committed_frames };
+        Ok((log, committed))
+    }
+
+    /// Appends one frame per change, the last marked as the commit, and
+    /// waits for them to reach the disk.
+    pub fn append_commit(&mut self, changes: &[Change]) -> io::Result<()> {
+        let mut bytes = Vec::new();
+        let mut checksum = self.checksum;
+        for (i, change) in changes.iter().enumerate() {
+            let payload = serde_json::to_vec(change)?;
+            let commit = i + 1 == changes.len();
+            checksum = Self::frame_checksum(checksum, commit, &payload);
+
+            bytes.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            bytes.push(commit as u8);
+            bytes.extend_from_slice(&checksum.to_le_bytes());
+            bytes.extend_from_slice(&payload);
+        }
+
+        self.file.write_all(&bytes)?;
+        self.file.sync_data()?;
+        self.checksum = checksum;
+        self.frame_count += changes.len();
+        Ok(())
+    }
+
+    /// Empties the log once its changes are in the main files.
+    pub fn reset(&mut self) -> io::Result<()> {
+        self.file.set_len(0)?;
+        self.file.sync_all()?;
+        self.checksum = CHECKSUM_SEED;
+        self.frame_count = 0;
+        Ok(())
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    /// FNV-1a over the previous checksum, the commit flag and the payload.
+    fn frame_checksum(previous: u64, commit: bool, payload: &[u8]) -> u64 {
+        let mut hash = CHECKSUM_SEED;
+        for byte in previous.to_le_bytes().iter().chain(&[commit as u8]).chain(payload) {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        hash
+    }
+}
@@ -0,0 +1,130 @@
+use rust_sqlite::execution_engine::{ExecutionError, QueryResult, Row};
+
+mod common;
+use common::{execute_sql, select_rows, setup_test_engine};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_sqlite::affinity::{convert, fits};
+    use rust_sqlite::ast::Value;
+    use rust_sqlite::schema::ColumnType;
+    use rust_sqlite::semantic_analyzer::SemanticError;
+
+    fn text(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    #[test]
+    fn test_convert_and_fits() {
+        assert_eq!(convert(&text(" 4.5 "), &ColumnType::Float), Value::Number(4.5));
+        assert_eq!(convert(&text("42"), &ColumnType::Boolean), Value::Number(42.0));
+        assert_eq!(convert(&text("4x"), &ColumnType::Integer), text("4x"));
+        assert_eq!(convert(&text("inf"), &ColumnType::Integer), text("inf"));
+        assert_eq!(convert(&Value::Number(7.0), &ColumnType::String), text("7"));
+        assert_eq!(convert(&Value::Null, &ColumnType::String), Value::Null);
+
+        assert!(fits(&text("42"), &ColumnType::Integer, false));
+        assert!(!fits(&text("forty"), &ColumnType::Integer, false));
+        assert!(fits(&Value::Number(2.5), &ColumnType::String, false));
+        assert!(!fits(&text("42"), &ColumnType::Integer, true));
+        assert!(!fits(&Value::Number(2.5), &ColumnType::Integer, true));
+        assert!(!fits(&Value::Number(2.0), &ColumnType::Boolean, true));
+        assert!(fits(&Value::Null, &ColumnType::Integer, true));
+    }
+
+    #[test]
+    fn test_values_convert_to_column_affinity() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE items (i INTEGER, r REAL, s TEXT, b BOOLEAN)").unwrap();
+        execute_sql(&mut engine, "INSERT INTO items VALUES ('42', ' 2.5 ', 7, '1'), (3.0, 1, 'x', 0), (3.5, NULL, 2.5, NULL)").unwrap();
+
+        assert_eq!(select_rows(&mut engine, "SELECT * FROM items"), vec![
+            Row { values: vec![Value::Number(42.0), Value::Number(2.5), text("7"), Value::Boolean(true)] },
+            Row { values: vec![Value::Number(3.0), Value::Number(1.0), text("x"), Value::Boolean(false)] },
+            Row { values: vec![Value::Number(3.5), Value::Null, text("2.5"), Value::Null] }
+        ]);
+
+        execute_sql(&mut engine, "UPDATE items SET s = 8 WHERE i = 42").unwrap();
+        assert_eq!(select_rows(&mut engine, "SELECT s FROM items WHERE i = 42"), vec![Row { values: vec![text("8")] }]);
+
+        assert!(matches!(execute_sql(&mut engine, "INSERT INTO items (i) VALUES ('forty')"), Err(ExecutionError::TypeMismatch(col)) if col == "i"));
+        assert!(matches!(execute_sql(&mut engine, "UPDATE items SET b = 'yes'"), Err(ExecutionError::TypeMismatch(col)) if col == "b"));
+        assert!(matches!(engine.prepare("INSERT INTO items (r) VALUES ('high')"), Err(ExecutionError::Semantic(SemanticError::TypeMismatch(col))) if col == "r"));
+    }
+
+    #[test]
+    fn test_comparisons_use_column_affinity() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE items (i INTEGER, s TEXT)").unwrap();
+        execute_sql(&mut engine, "INSERT INTO items VALUES (10, '9'), (2, '10')").unwrap();
+
+        assert_eq!(select_rows(&mut engine, "SELECT i FROM items WHERE i = '10'"), vec![Row { values: vec![Value::Number(10.0)] }]);
+        assert_eq!(select_rows(&mut engine, "SELECT i FROM items WHERE s = 9"), vec![Row { values: vec![Value::Number(10.0)] }]);
+        // TEXT columns compare as text, so '9' is after '10'.
+        assert_eq!(select_rows(&mut engine, "SELECT i FROM items WHERE s > 10"), vec![Row { values: vec![Value::Number(10.0)] }]);
+        // Against an INTEGER column the text is read as a number.
+        assert_eq!(select_rows(&mut engine, "SELECT i FROM items WHERE i > s"), vec![Row { values: vec![Value::Number(10.0)] }]);
+        // Text that is not a number is never equal to a number. Prepared
+        // statements are refused for comparing them at all.
+        assert_eq!(select_rows(&mut engine, "SELECT i FROM items WHERE i = 'ten'"), vec![]);
+        assert!(matches!(engine.prepare("SELECT i FROM items WHERE i = 'ten'"), Err(ExecutionError::Semantic(SemanticError::TypeMismatch(_)))));
+    }
+
+    #[test]
+    fn test_mixed_values_order_nulls_then_numbers_then_text() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE words (w TEXT)").unwrap();
+        execute_sql(&mut engine, "CREATE TABLE items (i INTEGER)").unwrap();
+        execute_sql(&mut engine, "INSERT INTO words VALUES ('b'), ('12'), (NULL), ('a'), ('3')").unwrap();
+
+        // Selected values are only known as the statement runs, so text that
+        // is not a number is kept as text, as SQLite does.
+        assert_eq!(execute_sql(&mut engine, "INSERT INTO items SELECT w FROM words").unwrap(), QueryResult::Insert(5));
+        assert_eq!(select_rows(&mut engine, "SELECT i FROM items ORDER BY i"), vec![
+            Row { values: vec![Value::Null] },
+            Row { values: vec![Value::Number(3.0)] },
+            Row { values: vec![Value::Number(12.0)] },
+            Row { values: vec![text("a")] },
+            Row { values: vec![text("b")] }
+        ]);
+        assert_eq!(select_rows(&mut engine, "SELECT i FROM items WHERE i > 100 ORDER BY i DESC"), vec![
+            Row { values: vec![text("b")] },
+            Row { values: vec![text("a")] }
+        ]);
+    }
+
+    #[test]
+    fn test_strict_tables() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE points (id INTEGER PRIMARY KEY, x REAL, label TEXT, shown BOOLEAN) STRICT").unwrap();
+        execute_sql(&mut engine, "INSERT INTO points VALUES (1, 2, 'a', 1)").unwrap();
+
+        let mismatch = |result: Result<QueryResult, ExecutionError>, column: &str| {
+            matches!(result, Err(ExecutionError::TypeMismatch(col)) if col == column)
+        };
+        assert!(mismatch(execute_sql(&mut engine, "INSERT INTO points VALUES ('2', 2, 'b', 0)"), "id"));
+        assert!(mismatch(execute_sql(&mut engine, "INSERT INTO points VALUES (2.5, 2, 'b', 0)"), "id"));
+        assert!(mismatch(execute_sql(&mut engine, "INSERT INTO points VALUES (2, '2', 'b', 0)"), "x"));
+        assert!(mismatch(execute_sql(&mut engine, "UPDATE points SET label = 3"), "label"));
+        assert!(mismatch(execute_sql(&mut engine, "UPDATE points SET shown = 2"), "shown"));
+        assert!(matches!(engine.prepare("CREATE TABLE bad (n INTEGER DEFAULT 'none') STRICT"), Err(ExecutionError::Semantic(SemanticError::TypeMismatch(col))) if col == "n"));
+
+        // Comparisons still convert, and values only known as the
+        // statement runs are checked as they are written.
+        assert_eq!(select_rows(&mut engine, "SELECT label FROM points WHERE id = '1'"), vec![Row { values: vec![text("a")] }]);
+        execute_sql(&mut engine, "CREATE TABLE labels (label TEXT)").unwrap();
+        execute_sql(&mut engine, "INSERT INTO labels VALUES ('3')").unwrap();
+        assert!(matches!(execute_sql(&mut engine, "INSERT INTO points (id, label) SELECT label, label FROM labels"), Err(ExecutionError::TypeMismatch(col)) if col == "id"));
+
+        let mut insert = engine.prepare("INSERT INTO points (id, label) VALUES (?, ?)").unwrap();
+        assert!(matches!(insert.bind(2, 7), Err(ExecutionError::Semantic(SemanticError::TypeMismatch(_)))));
+        insert.bind(1, 2).unwrap();
+        insert.bind(2, "b").unwrap();
+        insert.execute(&mut engine).unwrap();
+
+        assert_eq!(select_rows(&mut engine, "SELECT sql FROM sqlite_schema WHERE name = 'points'"), vec![
+            Row { values: vec![text("CREATE TABLE points (id INTEGER PRIMARY KEY, x REAL, label TEXT, shown BOOLEAN) STRICT")] }
+        ]);
+    }
+}
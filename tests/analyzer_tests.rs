@@ -1,9 +1,8 @@
 #[cfg(test)]
 mod tests {
-    use super::*;
     use rust_sqlite::semantic_analyzer::{SemanticAnalyzer, SemanticError};
-    use rust_sqlite::ast::{ASTNode, ComparisonOperator, Condition, OrderByClause, OrderDirection, SelectStatement, Value};
-    use rust_sqlite::schema::{DatabaseSchema, TableSchema, ColumnType};
+    use rust_sqlite::ast::{ASTNode, ComparisonOperator, Condition, InsertSource, InsertStatement, OrderByClause, OrderDirection, SelectStatement, Value};
+    use rust_sqlite::schema::{DatabaseSchema, TableSchema, ColumnType, ColumnConstraint};
     use std::collections::HashMap;
 
     fn setup_test_schema() -> DatabaseSchema {
@@ -18,8 +17,8 @@ mod tests {
         product_columns.insert("price".to_string(), ColumnType::Float);
 
         let mut tables = HashMap::new();
-        tables.insert("users".to_string(), TableSchema { columns: user_columns });
-        tables.insert("products".to_string(), TableSchema { columns: product_columns });
+        tables.insert("users".to_string(), TableSchema { columns: user_columns, ..Default::default() });
+        tables.insert("products".to_string(), TableSchema { columns: product_columns, ..Default::default() });
 
         DatabaseSchema { tables }
     }
@@ -112,4 +111,21 @@ mod tests {
         let result = analyzer.analyze(&ASTNode::Select(select_stmt));
         assert!(matches!(result, Err(SemanticError::ColumnNotFound(_))));
     }
+
+    #[test]
+    fn test_insert_missing_not_null_column() {
+        let mut schema = setup_test_schema();
+        schema.tables.get_mut("users").unwrap().column_constraints
+            .insert("name".to_string(), vec![ColumnConstraint::NotNull]);
+        let analyzer = SemanticAnalyzer::new(schema);
+
+        let insert_stmt = InsertStatement {
+            table: "users".to_string(),
+            columns: vec!["id".to_string(), "age".to_string()],
+            source: InsertSource::Values(vec![vec![Value::Number(1.0), Value::Number(30.0)]])
+        };
+
+        let result = analyzer.analyze(&ASTNode::Insert(insert_stmt));
+        assert!(matches!(result, Err(SemanticError::NotNullViolation(_))));
+    }
 }
@@ -0,0 +1,148 @@
+use rust_sqlite::ast::Value;
+use rust_sqlite::datastore::DataStore;
+use rust_sqlite::execution_engine::{ExecutionEngine, ExecutionError, Row};
+use rust_sqlite::lexer::Lexer;
+use rust_sqlite::parser::Parser;
+
+mod common;
+use common::{execute_sql, select_rows, setup_test_engine};
+
+fn blob(bytes: &[u8]) -> Value {
+    Value::Blob(bytes.to_vec())
+}
+
+/// A table of files whose hashes hold bytes that mean something in CSV:
+/// a newline, a comma, a quote and a zero byte.
+fn setup_files(engine: &mut ExecutionEngine) {
+    execute_sql(engine, "CREATE TABLE files (id INTEGER PRIMARY KEY, name TEXT, hash BLOB)").unwrap();
+    execute_sql(engine, "INSERT INTO files VALUES (1, 'a.png', X'0A2C22'), (2, 'b.png', x'00ff'), (3, 'c.png', X''), (4, 'd.png', NULL)").unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_sqlite::blob::{call, from_hex, to_hex};
+    use rust_sqlite::csv_io::{export_csv, import_csv, CsvOptions};
+    use rust_sqlite::evaluator::compare_values;
+    use std::cmp::Ordering;
+    use rust_sqlite::semantic_analyzer::SemanticError;
+
+    fn text(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    #[test]
+    fn test_functions() {
+        assert_eq!(call("hex", &[blob(&[0, 171, 255])]), text("00ABFF"));
+        assert_eq!(call("hex", &[text("hi")]), text("6869"));
+        assert_eq!(call("hex", &[Value::Number(12.0)]), text("3132"));
+        assert_eq!(call("hex", &[Value::Null]), text(""));
+        assert_eq!(call("unhex", &[text("00abFF")]), blob(&[0, 171, 255]));
+        assert_eq!(call("unhex", &[text("00-AB FF"), text(" -")]), blob(&[0, 171, 255]));
+        assert_eq!(call("unhex", &[text("0-0"), text("-")]), Value::Null);
+        assert_eq!(call("unhex", &[text("ABC")]), Value::Null);
+        assert_eq!(call("unhex", &[text("zz")]), Value::Null);
+        assert_eq!(call("length", &[blob(&[1, 2, 3])]), Value::Number(3.0));
+        assert_eq!(call("length", &[text("héllo")]), Value::Number(5.0));
+        assert_eq!(call("length", &[Value::Null]), Value::Null);
+        assert_eq!(call("length", &[]), Value::Null);
+
+        assert_eq!(to_hex(&[10, 44, 34]), "0A2C22");
+        assert_eq!(from_hex("0a2c22", ""), Some(vec![10, 44, 34]));
+    }
+
+    #[test]
+    fn test_blob_columns() {
+        let (mut engine, temp_dir) = setup_test_engine();
+        setup_files(&mut engine);
+
+        let hashes = vec![
+            Row { values: vec![blob(&[10, 44, 34])] },
+            Row { values: vec![blob(&[0, 255])] },
+            Row { values: vec![blob(&[])] },
+            Row { values: vec![Value::Null] }
+        ];
+        assert_eq!(select_rows(&mut engine, "SELECT hash FROM files ORDER BY id"), hashes);
+
+        // The bytes come back the same from storage.
+        let mut engine = ExecutionEngine::new(DataStore::new(temp_dir.path()).unwrap());
+        assert_eq!(select_rows(&mut engine, "SELECT hash FROM files ORDER BY id"), hashes);
+
+        assert_eq!(select_rows(&mut engine, "SELECT name FROM files WHERE hash = X'00FF'"), vec![Row { values: vec![text("b.png")] }]);
+        assert_eq!(select_rows(&mut engine, "SELECT name FROM files WHERE hex(hash) = '0A2C22'"), vec![Row { values: vec![text("a.png")] }]);
+        assert_eq!(select_rows(&mut engine, "SELECT name FROM files WHERE hash = unhex('00ff')"), vec![Row { values: vec![text("b.png")] }]);
+        assert_eq!(select_rows(&mut engine, "SELECT name FROM files WHERE length(hash) < 3 ORDER BY hash"), vec![
+            Row { values: vec![text("c.png")] },
+            Row { values: vec![text("b.png")] }
+        ]);
+
+        // Text written to a BLOB column is stored as its bytes.
+        execute_sql(&mut engine, "UPDATE files SET hash = 'ok' WHERE id = 4").unwrap();
+        assert_eq!(select_rows(&mut engine, "SELECT hash FROM files WHERE id = 4"), vec![Row { values: vec![blob(b"ok")] }]);
+
+        assert!(matches!(execute_sql(&mut engine, "UPDATE files SET name = X'00'"), Err(ExecutionError::TypeMismatch(col)) if col == "name"));
+        assert!(matches!(execute_sql(&mut engine, "UPDATE files SET hash = 7"), Err(ExecutionError::TypeMismatch(col)) if col == "hash"));
+        assert!(Parser::new(Lexer::new("SELECT * FROM files WHERE hash = X'ABC'")).parse().is_err());
+        assert_eq!(select_rows(&mut engine, "SELECT sql FROM sqlite_schema WHERE name = 'files'"), vec![
+            Row { values: vec![text("CREATE TABLE files (id INTEGER PRIMARY KEY, name TEXT, hash BLOB)")] }
+        ]);
+    }
+
+    #[test]
+    fn test_blobs_order_by_bytes_after_text() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE things (thing BLOB)").unwrap();
+        execute_sql(&mut engine, "CREATE TABLE words (word TEXT)").unwrap();
+        execute_sql(&mut engine, "INSERT INTO things VALUES (X'02'), (X'0100'), (X'01')").unwrap();
+
+        assert_eq!(select_rows(&mut engine, "SELECT thing FROM things ORDER BY thing"), vec![
+            Row { values: vec![blob(&[1])] },
+            Row { values: vec![blob(&[1, 0])] },
+            Row { values: vec![blob(&[2])] }
+        ]);
+        assert_eq!(select_rows(&mut engine, "SELECT thing FROM things WHERE thing > X'01' ORDER BY thing DESC"), vec![
+            Row { values: vec![blob(&[2])] },
+            Row { values: vec![blob(&[1, 0])] }
+        ]);
+        assert_eq!(compare_values(&text("zzz"), &blob(&[0])), Some(Ordering::Less));
+        assert_eq!(compare_values(&blob(&[]), &Value::Number(1e9)), Some(Ordering::Greater));
+
+        // Blobs are not copied into other columns as text.
+        assert!(matches!(execute_sql(&mut engine, "INSERT INTO words SELECT thing FROM things"), Err(ExecutionError::TypeMismatch(col)) if col == "word"));
+    }
+
+    #[test]
+    fn test_prepared_and_strict_blobs() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE thumbs (id INTEGER, image BLOB NOT NULL) STRICT").unwrap();
+
+        let mut insert = engine.prepare("INSERT INTO thumbs VALUES (?, ?)").unwrap();
+        insert.bind(1, 1).unwrap();
+        insert.bind(2, &[137u8, 80, 78, 71][..]).unwrap();
+        insert.execute(&mut engine).unwrap();
+        assert!(matches!(insert.bind(2, "PNG"), Err(ExecutionError::Semantic(SemanticError::TypeMismatch(_)))));
+        assert!(matches!(execute_sql(&mut engine, "INSERT INTO thumbs VALUES (2, 'PNG')"), Err(ExecutionError::TypeMismatch(col)) if col == "image"));
+
+        assert_eq!(select_rows(&mut engine, "SELECT image FROM thumbs"), vec![Row { values: vec![blob(&[137, 80, 78, 71])] }]);
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        setup_files(&mut engine);
+
+        let ast = Parser::new(Lexer::new("SELECT id, name, hash FROM files ORDER BY id")).parse().unwrap();
+        let mut output = Vec::new();
+        export_csv(&mut engine, &ast, &mut output, &CsvOptions::default()).unwrap();
+        let exported = String::from_utf8(output).unwrap();
+        assert_eq!(exported, "id,name,hash\n1,a.png,0A2C22\n2,b.png,00FF\n3,c.png,\n4,d.png,\n");
+
+        execute_sql(&mut engine, "CREATE TABLE copies (id INTEGER PRIMARY KEY, name TEXT, hash BLOB)").unwrap();
+        let report = import_csv(&mut engine, exported.as_bytes(), "copies", &CsvOptions::default()).unwrap();
+        assert_eq!(report.rejected.len(), 0);
+        assert_eq!(select_rows(&mut engine, "SELECT hash FROM copies WHERE id < 3 ORDER BY id"), vec![
+            Row { values: vec![blob(&[10, 44, 34])] },
+            Row { values: vec![blob(&[0, 255])] }
+        ]);
+    }
+}
@@ -0,0 +1,100 @@
+use rust_sqlite::ast::Value;
+use rust_sqlite::execution_engine::{ExecutionEngine, ExecutionError, QueryResult, Row};
+
+mod common;
+use common::{execute_sql, select_rows, setup_test_engine};
+
+/// The ids `sql` selects, in order.
+fn ids(engine: &mut ExecutionEngine, sql: &str) -> Vec<f64> {
+    select_rows(engine, sql).into_iter().map(|row| match row.values[0] {
+        Value::Number(n) => n,
+        ref other => panic!("expected an id, got {other:?}")
+    }).collect()
+}
+
+/// A table of users where 1 and 3 are active, 2 and 4 are not and 5 is not
+/// known to be either.
+fn setup_users(engine: &mut ExecutionEngine) {
+    execute_sql(engine, "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, active BOOLEAN DEFAULT TRUE)").unwrap();
+    execute_sql(engine, "INSERT INTO users (id, name) VALUES (1, 'Alice')").unwrap();
+    execute_sql(engine, "INSERT INTO users VALUES (2, 'Bob', FALSE), (3, 'Carol', 1), (4, 'Dave', 'false'), (5, 'Eve', NULL)").unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_sqlite::semantic_analyzer::SemanticError;
+
+    #[test]
+    fn test_boolean_columns_store_one_and_zero() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        setup_users(&mut engine);
+
+        assert_eq!(select_rows(&mut engine, "SELECT active FROM users ORDER BY id"), vec![
+            Row { values: vec![Value::Boolean(true)] },
+            Row { values: vec![Value::Boolean(false)] },
+            Row { values: vec![Value::Boolean(true)] },
+            Row { values: vec![Value::Boolean(false)] },
+            Row { values: vec![Value::Null] }
+        ]);
+
+        // Copied into a TEXT column, they show the stored form.
+        execute_sql(&mut engine, "CREATE TABLE flags (flag TEXT)").unwrap();
+        execute_sql(&mut engine, "INSERT INTO flags SELECT active FROM users WHERE id < 3").unwrap();
+        execute_sql(&mut engine, "INSERT INTO flags VALUES (TRUE)").unwrap();
+        assert_eq!(select_rows(&mut engine, "SELECT flag FROM flags"), vec![
+            Row { values: vec![Value::String("1".to_string())] },
+            Row { values: vec![Value::String("0".to_string())] },
+            Row { values: vec![Value::String("1".to_string())] }
+        ]);
+
+        assert!(matches!(execute_sql(&mut engine, "INSERT INTO users VALUES (6, 'Frank', 'yes')"), Err(ExecutionError::TypeMismatch(col)) if col == "active"));
+        assert_eq!(select_rows(&mut engine, "SELECT sql FROM sqlite_schema WHERE name = 'users'"), vec![
+            Row { values: vec![Value::String("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, active BOOLEAN DEFAULT TRUE)".to_string())] }
+        ]);
+    }
+
+    #[test]
+    fn test_boolean_predicates() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        setup_users(&mut engine);
+
+        assert_eq!(ids(&mut engine, "SELECT id FROM users WHERE active ORDER BY id"), vec![1.0, 3.0]);
+        assert_eq!(ids(&mut engine, "SELECT id FROM users WHERE NOT active ORDER BY id"), vec![2.0, 4.0]);
+        assert_eq!(ids(&mut engine, "SELECT id FROM users WHERE active IS TRUE ORDER BY id"), vec![1.0, 3.0]);
+        assert_eq!(ids(&mut engine, "SELECT id FROM users WHERE active IS FALSE ORDER BY id"), vec![2.0, 4.0]);
+        assert_eq!(ids(&mut engine, "SELECT id FROM users WHERE active IS NOT TRUE ORDER BY id"), vec![2.0, 4.0, 5.0]);
+        assert_eq!(ids(&mut engine, "SELECT id FROM users WHERE active IS NOT FALSE ORDER BY id"), vec![1.0, 3.0, 5.0]);
+        assert_eq!(ids(&mut engine, "SELECT id FROM users WHERE active = TRUE AND name > 'B' ORDER BY id"), vec![3.0]);
+        assert_eq!(ids(&mut engine, "SELECT id FROM users WHERE active = 0 OR id = 5 ORDER BY id"), vec![2.0, 4.0, 5.0]);
+        assert_eq!(ids(&mut engine, "SELECT id FROM users WHERE TRUE ORDER BY active DESC, id"), vec![1.0, 3.0, 2.0, 4.0, 5.0]);
+
+        // Any expression can be a predicate: numbers other than zero are
+        // true, and text that is not a number is false.
+        assert_eq!(ids(&mut engine, "SELECT id FROM users WHERE id - 1 ORDER BY id"), vec![2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(ids(&mut engine, "SELECT id FROM users WHERE name"), Vec::<f64>::new());
+
+        execute_sql(&mut engine, "UPDATE users SET active = TRUE WHERE active IS NULL").unwrap();
+        execute_sql(&mut engine, "DELETE FROM users WHERE NOT active").unwrap();
+        assert_eq!(ids(&mut engine, "SELECT id FROM users ORDER BY id"), vec![1.0, 3.0, 5.0]);
+    }
+
+    #[test]
+    fn test_strict_and_prepared_booleans() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE settings (name TEXT, enabled BOOLEAN CHECK (enabled IS NOT NULL), level INTEGER) STRICT").unwrap();
+        execute_sql(&mut engine, "INSERT INTO settings VALUES ('a', TRUE, FALSE), ('b', 0, 2)").unwrap();
+
+        assert!(matches!(execute_sql(&mut engine, "INSERT INTO settings VALUES ('c', 2, 1)"), Err(ExecutionError::TypeMismatch(col)) if col == "enabled"));
+        assert!(matches!(execute_sql(&mut engine, "INSERT INTO settings VALUES (TRUE, TRUE, 1)"), Err(ExecutionError::TypeMismatch(col)) if col == "name"));
+        assert_eq!(select_rows(&mut engine, "SELECT level FROM settings WHERE enabled"), vec![Row { values: vec![Value::Number(0.0)] }]);
+
+        let mut select = engine.prepare("SELECT name FROM settings WHERE enabled = ?").unwrap();
+        select.bind(1, false).unwrap();
+        assert!(matches!(select.execute(&mut engine).unwrap(), QueryResult::Select { rows, .. } if rows == vec![Row { values: vec![Value::String("b".to_string())] }]));
+        assert!(matches!(engine.prepare("SELECT name FROM settings WHERE enabled = 'maybe'"), Err(ExecutionError::Semantic(SemanticError::TypeMismatch(_)))));
+
+        execute_sql(&mut engine, "PRAGMA foreign_keys = FALSE").unwrap();
+        assert_eq!(select_rows(&mut engine, "PRAGMA foreign_keys"), vec![Row { values: vec![Value::Number(0.0)] }]);
+    }
+}
@@ -0,0 +1,74 @@
+use rust_sqlite::execution_engine::{ExecutionError, QueryResult, Row};
+
+mod common;
+use common::{execute_sql, select_rows, setup_test_engine};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_sqlite::ast::Value;
+
+    fn text(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    #[test]
+    fn test_schema_table_lists_tables_and_indexes() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE parents (code TEXT PRIMARY KEY, label TEXT NOT NULL DEFAULT 'it''s')").unwrap();
+        execute_sql(&mut engine, "CREATE TABLE kids (id INTEGER PRIMARY KEY AUTOINCREMENT, parent TEXT REFERENCES parents (code) ON DELETE CASCADE, \
+            age INTEGER CHECK (age >= 0 AND (age < 200 OR age IS NULL)), score REAL DEFAULT (1 + 2), FOREIGN KEY (parent) REFERENCES parents (code))").unwrap();
+
+        assert_eq!(select_rows(&mut engine, "SELECT type, name, tbl_name FROM sqlite_schema ORDER BY name"), vec![
+            Row { values: vec![text("table"), text("kids"), text("kids")] },
+            Row { values: vec![text("table"), text("parents"), text("parents")] },
+            Row { values: vec![text("index"), text("sqlite_autoindex_parents_1"), text("parents")] }
+        ]);
+        assert_eq!(select_rows(&mut engine, "SELECT sql FROM sqlite_master WHERE name = 'parents'"), vec![
+            Row { values: vec![text("CREATE TABLE parents (code TEXT PRIMARY KEY, label TEXT NOT NULL DEFAULT 'it''s')")] }
+        ]);
+
+        // The rebuilt statements make the same tables.
+        let (mut copy, _copy_dir) = setup_test_engine();
+        for row in select_rows(&mut engine, "SELECT sql FROM sqlite_schema WHERE type = 'table' ORDER BY name DESC") {
+            let Value::String(sql) = &row.values[0] else { panic!("expected SQL text") };
+            execute_sql(&mut copy, sql).unwrap();
+        }
+        assert_eq!(
+            select_rows(&mut copy, "SELECT * FROM sqlite_schema ORDER BY name"),
+            select_rows(&mut engine, "SELECT * FROM sqlite_schema ORDER BY name")
+        );
+    }
+
+    #[test]
+    fn test_schema_table_is_read_only() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE users (id INTEGER)").unwrap();
+
+        assert!(matches!(execute_sql(&mut engine, "INSERT INTO sqlite_schema VALUES ('table', 'x', 'x', 0, NULL)"), Err(ExecutionError::ReadOnlyTable(_))));
+        assert!(matches!(execute_sql(&mut engine, "DELETE FROM sqlite_schema"), Err(ExecutionError::ReadOnlyTable(_))));
+        assert!(matches!(execute_sql(&mut engine, "CREATE TABLE sqlite_master (id INTEGER)"), Err(ExecutionError::TableAlreadyExists(_))));
+        assert_eq!(select_rows(&mut engine, "SELECT name FROM sqlite_schema"), vec![Row { values: vec![text("users")] }]);
+    }
+
+    #[test]
+    fn test_table_info_and_index_list() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE orders (region TEXT, num INTEGER NOT NULL, total REAL DEFAULT 0, PRIMARY KEY (region, num))").unwrap();
+
+        let result = execute_sql(&mut engine, "PRAGMA table_info(orders)").unwrap();
+        assert_eq!(result, QueryResult::Select {
+            columns: ["cid", "name", "type", "notnull", "dflt_value", "pk"].iter().map(|c| c.to_string()).collect(),
+            rows: vec![
+                Row { values: vec![Value::Number(0.0), text("region"), text("TEXT"), Value::Number(0.0), Value::Null, Value::Number(1.0)] },
+                Row { values: vec![Value::Number(1.0), text("num"), text("INTEGER"), Value::Number(1.0), Value::Null, Value::Number(2.0)] },
+                Row { values: vec![Value::Number(2.0), text("total"), text("REAL"), Value::Number(0.0), text("0"), Value::Number(0.0)] }
+            ]
+        });
+
+        assert_eq!(select_rows(&mut engine, "PRAGMA index_list(orders)"), vec![
+            Row { values: vec![Value::Number(0.0), text("sqlite_autoindex_orders_1"), Value::Number(1.0), text("pk"), Value::Number(0.0)] }
+        ]);
+        assert!(matches!(execute_sql(&mut engine, "PRAGMA table_info(missing)"), Err(ExecutionError::TableNotFound(_))));
+    }
+}
@@ -0,0 +1,28 @@
+// Each test file that uses these compiles its own copy, and not every file
+// needs all of them.
+#![allow(dead_code)]
+
+use tempfile::TempDir;
+
+use rust_sqlite::datastore::DataStore;
+use rust_sqlite::execution_engine::{ExecutionEngine, ExecutionError, QueryResult, Row};
+use rust_sqlite::lexer::Lexer;
+use rust_sqlite::parser::Parser;
+
+pub fn setup_test_engine() -> (ExecutionEngine, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let data_store = DataStore::new(temp_dir.path()).unwrap();
+    (ExecutionEngine::new(data_store), temp_dir)
+}
+
+pub fn execute_sql(engine: &mut ExecutionEngine, sql: &str) -> Result<QueryResult, ExecutionError> {
+    let ast = Parser::new(Lexer::new(sql)).parse().unwrap();
+    engine.execute(&ast)
+}
+
+pub fn select_rows(engine: &mut ExecutionEngine, sql: &str) -> Vec<Row> {
+    match execute_sql(engine, sql).unwrap() {
+        QueryResult::Select { rows, .. } => rows,
+        other => panic!("expected rows, got {other:?}")
+    }
+}
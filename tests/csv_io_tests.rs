@@ -0,0 +1,78 @@
+use rust_sqlite::execution_engine::{ExecutionEngine, ExecutionError, QueryResult};
+use rust_sqlite::lexer::Lexer;
+use rust_sqlite::parser::Parser;
+
+mod common;
+use common::{execute_sql, setup_test_engine};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_sqlite::ast::Value;
+    use rust_sqlite::csv_io::{export_csv, import_csv, CsvOptions};
+
+    fn select_values(engine: &mut ExecutionEngine, sql: &str) -> Vec<Vec<Value>> {
+        match execute_sql(engine, sql).unwrap() {
+            QueryResult::Select { rows, .. } => rows.into_iter().map(|row| row.values).collect(),
+            other => panic!("Expected rows, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_import_maps_header_and_converts_types() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE people (id INTEGER PRIMARY KEY, name TEXT NOT NULL, score FLOAT)").unwrap();
+
+        let input = "Score;NAME;id\n1.5;Alice;1\nhigh;Bob;2\n;Carol;3\n2;Dave;1\n4;Erin\n";
+        let options = CsvOptions { delimiter: b';', ..Default::default() };
+        let report = import_csv(&mut engine, input.as_bytes(), "people", &options).unwrap();
+
+        assert_eq!(report.imported, 2);
+        let rejected: Vec<_> = report.rejected.iter().map(|rejected| rejected.line).collect();
+        assert_eq!(rejected, vec![3, 5, 6]);
+        assert!(matches!(report.rejected[0].error, ExecutionError::TypeMismatch(_)));
+        assert!(matches!(report.rejected[2].error, ExecutionError::ValueCountMismatch(_)));
+
+        assert_eq!(select_values(&mut engine, "SELECT id, name, score FROM people ORDER BY id"), vec![
+            vec![Value::Number(1.0), Value::String("Alice".to_string()), Value::Number(1.5)],
+            vec![Value::Number(3.0), Value::String("Carol".to_string()), Value::Null]
+        ]);
+    }
+
+    #[test]
+    fn test_import_creates_missing_table_and_rejects_unknown_columns() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+
+        let report = import_csv(&mut engine, "city,country\nOslo,Norway\n".as_bytes(), "cities", &CsvOptions::default()).unwrap();
+        assert_eq!(report.imported, 1);
+        assert_eq!(select_values(&mut engine, "SELECT * FROM cities"), vec![
+            vec![Value::String("Oslo".to_string()), Value::String("Norway".to_string())]
+        ]);
+
+        let error = import_csv(&mut engine, "city,population\nBergen,1\n".as_bytes(), "cities", &CsvOptions::default()).unwrap_err();
+        assert!(matches!(error, ExecutionError::ColumnNotFound(column) if column == "population"));
+
+        let options = CsvOptions { has_header: false, ..Default::default() };
+        let report = import_csv(&mut engine, "Bergen,Norway\n".as_bytes(), "cities", &options).unwrap();
+        assert_eq!(report.imported, 1);
+        assert!(import_csv(&mut engine, "a,b\n".as_bytes(), "missing", &options).is_err());
+    }
+
+    #[test]
+    fn test_export_writes_query_result() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE people (id INTEGER, name TEXT)").unwrap();
+        execute_sql(&mut engine, "INSERT INTO people VALUES (1, 'Alice, Jr.'), (2, NULL), (3, 'Carol')").unwrap();
+
+        let ast = Parser::new(Lexer::new("SELECT name, id FROM people WHERE id < 3")).parse().unwrap();
+        let mut output = Vec::new();
+        let written = export_csv(&mut engine, &ast, &mut output, &CsvOptions::default()).unwrap();
+        assert_eq!(written, 2);
+        assert_eq!(String::from_utf8(output).unwrap(), "name,id\n\"Alice, Jr.\",1\n,2\n");
+
+        let mut output = Vec::new();
+        let options = CsvOptions { delimiter: b'\t', has_header: false };
+        export_csv(&mut engine, &ast, &mut output, &options).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "Alice, Jr.\t1\n\t2\n");
+    }
+}
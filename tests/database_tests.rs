@@ -0,0 +1,101 @@
+use tempfile::TempDir;
+
+use rust_sqlite::ast::ASTNode;
+use rust_sqlite::database::Database;
+use rust_sqlite::execution_engine::QueryResult;
+use rust_sqlite::lexer::Lexer;
+use rust_sqlite::parser::Parser;
+
+fn setup_test_database() -> (Database, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let database = Database::open(temp_dir.path()).unwrap();
+    (database, temp_dir)
+}
+
+fn parse(sql: &str) -> ASTNode {
+    Parser::new(Lexer::new(sql)).parse().unwrap()
+}
+
+fn row_count(database: &Database, sql: &str) -> usize {
+    match database.execute(&parse(sql)).unwrap() {
+        QueryResult::Select { rows, .. } => rows.len(),
+        result => panic!("expected rows, got {result:?}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_database_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync + Clone>() {}
+        assert_send_sync::<Database>();
+    }
+
+    #[test]
+    fn test_readers_do_not_wait_for_an_open_transaction() {
+        for journal_mode in ["DELETE", "WAL"] {
+            let (database, _temp_dir) = setup_test_database();
+            database.execute(&parse(&format!("PRAGMA journal_mode = {journal_mode}"))).unwrap();
+            database.execute(&parse("CREATE TABLE users (name TEXT)")).unwrap();
+            database.execute(&parse("INSERT INTO users VALUES ('Alice')")).unwrap();
+
+            database.transaction(|engine| {
+                engine.execute(&parse("INSERT INTO users VALUES ('Bob')"))?;
+                let reader = database.clone();
+                let seen = thread::spawn(move || row_count(&reader, "SELECT name FROM users")).join().unwrap();
+                assert_eq!(seen, 1, "{journal_mode}");
+                Ok(())
+            }).unwrap();
+
+            assert_eq!(row_count(&database, "SELECT name FROM users"), 2, "{journal_mode}");
+        }
+    }
+
+    #[test]
+    fn test_readers_never_see_part_of_a_transaction() {
+        let (database, _temp_dir) = setup_test_database();
+        database.execute(&parse("CREATE TABLE items (n INTEGER)")).unwrap();
+
+        let writer = database.clone();
+        let writing = thread::spawn(move || {
+            for i in 0..20 {
+                writer.transaction(|engine| {
+                    engine.execute(&parse(&format!("INSERT INTO items VALUES ({i})")))?;
+                    engine.execute(&parse(&format!("INSERT INTO items VALUES ({i})")))
+                }).unwrap();
+            }
+        });
+
+        let readers: Vec<_> = (0..4).map(|_| {
+            let reader = database.clone();
+            thread::spawn(move || {
+                for _ in 0..20 {
+                    assert_eq!(row_count(&reader, "SELECT n FROM items") % 2, 0);
+                }
+            })
+        }).collect();
+
+        writing.join().unwrap();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(row_count(&database, "SELECT n FROM items"), 40);
+    }
+
+    #[test]
+    fn test_transaction_rolls_back_on_error() {
+        let (database, _temp_dir) = setup_test_database();
+        database.execute(&parse("CREATE TABLE users (name TEXT)")).unwrap();
+
+        let result = database.transaction(|engine| {
+            engine.execute(&parse("INSERT INTO users VALUES ('Alice')"))?;
+            engine.execute(&parse("INSERT INTO missing VALUES ('Bob')"))
+        });
+        assert!(result.is_err());
+        assert_eq!(row_count(&database, "SELECT name FROM users"), 0);
+        assert!(database.execute(&parse("BEGIN")).is_err());
+    }
+}
@@ -1,5 +1,4 @@
 use tempfile::TempDir; 
-use std::path::Path; 
 
 use rust_sqlite::datastore::DataStore;
 
@@ -11,10 +10,15 @@ fn setup_test_datastore() -> (DataStore, TempDir) {
 
 #[cfg(test)]
 mod tests {
-    use rust_sqlite::schema::{self, ColumnType, TableSchema};
+    use rust_sqlite::ast::{ComparisonOperator, Condition, Expression, TransactionMode, Value};
+    use rust_sqlite::lock::LockMode;
+    use rust_sqlite::schema::{ColumnConstraint, ColumnType, TableSchema};
 
     use super::*;
-    use std::{collections::HashMap, hash::Hash};
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
+    use std::process::{Command, Stdio};
+    use std::time::{Duration, Instant};
 
     #[test]
     fn test_create_table() {
@@ -23,7 +27,7 @@ mod tests {
         columns.insert("id".to_string(), ColumnType::Integer);
         columns.insert("name".to_string(), ColumnType::String);
         
-        let schema = TableSchema { columns };
+        let schema = TableSchema { columns, ..Default::default() };
         assert!(data_store.create_table("users".to_string(), schema.clone()).is_ok());
 
         let result = data_store.create_table("users".to_string(), schema);
@@ -37,7 +41,7 @@ mod tests {
         columns.insert("id".to_string(), ColumnType::Integer);
         columns.insert("name".to_string(), ColumnType::String);
 
-        let schema = TableSchema { columns };
+        let schema = TableSchema { columns, ..Default::default() };
         data_store.create_table("users".to_string(), schema).unwrap();
 
         let mut row = HashMap::new();
@@ -59,7 +63,7 @@ mod tests {
         columns.insert("id".to_string(), ColumnType::Integer);
         columns.insert("name".to_string(), ColumnType::String);
         
-        let schema = TableSchema { columns };
+        let schema = TableSchema { columns, ..Default::default() };
         data_store.create_table("users".to_string(), schema).unwrap();
     
         let mut row = HashMap::new();
@@ -102,4 +106,231 @@ mod tests {
             }
         }
     }
-}
\ No newline at end of file
+
+    fn setup_constrained_table(data_store: &mut DataStore) {
+        let mut columns = HashMap::new();
+        columns.insert("id".to_string(), ColumnType::Integer);
+        columns.insert("name".to_string(), ColumnType::String);
+        columns.insert("age".to_string(), ColumnType::Integer);
+
+        let mut column_constraints = HashMap::new();
+        column_constraints.insert("name".to_string(), vec![ColumnConstraint::NotNull]);
+        column_constraints.insert("age".to_string(), vec![
+            ColumnConstraint::Default(Expression::Literal(Value::Number(18.0))),
+            ColumnConstraint::Check(Condition::Comparison("age".to_string(), ComparisonOperator::GreaterEqualThan, Value::Number(0.0)))
+        ]);
+
+        let schema = TableSchema { columns, column_constraints, ..Default::default() };
+        data_store.create_table("users".to_string(), schema).unwrap();
+    }
+
+    #[test]
+    fn test_insert_fills_defaults_and_nulls() {
+        let (mut data_store, _temp_dir) = setup_test_datastore();
+        setup_constrained_table(&mut data_store);
+
+        let mut row = HashMap::new();
+        row.insert("name".to_string(), "Alice".to_string());
+        data_store.insert_row("users", row).unwrap();
+
+        let result = data_store.select("users", &["id".to_string(), "name".to_string(), "age".to_string()], None).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].get("id"), None);
+        assert_eq!(result[0].get("age"), Some(&"18".to_string()));
+    }
+
+    #[test]
+    fn test_not_null_and_check_are_enforced() {
+        let (mut data_store, _temp_dir) = setup_test_datastore();
+        setup_constrained_table(&mut data_store);
+
+        let mut missing_name = HashMap::new();
+        missing_name.insert("id".to_string(), "1".to_string());
+        assert!(data_store.insert_row("users", missing_name).is_err());
+
+        let mut negative_age = HashMap::new();
+        negative_age.insert("name".to_string(), "Bob".to_string());
+        negative_age.insert("age".to_string(), "-4".to_string());
+        assert!(data_store.insert_row("users", negative_age).is_err());
+
+        let mut row = HashMap::new();
+        row.insert("name".to_string(), "Carol".to_string());
+        data_store.insert_row("users", row).unwrap();
+
+        let mut updates = HashMap::new();
+        updates.insert("age".to_string(), "-1".to_string());
+        assert!(data_store.update("users", updates, |_| true).is_err());
+
+        let result = data_store.select("users", &["name".to_string(), "age".to_string()], None).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].get("age"), Some(&"18".to_string()));
+    }
+
+    #[test]
+    fn test_rowids_added_to_existing_tables() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("schemas.json"), r#"{"users":{"columns":{"id":"Integer","name":"String"}}}"#).unwrap();
+        std::fs::write(temp_dir.path().join("users.csv"), "id,name\n7,Alice\n8,Bob\n").unwrap();
+
+        let mut data_store = DataStore::new(temp_dir.path()).unwrap();
+        let result = data_store.select("users", &["rowid".to_string(), "id".to_string()], None).unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[1].get("rowid"), Some(&"2".to_string()));
+        assert_eq!(result[1].get("id"), Some(&"8".to_string()));
+
+        let mut row = HashMap::new();
+        row.insert("id".to_string(), "9".to_string());
+        row.insert("name".to_string(), "Carol".to_string());
+        data_store.insert_row("users", row).unwrap();
+        assert_eq!(data_store.last_insert_rowid(), 3);
+    }
+
+    #[test]
+    fn test_committed_journal_is_applied_on_open() {
+        let temp_dir = TempDir::new().unwrap();
+        {
+            let mut data_store = DataStore::new(temp_dir.path()).unwrap();
+            let mut columns = HashMap::new();
+            columns.insert("name".to_string(), ColumnType::String);
+            data_store.create_table("users".to_string(), TableSchema { columns, ..Default::default() }).unwrap();
+        }
+
+        // A crash after the commit marker was written but before the staged
+        // table was moved into place.
+        let journal = temp_dir.path().join("journal");
+        std::fs::create_dir(&journal).unwrap();
+        std::fs::write(journal.join("users.csv"), "_rowid_,name\n1,Alice\n").unwrap();
+        std::fs::write(journal.join("COMMIT"), "commit").unwrap();
+
+        let data_store = DataStore::new(temp_dir.path()).unwrap();
+        let result = data_store.select("users", &["name".to_string()], None).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].get("name"), Some(&"Alice".to_string()));
+        assert!(!journal.exists());
+    }
+
+    #[test]
+    fn test_half_written_state_is_repaired_on_open() {
+        let temp_dir = TempDir::new().unwrap();
+        {
+            let mut data_store = DataStore::new(temp_dir.path()).unwrap();
+            let mut columns = HashMap::new();
+            columns.insert("name".to_string(), ColumnType::String);
+            data_store.create_table("users".to_string(), TableSchema { columns, ..Default::default() }).unwrap();
+            let mut row = HashMap::new();
+            row.insert("name".to_string(), "Alice".to_string());
+            data_store.insert_row("users", row).unwrap();
+        }
+
+        // A crash part way through appending a row and through rewriting a table.
+        let data_file = temp_dir.path().join("users.csv");
+        let mut contents = std::fs::read_to_string(&data_file).unwrap();
+        contents.push_str("2,Bo");
+        std::fs::write(&data_file, contents).unwrap();
+        std::fs::write(temp_dir.path().join(".pending-abc123"), "_rowid_,na").unwrap();
+
+        let data_store = DataStore::new(temp_dir.path()).unwrap();
+        let result = data_store.select("users", &["name".to_string()], None).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].get("name"), Some(&"Alice".to_string()));
+        assert!(!temp_dir.path().join(".pending-abc123").exists());
+        assert!(std::fs::read_to_string(&data_file).unwrap().ends_with('\n'));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_rewritten_files_keep_their_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (mut data_store, temp_dir) = setup_test_datastore();
+        let mut columns = HashMap::new();
+        columns.insert("name".to_string(), ColumnType::String);
+        data_store.create_table("users".to_string(), TableSchema { columns, ..Default::default() }).unwrap();
+        let data_file = temp_dir.path().join("users.csv");
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&data_file), 0o644);
+        assert_eq!(mode(&temp_dir.path().join("schemas.json")), 0o644);
+
+        std::fs::set_permissions(&data_file, std::fs::Permissions::from_mode(0o640)).unwrap();
+        data_store.insert_row("users", HashMap::from([("name".to_string(), "Alice".to_string())])).unwrap();
+        assert_eq!(mode(&data_file), 0o640);
+    }
+
+    #[test]
+    fn test_missing_data_file_is_refused() {
+        let temp_dir = TempDir::new().unwrap();
+        {
+            let mut data_store = DataStore::new(temp_dir.path()).unwrap();
+            let mut columns = HashMap::new();
+            columns.insert("name".to_string(), ColumnType::String);
+            data_store.create_table("users".to_string(), TableSchema { columns, ..Default::default() }).unwrap();
+        }
+        std::fs::remove_file(temp_dir.path().join("users.csv")).unwrap();
+
+        let error = DataStore::new(temp_dir.path()).err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    fn wait_for(path: &Path) {
+        let deadline = Instant::now() + Duration::from_secs(30);
+        while !path.exists() {
+            assert!(Instant::now() < deadline, "timed out waiting for {}", path.display());
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Run by `test_writer_in_another_process_holds_the_lock` as a separate
+    /// process: takes the write lock and keeps it until told to commit.
+    #[test]
+    #[ignore]
+    fn lock_holding_child_process() {
+        let Some(data_directory) = std::env::var_os("RUST_SQLITE_LOCK_TEST_DIR") else { return };
+        let signals = PathBuf::from(std::env::var_os("RUST_SQLITE_LOCK_TEST_SIGNALS").unwrap());
+
+        let mut data_store = DataStore::new(data_directory).unwrap();
+        data_store.begin(TransactionMode::Immediate).unwrap();
+        data_store.insert_row("users", HashMap::from([("name".to_string(), "Child".to_string())])).unwrap();
+        std::fs::write(signals.join("locked"), "").unwrap();
+
+        wait_for(&signals.join("release"));
+        data_store.commit().unwrap();
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_writer_in_another_process_holds_the_lock() {
+        let (mut data_store, temp_dir) = setup_test_datastore();
+        let mut columns = HashMap::new();
+        columns.insert("name".to_string(), ColumnType::String);
+        data_store.create_table("users".to_string(), TableSchema { columns, ..Default::default() }).unwrap();
+        data_store.set_busy_timeout(Duration::from_millis(100));
+
+        let signals = TempDir::new().unwrap();
+        let mut child = Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", "tests::lock_holding_child_process", "--ignored", "--quiet"])
+            .env("RUST_SQLITE_LOCK_TEST_DIR", temp_dir.path())
+            .env("RUST_SQLITE_LOCK_TEST_SIGNALS", signals.path())
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+        wait_for(&signals.path().join("locked"));
+
+        let row = HashMap::from([("name".to_string(), "Parent".to_string())]);
+        let error = data_store.insert_row("users", row.clone()).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::ResourceBusy);
+        assert_eq!(error.to_string(), "database is locked");
+        // Readers carry on while the other process writes, without its rows.
+        data_store.lock(LockMode::Shared).unwrap();
+        assert!(data_store.select("users", &["name".to_string()], None).unwrap().is_empty());
+        data_store.unlock().unwrap();
+
+        std::fs::write(signals.path().join("release"), "").unwrap();
+        assert!(child.wait().unwrap().success());
+
+        data_store.insert_row("users", row).unwrap();
+        let mut names: Vec<_> = data_store.select("users", &["name".to_string()], None).unwrap()
+            .into_iter().map(|row| row["name"].clone()).collect();
+        names.sort();
+        assert_eq!(names, vec!["Child", "Parent"]);
+    }
+}
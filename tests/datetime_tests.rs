@@ -0,0 +1,141 @@
+use rust_sqlite::ast::Value;
+use rust_sqlite::datetime::call;
+use rust_sqlite::execution_engine::{ExecutionError, Row};
+use rust_sqlite::lexer::Lexer;
+use rust_sqlite::parser::Parser;
+
+mod common;
+use common::{execute_sql, select_rows, setup_test_engine};
+
+fn text(s: &str) -> Value {
+    Value::String(s.to_string())
+}
+
+/// Calls a date and time function with text arguments.
+fn run(name: &str, args: &[&str]) -> Value {
+    let args: Vec<Value> = args.iter().map(|arg| text(arg)).collect();
+    call(name, &args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_sqlite::schema::ColumnType;
+    use rust_sqlite::datetime::canonical;
+
+    #[test]
+    fn test_functions_match_sqlite() {
+        assert_eq!(run("date", &["2024-03-10 14:05:09"]), text("2024-03-10"));
+        assert_eq!(run("time", &["2024-03-10 14:05:09.750"]), text("14:05:09"));
+        assert_eq!(run("time", &["12:30"]), text("12:30:00"));
+        assert_eq!(run("datetime", &["2024-01-01T10:00:00+02:00"]), text("2024-01-01 08:00:00"));
+        assert_eq!(run("datetime", &["2024-01-01 10:00Z"]), text("2024-01-01 10:00:00"));
+        assert_eq!(run("julianday", &["2000-01-01 12:00:00"]), Value::Number(2451545.0));
+        assert_eq!(run("unixepoch", &["2024-01-01"]), Value::Number(1704067200.0));
+        assert_eq!(call("datetime", &[Value::Number(0.0), text("unixepoch")]), text("1970-01-01 00:00:00"));
+        assert_eq!(call("date", &[Value::Number(2460000.5)]), text("2023-02-25"));
+        assert_eq!(call("datetime", &[Value::Number(1704067200.0), text("auto")]), text("2024-01-01 00:00:00"));
+        assert_eq!(run("strftime", &["%Y-%m-%d %H:%M:%f %j %w %W %s %%", "2024-03-10 14:05:09.250"]), text("2024-03-10 14:05:09.250 070 0 10 1710079509 %"));
+        assert_eq!(run("strftime", &["%F %T %I%p %u", "2024-03-10 00:05:09"]), text("2024-03-10 00:05:09 12AM 7"));
+
+        // A day past the end of its month runs on into the next, as in SQLite.
+        assert_eq!(run("date", &["2024-02-30"]), text("2024-03-01"));
+
+        let now = run("datetime", &["now"]);
+        assert!(matches!(&now, Value::String(s) if s.len() == 19));
+        assert_eq!(call("datetime", &[]), now);
+    }
+
+    #[test]
+    fn test_modifiers() {
+        assert_eq!(run("date", &["2024-01-31", "+1 month"]), text("2024-03-02"));
+        assert_eq!(run("date", &["2024-03-13", "start of month", "+1 month", "-1 day"]), text("2024-03-31"));
+        assert_eq!(run("date", &["2024-03-13", "weekday 0"]), text("2024-03-17"));
+        assert_eq!(run("date", &["2024-03-17", "weekday 0"]), text("2024-03-17"));
+        assert_eq!(run("datetime", &["2024-03-13 10:00", "+90 minutes", "-30 seconds"]), text("2024-03-13 11:29:30"));
+        assert_eq!(run("datetime", &["2024-03-13 10:00", "start of day", "+1.5 hours"]), text("2024-03-13 01:30:00"));
+        assert_eq!(run("date", &["2023-06-15", "start of year", "+1 years"]), text("2024-01-01"));
+        assert_eq!(run("date", &["2024-03-13", "+1 DAY", "utc"]), text("2024-03-14"));
+
+        assert_eq!(run("date", &["2024-03-13", "+1 fortnight"]), Value::Null);
+        assert_eq!(run("date", &["2024-03-13", "weekday 7"]), Value::Null);
+        assert_eq!(run("date", &["9999-12-31", "+1 day"]), Value::Null);
+        assert_eq!(run("date", &["2024-13-01"]), Value::Null);
+        assert_eq!(run("date", &["yesterday"]), Value::Null);
+        assert_eq!(run("strftime", &["%Q", "2024-03-13"]), Value::Null);
+        assert_eq!(call("date", &[Value::Null]), Value::Null);
+    }
+
+    #[test]
+    fn test_huge_values_are_null() {
+        for unit in ["days", "hours", "seconds", "months", "years"] {
+            assert_eq!(run("date", &["2020-01-01", &format!("+1e300 {unit}")]), Value::Null);
+            assert_eq!(run("date", &["2020-01-01", &format!("-1e300 {unit}")]), Value::Null);
+            assert_eq!(run("date", &["2020-01-01", &format!("+1e15 {unit}"), &format!("-1e15 {unit}")]), Value::Null);
+        }
+        assert_eq!(call("date", &[Value::Number(-1e300)]), Value::Null);
+        assert_eq!(call("date", &[Value::Number(1e300)]), Value::Null);
+        assert_eq!(call("date", &[Value::Number(1e300), text("unixepoch")]), Value::Null);
+        assert_eq!(call("date", &[Value::Number(-1e300), text("auto")]), Value::Null);
+        assert_eq!(run("date", &["2020-01-01", "+100000 years", "-100000 years"]), text("2020-01-01"));
+    }
+
+    #[test]
+    fn test_canonical_forms() {
+        assert_eq!(canonical(&ColumnType::Date, "2024-03-10"), Some("2024-03-10".to_string()));
+        assert_eq!(canonical(&ColumnType::Date, "2024-03-10 00:00:00"), Some("2024-03-10".to_string()));
+        assert_eq!(canonical(&ColumnType::Date, "2024-03-10 12:00"), None);
+        assert_eq!(canonical(&ColumnType::Date, "2024-02-30"), None);
+        assert_eq!(canonical(&ColumnType::Time, "9:05"), None);
+        assert_eq!(canonical(&ColumnType::Time, "09:05"), Some("09:05:00".to_string()));
+        assert_eq!(canonical(&ColumnType::Time, "2024-03-10 09:05"), None);
+        assert_eq!(canonical(&ColumnType::Timestamp, "2024-03-10"), Some("2024-03-10 00:00:00".to_string()));
+        assert_eq!(canonical(&ColumnType::Timestamp, "2024-03-10T09:05:01.5-01:00"), Some("2024-03-10 10:05:01.500".to_string()));
+        assert_eq!(canonical(&ColumnType::String, "2024-03-10"), None);
+    }
+
+    #[test]
+    fn test_date_columns() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE events (id INTEGER PRIMARY KEY, day DATE, at TIME, logged TIMESTAMP DEFAULT (datetime('now')))").unwrap();
+        execute_sql(&mut engine, "INSERT INTO events (id, day, at, logged) VALUES (1, '2024-03-10', '09:05', '2024-03-10T23:00:00+02:00'), (2, '2024-01-05', '10:00:00', '2024-01-05 8:00')").unwrap_err();
+        execute_sql(&mut engine, "INSERT INTO events (id, day, at, logged) VALUES (1, '2024-03-10', '09:05', '2024-03-10T23:00:00+02:00'), (2, '2024-01-05', '10:00:00', '2024-01-05 08:00')").unwrap();
+        execute_sql(&mut engine, "INSERT INTO events (id, day, at) VALUES (3, '2023-12-31 00:00', '23:59:59.5')").unwrap();
+
+        assert_eq!(select_rows(&mut engine, "SELECT day, at, logged FROM events WHERE id < 3 ORDER BY logged"), vec![
+            Row { values: vec![text("2024-01-05"), text("10:00:00"), text("2024-01-05 08:00:00")] },
+            Row { values: vec![text("2024-03-10"), text("09:05:00"), text("2024-03-10 21:00:00")] }
+        ]);
+        assert_eq!(select_rows(&mut engine, "SELECT at FROM events WHERE id = 3"), vec![Row { values: vec![text("23:59:59.500")] }]);
+        assert_eq!(select_rows(&mut engine, "SELECT id FROM events WHERE logged > date('now', '-1 day')"), vec![Row { values: vec![Value::Number(3.0)] }]);
+
+        // Literals compared with a column take its form, so the order is
+        // the order in time rather than of the text written.
+        assert_eq!(select_rows(&mut engine, "SELECT id FROM events WHERE logged >= '2024-03-10' ORDER BY id"), vec![
+            Row { values: vec![Value::Number(1.0)] },
+            Row { values: vec![Value::Number(3.0)] }
+        ]);
+        assert_eq!(select_rows(&mut engine, "SELECT id FROM events WHERE day < date('2024-01-05', '+1 day') ORDER BY day DESC"), vec![
+            Row { values: vec![Value::Number(2.0)] },
+            Row { values: vec![Value::Number(3.0)] }
+        ]);
+        assert_eq!(select_rows(&mut engine, "SELECT id FROM events WHERE strftime('%m', day) = '03'"), vec![Row { values: vec![Value::Number(1.0)] }]);
+
+        assert!(matches!(execute_sql(&mut engine, "INSERT INTO events (id, day) VALUES (4, 'soon')"), Err(ExecutionError::TypeMismatch(col)) if col == "day"));
+        assert!(matches!(execute_sql(&mut engine, "UPDATE events SET at = 12"), Err(ExecutionError::TypeMismatch(col)) if col == "at"));
+        assert!(Parser::new(Lexer::new("SELECT id FROM events WHERE day = today()")).parse().is_err());
+
+        assert_eq!(select_rows(&mut engine, "SELECT sql FROM sqlite_schema WHERE name = 'events'"), vec![
+            Row { values: vec![text("CREATE TABLE events (id INTEGER PRIMARY KEY, day DATE, at TIME, logged TIMESTAMP DEFAULT (datetime('now')))")] }
+        ]);
+    }
+
+    #[test]
+    fn test_strict_date_columns() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE events (day DATE) STRICT").unwrap();
+
+        execute_sql(&mut engine, "INSERT INTO events VALUES ('2024-03-10')").unwrap();
+        assert!(matches!(execute_sql(&mut engine, "INSERT INTO events VALUES ('2024-03-10 00:00')"), Err(ExecutionError::TypeMismatch(_))));
+    }
+}
@@ -0,0 +1,479 @@
+use tempfile::TempDir;
+
+use rust_sqlite::datastore::DataStore;
+use rust_sqlite::execution_engine::{ExecutionEngine, ExecutionError, QueryResult};
+use rust_sqlite::lexer::Lexer;
+use rust_sqlite::parser::Parser;
+
+mod common;
+use common::{execute_sql, select_rows, setup_test_engine};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_sqlite::ast::{TransactionMode, Value};
+    use rust_sqlite::execution_engine::Row;
+    use rust_sqlite::explain::render_query_plan;
+
+    #[test]
+    fn test_create_insert_select() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE users (id INTEGER, name TEXT NOT NULL, age INTEGER DEFAULT 18)").unwrap();
+        execute_sql(&mut engine, "INSERT INTO users (id, name) VALUES (1, 'Alice')").unwrap();
+        execute_sql(&mut engine, "INSERT INTO users VALUES (2, 'Bob', 42)").unwrap();
+
+        let result = execute_sql(&mut engine, "SELECT * FROM users WHERE age >= 18 ORDER BY age DESC").unwrap();
+        assert_eq!(result, QueryResult::Select {
+            columns: vec!["id".to_string(), "name".to_string(), "age".to_string()],
+            rows: vec![
+                Row { values: vec![Value::Number(2.0), Value::String("Bob".to_string()), Value::Number(42.0)] },
+                Row { values: vec![Value::Number(1.0), Value::String("Alice".to_string()), Value::Number(18.0)] }
+            ]
+        });
+    }
+
+    #[test]
+    fn test_explicit_null_skips_default() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE users (id INTEGER, age INTEGER DEFAULT 18)").unwrap();
+        execute_sql(&mut engine, "INSERT INTO users VALUES (1, NULL)").unwrap();
+
+        let result = execute_sql(&mut engine, "SELECT id FROM users WHERE age IS NULL").unwrap();
+        assert_eq!(result, QueryResult::Select {
+            columns: vec!["id".to_string()],
+            rows: vec![Row { values: vec![Value::Number(1.0)] }]
+        });
+    }
+
+    #[test]
+    fn test_constraint_violations_are_rejected() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE ranges (low INTEGER NOT NULL, high INTEGER, CHECK (low <= high))").unwrap();
+
+        assert!(matches!(execute_sql(&mut engine, "INSERT INTO ranges (high) VALUES (10)"), Err(ExecutionError::Storage(_))));
+        assert!(matches!(execute_sql(&mut engine, "INSERT INTO ranges VALUES (5, 1)"), Err(ExecutionError::Storage(_))));
+
+        execute_sql(&mut engine, "INSERT INTO ranges VALUES (1, 5)").unwrap();
+        assert!(execute_sql(&mut engine, "UPDATE ranges SET low = NULL").is_err());
+        assert!(execute_sql(&mut engine, "UPDATE ranges SET high = 0").is_err());
+        assert_eq!(execute_sql(&mut engine, "UPDATE ranges SET high = 3 WHERE low = 1").unwrap(), QueryResult::Update(1));
+    }
+
+    fn select_values(engine: &mut ExecutionEngine, sql: &str) -> Vec<Vec<Value>> {
+        select_rows(engine, sql).into_iter().map(|row| row.values).collect()
+    }
+
+    fn setup_orders(engine: &mut ExecutionEngine, on_delete: &str) {
+        execute_sql(engine, "CREATE TABLE customers (id INTEGER NOT NULL, name TEXT)").unwrap();
+        execute_sql(engine, &format!("CREATE TABLE orders (id INTEGER, customer_id INTEGER REFERENCES customers (id) ON DELETE {on_delete} ON UPDATE CASCADE)")).unwrap();
+        execute_sql(engine, "INSERT INTO customers VALUES (1, 'Alice')").unwrap();
+        execute_sql(engine, "INSERT INTO customers VALUES (2, 'Bob')").unwrap();
+        execute_sql(engine, "INSERT INTO orders VALUES (10, 1)").unwrap();
+        execute_sql(engine, "INSERT INTO orders VALUES (11, 2)").unwrap();
+    }
+
+    #[test]
+    fn test_foreign_key_rejects_orphans() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        setup_orders(&mut engine, "NO ACTION");
+
+        assert!(execute_sql(&mut engine, "INSERT INTO orders VALUES (12, 3)").is_err());
+        assert!(execute_sql(&mut engine, "UPDATE orders SET customer_id = 3 WHERE id = 10").is_err());
+        assert!(execute_sql(&mut engine, "DELETE FROM customers WHERE id = 1").is_err());
+        execute_sql(&mut engine, "INSERT INTO orders VALUES (12, NULL)").unwrap();
+
+        execute_sql(&mut engine, "PRAGMA foreign_keys = OFF").unwrap();
+        execute_sql(&mut engine, "INSERT INTO orders VALUES (13, 3)").unwrap();
+        assert_eq!(select_values(&mut engine, "PRAGMA foreign_keys"), vec![vec![Value::Number(0.0)]]);
+    }
+
+    #[test]
+    fn test_foreign_key_cascade_and_set_null() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        setup_orders(&mut engine, "CASCADE");
+
+        assert_eq!(execute_sql(&mut engine, "DELETE FROM customers WHERE id = 1").unwrap(), QueryResult::Delete(1));
+        assert_eq!(select_values(&mut engine, "SELECT id FROM orders"), vec![vec![Value::Number(11.0)]]);
+
+        execute_sql(&mut engine, "UPDATE customers SET id = 20 WHERE id = 2").unwrap();
+        assert_eq!(select_values(&mut engine, "SELECT customer_id FROM orders"), vec![vec![Value::Number(20.0)]]);
+
+        let (mut engine, _temp_dir) = setup_test_engine();
+        setup_orders(&mut engine, "SET NULL");
+        execute_sql(&mut engine, "DELETE FROM customers WHERE name = 'Alice'").unwrap();
+        assert_eq!(select_values(&mut engine, "SELECT id FROM orders WHERE customer_id IS NULL"), vec![vec![Value::Number(10.0)]]);
+    }
+
+    #[test]
+    fn test_failed_cascade_leaves_parent_unchanged() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE p (id INTEGER NOT NULL)").unwrap();
+        execute_sql(&mut engine, "CREATE TABLE c (pid INTEGER NOT NULL REFERENCES p (id) ON UPDATE CASCADE ON DELETE SET NULL, CHECK (pid < 10))").unwrap();
+        execute_sql(&mut engine, "INSERT INTO p VALUES (1)").unwrap();
+        execute_sql(&mut engine, "INSERT INTO c VALUES (1)").unwrap();
+
+        assert!(execute_sql(&mut engine, "UPDATE p SET id = 50 WHERE id = 1").is_err());
+        assert_eq!(select_values(&mut engine, "SELECT id FROM p"), vec![vec![Value::Number(1.0)]]);
+        assert_eq!(select_values(&mut engine, "SELECT pid FROM c"), vec![vec![Value::Number(1.0)]]);
+
+        assert!(execute_sql(&mut engine, "DELETE FROM p").is_err());
+        assert_eq!(select_values(&mut engine, "SELECT id FROM p"), vec![vec![Value::Number(1.0)]]);
+        assert_eq!(select_values(&mut engine, "SELECT pid FROM c"), vec![vec![Value::Number(1.0)]]);
+    }
+
+    #[test]
+    fn test_self_referencing_foreign_key() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE employees (id INTEGER, manager_id INTEGER, FOREIGN KEY (manager_id) REFERENCES employees (id) ON DELETE CASCADE)").unwrap();
+        execute_sql(&mut engine, "INSERT INTO employees VALUES (1, 1)").unwrap();
+        execute_sql(&mut engine, "INSERT INTO employees VALUES (2, 1)").unwrap();
+        execute_sql(&mut engine, "INSERT INTO employees VALUES (3, 2)").unwrap();
+
+        execute_sql(&mut engine, "DELETE FROM employees WHERE id = 2").unwrap();
+        assert_eq!(select_values(&mut engine, "SELECT id FROM employees"), vec![vec![Value::Number(1.0)]]);
+    }
+
+    #[test]
+    fn test_integer_primary_key_aliases_rowid() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)").unwrap();
+        execute_sql(&mut engine, "INSERT INTO users (name) VALUES ('Alice')").unwrap();
+        assert_eq!(engine.last_insert_rowid(), 1);
+        execute_sql(&mut engine, "INSERT INTO users VALUES (10, 'Bob')").unwrap();
+        execute_sql(&mut engine, "INSERT INTO users (name) VALUES ('Carol')").unwrap();
+        assert_eq!(engine.last_insert_rowid(), 11);
+
+        assert!(execute_sql(&mut engine, "INSERT INTO users VALUES (10, 'Dave')").is_err());
+        assert!(execute_sql(&mut engine, "UPDATE users SET id = 1 WHERE name = 'Bob'").is_err());
+
+        assert_eq!(select_values(&mut engine, "SELECT rowid, id FROM users WHERE name = 'Carol'"),
+            vec![vec![Value::Number(11.0), Value::Number(11.0)]]);
+
+        execute_sql(&mut engine, "UPDATE users SET id = 5 WHERE name = 'Bob'").unwrap();
+        assert_eq!(select_values(&mut engine, "SELECT name FROM users WHERE rowid = 5"),
+            vec![vec![Value::String("Bob".to_string())]]);
+    }
+
+    #[test]
+    fn test_autoincrement_never_reuses_ids() {
+        let (mut engine, temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE plain (id INTEGER PRIMARY KEY, name TEXT)").unwrap();
+        execute_sql(&mut engine, "CREATE TABLE counted (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT)").unwrap();
+
+        for table in ["plain", "counted"] {
+            execute_sql(&mut engine, &format!("INSERT INTO {table} (name) VALUES ('a')")).unwrap();
+            execute_sql(&mut engine, &format!("INSERT INTO {table} (name) VALUES ('b')")).unwrap();
+            execute_sql(&mut engine, &format!("DELETE FROM {table} WHERE id = 2")).unwrap();
+        }
+
+        execute_sql(&mut engine, "INSERT INTO plain (name) VALUES ('c')").unwrap();
+        assert_eq!(engine.last_insert_rowid(), 2);
+
+        // The sequence survives reopening the data directory.
+        drop(engine);
+        let mut engine = ExecutionEngine::new(DataStore::new(temp_dir.path()).unwrap());
+        execute_sql(&mut engine, "INSERT INTO counted (name) VALUES ('c')").unwrap();
+        assert_eq!(engine.last_insert_rowid(), 3);
+    }
+
+    #[test]
+    fn test_commit_makes_changes_visible_together() {
+        let (mut engine, temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE accounts (id INTEGER PRIMARY KEY, balance INTEGER)").unwrap();
+        execute_sql(&mut engine, "INSERT INTO accounts VALUES (1, 100)").unwrap();
+
+        execute_sql(&mut engine, "BEGIN IMMEDIATE").unwrap();
+        assert!(engine.in_transaction());
+        execute_sql(&mut engine, "CREATE TABLE transfers (amount INTEGER)").unwrap();
+        execute_sql(&mut engine, "INSERT INTO transfers VALUES (40)").unwrap();
+        execute_sql(&mut engine, "UPDATE accounts SET balance = 60 WHERE id = 1").unwrap();
+
+        // The committed files are untouched until COMMIT.
+        let accounts = std::fs::read_to_string(temp_dir.path().join("accounts.csv")).unwrap();
+        assert!(accounts.contains("1,100"));
+        assert!(!temp_dir.path().join("transfers.csv").exists());
+
+        assert_eq!(select_values(&mut engine, "SELECT balance FROM accounts"), vec![vec![Value::Number(60.0)]]);
+        execute_sql(&mut engine, "COMMIT").unwrap();
+        assert!(!engine.in_transaction());
+
+        let mut other = ExecutionEngine::new(DataStore::new(temp_dir.path()).unwrap());
+        assert_eq!(select_values(&mut other, "SELECT balance FROM accounts"), vec![vec![Value::Number(60.0)]]);
+        assert_eq!(select_values(&mut other, "SELECT amount FROM transfers"), vec![vec![Value::Number(40.0)]]);
+    }
+
+    #[test]
+    fn test_rollback_discards_changes() {
+        let (mut engine, temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT)").unwrap();
+        execute_sql(&mut engine, "INSERT INTO users (name) VALUES ('Alice')").unwrap();
+
+        execute_sql(&mut engine, "BEGIN").unwrap();
+        execute_sql(&mut engine, "INSERT INTO users (name) VALUES ('Bob')").unwrap();
+        execute_sql(&mut engine, "DELETE FROM users WHERE name = 'Alice'").unwrap();
+        execute_sql(&mut engine, "CREATE TABLE scratch (id INTEGER)").unwrap();
+        execute_sql(&mut engine, "ROLLBACK").unwrap();
+
+        assert_eq!(select_values(&mut engine, "SELECT name FROM users"), vec![vec![Value::String("Alice".to_string())]]);
+        assert!(execute_sql(&mut engine, "SELECT id FROM scratch").is_err());
+        execute_sql(&mut engine, "INSERT INTO users (name) VALUES ('Carol')").unwrap();
+        assert_eq!(engine.last_insert_rowid(), 2);
+        assert!(!temp_dir.path().join("journal").exists());
+    }
+
+    #[test]
+    fn test_uncommitted_transaction_is_lost_on_crash() {
+        let (mut engine, temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE users (name TEXT)").unwrap();
+
+        engine.begin(TransactionMode::Deferred).unwrap();
+        execute_sql(&mut engine, "INSERT INTO users VALUES ('Alice')").unwrap();
+        // A copy of the directory taken mid-transaction is what a crash
+        // would leave behind, without the rollback a normal drop does.
+        let crashed_dir = TempDir::new().unwrap();
+        copy_directory(temp_dir.path(), crashed_dir.path());
+        assert!(crashed_dir.path().join("journal").exists());
+        drop(engine);
+
+        let mut engine = ExecutionEngine::new(DataStore::new(crashed_dir.path()).unwrap());
+        assert!(select_values(&mut engine, "SELECT name FROM users").is_empty());
+        assert!(!crashed_dir.path().join("journal").exists());
+    }
+
+    fn copy_directory(from: &std::path::Path, to: &std::path::Path) {
+        std::fs::create_dir_all(to).unwrap();
+        for entry in std::fs::read_dir(from).unwrap() {
+            let entry = entry.unwrap();
+            let target = to.join(entry.file_name());
+            if entry.file_type().unwrap().is_dir() {
+                copy_directory(&entry.path(), &target);
+            } else {
+                std::fs::copy(entry.path(), target).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_transaction_statement_errors() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        assert!(execute_sql(&mut engine, "COMMIT").is_err());
+        assert!(execute_sql(&mut engine, "ROLLBACK").is_err());
+        execute_sql(&mut engine, "BEGIN TRANSACTION").unwrap();
+        assert!(execute_sql(&mut engine, "BEGIN").is_err());
+        execute_sql(&mut engine, "END").unwrap();
+    }
+
+    #[test]
+    fn test_wal_commits_survive_crash_and_drop_torn_frames() {
+        let (mut engine, temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)").unwrap();
+        execute_sql(&mut engine, "INSERT INTO users VALUES (1, 'Alice')").unwrap();
+        assert_eq!(select_values(&mut engine, "PRAGMA journal_mode = WAL"), vec![vec![Value::String("wal".to_string())]]);
+
+        execute_sql(&mut engine, "INSERT INTO users VALUES (2, 'Bob')").unwrap();
+        execute_sql(&mut engine, "UPDATE users SET name = 'Alicia' WHERE id = 1").unwrap();
+        execute_sql(&mut engine, "BEGIN").unwrap();
+        execute_sql(&mut engine, "CREATE TABLE tags (name TEXT)").unwrap();
+        execute_sql(&mut engine, "INSERT INTO tags VALUES ('new')").unwrap();
+        execute_sql(&mut engine, "COMMIT").unwrap();
+        execute_sql(&mut engine, "BEGIN").unwrap();
+        execute_sql(&mut engine, "DELETE FROM users WHERE id = 2").unwrap();
+        execute_sql(&mut engine, "ROLLBACK").unwrap();
+
+        assert_eq!(select_values(&mut engine, "SELECT name FROM users ORDER BY id"),
+            vec![vec![Value::String("Alicia".to_string())], vec![Value::String("Bob".to_string())]]);
+        // Committed rows live in the log until a checkpoint.
+        let users = std::fs::read_to_string(temp_dir.path().join("users.csv")).unwrap();
+        assert!(users.contains("Alice") && !users.contains("Bob"));
+
+        // Crash without a checkpoint, leaving half a frame at the end of the log.
+        std::mem::forget(engine);
+        let wal_path = temp_dir.path().join("database-wal");
+        let mut wal = std::fs::read(&wal_path).unwrap();
+        wal.extend_from_slice(&[42, 0, 0, 0, 1, 7, 7]);
+        std::fs::write(&wal_path, wal).unwrap();
+
+        let mut engine = ExecutionEngine::new(DataStore::new(temp_dir.path()).unwrap());
+        assert_eq!(select_values(&mut engine, "SELECT name FROM users ORDER BY id"),
+            vec![vec![Value::String("Alicia".to_string())], vec![Value::String("Bob".to_string())]]);
+        assert_eq!(select_values(&mut engine, "SELECT name FROM tags"), vec![vec![Value::String("new".to_string())]]);
+        assert_eq!(std::fs::metadata(&wal_path).unwrap().len(), 0);
+        assert_eq!(select_values(&mut engine, "PRAGMA journal_mode"), vec![vec![Value::String("wal".to_string())]]);
+
+        execute_sql(&mut engine, "PRAGMA journal_mode = DELETE").unwrap();
+        assert!(!wal_path.exists());
+    }
+
+    #[test]
+    fn test_wal_table_created_in_rolled_back_transaction_leaves_no_file() {
+        let (mut engine, temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "PRAGMA journal_mode = WAL").unwrap();
+        execute_sql(&mut engine, "BEGIN").unwrap();
+        execute_sql(&mut engine, "CREATE TABLE tags (name TEXT)").unwrap();
+        execute_sql(&mut engine, "INSERT INTO tags VALUES ('new')").unwrap();
+        execute_sql(&mut engine, "ROLLBACK").unwrap();
+        assert!(!temp_dir.path().join("tags.csv").exists());
+        assert!(execute_sql(&mut engine, "SELECT name FROM tags").is_err());
+
+        execute_sql(&mut engine, "BEGIN").unwrap();
+        execute_sql(&mut engine, "CREATE TABLE tags (name TEXT)").unwrap();
+        let crashed_dir = TempDir::new().unwrap();
+        copy_directory(temp_dir.path(), crashed_dir.path());
+        assert!(crashed_dir.path().join("tags.csv").exists());
+        execute_sql(&mut engine, "COMMIT").unwrap();
+
+        let mut engine = ExecutionEngine::new(DataStore::new(crashed_dir.path()).unwrap());
+        assert!(!crashed_dir.path().join("tags.csv").exists());
+        execute_sql(&mut engine, "CREATE TABLE tags (label TEXT)").unwrap();
+        assert!(select_values(&mut engine, "SELECT label FROM tags").is_empty());
+    }
+
+    #[test]
+    fn test_wal_checkpoint_folds_rows_into_table_files() {
+        let (mut engine, temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "PRAGMA journal_mode = WAL").unwrap();
+        execute_sql(&mut engine, "CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT)").unwrap();
+        execute_sql(&mut engine, "INSERT INTO items VALUES (1, 'a')").unwrap();
+        execute_sql(&mut engine, "INSERT INTO items VALUES (2, 'b')").unwrap();
+        // Swapping keys must keep both rows.
+        execute_sql(&mut engine, "UPDATE items SET id = 3 WHERE id = 1").unwrap();
+        execute_sql(&mut engine, "UPDATE items SET id = 1 WHERE id = 2").unwrap();
+
+        let result = select_values(&mut engine, "PRAGMA wal_checkpoint");
+        assert_eq!(result[0][0], Value::Number(0.0));
+        let items = std::fs::read_to_string(temp_dir.path().join("items.csv")).unwrap();
+        assert!(items.contains("1,b") && items.contains("3,a"));
+        assert_eq!(select_values(&mut engine, "SELECT id, name FROM items ORDER BY id"), vec![
+            vec![Value::Number(1.0), Value::String("b".to_string())],
+            vec![Value::Number(3.0), Value::String("a".to_string())]
+        ]);
+    }
+
+    #[test]
+    fn test_query_streams_rows_and_stops_early() {
+        let (mut engine, temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE items (n INTEGER)").unwrap();
+        for n in 1..=10 {
+            execute_sql(&mut engine, &format!("INSERT INTO items VALUES ({n})")).unwrap();
+        }
+
+        let ast = Parser::new(Lexer::new("SELECT n FROM items WHERE n > 4")).parse().unwrap();
+        let mut cursor = engine.query(&ast).unwrap();
+        assert_eq!(cursor.columns(), ["n".to_string()]);
+        assert_eq!(cursor.next().unwrap().unwrap().values, vec![Value::Number(5.0)]);
+        assert_eq!(cursor.next().unwrap().unwrap().values, vec![Value::Number(6.0)]);
+
+        // The cursor keeps readers' view steady, so a write elsewhere waits.
+        let mut other = ExecutionEngine::new(DataStore::new(temp_dir.path()).unwrap());
+        execute_sql(&mut other, "PRAGMA busy_timeout = 50").unwrap();
+        assert!(execute_sql(&mut other, "INSERT INTO items VALUES (11)").is_err());
+        drop(cursor);
+        execute_sql(&mut other, "INSERT INTO items VALUES (11)").unwrap();
+
+        let ast = Parser::new(Lexer::new("SELECT n FROM items WHERE n > 8 ORDER BY n DESC")).parse().unwrap();
+        let rows: Vec<_> = engine.query(&ast).unwrap().map(|row| row.unwrap().values).collect();
+        assert_eq!(rows, vec![vec![Value::Number(11.0)], vec![Value::Number(10.0)], vec![Value::Number(9.0)]]);
+
+        let ast = Parser::new(Lexer::new("DELETE FROM items")).parse().unwrap();
+        assert!(engine.query(&ast).is_err());
+    }
+
+    #[test]
+    fn test_insert_multiple_rows_and_insert_select() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT NOT NULL)").unwrap();
+        execute_sql(&mut engine, "CREATE TABLE archive (id INTEGER, name TEXT)").unwrap();
+
+        let result = execute_sql(&mut engine, "INSERT INTO users (name) VALUES ('Alice'), ('Bob'), ('Carol')").unwrap();
+        assert_eq!(result, QueryResult::Insert(3));
+        assert_eq!(engine.last_insert_rowid(), 3);
+
+        // A bad row keeps the whole statement out.
+        assert!(execute_sql(&mut engine, "INSERT INTO users VALUES (4, 'Dave'), (2, 'Duplicate')").is_err());
+        assert!(execute_sql(&mut engine, "INSERT INTO users VALUES (5, 'Eve'), (5, 'Again')").is_err());
+        assert_eq!(select_values(&mut engine, "SELECT name FROM users").len(), 3);
+
+        let result = execute_sql(&mut engine, "INSERT INTO archive SELECT id, name FROM users WHERE id > 1").unwrap();
+        assert_eq!(result, QueryResult::Insert(2));
+        let result = execute_sql(&mut engine, "INSERT INTO archive (name, id) SELECT name, id FROM archive").unwrap();
+        assert_eq!(result, QueryResult::Insert(2));
+        assert_eq!(select_values(&mut engine, "SELECT id, name FROM archive ORDER BY id"), vec![
+            vec![Value::Number(2.0), Value::String("Bob".to_string())],
+            vec![Value::Number(2.0), Value::String("Bob".to_string())],
+            vec![Value::Number(3.0), Value::String("Carol".to_string())],
+            vec![Value::Number(3.0), Value::String("Carol".to_string())]
+        ]);
+
+        assert!(execute_sql(&mut engine, "INSERT INTO archive SELECT id FROM users").is_err());
+    }
+
+    #[test]
+    fn test_explain_query_plan_and_program() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT NOT NULL)").unwrap();
+        execute_sql(&mut engine, "INSERT INTO users VALUES (1, 'Alice')").unwrap();
+
+        let result = execute_sql(&mut engine, "EXPLAIN QUERY PLAN SELECT name FROM users WHERE id > 0 ORDER BY name").unwrap();
+        let QueryResult::Select { columns, rows } = result else { panic!("Expected rows") };
+        assert_eq!(columns, vec!["id", "parent", "notused", "detail"]);
+        assert_eq!(render_query_plan(&rows), "QUERY PLAN\n|--SCAN users\n`--USE TEMP B-TREE FOR ORDER BY\n");
+
+        let QueryResult::Select { rows, .. } = execute_sql(&mut engine, "EXPLAIN QUERY PLAN INSERT INTO users VALUES (2, 'Bob')").unwrap() else { panic!("Expected rows") };
+        assert!(rows.is_empty());
+
+        // The program loops over the table, skipping rows the filter rejects.
+        let QueryResult::Select { columns, rows } = execute_sql(&mut engine, "EXPLAIN SELECT name FROM users WHERE id > 0").unwrap() else { panic!("Expected rows") };
+        assert_eq!(columns, vec!["addr", "opcode", "p1", "p2", "comment"]);
+        let opcodes: Vec<_> = rows.iter().map(|row| row.values[1].clone()).collect();
+        assert_eq!(opcodes, ["Transaction", "OpenRead", "Rewind", "Filter", "ResultRow", "Next", "Halt"].map(|op| Value::String(op.to_string())));
+        assert_eq!(rows[2].values[3], Value::Number(6.0));
+        assert_eq!(rows[3].values[3], Value::Number(5.0));
+        assert_eq!(rows[3].values[4], Value::String("id > 0".to_string()));
+        assert_eq!(rows[5].values[3], Value::Number(3.0));
+
+        let QueryResult::Select { rows, .. } = execute_sql(&mut engine, "EXPLAIN INSERT INTO users VALUES (2, NULL)").unwrap() else { panic!("Expected rows") };
+        assert!(rows.iter().any(|row| row.values[1] == Value::String("NotNull".to_string()) && row.values[4] == Value::String("name".to_string())));
+
+        // Nothing explained is run.
+        let result = execute_sql(&mut engine, "SELECT name FROM users").unwrap();
+        assert_eq!(result, QueryResult::Select {
+            columns: vec!["name".to_string()],
+            rows: vec![Row { values: vec![Value::String("Alice".to_string())] }]
+        });
+        assert!(matches!(execute_sql(&mut engine, "EXPLAIN SELECT * FROM missing"), Err(ExecutionError::TableNotFound(_))));
+    }
+
+    #[test]
+    fn test_analyze_keeps_statistics_for_the_planner() {
+        let (mut engine, temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE items (id INTEGER PRIMARY KEY, kind TEXT)").unwrap();
+        execute_sql(&mut engine, "INSERT INTO items (kind) VALUES ('a'), ('a'), ('a'), ('b'), (NULL)").unwrap();
+        assert!(matches!(execute_sql(&mut engine, "ANALYZE missing"), Err(ExecutionError::TableNotFound(_))));
+
+        // An ANALYZE that is rolled back leaves no statistics behind.
+        execute_sql(&mut engine, "BEGIN").unwrap();
+        assert_eq!(execute_sql(&mut engine, "ANALYZE items").unwrap(), QueryResult::Analyze);
+        assert!(engine.data_store().table_statistics("items").is_some());
+        execute_sql(&mut engine, "ROLLBACK").unwrap();
+        assert!(engine.data_store().table_statistics("items").is_none());
+
+        execute_sql(&mut engine, "ANALYZE").unwrap();
+        drop(engine);
+        let mut engine = ExecutionEngine::new(DataStore::new(temp_dir.path()).unwrap());
+        let statistics = engine.data_store().table_statistics("items").unwrap();
+        assert_eq!(statistics.row_count, 5);
+        assert_eq!((statistics.columns["kind"].distinct, statistics.columns["kind"].null_count), (2, 1));
+
+        let QueryResult::Select { rows, .. } = execute_sql(&mut engine, "EXPLAIN QUERY PLAN SELECT * FROM items WHERE kind = 'a'").unwrap() else { panic!("Expected rows") };
+        assert_eq!(render_query_plan(&rows), "QUERY PLAN\n`--SCAN items (~3 rows)\n");
+
+        // The rarer term is tested first, and the rows are the same.
+        let QueryResult::Select { rows, .. } = execute_sql(&mut engine, "EXPLAIN SELECT id FROM items WHERE kind = 'a' AND kind IS NULL").unwrap() else { panic!("Expected rows") };
+        assert_eq!(rows[3].values[4], Value::String("(kind IS NULL AND kind = 'a')".to_string()));
+        let result = execute_sql(&mut engine, "SELECT id FROM items WHERE kind = 'b' OR id < 2").unwrap();
+        assert_eq!(result, QueryResult::Select {
+            columns: vec!["id".to_string()],
+            rows: vec![Row { values: vec![Value::Number(1.0)] }, Row { values: vec![Value::Number(4.0)] }]
+        });
+    }
+}
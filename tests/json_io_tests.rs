@@ -0,0 +1,130 @@
+use rust_sqlite::execution_engine::{ExecutionEngine, ExecutionError, QueryResult};
+use rust_sqlite::lexer::Lexer;
+use rust_sqlite::parser::Parser;
+
+mod common;
+use common::{execute_sql, setup_test_engine};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_sqlite::ast::Value;
+    use rust_sqlite::json_io::{export_json, import_ndjson, JsonFormat, JsonImportOptions};
+    use rust_sqlite::schema::ColumnType;
+
+    fn select_values(engine: &mut ExecutionEngine, sql: &str) -> Vec<Vec<Value>> {
+        match execute_sql(engine, sql).unwrap() {
+            QueryResult::Select { rows, .. } => rows.into_iter().map(|row| row.values).collect(),
+            other => panic!("Expected rows, got {:?}", other)
+        }
+    }
+
+    fn export(engine: &mut ExecutionEngine, sql: &str, format: JsonFormat) -> String {
+        let ast = Parser::new(Lexer::new(sql)).parse().unwrap();
+        let mut output = Vec::new();
+        export_json(engine, &ast, &mut output, format).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_export_writes_typed_values_in_column_order() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE people (name TEXT, id INTEGER, score FLOAT)").unwrap();
+        execute_sql(&mut engine, "INSERT INTO people VALUES ('Alice \"Al\"', 1, 1.5), (NULL, 2, NULL)").unwrap();
+
+        assert_eq!(
+            export(&mut engine, "SELECT name, id, score FROM people", JsonFormat::Array),
+            "[\n{\"name\":\"Alice \\\"Al\\\"\",\"id\":1,\"score\":1.5},\n{\"name\":null,\"id\":2,\"score\":null}\n]\n"
+        );
+        assert_eq!(
+            export(&mut engine, "SELECT score, id FROM people WHERE id = 1", JsonFormat::Lines),
+            "{\"score\":1.5,\"id\":1}\n"
+        );
+        assert_eq!(export(&mut engine, "SELECT * FROM people WHERE id > 5", JsonFormat::Array), "[]\n");
+        assert_eq!(export(&mut engine, "SELECT * FROM people WHERE id > 5", JsonFormat::Lines), "");
+    }
+
+    #[test]
+    fn test_import_validates_records_against_schema() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        execute_sql(&mut engine, "CREATE TABLE people (id INTEGER PRIMARY KEY, name TEXT NOT NULL, active BOOLEAN DEFAULT 1)").unwrap();
+
+        let input = [
+            r#"{"ID": 1, "name": "Alice", "active": false}"#,
+            r#"{"id": "2", "name": "Bob"}"#,
+            "",
+            r#"{"id": 3, "name": "Carol"}"#,
+            r#"{"id": 4, "name": "Dave", "age": 40}"#,
+            r#"{"id": 5, "name": "#,
+            r#"[5, "Erin"]"#,
+            r#"{"id": 1, "name": "Frank"}"#,
+        ].join("\n");
+        let report = import_ndjson(&mut engine, input.as_bytes(), "people", &JsonImportOptions::default()).unwrap();
+
+        assert_eq!(report.imported, 2);
+        let rejected: Vec<_> = report.rejected.iter().map(|rejected| rejected.line).collect();
+        assert_eq!(rejected, vec![2, 5, 6, 7, 8]);
+        assert!(matches!(report.rejected[0].error, ExecutionError::TypeMismatch(_)));
+        assert!(matches!(&report.rejected[1].error, ExecutionError::ColumnNotFound(column) if column == "age"));
+        assert!(matches!(report.rejected[2].error, ExecutionError::Storage(_)));
+
+        assert_eq!(select_values(&mut engine, "SELECT id, name, active FROM people ORDER BY id"), vec![
+            vec![Value::Number(1.0), Value::String("Alice".to_string()), Value::Boolean(false)],
+            vec![Value::Number(3.0), Value::String("Carol".to_string()), Value::Boolean(true)]
+        ]);
+    }
+
+    #[test]
+    fn test_import_creates_table_from_inferred_types() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        let input = concat!(
+            "{\"id\": 1, \"price\": 2, \"tag\": \"a\", \"sold\": true, \"note\": null}\n",
+            "{\"id\": 2, \"price\": 2.5, \"tag\": 7, \"sold\": false}\n"
+        );
+
+        let error = import_ndjson(&mut engine, input.as_bytes(), "items", &JsonImportOptions::default()).unwrap_err();
+        assert!(matches!(error, ExecutionError::TableNotFound(_)));
+
+        let options = JsonImportOptions { create_table: true };
+        let report = import_ndjson(&mut engine, input.as_bytes(), "items", &options).unwrap();
+        let schema = engine.data_store().get_table_schema("items").unwrap();
+        assert_eq!(schema.column_type("id"), Some(&ColumnType::Integer));
+        assert_eq!(schema.column_type("price"), Some(&ColumnType::Float));
+        assert_eq!(schema.column_type("tag"), Some(&ColumnType::String));
+        assert_eq!(schema.column_type("sold"), Some(&ColumnType::Boolean));
+        assert_eq!(schema.column_type("note"), Some(&ColumnType::String));
+
+        // The second record's tag is a number, which the TEXT column keeps
+        // as its JSON text.
+        assert_eq!(report.imported, 2);
+        assert!(report.rejected.is_empty());
+        assert_eq!(select_values(&mut engine, "SELECT id, price, tag, sold, note FROM items"), vec![
+            vec![Value::Number(1.0), Value::Number(2.0), Value::String("a".to_string()), Value::Boolean(true), Value::Null],
+            vec![Value::Number(2.0), Value::Number(2.5), Value::String("7".to_string()), Value::Boolean(false), Value::Null]
+        ]);
+    }
+
+    #[test]
+    fn test_import_keeps_mixed_type_keys_as_json_text() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        let input = concat!(
+            "{\"a\": 1, \"b\": true, \"c\": {\"x\": [1, 2]}}\n",
+            "{\"a\": \"x\", \"b\": 2, \"c\": [null, \"y\"]}\n"
+        );
+
+        let options = JsonImportOptions { create_table: true };
+        let report = import_ndjson(&mut engine, input.as_bytes(), "mixed", &options).unwrap();
+        let schema = engine.data_store().get_table_schema("mixed").unwrap();
+        for column in ["a", "b", "c"] {
+            assert_eq!(schema.column_type(column), Some(&ColumnType::String));
+        }
+
+        assert_eq!(report.imported, 2);
+        assert!(report.rejected.is_empty());
+        let text = |s: &str| Value::String(s.to_string());
+        assert_eq!(select_values(&mut engine, "SELECT a, b, c FROM mixed ORDER BY rowid"), vec![
+            vec![text("1"), text("true"), text(r#"{"x":[1,2]}"#)],
+            vec![text("x"), text("2"), text(r#"[null,"y"]"#)]
+        ]);
+    }
+}
@@ -153,4 +153,45 @@ mod tests {
             Token::Number(10.0)
         ]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_parameters() {
+        let tokens: Vec<Token> = Lexer::new("? ?12 :name $id").collect();
+        assert_eq!(tokens, vec![
+            Token::Parameter("?".to_string()),
+            Token::Parameter("?12".to_string()),
+            Token::Parameter(":name".to_string()),
+            Token::Parameter("$id".to_string())
+        ]);
+    }
+
+    #[test]
+    fn test_doubled_quotes_in_strings() {
+        let tokens: Vec<Token> = Lexer::new("'it''s' 'a'").collect();
+        assert_eq!(tokens, vec![Token::String("it's".to_string()), Token::String("a".to_string())]);
+    }
+
+    #[test]
+    fn test_dots_and_semicolons() {
+        let tokens: Vec<Token> = Lexer::new("NEW.id;").collect();
+        assert_eq!(tokens, vec![Token::Identifier("NEW".to_string()), Token::Dot, Token::Identifier("id".to_string()), Token::Semicolon]);
+    }
+
+    #[test]
+    fn test_boolean_literals() {
+        let tokens: Vec<Token> = Lexer::new("true False truth").collect();
+        assert_eq!(tokens, vec![Token::True, Token::False, Token::Identifier("truth".to_string())]);
+    }
+
+    #[test]
+    fn test_blob_literals() {
+        let tokens: Vec<Token> = Lexer::new("X'00ff' x'' x = 1").collect();
+        assert_eq!(tokens, vec![
+            Token::Blob("00ff".to_string()),
+            Token::Blob(String::new()),
+            Token::Identifier("x".to_string()),
+            Token::Operator("=".to_string()),
+            Token::Number(1.0)
+        ]);
+    }
+}
@@ -0,0 +1,59 @@
+use rust_sqlite::ast::Value;
+use rust_sqlite::execution_engine::Row;
+use rust_sqlite::output::{write_rows, OutputMode, OutputOptions};
+
+fn render(mode: OutputMode, headers: bool) -> String {
+    let columns = vec!["id".to_string(), "name".to_string()];
+    let rows = vec![
+        Row { values: vec![Value::Number(1.0), Value::String("it's | ok".to_string())] },
+        Row { values: vec![Value::Number(2.5), Value::Null] }
+    ];
+    let options = OutputOptions { mode, headers, null_value: "(null)".to_string() };
+    let mut output = Vec::new();
+    write_rows(&mut output, &columns, &rows, &options).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_modes() {
+        assert_eq!(render(OutputMode::List, false), "1|it's | ok\n2.5|(null)\n");
+        assert_eq!(render(OutputMode::List, true), "id|name\n1|it's | ok\n2.5|(null)\n");
+        assert_eq!(render(OutputMode::Csv, true), "id,name\n1,it's | ok\n2.5,(null)\n");
+        assert_eq!(render(OutputMode::Tsv, false), "1\tit's | ok\n2.5\t(null)\n");
+        assert_eq!(render(OutputMode::Line, false), "  id = 1\nname = it's | ok\n\n  id = 2.5\nname = (null)\n");
+    }
+
+    #[test]
+    fn test_table_modes() {
+        assert_eq!(render(OutputMode::Box, false), "\
+┌─────┬───────────┐
+│ id  │ name      │
+├─────┼───────────┤
+│ 1   │ it's | ok │
+│ 2.5 │ (null)    │
+└─────┴───────────┘
+");
+        assert_eq!(render(OutputMode::Markdown, false), "\
+| id  | name       |
+|-----|------------|
+| 1   | it's \\| ok |
+| 2.5 | (null)     |
+");
+    }
+
+    #[test]
+    fn test_json_and_insert_modes() {
+        assert_eq!(render(OutputMode::Json, true), "[\n{\"id\":1,\"name\":\"it's | ok\"},\n{\"id\":2.5,\"name\":null}\n]\n");
+        assert_eq!(render(OutputMode::Ndjson, false), "{\"id\":1,\"name\":\"it's | ok\"}\n{\"id\":2.5,\"name\":null}\n");
+        assert_eq!(render(OutputMode::Insert("t".to_string()), false), "INSERT INTO t VALUES(1,'it''s | ok');\nINSERT INTO t VALUES(2.5,NULL);\n");
+        assert_eq!(render(OutputMode::Insert("t".to_string()), true), "INSERT INTO t(id,name) VALUES(1,'it''s | ok');\nINSERT INTO t(id,name) VALUES(2.5,NULL);\n");
+
+        assert_eq!(OutputMode::from_name("INSERT", None), Some(OutputMode::Insert("table".to_string())));
+        assert_eq!(OutputMode::from_name("box", Some("t")), None);
+        assert_eq!(OutputMode::from_name("columns", None), None);
+    }
+}
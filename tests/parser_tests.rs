@@ -1,11 +1,13 @@
 #[cfg(test)]
 mod tests {
-    use super::*;
     use rust_sqlite::lexer::Lexer;
     use rust_sqlite::parser::Parser;
     use rust_sqlite::ast::{
-        ASTNode, ComparisonOperator, Condition, InsertStatement, SelectStatement, UpdateStatement, Value, OrderByClause
+        ASTNode, ComparisonOperator, Condition, InsertSource, InsertStatement, SelectStatement, UpdateStatement, Value, OrderByClause,
+        CreateTableStatement, ColumnDefinition, Expression, TransactionMode, ExplainStatement, ExplainMode, CreateViewStatement, DropViewStatement,
+        CreateTriggerStatement, DropTriggerStatement, TriggerEvent, TriggerTiming, DeleteStatement
     };
+    use rust_sqlite::schema::{ColumnConstraint, ColumnType, ForeignKey, ForeignKeyAction, TableConstraint};
 
     fn parse_sql(sql: &str) -> Result<ASTNode, String> {
         let lexer = Lexer::new(sql);
@@ -53,11 +55,49 @@ mod tests {
             ASTNode::Insert(InsertStatement {
                 table: "users".to_string(),
                 columns: vec!["name".to_string(), "age".to_string()],
-                values: vec![Value::String("John".to_string()), Value::Number(30.0)],
+                source: InsertSource::Values(vec![vec![Value::String("John".to_string()), Value::Number(30.0)]]),
             })
         );
     }
 
+    #[test]
+    fn test_insert_multiple_rows_and_select() {
+        let ast = parse_sql("INSERT INTO users (name, age) VALUES ('John', 30), ('Jane', 25)").unwrap();
+        assert_eq!(
+            ast,
+            ASTNode::Insert(InsertStatement {
+                table: "users".to_string(),
+                columns: vec!["name".to_string(), "age".to_string()],
+                source: InsertSource::Values(vec![
+                    vec![Value::String("John".to_string()), Value::Number(30.0)],
+                    vec![Value::String("Jane".to_string()), Value::Number(25.0)]
+                ]),
+            })
+        );
+
+        let ast = parse_sql("INSERT INTO archive SELECT * FROM users WHERE age > 18").unwrap();
+        assert_eq!(
+            ast,
+            ASTNode::Insert(InsertStatement {
+                table: "archive".to_string(),
+                columns: vec![],
+                source: InsertSource::Select(Box::new(SelectStatement {
+                    columns: vec!["*".to_string()],
+                    table: "users".to_string(),
+                    condition: Some(Condition::Comparison(
+                        "age".to_string(),
+                        ComparisonOperator::GreaterThan,
+                        Value::Number(18.0),
+                    )),
+                    order_by: Vec::<OrderByClause>::new()
+                })),
+            })
+        );
+
+        assert!(parse_sql("INSERT INTO users VALUES (1, 2), (3)").is_err());
+        assert!(parse_sql("INSERT INTO users VALUES (1, 2),").is_err());
+    }
+
     #[test]
     fn test_select_with_different_operators() {
         let operators = vec![
@@ -128,4 +168,291 @@ mod tests {
     fn test_bad_update_statement() {
         parse_sql("UPDATE table SET = 10").unwrap();
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_create_table_with_constraints() {
+        let ast = parse_sql("CREATE TABLE products (id INTEGER NOT NULL, name TEXT DEFAULT 'unnamed', price REAL CHECK (price > 0), stock INTEGER DEFAULT -1, CHECK (stock >= 0 OR stock = -1))").unwrap();
+        assert_eq!(ast,
+            ASTNode::CreateTable(CreateTableStatement {
+                table: "products".to_string(),
+                columns: vec![
+                    ColumnDefinition {
+                        name: "id".to_string(),
+                        column_type: ColumnType::Integer,
+                        constraints: vec![ColumnConstraint::NotNull]
+                    },
+                    ColumnDefinition {
+                        name: "name".to_string(),
+                        column_type: ColumnType::String,
+                        constraints: vec![ColumnConstraint::Default(Expression::Literal(Value::String("unnamed".to_string())))]
+                    },
+                    ColumnDefinition {
+                        name: "price".to_string(),
+                        column_type: ColumnType::Float,
+                        constraints: vec![ColumnConstraint::Check(Condition::Comparison("price".to_string(), ComparisonOperator::GreaterThan, Value::Number(0.0)))]
+                    },
+                    ColumnDefinition {
+                        name: "stock".to_string(),
+                        column_type: ColumnType::Integer,
+                        constraints: vec![ColumnConstraint::Default(Expression::Literal(Value::Number(-1.0)))]
+                    }
+                ],
+                constraints: vec![TableConstraint::Check(Condition::Or(
+                    Box::new(Condition::Comparison("stock".to_string(), ComparisonOperator::GreaterEqualThan, Value::Number(0.0))),
+                    Box::new(Condition::Comparison("stock".to_string(), ComparisonOperator::Equals, Value::Number(-1.0)))
+                ))],
+                strict: false
+            })
+        )
+    }
+
+    #[test]
+    fn test_condition_with_and_not_and_is_null() {
+        let ast = parse_sql("DELETE FROM users WHERE NOT (age < 18 AND name IS NOT NULL)").unwrap();
+        match ast {
+            ASTNode::Delete(stmt) => assert_eq!(stmt.condition, Some(Condition::Not(Box::new(Condition::And(
+                Box::new(Condition::Comparison("age".to_string(), ComparisonOperator::LessThan, Value::Number(18.0))),
+                Box::new(Condition::IsNotNull(Expression::Column("name".to_string())))
+            ))))),
+            _ => panic!("Expected DELETE statement")
+        }
+    }
+
+    #[test]
+    fn test_boolean_conditions() {
+        let condition = |sql: &str| match parse_sql(sql).unwrap() {
+            ASTNode::Select(stmt) => stmt.condition.unwrap(),
+            other => panic!("Expected SELECT statement, got {other:?}")
+        };
+        let active = || Expression::Column("active".to_string());
+
+        assert_eq!(condition("SELECT * FROM users WHERE active"), Condition::Truth(active()));
+        assert_eq!(condition("SELECT * FROM users WHERE NOT active AND age > 30"), Condition::And(
+            Box::new(Condition::Not(Box::new(Condition::Truth(active())))),
+            Box::new(Condition::Comparison("age".to_string(), ComparisonOperator::GreaterThan, Value::Number(30.0)))
+        ));
+        assert_eq!(condition("SELECT * FROM users WHERE active IS TRUE"), Condition::IsTrue(active()));
+        assert_eq!(condition("SELECT * FROM users WHERE active IS NOT FALSE"), Condition::Not(Box::new(Condition::IsFalse(active()))));
+        assert_eq!(condition("SELECT * FROM users WHERE active = FALSE"), Condition::Comparison("active".to_string(), ComparisonOperator::Equals, Value::Boolean(false)));
+        assert_eq!(condition("SELECT * FROM users WHERE TRUE"), Condition::Truth(Expression::Literal(Value::Boolean(true))));
+
+        match parse_sql("INSERT INTO users VALUES (1, TRUE)").unwrap() {
+            ASTNode::Insert(stmt) => assert_eq!(stmt.source, InsertSource::Values(vec![vec![Value::Number(1.0), Value::Boolean(true)]])),
+            other => panic!("Expected INSERT statement, got {other:?}")
+        }
+        assert!(parse_sql("SELECT * FROM users WHERE active IS 1").is_err());
+    }
+
+    #[test]
+    fn test_blobs() {
+        match parse_sql("INSERT INTO files VALUES (X'00fF', X'')").unwrap() {
+            ASTNode::Insert(stmt) => assert_eq!(stmt.source, InsertSource::Values(vec![vec![Value::Blob(vec![0, 255]), Value::Blob(vec![])]])),
+            other => panic!("Expected INSERT statement, got {other:?}")
+        }
+        match parse_sql("CREATE TABLE files (hash BLOB)").unwrap() {
+            ASTNode::CreateTable(stmt) => assert_eq!(stmt.columns[0].column_type, ColumnType::Blob),
+            other => panic!("Expected CREATE TABLE, got {other:?}")
+        }
+        assert_eq!(parse_sql("DELETE FROM files WHERE length(hash) > 2").unwrap(), ASTNode::Delete(DeleteStatement {
+            table: "files".to_string(),
+            condition: Some(Condition::Compare(
+                Expression::Function("length".to_string(), vec![Expression::Column("hash".to_string())]),
+                ComparisonOperator::GreaterThan,
+                Expression::Literal(Value::Number(2.0))
+            ))
+        }));
+
+        assert!(parse_sql("INSERT INTO files VALUES (X'ABC')").is_err());
+        assert!(parse_sql("INSERT INTO files VALUES (X'GG')").is_err());
+    }
+
+    #[test]
+    fn test_create_table_with_foreign_key() {
+        let ast = parse_sql("CREATE TABLE orders (id INTEGER, customer_id INTEGER, FOREIGN KEY (customer_id) REFERENCES customers (id) ON DELETE SET NULL ON UPDATE CASCADE)").unwrap();
+        match ast {
+            ASTNode::CreateTable(stmt) => assert_eq!(stmt.constraints, vec![TableConstraint::ForeignKey(ForeignKey {
+                columns: vec!["customer_id".to_string()],
+                parent_table: "customers".to_string(),
+                parent_columns: vec!["id".to_string()],
+                on_delete: ForeignKeyAction::SetNull,
+                on_update: ForeignKeyAction::Cascade
+            })]),
+            _ => panic!("Expected CREATE TABLE statement")
+        }
+    }
+
+    #[test]
+    fn test_create_table_with_primary_key() {
+        let ast = parse_sql("CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT)").unwrap();
+        match ast {
+            ASTNode::CreateTable(stmt) => assert_eq!(stmt.columns[0].constraints, vec![ColumnConstraint::PrimaryKey { autoincrement: true }]),
+            _ => panic!("Expected CREATE TABLE statement")
+        }
+
+        let ast = parse_sql("CREATE TABLE tags (post INTEGER, tag TEXT, PRIMARY KEY (post, tag))").unwrap();
+        match ast {
+            ASTNode::CreateTable(stmt) => assert_eq!(stmt.constraints, vec![TableConstraint::PrimaryKey(vec!["post".to_string(), "tag".to_string()])]),
+            _ => panic!("Expected CREATE TABLE statement")
+        }
+
+        let ast = parse_sql("CREATE TABLE points (x INTEGER, y INTEGER) STRICT").unwrap();
+        match ast {
+            ASTNode::CreateTable(stmt) => assert!(stmt.strict),
+            _ => panic!("Expected CREATE TABLE statement")
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_bad_create_table_statement() {
+        parse_sql("CREATE TABLE users (id INTEGER NOT 5)").unwrap();
+    }
+
+    #[test]
+    fn test_transaction_statements() {
+        assert_eq!(parse_sql("BEGIN").unwrap(), ASTNode::Begin(TransactionMode::Deferred));
+        assert_eq!(parse_sql("BEGIN DEFERRED TRANSACTION").unwrap(), ASTNode::Begin(TransactionMode::Deferred));
+        assert_eq!(parse_sql("begin immediate").unwrap(), ASTNode::Begin(TransactionMode::Immediate));
+        assert_eq!(parse_sql("COMMIT TRANSACTION").unwrap(), ASTNode::Commit);
+        assert_eq!(parse_sql("END").unwrap(), ASTNode::Commit);
+        assert_eq!(parse_sql("ROLLBACK").unwrap(), ASTNode::Rollback);
+        assert!(parse_sql("BEGIN users").is_err());
+    }
+
+    #[test]
+    fn test_explain_statements() {
+        assert_eq!(parse_sql("EXPLAIN QUERY PLAN COMMIT").unwrap(), ASTNode::Explain(ExplainStatement {
+            mode: ExplainMode::QueryPlan,
+            statement: Box::new(ASTNode::Commit)
+        }));
+        assert_eq!(parse_sql("explain SELECT * FROM users").unwrap(), ASTNode::Explain(ExplainStatement {
+            mode: ExplainMode::Program,
+            statement: Box::new(parse_sql("SELECT * FROM users").unwrap())
+        }));
+        assert!(parse_sql("EXPLAIN QUERY SELECT * FROM users").is_err());
+        assert!(parse_sql("EXPLAIN EXPLAIN SELECT * FROM users").is_err());
+    }
+
+    #[test]
+    fn test_analyze_statement() {
+        assert_eq!(parse_sql("ANALYZE").unwrap(), ASTNode::Analyze(None));
+        assert_eq!(parse_sql("analyze users").unwrap(), ASTNode::Analyze(Some("users".to_string())));
+        assert!(parse_sql("ANALYZE users, orders").is_err());
+    }
+
+    #[test]
+    fn test_parameters() {
+        let mut parser = Parser::new(Lexer::new("UPDATE users SET name = :name, age = ? WHERE id = ?5 AND name <> :name"));
+        assert_eq!(parser.parse().unwrap(), ASTNode::Update(UpdateStatement {
+            table: "users".to_string(),
+            updates: vec![
+                ("name".to_string(), Value::Parameter(1)),
+                ("age".to_string(), Value::Parameter(2))
+            ],
+            condition: Some(Condition::And(
+                Box::new(Condition::Comparison("id".to_string(), ComparisonOperator::Equals, Value::Parameter(5))),
+                Box::new(Condition::Comparison("name".to_string(), ComparisonOperator::NotEquals, Value::Parameter(1)))
+            ))
+        }));
+        assert_eq!(parser.parameters(), &[Some(":name".to_string()), None, None, None, None]);
+
+        assert!(parse_sql("SELECT * FROM users WHERE id = ?0").is_err());
+        assert!(parse_sql("SELECT * FROM users WHERE id = ?32766").is_ok());
+        assert!(parse_sql("SELECT * FROM users WHERE id = ?32767").is_err());
+        assert!(parse_sql("SELECT a FROM t WHERE a = ?99999999999").is_err());
+        assert!(parse_sql("SELECT a FROM t WHERE a = ?99999999999999999999999").is_err());
+        assert!(parse_sql("CREATE TABLE users (id INTEGER DEFAULT ?)").is_err());
+        assert!(parse_sql("PRAGMA foreign_keys = ?").is_err());
+    }
+
+    #[test]
+    fn test_views() {
+        assert_eq!(parse_sql("CREATE VIEW adults (who, years) AS SELECT name, age FROM users WHERE age >= 18").unwrap(), ASTNode::CreateView(CreateViewStatement {
+            name: "adults".to_string(),
+            columns: vec!["who".to_string(), "years".to_string()],
+            query: SelectStatement {
+                columns: vec!["name".to_string(), "age".to_string()],
+                table: "users".to_string(),
+                condition: Some(Condition::Comparison("age".to_string(), ComparisonOperator::GreaterEqualThan, Value::Number(18.0))),
+                order_by: vec![]
+            }
+        }));
+        assert_eq!(parse_sql("DROP VIEW IF EXISTS adults").unwrap(), ASTNode::DropView(DropViewStatement { name: "adults".to_string(), if_exists: true }));
+        assert!(parse_sql("CREATE VIEW adults AS SELECT * FROM users WHERE age > ?").is_err());
+        assert!(parse_sql("CREATE VIEW adults SELECT * FROM users").is_err());
+        assert!(parse_sql("DROP TABLE users").is_err());
+    }
+
+    #[test]
+    fn test_triggers() {
+        let sql = "CREATE TRIGGER audit AFTER UPDATE OF age ON users WHEN NEW.age > OLD.age BEGIN INSERT INTO log VALUES (OLD.id, NEW.age); END";
+        assert_eq!(parse_sql(sql).unwrap(), ASTNode::CreateTrigger(CreateTriggerStatement {
+            name: "audit".to_string(),
+            table: "users".to_string(),
+            timing: TriggerTiming::After,
+            event: TriggerEvent::Update(vec!["age".to_string()]),
+            when: Some(Condition::Compare(
+                Expression::Literal(Value::Parameter(1)),
+                ComparisonOperator::GreaterThan,
+                Expression::Literal(Value::Parameter(2))
+            )),
+            body: vec![ASTNode::Insert(InsertStatement {
+                table: "log".to_string(),
+                columns: vec![],
+                source: InsertSource::Values(vec![vec![Value::Parameter(3), Value::Parameter(1)]])
+            })],
+            references: vec!["NEW.age".to_string(), "OLD.age".to_string(), "OLD.id".to_string()]
+        }));
+
+        let sql = "CREATE TRIGGER forget INSTEAD OF DELETE ON adults FOR EACH ROW BEGIN DELETE FROM users WHERE id = OLD.id; END";
+        assert_eq!(parse_sql(sql).unwrap(), ASTNode::CreateTrigger(CreateTriggerStatement {
+            name: "forget".to_string(),
+            table: "adults".to_string(),
+            timing: TriggerTiming::InsteadOf,
+            event: TriggerEvent::Delete,
+            when: None,
+            body: vec![ASTNode::Delete(DeleteStatement {
+                table: "users".to_string(),
+                condition: Some(Condition::Comparison("id".to_string(), ComparisonOperator::Equals, Value::Parameter(1)))
+            })],
+            references: vec!["OLD.id".to_string()]
+        }));
+        assert_eq!(parse_sql("DROP TRIGGER IF EXISTS audit").unwrap(), ASTNode::DropTrigger(DropTriggerStatement { name: "audit".to_string(), if_exists: true }));
+
+        assert!(parse_sql("CREATE TRIGGER t AFTER INSERT ON users BEGIN END").is_err());
+        assert!(parse_sql("CREATE TRIGGER t AFTER INSERT ON users BEGIN DELETE FROM log END").is_err());
+        assert!(parse_sql("CREATE TRIGGER t AFTER INSERT ON users BEGIN DELETE FROM log WHERE id = ?; END").is_err());
+        assert!(parse_sql("CREATE TRIGGER t AFTER INSERT ON users BEGIN DELETE FROM log WHERE id = users.id; END").is_err());
+        assert!(parse_sql("CREATE TRIGGER t AFTER INSERT ON users BEGIN CREATE TABLE log (id INTEGER); END").is_err());
+        assert!(parse_sql("SELECT * FROM users WHERE id = NEW.id").is_err());
+    }
+
+    #[test]
+    fn test_date_functions_and_types() {
+        let ast = parse_sql("SELECT * FROM events WHERE day > DATE('now', '-7 days')").unwrap();
+        assert_eq!(ast, ASTNode::Select(SelectStatement {
+            columns: vec!["*".to_string()],
+            table: "events".to_string(),
+            condition: Some(Condition::Compare(
+                Expression::Column("day".to_string()),
+                ComparisonOperator::GreaterThan,
+                Expression::Function("date".to_string(), vec![
+                    Expression::Literal(Value::String("now".to_string())),
+                    Expression::Literal(Value::String("-7 days".to_string()))
+                ])
+            )),
+            order_by: Vec::<OrderByClause>::new()
+        }));
+
+        match parse_sql("CREATE TABLE events (day DATE, at TIME, logged TIMESTAMP, seen DATETIME)").unwrap() {
+            ASTNode::CreateTable(stmt) => assert_eq!(
+                stmt.columns.iter().map(|col| col.column_type.clone()).collect::<Vec<_>>(),
+                vec![ColumnType::Date, ColumnType::Time, ColumnType::Timestamp, ColumnType::Timestamp]
+            ),
+            other => panic!("expected CREATE TABLE, got {other:?}")
+        }
+
+        assert!(parse_sql("SELECT * FROM events WHERE day = today()").is_err());
+        assert!(parse_sql("SELECT * FROM events WHERE day = date('now',)").is_err());
+    }
+}
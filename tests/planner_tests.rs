@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::io;
+
+use rust_sqlite::ast::Condition;
+use rust_sqlite::lexer::Lexer;
+use rust_sqlite::parser::Parser;
+use rust_sqlite::schema::{ColumnType, TableSchema};
+use rust_sqlite::statistics::TableStatistics;
+
+fn test_schema() -> TableSchema {
+    let mut schema = TableSchema::default();
+    for (name, column_type) in [("id", ColumnType::Integer), ("kind", ColumnType::String)] {
+        schema.columns.insert(name.to_string(), column_type);
+        schema.column_order.push(name.to_string());
+    }
+    schema
+}
+
+/// Rows with ids 1 to `count`, a kind cycling through three values and a
+/// missing kind on every tenth row.
+fn test_rows(count: usize) -> impl Iterator<Item = io::Result<HashMap<String, String>>> {
+    (1..=count).map(|id| {
+        let mut row = HashMap::from([("id".to_string(), id.to_string())]);
+        if id % 10 != 0 {
+            row.insert("kind".to_string(), format!("k{}", id % 3));
+        }
+        Ok(row)
+    })
+}
+
+fn parse_condition(sql: &str) -> Condition {
+    let mut parser = Parser::new(Lexer::new(sql));
+    parser.parse_condition().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_sqlite::planner::{estimated_rows, order_condition, selectivity};
+
+    #[test]
+    fn test_collect_counts_rows_nulls_and_distinct_values() {
+        let schema = test_schema();
+        let statistics = TableStatistics::collect(test_rows(1000), &schema).unwrap();
+
+        assert_eq!(statistics.row_count, 1000);
+        let id = &statistics.columns["id"];
+        assert_eq!((id.null_count, id.distinct), (0, 1000));
+        assert_eq!(id.histogram.iter().map(|bucket| bucket.rows).sum::<u64>(), 1000);
+        let kind = &statistics.columns["kind"];
+        assert_eq!((kind.null_count, kind.distinct), (100, 3));
+
+        // A table too large to sample in full gets an estimate.
+        let statistics = TableStatistics::collect(test_rows(30_000), &schema).unwrap();
+        let id = &statistics.columns["id"];
+        assert!((25_000..=30_000).contains(&id.distinct), "estimated {} distinct ids", id.distinct);
+        assert_eq!(statistics.columns["kind"].distinct, 3);
+    }
+
+    #[test]
+    fn test_estimates_follow_statistics() {
+        let schema = test_schema();
+        let statistics = TableStatistics::collect(test_rows(1000), &schema).unwrap();
+        let estimate = |sql: &str| estimated_rows(Some(&parse_condition(sql)), &statistics, &schema);
+
+        assert_eq!(estimate("id > 900"), 100);
+        assert_eq!(estimate("id <= 250"), 250);
+        assert_eq!(estimate("id = 5"), 1);
+        assert_eq!(estimate("kind = 'k1'"), 300);
+        assert_eq!(estimate("kind IS NULL"), 100);
+        assert_eq!(estimate("kind = NULL"), 0);
+        assert!((25..=35).contains(&estimate("kind = 'k1' AND id <= 100")));
+
+        // Without statistics, a fixed guess.
+        assert_eq!(selectivity(&parse_condition("kind = 'k1'"), None, &schema), 0.1);
+    }
+
+    #[test]
+    fn test_order_condition_tests_selective_terms_first() {
+        let schema = test_schema();
+        let statistics = TableStatistics::collect(test_rows(1000), &schema).unwrap();
+
+        let ordered = order_condition(&parse_condition("kind = 'k1' AND id > 10 AND id < 20"), Some(&statistics), &schema);
+        assert_eq!(ordered, parse_condition("id < 20 AND kind = 'k1' AND id > 10"));
+
+        let ordered = order_condition(&parse_condition("id < 20 OR kind IS NOT NULL"), Some(&statistics), &schema);
+        assert_eq!(ordered, parse_condition("kind IS NOT NULL OR id < 20"));
+    }
+}
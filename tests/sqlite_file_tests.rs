@@ -0,0 +1,307 @@
+use std::io;
+
+use rust_sqlite::ast::{ASTNode, Value};
+use rust_sqlite::database::Database;
+use rust_sqlite::execution_engine::{ExecutionEngine, ExecutionError, QueryResult, Row};
+use rust_sqlite::lexer::Lexer;
+use rust_sqlite::parser::Parser;
+
+mod common;
+use common::{select_rows as engine_rows, setup_test_engine};
+
+/// Written by SQLite 3.40 with a page size of 1024, so the 200 books left
+/// span several leaf pages under an interior one and the long values go
+/// on to overflow pages:
+///
+/// ```sql
+/// CREATE TABLE books (id INTEGER PRIMARY KEY, title TEXT NOT NULL, price REAL, cover BLOB, notes);
+/// CREATE TABLE "loans" ("isbn" VARCHAR(13) PRIMARY KEY, [book id] INT REFERENCES books(id), due DATE DEFAULT (date('now')), returned BOOLEAN);
+/// CREATE TABLE tags (tag TEXT PRIMARY KEY, uses INTEGER) WITHOUT ROWID;
+/// CREATE INDEX books_by_title ON books (title);
+/// CREATE VIEW cheap_books AS SELECT title, price FROM books WHERE price < 5;
+/// -- Books 1 to 300, titled 'Book 1' and so on, priced at a quarter of their id.
+/// UPDATE books SET cover = CAST(zeroblob(3000) || X'FF' AS BLOB), notes = 42 WHERE id = 7;
+/// UPDATE books SET notes = replace(hex(zeroblob(1500)), '00', 'ab') WHERE id = 8;
+/// UPDATE books SET notes = 'café ☕', price = NULL WHERE id = 9;
+/// DELETE FROM books WHERE id BETWEEN 100 AND 199;
+/// INSERT INTO loans VALUES ('9780000000001', 7, '2024-03-10', 1), ('9780000000002', 9, '2024-04-01', 0);
+/// INSERT INTO tags VALUES ('fiction', 3);
+/// ```
+const LIBRARY: &str = "tests/fixtures/library.db";
+
+/// A UTF-16le database with one table, `words (word TEXT, meaning TEXT)`,
+/// holding ('größe', 'size') and ('日本', 'Japan').
+const UTF16: &str = "tests/fixtures/utf16.db";
+
+/// `counters (id INTEGER PRIMARY KEY, n INTEGER, label TEXT, amount REAL)`
+/// holding (1, 9007199254740993, '9007199254740993', 9007199254740993.0),
+/// (2, -9223372036854775808, 'x', 1.5) and (3, 9223372036854775807, NULL, NULL).
+const BIG_INTEGERS: &str = "tests/fixtures/big_integers.db";
+
+/// Two tables with options after their columns:
+///
+/// ```sql
+/// CREATE TABLE settings (key TEXT PRIMARY KEY, value INTEGER) WITHOUT ROWID, STRICT;
+/// CREATE TABLE events (id INTEGER PRIMARY KEY, name TEXT) STRICT;
+/// INSERT INTO settings VALUES ('retries', 3);
+/// INSERT INTO events VALUES (1, 'start'), (2, 'stop');
+/// ```
+const TABLE_OPTIONS: &str = "tests/fixtures/table_options.db";
+
+/// A table with columns added after its first row was written, so that
+/// row's record is shorter than the column list:
+///
+/// ```sql
+/// CREATE TABLE members (id INTEGER PRIMARY KEY, name TEXT);
+/// INSERT INTO members VALUES (1, 'Alice');
+/// ALTER TABLE members ADD COLUMN level INTEGER DEFAULT 1;
+/// ALTER TABLE members ADD COLUMN status TEXT DEFAULT 'active';
+/// ALTER TABLE members ADD COLUMN note;
+/// INSERT INTO members VALUES (2, 'Bob', 5, 'away', 'new');
+/// ```
+const ADDED_COLUMNS: &str = "tests/fixtures/added_columns.db";
+
+/// Blobs in columns of every kind that can hold them:
+///
+/// ```sql
+/// CREATE TABLE attachments (name TEXT, data, kind TEXT, size BLOB);
+/// INSERT INTO attachments VALUES ('a', X'00FF', X'CAFE', 42), ('b', NULL, 'text/plain', X'01');
+/// ```
+const BLOBS: &str = "tests/fixtures/blobs.db";
+
+fn parse(sql: &str) -> ASTNode {
+    Parser::new(Lexer::new(sql)).parse().unwrap()
+}
+
+fn select_rows(database: &Database, sql: &str) -> Vec<Row> {
+    match database.execute(&parse(sql)).unwrap() {
+        QueryResult::Select { rows, .. } => rows,
+        result => panic!("expected rows, got {result:?}")
+    }
+}
+
+fn text(s: &str) -> Value {
+    Value::String(s.to_string())
+}
+
+/// A shop with enough orders to need interior pages, notes long enough to
+/// need overflow pages, a PRIMARY KEY that is not the rowid, a view and a
+/// trigger.
+fn setup_shop(engine: &mut ExecutionEngine) {
+    for sql in [
+        "CREATE TABLE products (sku TEXT PRIMARY KEY, name TEXT NOT NULL, price REAL, in_stock BOOLEAN DEFAULT TRUE, added DATE, image BLOB)",
+        "CREATE TABLE orders (id INTEGER PRIMARY KEY AUTOINCREMENT, sku TEXT REFERENCES products (sku), quantity INTEGER CHECK (quantity > 0), note TEXT)",
+        "CREATE VIEW big_orders AS SELECT id, sku FROM orders WHERE quantity > 8",
+        "CREATE TRIGGER no_stock AFTER INSERT ON orders WHEN NEW.quantity > 9 BEGIN UPDATE products SET in_stock = FALSE WHERE sku = NEW.sku; END",
+        "INSERT INTO products VALUES ('b-2', 'Bolt', 0.25, TRUE, '2024-01-05', X'B017'), ('a-1', 'Anvil', 120, FALSE, NULL, X''), ('c-3', 'Chain', NULL, NULL, '2023-12-31', NULL)"
+    ] {
+        engine.execute(&parse(sql)).unwrap();
+    }
+    let skus = ["a-1", "b-2", "c-3"];
+    let orders: Vec<String> = (1..=600).map(|i| {
+        let note = if i % 100 == 0 { "x".repeat(10000) } else { format!("order {i}") };
+        format!("('{}', {}, '{note}')", skus[i % 3], i % 10 + 1)
+    }).collect();
+    engine.execute(&parse(&format!("INSERT INTO orders (sku, quantity, note) VALUES {}", orders.join(", ")))).unwrap();
+    engine.execute(&parse("DELETE FROM orders WHERE id > 590")).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_sqlite::sqlite_file::{export_sqlite_file, SqliteFile};
+
+    #[test]
+    fn test_reads_tables_across_pages() {
+        let database = Database::open_sqlite_file(LIBRARY).unwrap();
+
+        assert_eq!(select_rows(&database, "SELECT id FROM books").len(), 200);
+        assert_eq!(select_rows(&database, "SELECT id, title, price FROM books WHERE id > 97 AND id < 202 ORDER BY id"), vec![
+            Row { values: vec![Value::Number(98.0), text("Book 98"), Value::Number(24.5)] },
+            Row { values: vec![Value::Number(99.0), text("Book 99"), Value::Number(24.75)] },
+            Row { values: vec![Value::Number(200.0), text("Book 200"), Value::Number(50.0)] },
+            Row { values: vec![Value::Number(201.0), text("Book 201"), Value::Number(50.25)] }
+        ]);
+        assert_eq!(select_rows(&database, "SELECT rowid FROM books WHERE title = 'Book 300'"), vec![Row { values: vec![Value::Number(300.0)] }]);
+
+        // Values too long for their page are read from overflow pages.
+        let mut cover = vec![0; 3000];
+        cover.push(255);
+        assert_eq!(select_rows(&database, "SELECT cover, notes FROM books WHERE id = 7"), vec![
+            Row { values: vec![Value::Blob(cover), text("42")] }
+        ]);
+        assert_eq!(select_rows(&database, "SELECT notes FROM books WHERE id = 8"), vec![Row { values: vec![text(&"ab".repeat(1500))] }]);
+        assert_eq!(select_rows(&database, "SELECT price, notes FROM books WHERE id = 9"), vec![
+            Row { values: vec![Value::Null, text("café ☕")] }
+        ]);
+
+        // A table whose primary key is not an INTEGER keeps its own rowids.
+        assert_eq!(select_rows(&database, "SELECT rowid, isbn, due, returned FROM loans ORDER BY rowid"), vec![
+            Row { values: vec![Value::Number(1.0), text("9780000000001"), text("2024-03-10"), Value::Boolean(true)] },
+            Row { values: vec![Value::Number(2.0), text("9780000000002"), text("2024-04-01"), Value::Boolean(false)] }
+        ]);
+        assert_eq!(select_rows(&database, "SELECT title FROM cheap_books WHERE price > 4"), vec![
+            Row { values: vec![text("Book 17")] },
+            Row { values: vec![text("Book 18")] },
+            Row { values: vec![text("Book 19")] }
+        ]);
+
+        // WITHOUT ROWID tables are not read.
+        assert!(database.execute(&parse("SELECT tag FROM tags")).is_err());
+    }
+
+    #[test]
+    fn test_reads_utf16_text() {
+        let database = Database::open_sqlite_file(UTF16).unwrap();
+        assert_eq!(select_rows(&database, "SELECT word FROM words WHERE meaning = 'Japan'"), vec![Row { values: vec![text("日本")] }]);
+        assert_eq!(select_rows(&database, "SELECT meaning FROM words WHERE word = 'größe'"), vec![Row { values: vec![text("size")] }]);
+    }
+
+    #[test]
+    fn test_keeps_integers_past_f64_precision() {
+        let (_, _, rows) = SqliteFile::open(BIG_INTEGERS).unwrap().tables().unwrap().remove(0);
+        let field = |row: usize, column: &str| rows[row].get(column).cloned().flatten();
+        assert_eq!(field(0, "n").as_deref(), Some("9007199254740993"));
+        assert_eq!(field(0, "label").as_deref(), Some("9007199254740993"));
+        assert_eq!(field(0, "amount").as_deref(), Some("9007199254740992"));
+        assert_eq!(field(1, "n").as_deref(), Some("-9223372036854775808"));
+        assert_eq!(field(2, "n").as_deref(), Some("9223372036854775807"));
+    }
+
+    #[test]
+    fn test_skips_without_rowid_tables_with_other_options() {
+        let database = Database::open_sqlite_file(TABLE_OPTIONS).unwrap();
+        assert_eq!(select_rows(&database, "SELECT name FROM events ORDER BY id"), vec![
+            Row { values: vec![text("start")] },
+            Row { values: vec![text("stop")] }
+        ]);
+        assert!(database.execute(&parse("SELECT key FROM settings")).is_err());
+    }
+
+    #[test]
+    fn test_fills_added_columns_from_their_defaults() {
+        let database = Database::open_sqlite_file(ADDED_COLUMNS).unwrap();
+        assert_eq!(select_rows(&database, "SELECT id, name, level, status, note FROM members ORDER BY id"), vec![
+            Row { values: vec![Value::Number(1.0), text("Alice"), Value::Number(1.0), text("active"), Value::Null] },
+            Row { values: vec![Value::Number(2.0), text("Bob"), Value::Number(5.0), text("away"), text("new")] }
+        ]);
+    }
+
+    #[test]
+    fn test_keeps_blobs_in_untyped_and_text_columns() {
+        let database = Database::open_sqlite_file(BLOBS).unwrap();
+        assert_eq!(select_rows(&database, "SELECT name, data, kind, size FROM attachments ORDER BY name"), vec![
+            Row { values: vec![text("a"), Value::Blob(vec![0x00, 0xFF]), Value::Blob(vec![0xCA, 0xFE]), Value::Blob(b"42".to_vec())] },
+            Row { values: vec![text("b"), Value::Null, Value::Blob(b"text/plain".to_vec()), Value::Blob(vec![0x01])] }
+        ]);
+    }
+
+    #[test]
+    fn test_schema() {
+        let file = SqliteFile::open(LIBRARY).unwrap();
+        let objects: Vec<(String, String, u32)> = file.schema().unwrap().into_iter().map(|entry| (entry.kind, entry.name, entry.root_page)).collect();
+        assert_eq!(objects, vec![
+            ("table".to_string(), "books".to_string(), 2),
+            ("table".to_string(), "loans".to_string(), 3),
+            ("index".to_string(), "sqlite_autoindex_loans_1".to_string(), 4),
+            ("table".to_string(), "tags".to_string(), 5),
+            ("index".to_string(), "books_by_title".to_string(), 6),
+            ("view".to_string(), "cheap_books".to_string(), 0)
+        ]);
+
+        let tables: Vec<String> = file.tables().unwrap().into_iter().map(|(name, ..)| name).collect();
+        assert_eq!(tables, vec!["books", "loans"]);
+        let (_, loans, _) = file.tables().unwrap().remove(1);
+        assert_eq!(loans.column_names(), vec!["isbn", "book id", "due", "returned"]);
+        assert_eq!(loans.rowid_alias(), None);
+    }
+
+    #[test]
+    fn test_is_read_only() {
+        let database = Database::open_sqlite_file(LIBRARY).unwrap();
+        assert!(database.is_read_only());
+
+        for sql in ["INSERT INTO books (title) VALUES ('New')", "DELETE FROM books", "CREATE TABLE t (a INTEGER)", "PRAGMA foreign_keys = OFF"] {
+            assert!(matches!(database.execute(&parse(sql)), Err(ExecutionError::Storage(err)) if err.kind() == io::ErrorKind::PermissionDenied), "{sql}");
+        }
+        assert!(matches!(database.transaction(|_| Ok(())), Err(ExecutionError::Storage(err)) if err.kind() == io::ErrorKind::PermissionDenied));
+        assert_eq!(select_rows(&database, "PRAGMA foreign_keys"), vec![Row { values: vec![Value::Number(1.0)] }]);
+        assert_eq!(select_rows(&database, "SELECT id FROM books").len(), 200);
+    }
+
+    #[test]
+    fn test_export_reads_back() {
+        let (mut engine, temp_dir) = setup_test_engine();
+        setup_shop(&mut engine);
+
+        let mut output = Vec::new();
+        assert_eq!(export_sqlite_file(&mut engine, &mut output).unwrap(), 593);
+        assert_eq!(&output[..16], b"SQLite format 3\0");
+        assert_eq!(output.len() % 4096, 0);
+
+        let file = SqliteFile::from_bytes(output.clone()).unwrap();
+        let objects: Vec<(String, String, Option<String>)> = file.schema().unwrap().into_iter().map(|entry| (entry.kind, entry.name, entry.sql)).collect();
+        assert_eq!(objects, vec![
+            ("view".to_string(), "big_orders".to_string(), Some("CREATE VIEW big_orders (id, sku) AS SELECT id, sku FROM orders WHERE quantity > 8".to_string())),
+            ("table".to_string(), "orders".to_string(), Some("CREATE TABLE orders (id INTEGER PRIMARY KEY AUTOINCREMENT, sku TEXT REFERENCES products (sku), quantity INTEGER CHECK (quantity > 0), note TEXT)".to_string())),
+            ("trigger".to_string(), "no_stock".to_string(), Some("CREATE TRIGGER no_stock AFTER INSERT ON orders FOR EACH ROW WHEN NEW.quantity > 9 BEGIN UPDATE products SET in_stock = FALSE WHERE sku = NEW.sku; END".to_string())),
+            ("table".to_string(), "products".to_string(), Some("CREATE TABLE products (sku TEXT PRIMARY KEY, name TEXT NOT NULL, price REAL, in_stock BOOLEAN DEFAULT TRUE, added DATE, image BLOB)".to_string())),
+            ("index".to_string(), "sqlite_autoindex_products_1".to_string(), None),
+            ("table".to_string(), "sqlite_sequence".to_string(), Some("CREATE TABLE sqlite_sequence(name,seq)".to_string()))
+        ]);
+        let sequence_root = file.schema().unwrap().last().unwrap().root_page;
+        assert_eq!(file.table_rows(sequence_root).unwrap(), vec![(1, vec![text("orders"), Value::Number(600.0)])]);
+
+        // An INTEGER PRIMARY KEY is stored as NULL, since the rowid is its value.
+        let orders_root = file.schema().unwrap()[1].root_page;
+        let orders = file.table_rows(orders_root).unwrap();
+        assert_eq!(orders.len(), 590);
+        assert_eq!(orders[99], (100, vec![Value::Null, text("b-2"), Value::Number(1.0), text(&"x".repeat(10000))]));
+
+        // Every row reads back as it was.
+        let path = temp_dir.path().join("shop.db");
+        std::fs::write(&path, output).unwrap();
+        let database = Database::open_sqlite_file(&path).unwrap();
+        for sql in [
+            "SELECT rowid, sku, name, price, in_stock, added, image FROM products ORDER BY sku",
+            "SELECT id, sku, quantity, note FROM orders ORDER BY id",
+            "SELECT id FROM big_orders WHERE sku = 'a-1' ORDER BY id"
+        ] {
+            assert_eq!(select_rows(&database, sql), engine_rows(&mut engine, sql), "{sql}");
+        }
+        assert_eq!(select_rows(&database, "SELECT price FROM products WHERE sku = 'a-1'"), vec![Row { values: vec![Value::Number(120.0)] }]);
+    }
+
+    #[test]
+    fn test_export_empty_database() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        let mut output = Vec::new();
+        assert_eq!(export_sqlite_file(&mut engine, &mut output).unwrap(), 0);
+        assert_eq!(output.len(), 4096);
+        assert_eq!(SqliteFile::from_bytes(output).unwrap().schema().unwrap(), Vec::new());
+    }
+
+    #[test]
+    fn test_refuses_files_that_are_not_databases() {
+        let err = SqliteFile::open("Cargo.toml").err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "file is not a database");
+
+        // A file cut short is malformed rather than read past its end.
+        let data = std::fs::read(LIBRARY).unwrap();
+        let err = SqliteFile::from_bytes(data[..4096].to_vec()).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().starts_with("database disk image is malformed"));
+    }
+
+    #[test]
+    fn test_refuses_cells_past_the_end_of_their_page() {
+        // The first cell pointer of the interior page at the root of books,
+        // moved to the last two bytes of the page.
+        let mut data = std::fs::read(LIBRARY).unwrap();
+        data[1024 + 12..1024 + 14].copy_from_slice(&1022u16.to_be_bytes());
+        let err = SqliteFile::from_bytes(data).unwrap().tables().err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().starts_with("database disk image is malformed"));
+    }
+}
@@ -0,0 +1,98 @@
+use tempfile::TempDir;
+
+use rust_sqlite::execution_engine::{ExecutionEngine, ExecutionError, QueryResult, Row};
+use rust_sqlite::lexer::Lexer;
+use rust_sqlite::parser::Parser;
+
+mod common;
+use common::execute_sql;
+
+fn setup_test_engine() -> (ExecutionEngine, TempDir) {
+    let (mut engine, temp_dir) = common::setup_test_engine();
+    execute_sql(&mut engine, "CREATE TABLE users (id INTEGER, name TEXT, age INTEGER)").unwrap();
+    (engine, temp_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_sqlite::ast::Value;
+    use rust_sqlite::database::Database;
+    use rust_sqlite::semantic_analyzer::SemanticError;
+
+    #[test]
+    fn test_prepared_insert_runs_many_times() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        let mut insert = engine.prepare("INSERT INTO users VALUES (?, ?, ?)").unwrap();
+        assert_eq!(insert.parameter_count(), 3);
+
+        for (id, name) in [(1, "Alice"), (2, "Bob's")] {
+            insert.bind(1, id).unwrap();
+            insert.bind(2, name).unwrap();
+            insert.bind(3, None::<i64>).unwrap();
+            insert.execute(&mut engine).unwrap();
+        }
+
+        let select = engine.prepare("SELECT name FROM users WHERE age IS NULL ORDER BY id").unwrap();
+        let rows: Vec<Row> = select.query(&mut engine).unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(rows, vec![
+            Row { values: vec![Value::String("Alice".to_string())] },
+            Row { values: vec![Value::String("Bob's".to_string())] }
+        ]);
+    }
+
+    #[test]
+    fn test_named_parameters() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        let mut insert = engine.prepare("INSERT INTO users VALUES ($id, :name, :age)").unwrap();
+        for (id, name, age) in [(1, "Alice", 30), (2, "Bob", 40), (3, "Carol", 50)] {
+            insert.bind_named("$id", id).unwrap();
+            insert.bind_named(":name", name).unwrap();
+            insert.bind_named(":age", age).unwrap();
+            insert.execute(&mut engine).unwrap();
+        }
+
+        let mut select = engine.prepare("SELECT name FROM users WHERE age > :age AND age <> :age + 20").unwrap();
+        assert_eq!(select.parameter_count(), 1);
+        select.bind_named(":age", 30).unwrap();
+        let names: Vec<Row> = select.query(&mut engine).unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(names, vec![Row { values: vec![Value::String("Bob".to_string())] }]);
+
+        // Unbound parameters are NULL, which matches nothing.
+        select.clear_bindings();
+        assert_eq!(select.query(&mut engine).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_bind_errors() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        let mut update = engine.prepare("UPDATE users SET name = ? WHERE id = :id").unwrap();
+
+        // Numbers are stored in TEXT columns as their text, but text that is
+        // not a number is no INTEGER.
+        update.bind(1, 42).unwrap();
+        assert!(matches!(update.bind_named(":id", "one"), Err(ExecutionError::Semantic(SemanticError::TypeMismatch(col))) if col == "id"));
+        assert!(matches!(update.bind(3, 1), Err(ExecutionError::UnknownParameter(_))));
+        assert!(matches!(update.bind_named(":missing", 1), Err(ExecutionError::UnknownParameter(_))));
+
+        assert!(matches!(engine.prepare("SELECT * FROM missing WHERE id = ?"), Err(ExecutionError::Semantic(SemanticError::TableNotFound(_)))));
+        assert!(matches!(engine.prepare("SELECT * FROM users WHERE"), Err(ExecutionError::Parse(_))));
+    }
+
+    #[test]
+    fn test_database_prepare() {
+        let temp_dir = TempDir::new().unwrap();
+        let database = Database::open(temp_dir.path()).unwrap();
+        database.execute(&Parser::new(Lexer::new("CREATE TABLE users (id INTEGER, name TEXT)")).parse().unwrap()).unwrap();
+
+        let mut insert = database.prepare("INSERT INTO users VALUES (?1, ?2)").unwrap();
+        insert.bind(1, 7).unwrap();
+        insert.bind(2, "Alice").unwrap();
+        database.execute(&insert.to_ast()).unwrap();
+
+        let mut select = database.prepare("SELECT name FROM users WHERE id = ?").unwrap();
+        select.bind(1, 7).unwrap();
+        let QueryResult::Select { rows, .. } = database.execute(&select.to_ast()).unwrap() else { panic!("Expected rows") };
+        assert_eq!(rows, vec![Row { values: vec![Value::String("Alice".to_string())] }]);
+    }
+}
@@ -0,0 +1,176 @@
+use rust_sqlite::datastore::DataStore;
+use rust_sqlite::execution_engine::{ExecutionEngine, ExecutionError, QueryResult, Row};
+
+mod common;
+use common::{execute_sql, select_rows, setup_test_engine};
+
+fn setup_users(engine: &mut ExecutionEngine) {
+    execute_sql(engine, "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, age INTEGER)").unwrap();
+    execute_sql(engine, "CREATE TABLE log (user_id INTEGER, note TEXT NOT NULL, age INTEGER)").unwrap();
+    execute_sql(engine, "INSERT INTO users VALUES (1, 'Alice', 30), (2, 'Bob', 17)").unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_sqlite::ast::Value;
+    use rust_sqlite::semantic_analyzer::SemanticError;
+
+    fn text(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    fn log_row(user_id: f64, note: &str, age: Value) -> Row {
+        Row { values: vec![Value::Number(user_id), text(note), age] }
+    }
+
+    #[test]
+    fn test_insert_triggers() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        setup_users(&mut engine);
+        execute_sql(&mut engine, "CREATE TRIGGER added AFTER INSERT ON users BEGIN INSERT INTO log VALUES (NEW.id, 'added', NEW.age); END").unwrap();
+        execute_sql(&mut engine, "CREATE TRIGGER adult AFTER INSERT ON users FOR EACH ROW WHEN NEW.age >= 18 BEGIN INSERT INTO log VALUES (NEW.id, 'adult', NULL); END").unwrap();
+
+        assert_eq!(execute_sql(&mut engine, "INSERT INTO users (name, age) VALUES ('Carol', 45), ('Dave', 12)").unwrap(), QueryResult::Insert(2));
+        assert_eq!(select_rows(&mut engine, "SELECT * FROM log"), vec![
+            log_row(3.0, "added", Value::Number(45.0)),
+            log_row(3.0, "adult", Value::Null),
+            log_row(4.0, "added", Value::Number(12.0))
+        ]);
+    }
+
+    #[test]
+    fn test_update_and_delete_triggers() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        setup_users(&mut engine);
+        execute_sql(&mut engine, "CREATE TRIGGER birthday AFTER UPDATE OF age ON users BEGIN INSERT INTO log VALUES (OLD.id, OLD.name, NEW.age); END").unwrap();
+        execute_sql(&mut engine, "CREATE TRIGGER removed BEFORE DELETE ON users BEGIN INSERT INTO log VALUES (OLD.id, 'removed', OLD.age); END").unwrap();
+
+        // Only updates setting one of the listed columns fire the trigger.
+        execute_sql(&mut engine, "UPDATE users SET name = 'Bobby' WHERE id = 2").unwrap();
+        assert_eq!(execute_sql(&mut engine, "UPDATE users SET age = 18 WHERE age < 40").unwrap(), QueryResult::Update(2));
+        execute_sql(&mut engine, "DELETE FROM users WHERE id = 2").unwrap();
+
+        assert_eq!(select_rows(&mut engine, "SELECT * FROM log"), vec![
+            log_row(1.0, "Alice", Value::Number(18.0)),
+            log_row(2.0, "Bobby", Value::Number(18.0)),
+            log_row(2.0, "removed", Value::Number(18.0))
+        ]);
+        assert_eq!(select_rows(&mut engine, "SELECT name FROM users"), vec![Row { values: vec![text("Alice")] }]);
+    }
+
+    #[test]
+    fn test_instead_of_triggers_on_views() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        setup_users(&mut engine);
+        execute_sql(&mut engine, "CREATE VIEW adults AS SELECT id, name FROM users WHERE age >= 18").unwrap();
+        execute_sql(&mut engine, "CREATE TRIGGER add_adult INSTEAD OF INSERT ON adults BEGIN INSERT INTO users VALUES (NEW.id, NEW.name, 18); END").unwrap();
+        execute_sql(&mut engine, "CREATE TRIGGER rename_adult INSTEAD OF UPDATE ON adults BEGIN UPDATE users SET name = NEW.name WHERE id = OLD.id; END").unwrap();
+        execute_sql(&mut engine, "CREATE TRIGGER forget_adult INSTEAD OF DELETE ON adults BEGIN DELETE FROM users WHERE id = OLD.id; END").unwrap();
+
+        assert_eq!(execute_sql(&mut engine, "INSERT INTO adults VALUES (3, 'Carol')").unwrap(), QueryResult::Insert(1));
+        assert_eq!(execute_sql(&mut engine, "UPDATE adults SET name = 'Alicia' WHERE id = 1").unwrap(), QueryResult::Update(1));
+        assert_eq!(execute_sql(&mut engine, "DELETE FROM adults WHERE id = 3").unwrap(), QueryResult::Delete(1));
+        assert_eq!(select_rows(&mut engine, "SELECT * FROM users"), vec![
+            Row { values: vec![Value::Number(1.0), text("Alicia"), Value::Number(30.0)] },
+            Row { values: vec![Value::Number(2.0), text("Bob"), Value::Number(17.0)] }
+        ]);
+
+        // Views without a trigger for the change stay read-only.
+        execute_sql(&mut engine, "DROP TRIGGER forget_adult").unwrap();
+        assert!(matches!(execute_sql(&mut engine, "DELETE FROM adults"), Err(ExecutionError::ReadOnlyTable(_))));
+    }
+
+    #[test]
+    fn test_failing_trigger_rolls_back_statement() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        setup_users(&mut engine);
+        execute_sql(&mut engine, "CREATE TRIGGER added AFTER INSERT ON users BEGIN INSERT INTO log VALUES (NEW.id, NEW.name, NEW.age); END").unwrap();
+
+        assert!(execute_sql(&mut engine, "INSERT INTO users VALUES (3, 'Carol', 45), (4, NULL, 12)").is_err());
+        assert_eq!(select_rows(&mut engine, "SELECT id FROM users WHERE id > 2"), vec![]);
+        assert_eq!(select_rows(&mut engine, "SELECT * FROM log"), vec![]);
+
+        // Inside a transaction only the failing statement is undone.
+        execute_sql(&mut engine, "BEGIN").unwrap();
+        execute_sql(&mut engine, "INSERT INTO users VALUES (3, 'Carol', 45)").unwrap();
+        assert!(execute_sql(&mut engine, "INSERT INTO users VALUES (4, NULL, 12)").is_err());
+        execute_sql(&mut engine, "COMMIT").unwrap();
+        assert_eq!(select_rows(&mut engine, "SELECT user_id FROM log"), vec![Row { values: vec![Value::Number(3.0)] }]);
+    }
+
+    #[test]
+    fn test_triggers_do_not_recurse() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        setup_users(&mut engine);
+        execute_sql(&mut engine, "CREATE TRIGGER older AFTER UPDATE ON users BEGIN UPDATE users SET age = 99 WHERE id = NEW.id; END").unwrap();
+
+        execute_sql(&mut engine, "UPDATE users SET age = 40 WHERE id = 1").unwrap();
+        assert_eq!(select_rows(&mut engine, "SELECT age FROM users WHERE id = 1"), vec![Row { values: vec![Value::Number(99.0)] }]);
+    }
+
+    #[test]
+    fn test_create_trigger_errors() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        setup_users(&mut engine);
+        execute_sql(&mut engine, "CREATE VIEW adults AS SELECT id, name FROM users WHERE age >= 18").unwrap();
+
+        assert!(matches!(execute_sql(&mut engine, "CREATE TRIGGER t AFTER INSERT ON missing BEGIN DELETE FROM log; END"), Err(ExecutionError::Semantic(SemanticError::TableNotFound(_)))));
+        assert!(matches!(execute_sql(&mut engine, "CREATE TRIGGER t AFTER UPDATE OF height ON users BEGIN DELETE FROM log; END"), Err(ExecutionError::Semantic(SemanticError::ColumnNotFound(_)))));
+        assert!(matches!(execute_sql(&mut engine, "CREATE TRIGGER t AFTER INSERT ON users BEGIN DELETE FROM log WHERE user_id = OLD.id; END"), Err(ExecutionError::Semantic(SemanticError::ColumnNotFound(_)))));
+        assert!(matches!(execute_sql(&mut engine, "CREATE TRIGGER t AFTER DELETE ON users BEGIN DELETE FROM log WHERE user_id = NEW.id; END"), Err(ExecutionError::Semantic(SemanticError::ColumnNotFound(_)))));
+        assert!(matches!(execute_sql(&mut engine, "CREATE TRIGGER t AFTER INSERT ON users BEGIN DELETE FROM log WHERE user_id = NEW.height; END"), Err(ExecutionError::Semantic(SemanticError::ColumnNotFound(_)))));
+        assert!(matches!(execute_sql(&mut engine, "CREATE TRIGGER t AFTER INSERT ON users BEGIN DELETE FROM missing; END"), Err(ExecutionError::Semantic(SemanticError::TableNotFound(_)))));
+        assert!(matches!(execute_sql(&mut engine, "CREATE TRIGGER t INSTEAD OF INSERT ON users BEGIN DELETE FROM log; END"), Err(ExecutionError::InvalidTrigger(_))));
+        assert!(matches!(execute_sql(&mut engine, "CREATE TRIGGER t AFTER INSERT ON adults BEGIN DELETE FROM log; END"), Err(ExecutionError::InvalidTrigger(_))));
+        assert!(matches!(execute_sql(&mut engine, "CREATE TRIGGER t AFTER INSERT ON sqlite_schema BEGIN DELETE FROM log; END"), Err(ExecutionError::ReadOnlyTable(_))));
+
+        execute_sql(&mut engine, "CREATE TRIGGER t AFTER INSERT ON users BEGIN DELETE FROM log; END").unwrap();
+        assert!(matches!(execute_sql(&mut engine, "CREATE TRIGGER t AFTER DELETE ON users BEGIN DELETE FROM log; END"), Err(ExecutionError::TriggerAlreadyExists(_))));
+    }
+
+    #[test]
+    fn test_drop_trigger() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        setup_users(&mut engine);
+        execute_sql(&mut engine, "CREATE TRIGGER added AFTER INSERT ON users BEGIN INSERT INTO log VALUES (NEW.id, 'added', NULL); END").unwrap();
+
+        assert_eq!(execute_sql(&mut engine, "DROP TRIGGER added").unwrap(), QueryResult::DropTrigger);
+        execute_sql(&mut engine, "INSERT INTO users VALUES (3, 'Carol', 45)").unwrap();
+        assert_eq!(select_rows(&mut engine, "SELECT * FROM log"), vec![]);
+        assert!(matches!(execute_sql(&mut engine, "DROP TRIGGER added"), Err(ExecutionError::TriggerNotFound(_))));
+        assert_eq!(execute_sql(&mut engine, "DROP TRIGGER IF EXISTS added").unwrap(), QueryResult::DropTrigger);
+
+        // Dropping a view drops its triggers.
+        execute_sql(&mut engine, "CREATE VIEW adults AS SELECT id, name FROM users WHERE age >= 18").unwrap();
+        execute_sql(&mut engine, "CREATE TRIGGER forget INSTEAD OF DELETE ON adults BEGIN DELETE FROM users WHERE id = OLD.id; END").unwrap();
+        execute_sql(&mut engine, "DROP VIEW adults").unwrap();
+        assert!(matches!(execute_sql(&mut engine, "DROP TRIGGER forget"), Err(ExecutionError::TriggerNotFound(_))));
+    }
+
+    #[test]
+    fn test_triggers_persist_and_roll_back() {
+        let (mut engine, temp_dir) = setup_test_engine();
+        setup_users(&mut engine);
+        execute_sql(&mut engine, "CREATE TRIGGER added AFTER INSERT ON users WHEN NEW.age > 18 BEGIN INSERT INTO log (user_id, note) VALUES (NEW.id, 'added'); UPDATE log SET age = NEW.age WHERE user_id = NEW.id; END").unwrap();
+
+        execute_sql(&mut engine, "BEGIN").unwrap();
+        execute_sql(&mut engine, "DROP TRIGGER added").unwrap();
+        execute_sql(&mut engine, "CREATE TRIGGER removed AFTER DELETE ON users BEGIN DELETE FROM log WHERE user_id = OLD.id; END").unwrap();
+        execute_sql(&mut engine, "ROLLBACK").unwrap();
+        drop(engine);
+
+        let mut engine = ExecutionEngine::new(DataStore::new(temp_dir.path()).unwrap());
+        assert!(matches!(execute_sql(&mut engine, "DROP TRIGGER removed"), Err(ExecutionError::TriggerNotFound(_))));
+        execute_sql(&mut engine, "INSERT INTO users VALUES (3, 'Carol', 45)").unwrap();
+        assert_eq!(select_rows(&mut engine, "SELECT * FROM log"), vec![log_row(3.0, "added", Value::Number(45.0))]);
+        assert_eq!(select_rows(&mut engine, "SELECT type, name, tbl_name, sql FROM sqlite_schema WHERE type = 'trigger'"), vec![
+            Row { values: vec![
+                text("trigger"),
+                text("added"),
+                text("users"),
+                text("CREATE TRIGGER added AFTER INSERT ON users FOR EACH ROW WHEN NEW.age > 18 BEGIN INSERT INTO log (user_id, note) VALUES (NEW.id, 'added'); UPDATE log SET age = NEW.age WHERE user_id = NEW.id; END")
+            ] }
+        ]);
+    }
+}
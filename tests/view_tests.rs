@@ -0,0 +1,108 @@
+use rust_sqlite::datastore::DataStore;
+use rust_sqlite::execution_engine::{ExecutionEngine, ExecutionError, QueryResult, Row};
+
+mod common;
+use common::{execute_sql, select_rows, setup_test_engine};
+
+fn setup_users(engine: &mut ExecutionEngine) {
+    execute_sql(engine, "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, age INTEGER)").unwrap();
+    execute_sql(engine, "INSERT INTO users VALUES (1, 'Alice', 30), (2, 'Bob', 17), (3, 'Carol', 45)").unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_sqlite::ast::Value;
+    use rust_sqlite::semantic_analyzer::SemanticError;
+
+    fn text(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    #[test]
+    fn test_select_from_view() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        setup_users(&mut engine);
+        execute_sql(&mut engine, "CREATE VIEW adults (who, years) AS SELECT name, age FROM users WHERE age >= 18").unwrap();
+
+        let result = execute_sql(&mut engine, "SELECT * FROM adults ORDER BY years DESC").unwrap();
+        assert_eq!(result, QueryResult::Select {
+            columns: vec!["who".to_string(), "years".to_string()],
+            rows: vec![
+                Row { values: vec![text("Carol"), Value::Number(45.0)] },
+                Row { values: vec![text("Alice"), Value::Number(30.0)] }
+            ]
+        });
+        assert_eq!(select_rows(&mut engine, "SELECT who FROM adults WHERE years < 40"), vec![Row { values: vec![text("Alice")] }]);
+
+        // Views see later changes to their table, and can be built on.
+        execute_sql(&mut engine, "UPDATE users SET age = 18 WHERE id = 2").unwrap();
+        execute_sql(&mut engine, "CREATE VIEW young_adults AS SELECT who FROM adults WHERE years < 35 ORDER BY who").unwrap();
+        assert_eq!(select_rows(&mut engine, "SELECT * FROM young_adults"), vec![
+            Row { values: vec![text("Alice")] },
+            Row { values: vec![text("Bob")] }
+        ]);
+
+        execute_sql(&mut engine, "CREATE TABLE names (name TEXT)").unwrap();
+        execute_sql(&mut engine, "INSERT INTO names SELECT who FROM young_adults").unwrap();
+        assert_eq!(select_rows(&mut engine, "SELECT name FROM names ORDER BY name"), vec![
+            Row { values: vec![text("Alice")] },
+            Row { values: vec![text("Bob")] }
+        ]);
+    }
+
+    #[test]
+    fn test_create_view_errors() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        setup_users(&mut engine);
+
+        assert!(matches!(execute_sql(&mut engine, "CREATE VIEW v AS SELECT * FROM missing"), Err(ExecutionError::Semantic(SemanticError::TableNotFound(_)))));
+        assert!(matches!(execute_sql(&mut engine, "CREATE VIEW v AS SELECT height FROM users"), Err(ExecutionError::Semantic(SemanticError::ColumnNotFound(_)))));
+        assert!(matches!(execute_sql(&mut engine, "CREATE VIEW v (a) AS SELECT id, name FROM users"), Err(ExecutionError::Semantic(SemanticError::ValueCountMismatch(_)))));
+        assert!(matches!(execute_sql(&mut engine, "CREATE VIEW v (a, a) AS SELECT id, name FROM users"), Err(ExecutionError::Semantic(SemanticError::DuplicateColumn(_)))));
+        assert!(matches!(execute_sql(&mut engine, "CREATE VIEW users AS SELECT id FROM users"), Err(ExecutionError::Semantic(SemanticError::TableAlreadyExists(_)))));
+
+        execute_sql(&mut engine, "CREATE VIEW v AS SELECT id FROM users").unwrap();
+        assert!(matches!(execute_sql(&mut engine, "CREATE TABLE v (id INTEGER)"), Err(ExecutionError::TableAlreadyExists(_))));
+        assert!(matches!(execute_sql(&mut engine, "CREATE VIEW v AS SELECT name FROM users"), Err(ExecutionError::Semantic(SemanticError::TableAlreadyExists(_)))));
+        assert!(matches!(execute_sql(&mut engine, "INSERT INTO v VALUES (4)"), Err(ExecutionError::ReadOnlyTable(_))));
+        assert!(matches!(execute_sql(&mut engine, "UPDATE v SET id = 5"), Err(ExecutionError::ReadOnlyTable(_))));
+        assert!(matches!(execute_sql(&mut engine, "DELETE FROM v"), Err(ExecutionError::ReadOnlyTable(_))));
+    }
+
+    #[test]
+    fn test_drop_view() {
+        let (mut engine, _temp_dir) = setup_test_engine();
+        setup_users(&mut engine);
+        execute_sql(&mut engine, "CREATE VIEW v AS SELECT id FROM users").unwrap();
+
+        assert_eq!(execute_sql(&mut engine, "DROP VIEW v").unwrap(), QueryResult::DropView);
+        assert!(matches!(execute_sql(&mut engine, "SELECT * FROM v"), Err(ExecutionError::TableNotFound(_))));
+        assert!(matches!(execute_sql(&mut engine, "DROP VIEW v"), Err(ExecutionError::TableNotFound(_))));
+        assert_eq!(execute_sql(&mut engine, "DROP VIEW IF EXISTS v").unwrap(), QueryResult::DropView);
+        assert!(matches!(execute_sql(&mut engine, "DROP VIEW users"), Err(ExecutionError::TableNotFound(_))));
+    }
+
+    #[test]
+    fn test_views_persist_and_roll_back() {
+        let (mut engine, temp_dir) = setup_test_engine();
+        setup_users(&mut engine);
+        execute_sql(&mut engine, "CREATE VIEW adults AS SELECT name FROM users WHERE age >= 18 ORDER BY name").unwrap();
+
+        execute_sql(&mut engine, "BEGIN").unwrap();
+        execute_sql(&mut engine, "DROP VIEW adults").unwrap();
+        execute_sql(&mut engine, "CREATE VIEW minors AS SELECT name FROM users WHERE age < 18").unwrap();
+        execute_sql(&mut engine, "ROLLBACK").unwrap();
+        drop(engine);
+
+        let mut engine = ExecutionEngine::new(DataStore::new(temp_dir.path()).unwrap());
+        assert!(matches!(execute_sql(&mut engine, "SELECT * FROM minors"), Err(ExecutionError::TableNotFound(_))));
+        assert_eq!(select_rows(&mut engine, "SELECT * FROM adults"), vec![
+            Row { values: vec![text("Alice")] },
+            Row { values: vec![text("Carol")] }
+        ]);
+        assert_eq!(select_rows(&mut engine, "SELECT type, name, sql FROM sqlite_schema WHERE type = 'view'"), vec![
+            Row { values: vec![text("view"), text("adults"), text("CREATE VIEW adults (name) AS SELECT name FROM users WHERE age >= 18 ORDER BY name ASC")] }
+        ]);
+    }
+}